@@ -73,6 +73,14 @@ pub enum SetupScope {
     System,
 }
 
+#[derive(clap::ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+    Pwsh,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug, Copy)]
 pub enum InstallScope {
     User,
@@ -137,6 +145,10 @@ enum Commands {
         /// Download and index file lists for global search
         #[arg(short, long)]
         files: bool,
+
+        /// Sync git registries to a past state: a commit, tag or date (YYYY-MM-DD)
+        #[arg(long, value_name = "REV")]
+        at: Option<String>,
     },
 
     /// Migration helpers for converting external manifests to Zoi package files
@@ -311,6 +323,10 @@ enum Commands {
         /// Use PURL (Package URL) specification for resolving packages
         #[arg(long)]
         purl: bool,
+
+        /// Install alongside other installed versions of the same package and keep them on update
+        #[arg(long)]
+        keep: bool,
     },
 
     /// Uninstalls one or more packages previously installed by Zoi
@@ -355,6 +371,15 @@ enum Commands {
         cmd_alias: Option<String>,
         /// Arguments to pass to the command
         args: Vec<String>,
+        /// Re-run the command whenever its `inputs` or `watch` files change
+        #[arg(long)]
+        watch: bool,
+        /// Run the command in this workspace member (name or path)
+        #[arg(short = 'w', long = "member", value_name = "MEMBER")]
+        member: Option<String>,
+        /// Run the command in every workspace member that defines it
+        #[arg(long, conflicts_with_all = ["member", "watch"])]
+        all: bool,
     },
 
     /// Manage and set up project environments from a local zoi.yaml file
@@ -377,6 +402,25 @@ enum Commands {
         run: Option<String>,
     },
 
+    /// Print a shell hook that loads project environments on `cd`
+    #[command(
+        long_about = "Prints a script for your shell's startup file (e.g. `eval \"$(zoi hook bash)\"` in ~/.bashrc). Before each prompt it loads the dev environment of the project you are in (its .zoi bin dir on PATH, env files and shell env) and unloads it once you leave. A project has to be trusted with --allow first."
+    )]
+    Hook {
+        /// The shell to print the hook for
+        #[arg(value_enum)]
+        shell: Option<HookShell>,
+        /// Print the variable changes for the current directory (used by the hook itself)
+        #[arg(long, hide = true, requires = "shell")]
+        export: bool,
+        /// Trust the current project's zoi.yaml to change your environment
+        #[arg(long, conflicts_with_all = ["shell", "deny"])]
+        allow: bool,
+        /// Stop trusting the current project's zoi.yaml
+        #[arg(long, conflicts_with = "shell")]
+        deny: bool,
+    },
+
     /// Upgrades the Zoi binary to the latest version
     #[command(
         alias = "ug",
@@ -409,6 +453,22 @@ enum Commands {
         package_name: String,
     },
 
+    /// Pin the version of a tool for this directory or globally
+    #[command(
+        long_about = "Pins a package version for the current directory by writing it to the version file there ('.zoi-version', or an existing '.tool-versions', '.nvmrc' or '.python-version'), or for every directory with --global. Shims pick the version from the nearest version file above the working directory. The version is installed if it is missing."
+    )]
+    Use {
+        /// Package and version, e.g. node@20
+        #[arg(help = PKG_SOURCE_HELP)]
+        source: String,
+        /// Pin for the current directory (the default)
+        #[arg(long, conflicts_with = "global")]
+        local: bool,
+        /// Pin in the user configuration, for every directory
+        #[arg(long)]
+        global: bool,
+    },
+
     /// Find which package owns a file
     #[command(alias = "owns")]
     Owner {
@@ -423,6 +483,19 @@ enum Commands {
         package: String,
     },
 
+    /// Check installed files against what was recorded at install time
+    #[command(
+        long_about = "Reports files of installed packages that are missing, whose content changed, or whose permissions changed since they were installed. Edited 'backup' files are reported as modified configuration rather than corruption. Exits with an error if any package fails verification."
+    )]
+    Verify {
+        /// Packages to verify (all installed packages if omitted)
+        #[arg(value_name = "PACKAGES", help = PKG_SOURCE_HELP)]
+        packages: Vec<String>,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Shows the history of package operations
     History {
         /// Verify audit log chain integrity instead of printing history entries
@@ -523,12 +596,43 @@ enum Commands {
         dry_run: bool,
     },
 
+    /// Serve the archive cache and synced registries over HTTP
+    #[command(
+        long_about = "Starts a lightweight HTTP server that exposes this machine's archive cache and synced registries, so other machines can use it as a cache mirror or sync registries from it.\n\nEndpoints:\n- /archives/<file>: cached package archives and their sidecars, usable in 'cache_mirrors' or as a registry 'pkg' URL.\n- /registries/<handle>.tar.zst: a registry snapshot for 'zoi sync add'.\n- /registries/<handle>/<path>: files from a registry tree."
+    )]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+        /// Port to listen on
+        #[arg(long, short = 'p', default_value_t = crate::pkg::serve::DEFAULT_PORT)]
+        port: u16,
+    },
+
     /// Manage Zoi's local cache
     Cache {
         #[command(subcommand)]
         command: CacheCommands,
     },
 
+    /// Review and merge configuration files changed by upgrades
+    #[command(
+        long_about = "Files listed in a package's 'backup' keep your edits across upgrades. When both you and the new version changed a file, the new version is saved next to yours as '.zoinew'.\n\n'diff' shows your changes to the shipped files and what each '.zoinew' would change. 'merge' three-way merges each '.zoinew' into your copy, or lets you keep either side."
+    )]
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommands,
+    },
+
+    /// Manage the content-addressed object store shared by installed versions
+    #[command(
+        long_about = "Installed files are stored once per content in 'pkgs/objects' and hard-linked into each version directory, so versions kept for rollback share identical files.\n\nUninstalls and update cleanups free objects no other version uses. 'gc' catches anything left behind, and 'verify' re-hashes every object to detect corruption."
+    )]
+    Store {
+        #[command(subcommand)]
+        command: StoreCommands,
+    },
+
     /// Inspect recorded transactions
     #[command(alias = "tx")]
     Transaction {
@@ -580,6 +684,17 @@ enum Commands {
         command: SystemCommands,
     },
 
+    /// Switch the default version of a package installed side by side
+    Switch {
+        #[arg(value_name = "PACKAGE", help = PKG_SOURCE_HELP)]
+        package: String,
+        /// The installed version to make the default (e.g. '20' or '20.11.1')
+        version: String,
+        /// The scope the package is installed in
+        #[arg(long, value_enum)]
+        scope: Option<InstallScope>,
+    },
+
     /// Rollback a package to the previously installed version
     Rollback {
         #[arg(value_name = "PACKAGE", required_unless_present = "last_transaction", help = PKG_SOURCE_HELP)]
@@ -661,7 +776,14 @@ pub enum SystemCommands {
     /// Encrypt a phrase (e.g. password) for use in zoi.lua
     Encrypt {
         /// The phrase to encrypt
-        phrase: String,
+        #[arg(required_unless_present = "file")]
+        phrase: Option<String>,
+        /// Encrypt with your user key instead, for `enc:` values in zoi.yaml env files
+        #[arg(long)]
+        user: bool,
+        /// Encrypt a whole env file with your user key into <FILE>.enc
+        #[arg(long, conflicts_with = "phrase", requires = "user", value_hint = ValueHint::FilePath)]
+        file: Option<std::path::PathBuf>,
     },
 }
 
@@ -729,6 +851,36 @@ pub enum CacheMirrorCommands {
     List,
 }
 
+#[derive(clap::Subcommand)]
+pub enum StoreCommands {
+    /// Remove objects no installed version uses
+    Gc {
+        /// Only show what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Re-hash every object and report corruption
+    Verify {
+        /// Remove corrupt objects so reinstalls write fresh copies
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
+#[derive(clap::Subcommand)]
+pub enum BackupCommands {
+    /// Show changes to a package's configuration files
+    Diff {
+        #[arg(help = PKG_SOURCE_HELP)]
+        package: String,
+    },
+    /// Resolve the '.zoinew' files left by an upgrade (with --yes, apply clean merges only)
+    Merge {
+        #[arg(help = PKG_SOURCE_HELP)]
+        package: String,
+    },
+}
+
 #[derive(clap::Subcommand)]
 pub enum TransactionCommands {
     /// List known transaction logs
@@ -817,6 +969,10 @@ pub fn run() -> anyhow::Result<()> {
                 | Commands::Update { .. }
                 | Commands::Autoremove { .. }
                 | Commands::Rollback { .. }
+                | Commands::Switch { .. }
+                | Commands::Store { .. }
+                | Commands::Backup { .. }
+                | Commands::Use { .. }
                 | Commands::Package(_)
         );
 
@@ -849,6 +1005,7 @@ pub fn run() -> anyhow::Result<()> {
                 fallback,
                 no_package_managers,
                 files,
+                at,
             } => {
                 if let Some(cmd) = command {
                     match cmd {
@@ -859,7 +1016,13 @@ pub fn run() -> anyhow::Result<()> {
                     }
                 } else {
                     plugin_manager.trigger_hook("on_pre_sync", None)?;
-                    let res = cmd::sync::run(verbose, fallback, no_package_managers, files);
+                    let res = cmd::sync::run(
+                        verbose,
+                        fallback,
+                        no_package_managers,
+                        files,
+                        at.as_deref(),
+                    );
                     plugin_manager.trigger_hook_nonfatal("on_post_sync", None);
                     res
                 }
@@ -953,6 +1116,7 @@ pub fn run() -> anyhow::Result<()> {
                 retry,
                 verbose,
                 purl,
+                keep,
             } => cmd::install::run(
                 &sources,
                 repo,
@@ -973,6 +1137,7 @@ pub fn run() -> anyhow::Result<()> {
                 retry,
                 verbose,
                 purl,
+                keep,
             )
             .map_err(|e| cmd::ux::with_failure_hint("install", e)),
             Commands::Uninstall {
@@ -997,9 +1162,31 @@ pub fn run() -> anyhow::Result<()> {
                 plan_json,
             )
             .map_err(|e| cmd::ux::with_failure_hint("uninstall", e)),
-            Commands::Run { cmd_alias, args } => cmd::run::run(cmd_alias, args),
+            Commands::Run {
+                cmd_alias,
+                args,
+                watch,
+                member,
+                all,
+            } => cmd::run::run(cmd_alias, args, watch, member, all),
             Commands::Env { env_alias } => cmd::env::run(env_alias),
             Commands::Dev { run } => cmd::dev::run(run),
+            Commands::Hook {
+                shell,
+                export,
+                allow,
+                deny,
+            } => {
+                if shell.is_none() && !allow && !deny {
+                    let mut cmd = Cli::command();
+                    if let Some(subcmd) = cmd.find_subcommand_mut("hook") {
+                        subcmd.print_help()?;
+                    }
+                    Ok(())
+                } else {
+                    cmd::hook::run(shell, export, allow, deny)
+                }
+            }
             Commands::Upgrade { force, tag, branch } => {
                 match cmd::upgrade::run(BRANCH, STATUS, NUMBER, force, tag, branch) {
                     Ok(()) => {
@@ -1027,6 +1214,12 @@ pub fn run() -> anyhow::Result<()> {
             Commands::Why { package_name } => cmd::why::run(&package_name),
             Commands::Owner { path } => cmd::owner::run(&path),
             Commands::Files { package } => cmd::files::run(&package),
+            Commands::Use {
+                source,
+                local: _,
+                global,
+            } => cmd::use_version::run(&source, global, cli.yes, &plugin_manager),
+            Commands::Verify { packages, json } => cmd::verify::run(&packages, json),
             Commands::History {
                 verify,
                 export,
@@ -1082,6 +1275,7 @@ pub fn run() -> anyhow::Result<()> {
                 Err(e) => Err(e),
             },
             Commands::Clean { dry_run } => cmd::clean::run(dry_run),
+            Commands::Serve { bind, port } => cmd::serve::run(&bind, port),
             Commands::Cache { command } => match command {
                 CacheCommands::Add { files } => cmd::cache::add(&files),
                 CacheCommands::Clear { dry_run } => cmd::cache::clear(dry_run),
@@ -1092,6 +1286,14 @@ pub fn run() -> anyhow::Result<()> {
                     CacheMirrorCommands::List => cmd::cache::list_mirrors(),
                 },
             },
+            Commands::Backup { command } => match command {
+                BackupCommands::Diff { package } => cmd::backup::diff(&package),
+                BackupCommands::Merge { package } => cmd::backup::merge(&package, cli.yes),
+            },
+            Commands::Store { command } => match command {
+                StoreCommands::Gc { dry_run } => cmd::store::gc(dry_run),
+                StoreCommands::Verify { repair } => cmd::store::verify(repair),
+            },
             Commands::Transaction { command } => match command {
                 TransactionCommands::List => cmd::transaction::list(),
                 TransactionCommands::Show { id } => cmd::transaction::show(&id),
@@ -1118,16 +1320,15 @@ pub fn run() -> anyhow::Result<()> {
             Commands::Extension(args) => cmd::extension::run(args, cli.yes, &plugin_manager),
             Commands::System { command } => match command {
                 SystemCommands::Apply => crate::pkg::system::apply(cli.yes, &plugin_manager),
-                SystemCommands::Encrypt { phrase } => {
-                    match crate::pkg::system::encrypt_password(&phrase) {
-                        Ok(enc) => {
-                            println!("Encrypted value (safe for zoi.lua):\n{}", enc);
-                            Ok(())
-                        }
-                        Err(e) => Err(e),
-                    }
+                SystemCommands::Encrypt { phrase, user, file } => {
+                    crate::pkg::system::run_encrypt(phrase.as_deref(), user, file.as_deref())
                 }
             },
+            Commands::Switch {
+                package,
+                version,
+                scope,
+            } => cmd::switch::run(&package, &version, scope, cli.yes),
             Commands::Rollback {
                 package,
                 last_transaction,
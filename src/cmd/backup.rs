@@ -0,0 +1,152 @@
+use crate::pkg::{backup, local, resolve, types};
+use crate::utils;
+use anyhow::{Result, anyhow};
+use colored::*;
+use dialoguer::{Select, theme::ColorfulTheme};
+use std::path::Path;
+
+fn find_manifest(package_name: &str) -> Result<types::InstallManifest> {
+    let request = resolve::parse_source_string(package_name)?;
+    let mut candidates = Vec::new();
+    for scope in [
+        types::Scope::User,
+        types::Scope::System,
+        types::Scope::Project,
+    ] {
+        candidates.extend(local::find_installed_manifests_matching(&request, scope)?);
+    }
+    if candidates.is_empty() {
+        return Err(anyhow!("Package '{}' is not installed.", package_name));
+    }
+    crate::cmd::installed_select::choose_installed_manifest(package_name, &candidates, false)
+}
+
+fn print_diff(title: &str, from: &Path, to: &Path) -> Result<()> {
+    println!("{}", title.bold());
+    match backup::diff(
+        from,
+        to,
+        colored::control::SHOULD_COLORIZE.should_colorize(),
+    )? {
+        Some(diff) => print!("{}", diff),
+        None => println!("Binary files differ."),
+    }
+    Ok(())
+}
+
+pub fn diff(package_name: &str) -> Result<()> {
+    let manifest = find_manifest(package_name)?;
+    let files = backup::files(&manifest)?;
+    if files.is_empty() {
+        println!("{} has no configuration files.", manifest.name.cyan());
+        return Ok(());
+    }
+    let pending = backup::pending(&manifest)?;
+
+    let mut shown = false;
+    for (path, shipped) in &files {
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(shipped) = shipped
+            && !backup::same_content(shipped, path)?
+        {
+            print_diff(
+                &format!("Your changes to {}:", path.display()),
+                shipped,
+                path,
+            )?;
+            shown = true;
+        }
+        if let Some(p) = pending.iter().find(|p| &p.path == path) {
+            print_diff(
+                &format!("Changes in {}:", p.zoinew.display()),
+                path,
+                &p.zoinew,
+            )?;
+            shown = true;
+        }
+    }
+    if !shown {
+        println!(
+            "Configuration files of {} match what was shipped.",
+            manifest.name.cyan()
+        );
+    }
+    Ok(())
+}
+
+pub fn merge(package_name: &str, yes: bool) -> Result<()> {
+    let manifest = find_manifest(package_name)?;
+    let pending = backup::pending(&manifest)?;
+    if pending.is_empty() {
+        println!(
+            "No pending configuration changes for {}.",
+            manifest.name.cyan()
+        );
+        return Ok(());
+    }
+
+    let mut skipped = 0;
+    for item in &pending {
+        println!(
+            "\n{} {}",
+            "::".bold().blue(),
+            item.path.display().to_string().bold()
+        );
+        let merged = backup::merge(item).ok();
+
+        if let Some(Ok(clean)) = &merged {
+            let tmp = tempfile::NamedTempFile::new()?;
+            std::fs::write(tmp.path(), clean)?;
+            print_diff("Merged result:", &item.path, tmp.path())?;
+            if utils::ask_for_confirmation("Apply the merged result?", yes) {
+                backup::resolve(item, clean.as_bytes())?;
+                println!("{}", "Merged.".green());
+                continue;
+            }
+        }
+
+        if yes {
+            println!("Not merged cleanly, leaving {}.", item.zoinew.display());
+            skipped += 1;
+            continue;
+        }
+
+        let mut choices = vec!["Keep my version", "Use the new version"];
+        if let Some(Err(_)) = &merged {
+            choices.push("Write the merge with conflict markers to edit by hand");
+        }
+        choices.push("Skip");
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("How should this file be resolved?")
+            .items(&choices)
+            .default(0)
+            .interact_opt()?
+            .ok_or(anyhow!("No choice made."))?;
+
+        match choices[selection] {
+            "Keep my version" => backup::resolve(item, &std::fs::read(&item.path)?)?,
+            "Use the new version" => backup::resolve(item, &std::fs::read(&item.zoinew)?)?,
+            "Skip" => skipped += 1,
+            _ => {
+                if let Some(Err(conflicted)) = &merged {
+                    backup::resolve(item, conflicted.as_bytes())?;
+                    println!(
+                        "Conflict markers written to {}. Edit it to finish the merge.",
+                        item.path.display()
+                    );
+                }
+            }
+        }
+    }
+
+    if skipped > 0 {
+        println!(
+            "\n{} file{} left unresolved.",
+            skipped,
+            if skipped == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
@@ -1,9 +1,8 @@
 use crate::pkg::{install, local, types};
 use crate::project::config as project_config;
-use crate::utils;
+use crate::project::devenv;
 use anyhow::{Result, anyhow};
 use colored::*;
-use std::collections::HashMap;
 use std::process::Command;
 
 pub fn run(run_cmd: Option<String>) -> Result<()> {
@@ -48,119 +47,19 @@ pub fn run(run_cmd: Option<String>) -> Result<()> {
         }
     }
 
-    let mut env_vars: HashMap<String, String> = HashMap::new();
-
-    let mut bin_paths = Vec::new();
-    let mut lib_paths = Vec::new();
-    let mut include_paths = Vec::new();
-    let mut pkg_config_paths = Vec::new();
-
-    let sep = if cfg!(windows) { ";" } else { ":" };
-
+    let mut version_dirs = Vec::new();
     for node in graph.nodes.values() {
-        let handle = &node.registry_handle;
         let pkg = &node.pkg;
-        let package_dir = local::get_package_dir(pkg.scope, handle, &pkg.repo, &pkg.name)?;
-        let version_dir = package_dir.join(&node.version);
-
-        let bin_dir = version_dir.join("bin");
-        if bin_dir.exists() {
-            bin_paths.push(bin_dir);
-        }
-
-        let lib_dir = version_dir.join("lib");
-        if lib_dir.exists() {
-            lib_paths.push(lib_dir.clone());
-            let pkgconfig_dir = lib_dir.join("pkgconfig");
-            if pkgconfig_dir.exists() {
-                pkg_config_paths.push(pkgconfig_dir);
-            }
-        }
-
-        let include_dir = version_dir.join("include");
-        if include_dir.exists() {
-            include_paths.push(include_dir);
-        }
-
-        let share_dir = version_dir.join("share");
-        if share_dir.exists() {
-            let pkgconfig_dir = share_dir.join("pkgconfig");
-            if pkgconfig_dir.exists() {
-                pkg_config_paths.push(pkgconfig_dir);
-            }
-        }
-    }
-
-    if !bin_paths.is_empty() {
-        let mut path = bin_paths
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect::<Vec<_>>()
-            .join(sep);
-        if let Ok(old_path) = std::env::var("PATH") {
-            path = format!("{}{}{}", path, sep, old_path);
-        }
-        env_vars.insert("PATH".to_string(), path);
-    }
-
-    if !lib_paths.is_empty() {
-        let lib_path_var = if cfg!(target_os = "macos") {
-            "DYLD_LIBRARY_PATH"
-        } else {
-            "LD_LIBRARY_PATH"
-        };
-        let mut path = lib_paths
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect::<Vec<_>>()
-            .join(sep);
-        if let Ok(old_path) = std::env::var(lib_path_var) {
-            path = format!("{}{}{}", path, sep, old_path);
-        }
-        env_vars.insert(lib_path_var.to_string(), path);
-    }
-
-    if !include_paths.is_empty() {
-        let path = include_paths
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect::<Vec<_>>()
-            .join(sep);
-        for var in &["CPATH", "C_INCLUDE_PATH", "CPLUS_INCLUDE_PATH"] {
-            let mut full_path = path.clone();
-            if let Ok(old_path) = std::env::var(var) {
-                full_path = format!("{}{}{}", full_path, sep, old_path);
-            }
-            env_vars.insert(var.to_string(), full_path);
-        }
-    }
-
-    if !pkg_config_paths.is_empty() {
-        let mut path = pkg_config_paths
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect::<Vec<_>>()
-            .join(sep);
-        if let Ok(old_path) = std::env::var("PKG_CONFIG_PATH") {
-            path = format!("{}{}{}", path, sep, old_path);
-        }
-        env_vars.insert("PKG_CONFIG_PATH".to_string(), path);
-    }
-
-    if let Some(shell_spec) = &config.shell {
-        let platform = utils::get_platform()?;
-        let extra_env = match &shell_spec.env {
-            project_config::PlatformOrEnvMap::EnvMap(m) => m.clone(),
-            project_config::PlatformOrEnvMap::Platform(p) => p
-                .get(&platform)
-                .or_else(|| p.get("default"))
-                .cloned()
-                .unwrap_or_default(),
-        };
-        for (k, v) in extra_env {
-            env_vars.insert(k, v);
-        }
+        version_dirs.push(local::get_package_version_dir(
+            pkg.scope,
+            &node.registry_handle,
+            &pkg.repo,
+            &pkg.name,
+            &node.version,
+        )?);
     }
+    let paths = devenv::search_paths(&version_dirs);
+    let env_vars = devenv::compose(&config, &paths, &std::env::vars().collect())?;
 
     if let Some(cmd_str) = run_cmd {
         println!("{} Running: {}", "::".bold().blue(), cmd_str.cyan());
@@ -77,6 +77,36 @@ pub fn run() -> Result<()> {
         }
     }
 
+    println!(
+        "\n{} Checking package database schemas...",
+        "->".bold().cyan()
+    );
+    match pkg::doctor::check_database_schemas() {
+        Ok(newer) => {
+            if newer.is_empty() {
+                println!("{}", "Package databases are compatible.".green());
+            } else {
+                issues_found += newer.len();
+                println!(
+                    "{}: Found {} databases written by a newer Zoi (this version supports schema {}):",
+                    "Warning".yellow(),
+                    newer.len(),
+                    pkg::db::SCHEMA_VERSION
+                );
+                for (path, version) in newer {
+                    println!("  - {} (schema {})", path.display(), version);
+                }
+                println!(
+                    "\nUpgrade Zoi, or run 'zoi sync' and confirm the rebuild prompt to recreate them."
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("{}: Failed to check database schemas: {}", "Error".red(), e);
+            issues_found += 1;
+        }
+    }
+
     println!(
         "\n{} Checking for duplicate package IDs...",
         "->".bold().cyan()
@@ -141,5 +141,6 @@ Initiating install for {}...",
         3,
         false,
         false,
+        false,
     )
 }
@@ -0,0 +1,136 @@
+use crate::cli::HookShell;
+use crate::project::hook::{self, Change};
+use anyhow::Result;
+use colored::*;
+use std::env;
+
+pub fn run(shell: Option<HookShell>, export: bool, allow: bool, deny: bool) -> Result<()> {
+    if allow {
+        let path = hook::allow()?;
+        println!(
+            "{} Allowed {} to change your environment.",
+            "::".bold().green(),
+            path.display()
+        );
+    } else if deny {
+        let path = hook::deny()?;
+        println!(
+            "{} {} may no longer change your environment.",
+            "::".bold().blue(),
+            path.display()
+        );
+    } else if let Some(shell) = shell {
+        if export {
+            print!("{}", render(shell, &hook::export()?));
+        } else {
+            let exe = env::current_exe()?;
+            print!("{}", script(shell, &exe.to_string_lossy()));
+        }
+    }
+    Ok(())
+}
+
+fn quote_posix(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn quote_fish(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+fn quote_pwsh(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Shell statements applying `changes`.
+pub fn render(shell: HookShell, changes: &[Change]) -> String {
+    let mut out = String::new();
+    for (var, value) in changes {
+        let line = match (shell, value) {
+            (HookShell::Bash | HookShell::Zsh, Some(value)) => {
+                format!("export {}={};", var, quote_posix(value))
+            }
+            (HookShell::Bash | HookShell::Zsh, None) => format!("unset {};", var),
+            // fish keeps PATH-like variables as lists.
+            (HookShell::Fish, Some(value)) if var.ends_with("PATH") => {
+                format!("set -gx {} (string split -- : {});", var, quote_fish(value))
+            }
+            (HookShell::Fish, Some(value)) => format!("set -gx {} {};", var, quote_fish(value)),
+            (HookShell::Fish, None) => format!("set -e {};", var),
+            (HookShell::Pwsh, Some(value)) => format!("${{env:{}}} = {}", var, quote_pwsh(value)),
+            (HookShell::Pwsh, None) => {
+                format!(
+                    "Remove-Item -Path Env:{} -ErrorAction SilentlyContinue",
+                    var
+                )
+            }
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// The hook for `shell`, calling the zoi binary at `exe` before each prompt.
+pub fn script(shell: HookShell, exe: &str) -> String {
+    match shell {
+        HookShell::Bash => format!(
+            r#"_zoi_hook() {{
+  local previous_exit_status=$?
+  trap -- '' SIGINT
+  eval "$({exe} hook bash --export)"
+  trap - SIGINT
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_zoi_hook;"* ]]; then
+  if [[ "$(declare -p PROMPT_COMMAND 2>&1)" == "declare -a"* ]]; then
+    PROMPT_COMMAND=(_zoi_hook "${{PROMPT_COMMAND[@]}}")
+  else
+    PROMPT_COMMAND="_zoi_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+  fi
+fi
+"#,
+            exe = quote_posix(exe)
+        ),
+        HookShell::Zsh => format!(
+            r#"_zoi_hook() {{
+  trap -- '' SIGINT
+  eval "$({exe} hook zsh --export)"
+  trap - SIGINT
+}}
+typeset -ag precmd_functions
+if (( ! ${{precmd_functions[(I)_zoi_hook]}} )); then
+  precmd_functions=(_zoi_hook $precmd_functions)
+fi
+typeset -ag chpwd_functions
+if (( ! ${{chpwd_functions[(I)_zoi_hook]}} )); then
+  chpwd_functions=(_zoi_hook $chpwd_functions)
+fi
+"#,
+            exe = quote_posix(exe)
+        ),
+        HookShell::Fish => format!(
+            r#"function __zoi_hook --on-event fish_prompt
+    {exe} hook fish --export | source
+end
+function __zoi_hook_cd --on-variable PWD
+    status --is-command-substitution; and return
+    __zoi_hook
+end
+"#,
+            exe = quote_fish(exe)
+        ),
+        HookShell::Pwsh => format!(
+            r#"if (-not $global:__zoi_prompt) {{
+    $global:__zoi_prompt = $function:prompt
+    function global:prompt {{
+        $exports = & {exe} hook pwsh --export | Out-String
+        if ($exports) {{ Invoke-Expression $exports }}
+        & $global:__zoi_prompt
+    }}
+}}
+"#,
+            exe = quote_pwsh(exe)
+        ),
+    }
+}
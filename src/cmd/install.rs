@@ -1,5 +1,5 @@
 use crate::cmd::ux;
-use crate::pkg::{config, install, lock, resolve, transaction, types};
+use crate::pkg::{config, foreign, install, lock, resolve, transaction, types};
 use crate::project;
 use anyhow::{Result, anyhow};
 use colored::Colorize;
@@ -7,7 +7,8 @@ use indicatif::MultiProgress;
 use mlua::LuaSerdeExt;
 use rayon::prelude::*;
 use serde_json::json;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -31,6 +32,7 @@ pub fn run(
     retry: u32,
     verbose: bool,
     purl: bool,
+    keep: bool,
 ) -> Result<()> {
     crate::pkg::install::util::set_download_retry_attempts(retry);
 
@@ -46,7 +48,11 @@ pub fn run(
         scope_override = Some(types::Scope::User);
     }
 
-    if save && scope_override.is_none() && std::path::Path::new("zoi.yaml").exists() {
+    let project_root = project::config::project_root()?;
+    let has_project_config = project_root.join(project::config::CONFIG_FILE).exists();
+    let has_project_lock = project_root.join("zoi.lock").exists();
+
+    if save && scope_override.is_none() && has_project_config {
         scope_override = Some(types::Scope::Project);
     }
 
@@ -61,12 +67,12 @@ pub fn run(
                 "--save cannot be used with --frozen-lockfile because the lockfile must remain unchanged."
             ));
         }
-        if !std::path::Path::new("zoi.yaml").exists() {
+        if !has_project_config {
             return Err(anyhow!(
                 "--frozen-lockfile requires a local zoi.yaml in the current project."
             ));
         }
-        if !std::path::Path::new("zoi.lock").exists() {
+        if !has_project_lock {
             return Err(anyhow!(
                 "--frozen-lockfile requires zoi.lock. Generate it first with a normal project install."
             ));
@@ -81,10 +87,8 @@ pub fn run(
         scope_override = Some(types::Scope::Project);
     }
 
-    let lockfile_exists = sources.is_empty()
-        && repo.is_none()
-        && std::path::Path::new("zoi.lock").exists()
-        && std::path::Path::new("zoi.yaml").exists();
+    let lockfile_exists =
+        sources.is_empty() && repo.is_none() && has_project_lock && has_project_config;
 
     let mut sources_to_process: Vec<String> = sources.to_vec();
     let mut is_project_install = false;
@@ -108,7 +112,7 @@ pub fn run(
         );
         is_project_install = true;
     } else if sources.is_empty() && repo.is_none() {
-        if std::path::Path::new("zoi.yaml").exists() {
+        if has_project_config {
             if let Ok(config) = project::config::load() {
                 if lockfile_exists {
                     println!(
@@ -121,7 +125,10 @@ pub fn run(
                         "::".bold().blue()
                     );
                 }
-                sources_to_process = config.pkgs.clone();
+                sources_to_process = match project::workspace::Workspace::load()? {
+                    Some(workspace) => workspace.all_pkgs()?,
+                    None => config.pkgs.clone(),
+                };
                 if scope_override.is_none() {
                     scope_override = Some(types::Scope::Project);
                 }
@@ -132,6 +139,18 @@ pub fn run(
         }
     }
 
+    // Held until the install returns, then the registries go back to
+    // their synced state.
+    let _locked_revisions =
+        if is_project_install && (frozen_lockfile || lockfile_exists) && !dry_run {
+            let lockfile = project::lockfile::read_zoi_lock()?;
+            Some(crate::pkg::sync::checkout_locked_revisions(
+                &lockfile, false,
+            )?)
+        } else {
+            None
+        };
+
     if let Some(repo_spec) = repo {
         if scope_override == Some(types::Scope::Project) {
             return Err(anyhow!(
@@ -193,11 +212,19 @@ pub fn run(
 
     let failed_packages = Mutex::new(Vec::new());
     let mut temp_files = Vec::new();
+    let mut temp_dirs = Vec::new();
     let mut final_sources = Vec::new();
 
     for source in &sources_to_process {
         if source.ends_with("zoi.pkgs.json") {
             install::lockfile::process_lockfile(source, &mut final_sources, &mut temp_files)?;
+        } else if foreign::Format::detect(Path::new(source)).is_some()
+            && Path::new(source).is_file()
+        {
+            let dir = tempfile::Builder::new().prefix("zoi-import-").tempdir()?;
+            let pkg_lua = foreign::write_pkg_lua(Path::new(source), dir.path())?;
+            final_sources.push(pkg_lua.to_string_lossy().to_string());
+            temp_dirs.push(dir);
         } else {
             final_sources.push(source.to_string());
         }
@@ -213,6 +240,16 @@ pub fn run(
             false,
             yes,
         )?
+    } else if keep {
+        install::resolver::resolve_side_by_side(
+            &final_sources,
+            scope_override,
+            force,
+            yes,
+            all_optional,
+            build_type.as_deref(),
+            false,
+        )?
     } else {
         install::resolver::resolve_dependency_graph(
             &final_sources,
@@ -556,6 +593,12 @@ pub fn run(
         return Ok(());
     }
 
+    let previous_defaults = if keep {
+        collect_default_versions(&direct_packages)?
+    } else {
+        HashMap::new()
+    };
+
     let stages = graph.toposort()?;
     let transaction = transaction::begin()?;
     let transaction_id = &transaction.id;
@@ -728,6 +771,10 @@ pub fn run(
         .lock()
         .expect("Installed manifests mutex poisoned during finalization")
         .clone();
+    if keep && let Err(e) = settle_kept_versions(&installed_manifests_vec, &previous_defaults, yes)
+    {
+        eprintln!("Warning: failed to update default versions: {}", e);
+    }
     for manifest in &installed_manifests_vec {
         let pkg_val = plugin_manager
             .lua
@@ -750,6 +797,8 @@ pub fn run(
 
             lockfile.packages.clear();
             lockfile.details.clear();
+            lockfile.registry_revisions.clear();
+            let db_root = crate::pkg::resolve::get_db_root()?;
 
             let all_regs_config = crate::pkg::config::read_config().unwrap_or_default();
             let mut all_configured_regs = all_regs_config.added_registries;
@@ -786,6 +835,13 @@ pub fn run(
                         .registries
                         .insert(reg.handle.clone(), reg.url.clone());
                 }
+                if let Some(commit) =
+                    crate::pkg::sync::head_commit_id(&db_root.join(&manifest.registry_handle))
+                {
+                    lockfile
+                        .registry_revisions
+                        .insert(manifest.registry_handle.clone(), commit);
+                }
 
                 let package_dir = crate::pkg::local::get_package_dir(
                     types::Scope::Project,
@@ -840,6 +896,8 @@ pub fn run(
                     dependencies,
                     options_dependencies: manifest.chosen_options.clone(),
                     optionals_dependencies: manifest.chosen_optionals.clone(),
+                    provider_for: manifest.provider_for.clone(),
+                    features: manifest.features.clone(),
                 };
 
                 let registry_key = format!("#{}", manifest.registry_handle);
@@ -895,3 +953,114 @@ pub fn run(
 
     Ok(())
 }
+
+type PackageKey = (types::Scope, String, String, String, Option<String>);
+
+fn package_key(
+    scope: types::Scope,
+    handle: &str,
+    repo: &str,
+    name: &str,
+    sub_package: Option<&str>,
+) -> PackageKey {
+    (
+        scope,
+        handle.to_string(),
+        repo.to_string(),
+        name.to_string(),
+        sub_package.map(str::to_string),
+    )
+}
+
+fn collect_default_versions(
+    direct_packages: &[&install::resolver::InstallNode],
+) -> Result<HashMap<PackageKey, String>> {
+    let mut defaults = HashMap::new();
+    for node in direct_packages {
+        if let Some(version) = crate::pkg::local::get_default_version(
+            node.pkg.scope,
+            &node.registry_handle,
+            &node.pkg.repo,
+            &node.pkg.name,
+        )? {
+            defaults.insert(
+                package_key(
+                    node.pkg.scope,
+                    &node.registry_handle,
+                    &node.pkg.repo,
+                    &node.pkg.name,
+                    node.sub_package.as_deref(),
+                ),
+                version,
+            );
+        }
+    }
+    Ok(defaults)
+}
+
+/// After a `--keep` install, marks every version involved as kept and points
+/// each package back at the default it had before, or at the newest version
+/// when it was not installed yet.
+fn settle_kept_versions(
+    manifests: &[types::InstallManifest],
+    previous_defaults: &HashMap<PackageKey, String>,
+    yes: bool,
+) -> Result<()> {
+    let mut by_package: HashMap<PackageKey, Vec<&types::InstallManifest>> = HashMap::new();
+    for manifest in manifests {
+        by_package
+            .entry(package_key(
+                manifest.scope,
+                &manifest.registry_handle,
+                &manifest.repo,
+                &manifest.name,
+                manifest.sub_package.as_deref(),
+            ))
+            .or_default()
+            .push(manifest);
+    }
+
+    for (key, installed) in by_package {
+        let (scope, handle, repo, name, sub_package) = &key;
+        let mut versions: Vec<String> = installed.iter().map(|m| m.version.clone()).collect();
+        if let Some(previous) = previous_defaults.get(&key) {
+            versions.push(previous.clone());
+        }
+        for version in &versions {
+            let version_dir =
+                crate::pkg::local::get_package_version_dir(*scope, handle, repo, name, version)?;
+            if version_dir.is_dir() {
+                crate::pkg::local::mark_version_kept(&version_dir)?;
+            }
+        }
+
+        let target = match previous_defaults.get(&key) {
+            Some(previous) => previous.clone(),
+            None => versions
+                .iter()
+                .max_by(|a, b| crate::pkg::local::compare_versions(a, b))
+                .cloned()
+                .unwrap_or_default(),
+        };
+        if let Some(manifest) = crate::pkg::local::read_version_manifest(
+            *scope,
+            handle,
+            repo,
+            name,
+            sub_package.as_deref(),
+            &target,
+        )? {
+            crate::pkg::switch::set_default(&manifest, yes)?;
+            if installed.len() > 1 || previous_defaults.contains_key(&key) {
+                println!(
+                    "{} '{}' defaults to version {}. Use 'zoi switch {} <version>' to change it.",
+                    "::".bold().blue(),
+                    name.cyan(),
+                    target.green(),
+                    name
+                );
+            }
+        }
+    }
+    Ok(())
+}
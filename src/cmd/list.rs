@@ -204,6 +204,19 @@ fn run_list_installed(
                 continue;
             }
 
+            let version_display = match (&pkg.version, &pkg.registry_handle) {
+                (Some(version), Some(handle)) => installed_versions_display(
+                    version,
+                    pkg.scope,
+                    handle,
+                    &pkg.repo,
+                    &pkg.name,
+                    pkg.sub_package.as_deref(),
+                ),
+                (Some(version), None) => version.clone(),
+                (None, _) => "N/A".to_string(),
+            };
+
             let package_display = if let Some(sub) = &pkg.sub_package {
                 format!("{}:{}", pkg.name, sub)
             } else {
@@ -214,7 +227,7 @@ fn run_list_installed(
 
             table.add_row(vec![
                 package_display,
-                pkg.version.unwrap_or_else(|| "N/A".to_string()),
+                version_display,
                 repo_display.to_string(),
                 pkg.registry_handle.unwrap_or_else(|| "none".to_string()),
                 format!("{:?}", pkg.package_type),
@@ -272,6 +285,15 @@ fn run_list_installed(
                 continue;
             }
 
+            let version_display = installed_versions_display(
+                &pkg.version,
+                m.scope,
+                &m.registry_handle,
+                &m.repo,
+                &m.name,
+                m.sub_package.as_deref(),
+            );
+
             let package_display = if let Some(sub) = pkg.sub_package {
                 format!("{}:{}", pkg.name, sub)
             } else {
@@ -282,7 +304,7 @@ fn run_list_installed(
 
             table.add_row(vec![
                 package_display,
-                pkg.version,
+                version_display,
                 repo_display.to_string(),
                 m.registry_handle,
                 format!("{:?}", pkg.package_type),
@@ -300,6 +322,42 @@ fn run_list_installed(
     Ok(())
 }
 
+/// Lists every installed version of a package, marking the default one when
+/// several versions are installed side by side.
+fn installed_versions_display(
+    default_version: &str,
+    scope: types::Scope,
+    handle: &str,
+    repo: &str,
+    name: &str,
+    sub_package: Option<&str>,
+) -> String {
+    let versions: Vec<String> = local::get_installed_versions(scope, handle, repo, name)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|version| {
+            matches!(
+                local::read_version_manifest(scope, handle, repo, name, sub_package, version),
+                Ok(Some(_))
+            )
+        })
+        .collect();
+    if versions.len() <= 1 {
+        return default_version.to_string();
+    }
+    versions
+        .iter()
+        .map(|version| {
+            if version == default_version {
+                format!("{} (default)", version)
+            } else {
+                version.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 fn run_list_names(
     all: bool,
     registry_filter: Option<String>,
@@ -1,6 +1,7 @@
 pub mod about;
 pub mod audit;
 pub mod autoremove;
+pub mod backup;
 pub mod cache;
 pub mod clean;
 pub mod create;
@@ -14,6 +15,7 @@ pub mod files;
 pub mod gen_man;
 pub mod helper;
 pub mod history;
+pub mod hook;
 pub mod info;
 pub mod install;
 pub mod installed_select;
@@ -30,9 +32,12 @@ pub mod repo;
 pub mod rollback;
 pub mod run;
 pub mod search;
+pub mod serve;
 pub mod service;
 pub mod shell;
 pub mod show;
+pub mod store;
+pub mod switch;
 pub mod sync;
 pub mod telemetry;
 pub mod transaction;
@@ -41,7 +46,9 @@ pub mod uninstall;
 pub mod unpin;
 pub mod update;
 pub mod upgrade;
+pub mod use_version;
 pub mod utils;
 pub mod ux;
+pub mod verify;
 pub mod version;
 pub mod why;
@@ -33,6 +33,16 @@ pub struct BuildCommand {
     #[arg(long, short = 'o')]
     pub output_dir: Option<PathBuf>,
 
+    /// Features to build with, e.g. `--features +vaapi,-x11`.
+    /// Without this the package's default features are used.
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    pub features: Vec<String>,
+
+    /// Previous archive(s) to generate `.delta` files against, so clients
+    /// that have them cached can update without a full download.
+    #[arg(long, num_args = 1..)]
+    pub delta_from: Vec<PathBuf>,
+
     /// Automatically install build-time dependencies
     #[arg(long)]
     pub install_deps: bool,
@@ -65,6 +75,8 @@ pub fn run(args: BuildCommand) -> Result<()> {
         args.output_dir.as_deref(),
         args.version_override.as_deref(),
         args.sub,
+        &args.features,
+        &args.delta_from,
         false,
         args.install_deps,
         &args.method,
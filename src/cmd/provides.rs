@@ -25,8 +25,16 @@ pub fn run(term: &str) -> Result<()> {
 
     let all_results: Vec<(crate::pkg::types::Package, String)> = registries
         .into_par_iter()
-        .filter_map(|handle| db::find_provides(&handle, term).ok())
-        .flatten()
+        .flat_map(|handle| {
+            let mut results = db::find_provides(&handle, term).unwrap_or_default();
+            results.extend(
+                db::find_virtual_providers(&handle, term)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|pkg| (pkg, format!("provides {}", term))),
+            );
+            results
+        })
         .collect();
 
     if all_results.is_empty() {
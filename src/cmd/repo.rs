@@ -1,10 +1,11 @@
-use crate::pkg::config;
+use crate::pkg::{bundle, config, snapshot};
 use anyhow::Result;
 use anyhow::anyhow;
 use clap::{Parser, Subcommand};
 use colored::*;
 use comfy_table::{Table, presets::UTF8_FULL};
 use std::collections::HashSet;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 pub struct RepoCommand {
@@ -24,7 +25,8 @@ enum Commands {
     /// Add a repository to the configuration or clone from a git URL
     #[command(alias = "a")]
     Add {
-        /// The name of the repository to add or a git URL to clone
+        /// The name of the repository to add, a git URL to clone, or a
+        /// `file://` directory to use as a local registry
         repo_or_url: Option<String>,
     },
     /// Remove a repository from the active configuration
@@ -39,6 +41,39 @@ enum Commands {
     /// Manage cloned git repositories
     #[command(subcommand)]
     Git(GitCommand),
+    /// Export registries and package archives into an offline bundle
+    Export {
+        /// Path of the bundle to write, e.g. `out.zoibundle`
+        #[arg(long, value_name = "FILE")]
+        bundle: PathBuf,
+        /// Packages to include along with their dependencies
+        packages: Vec<String>,
+        /// Sign the registry snapshots with the given PGP key (name or
+        /// fingerprint)
+        #[arg(long)]
+        sign: Option<String>,
+    },
+    /// Import an offline bundle created with `zoi repo export`
+    Import {
+        /// Path to the bundle
+        bundle: PathBuf,
+        /// Overwrite git registries, registries whose authorities didn't sign
+        /// the bundle, and differing keys of the same name
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Pack a registry directory into a snapshot archive for static hosting
+    Snapshot {
+        /// The registry directory (containing repo.yaml)
+        dir: PathBuf,
+        /// Path of the archive to write, e.g. `zoidberg.tar.zst`
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+        /// Serial of the snapshot (defaults to the current time, and always
+        /// above the serial of an existing archive at the output path)
+        #[arg(long)]
+        serial: Option<u64>,
+    },
 }
 
 pub fn run(args: RepoCommand) -> Result<()> {
@@ -51,6 +86,10 @@ pub fn run(args: RepoCommand) -> Result<()> {
                     || val.ends_with(".git")
                 {
                     config::clone_git_repo(&val)?;
+                } else if val.starts_with("file://") {
+                    let url = config::add_local_registry(&val)?;
+                    println!("Local registry '{}' added successfully.", url.green());
+                    println!("Run 'zoi sync' to index it.");
                 } else {
                     config::add_repo(&val)?;
                     println!("Repository '{}' added successfully.", val.green());
@@ -75,6 +114,58 @@ pub fn run(args: RepoCommand) -> Result<()> {
             GitCommand::List => run_list_git_only()?,
             GitCommand::Rm { repo_name } => config::remove_git_repo(&repo_name)?,
         },
+        Commands::Export {
+            bundle: path,
+            packages,
+            sign,
+        } => {
+            println!("{} Exporting offline bundle...", "::".bold().blue());
+            let manifest = bundle::export_bundle(&path, &packages, sign.as_deref())?;
+            println!(
+                "Wrote {} ({} registries, {} archives, {} keys).",
+                path.display().to_string().green(),
+                manifest.registries.len(),
+                manifest.archives.len(),
+                manifest.keys.len()
+            );
+        }
+        Commands::Import {
+            bundle: path,
+            replace,
+        } => {
+            println!("{} Importing offline bundle...", "::".bold().blue());
+            let summary = bundle::import_bundle(&path, replace, yes)?;
+            println!(
+                "Imported registries {} with {} archives and {} keys.",
+                summary.registries.join(", ").green(),
+                summary.archives,
+                summary.keys
+            );
+            println!("Enable 'offline_mode' to install from it without network access.");
+        }
+        Commands::Snapshot {
+            dir,
+            output,
+            serial,
+        } => {
+            let serial = serial.unwrap_or_else(|| {
+                let previous = snapshot::read_archive_index(&output).ok();
+                snapshot::next_serial(previous.map(|index| index.serial))
+            });
+            println!("{} Creating registry snapshot...", "::".bold().blue());
+            let index = snapshot::create_snapshot(&dir, &output, serial)?;
+            println!(
+                "Wrote {} (serial {}, {} packages, {} advisories).",
+                output.display().to_string().green(),
+                index.serial,
+                index.packages.len(),
+                index.advisories.len()
+            );
+            println!(
+                "Sign it with 'gpg --detach-sign {}' if the registry has authorities.",
+                output.display()
+            );
+        }
     }
     Ok(())
 }
@@ -1,7 +1,34 @@
-use crate::project::{config, runner};
-use anyhow::Result;
+use crate::project::{config, runner, watch, workspace};
+use anyhow::{Result, anyhow};
 
-pub fn run(cmd_alias: Option<String>, args: Vec<String>) -> Result<()> {
-    let config = config::load()?;
-    runner::run(cmd_alias.as_deref(), &args, &config)
+pub fn run(
+    cmd_alias: Option<String>,
+    args: Vec<String>,
+    watch: bool,
+    member: Option<String>,
+    all: bool,
+) -> Result<()> {
+    if member.is_none() && !all {
+        let config = config::load()?;
+        return if watch {
+            watch::run(cmd_alias.as_deref(), &args, &config, None)
+        } else {
+            runner::run(cmd_alias.as_deref(), &args, &config)
+        };
+    }
+
+    let workspace = workspace::Workspace::load()?.ok_or_else(|| {
+        anyhow!("Not inside a workspace. Declare `workspace.members` in the root zoi.yaml first.")
+    })?;
+    if let Some(name) = member {
+        let member = workspace.member(&name)?;
+        return if watch {
+            watch::run(cmd_alias.as_deref(), &args, &member.config, Some(&name))
+        } else {
+            runner::run(cmd_alias.as_deref(), &args, &member.config)
+        };
+    }
+
+    let cmd_alias = cmd_alias.ok_or_else(|| anyhow!("--all needs a command to run."))?;
+    runner::run_all(&cmd_alias, &args, &workspace)
 }
@@ -0,0 +1,20 @@
+use crate::pkg::serve;
+use anyhow::Result;
+use colored::*;
+
+pub fn run(bind: &str, port: u16) -> Result<()> {
+    let listener = serve::bind(bind, port)?;
+    let address = listener.local_addr()?;
+    println!(
+        "{} Serving archives and registries on {}",
+        "::".bold().blue(),
+        format!("http://{}", address).cyan()
+    );
+    println!("  cache mirror: http://{}/archives", address);
+    println!(
+        "  registries:   http://{}/registries/<handle>.tar.zst",
+        address
+    );
+    println!("Press Ctrl+C to stop.");
+    serve::serve(listener)
+}
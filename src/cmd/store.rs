@@ -0,0 +1,122 @@
+use crate::pkg::{store, types::Scope};
+use crate::utils::format_bytes;
+use anyhow::{Result, anyhow};
+use colored::*;
+
+const SCOPES: [Scope; 3] = [Scope::User, Scope::System, Scope::Project];
+
+fn scopes_with_objects() -> Vec<Scope> {
+    SCOPES
+        .into_iter()
+        .filter(|scope| store::get_objects_dir(*scope).is_ok_and(|dir| dir.exists()))
+        .collect()
+}
+
+pub fn gc(dry_run: bool) -> Result<()> {
+    if dry_run {
+        println!(
+            "{} Collecting unused store objects (Dry-run)...",
+            "::".bold().yellow()
+        );
+    } else {
+        println!("{} Collecting unused store objects...", "::".bold().blue());
+    }
+
+    let mut removed = 0;
+    let mut freed = 0;
+    for scope in scopes_with_objects() {
+        match store::gc(scope, dry_run) {
+            Ok(report) => {
+                if report.stale_versions > 0 {
+                    println!(
+                        "  {:?}: forgot {} removed version director{}",
+                        scope,
+                        report.stale_versions,
+                        if report.stale_versions == 1 {
+                            "y"
+                        } else {
+                            "ies"
+                        }
+                    );
+                }
+                removed += report.removed_objects;
+                freed += report.freed_bytes;
+            }
+            Err(e) => eprintln!(
+                "{}: Could not collect the {:?} store: {}",
+                "Warning".yellow().bold(),
+                scope,
+                e
+            ),
+        }
+    }
+
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    println!(
+        "{} {} unused object(s), {}.",
+        verb,
+        removed.to_string().bold(),
+        format_bytes(freed).green()
+    );
+    Ok(())
+}
+
+pub fn verify(repair: bool) -> Result<()> {
+    println!("{} Verifying store objects...", "::".bold().blue());
+
+    let mut checked = 0;
+    let mut problems = 0;
+    for scope in scopes_with_objects() {
+        let report = store::verify(scope, repair)?;
+        checked += report.checked_objects;
+        problems += report.corrupt.len() + report.missing.len();
+
+        for (object, files) in &report.corrupt {
+            println!(
+                "{} {:?}: object {} does not match its hash",
+                "✗".red(),
+                scope,
+                object.red()
+            );
+            for file in files {
+                println!("    used by {}", file.display());
+            }
+        }
+        for object in &report.missing {
+            println!(
+                "{} {:?}: object {} is missing",
+                "✗".red(),
+                scope,
+                object.red()
+            );
+        }
+        if !report.detached.is_empty() {
+            println!(
+                "{} {:?}: {} file(s) no longer share their object; 'zoi store gc' will forget them.",
+                "!".yellow(),
+                scope,
+                report.detached.len()
+            );
+        }
+    }
+
+    if problems > 0 {
+        let fix = if repair {
+            "Reinstall the affected packages with 'zoi install --force'."
+        } else {
+            "Run 'zoi store verify --repair', then reinstall the affected packages with 'zoi install --force'."
+        };
+        return Err(anyhow!(
+            "{} problem(s) found in {} object(s). {}",
+            problems,
+            checked,
+            fix
+        ));
+    }
+    println!(
+        "{} {} object(s) verified.",
+        "Success:".green(),
+        checked.to_string().bold()
+    );
+    Ok(())
+}
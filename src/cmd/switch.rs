@@ -0,0 +1,17 @@
+use crate::pkg::{switch, types};
+use anyhow::Result;
+
+pub fn run(
+    package: &str,
+    version: &str,
+    scope: Option<crate::cli::InstallScope>,
+    yes: bool,
+) -> Result<()> {
+    let scope_override = scope.map(|s| match s {
+        crate::cli::InstallScope::User => types::Scope::User,
+        crate::cli::InstallScope::System => types::Scope::System,
+        crate::cli::InstallScope::Project => types::Scope::Project,
+    });
+    switch::run(package, version, scope_override, yes)?;
+    Ok(())
+}
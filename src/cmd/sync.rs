@@ -2,10 +2,16 @@ use crate::pkg;
 use anyhow::Result;
 use colored::*;
 
-pub fn run(verbose: bool, fallback: bool, no_pm: bool, files: bool) -> Result<()> {
+pub fn run(
+    verbose: bool,
+    fallback: bool,
+    no_pm: bool,
+    files: bool,
+    at: Option<&str>,
+) -> Result<()> {
     println!("{} Syncing package databases...", "::".bold().blue());
 
-    pkg::sync::run(verbose, fallback, no_pm, files)?;
+    pkg::sync::run(verbose, fallback, no_pm, files, at)?;
 
     println!("{}", "Sync complete.".green());
     Ok(())
@@ -31,7 +37,12 @@ pub fn set_registry(url_or_keyword: &str) -> Result<()> {
 }
 
 pub fn add_registry(url: &str) -> Result<()> {
-    pkg::config::add_added_registry(url)?;
+    let url = if url.starts_with("file://") {
+        pkg::config::add_local_registry(url)?
+    } else {
+        pkg::config::add_added_registry(url)?;
+        url.to_string()
+    };
     println!("Registry '{}' added.", url.cyan());
     println!("It will be synced on the next 'zoi sync' run.");
     Ok(())
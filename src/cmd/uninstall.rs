@@ -121,17 +121,7 @@ pub fn run(
 
     println!("Packages to remove:");
     for manifest in &manifests_to_uninstall {
-        let source_str = if let Some(sub) = &manifest.sub_package {
-            format!(
-                "#{}@{}/{}:{}",
-                manifest.registry_handle, manifest.repo, manifest.name, sub
-            )
-        } else {
-            format!(
-                "#{}@{}/{}",
-                manifest.registry_handle, manifest.repo, manifest.name
-            )
-        };
+        let source_str = pkg::local::installed_manifest_source(manifest);
         println!("  - {}", source_str);
     }
 
@@ -428,7 +418,28 @@ fn resolve_and_add_manifest(
         Err(e) => return Err(format!("Error: Invalid package name '{}': {}", name, e)),
     };
 
-    let mut candidates: Vec<_> = installed_packages
+    let all_versions;
+    let search_in = if let Some(version) = &request.version_spec {
+        let mut manifests = Vec::new();
+        for scope in [
+            types::Scope::Project,
+            types::Scope::User,
+            types::Scope::System,
+        ] {
+            manifests.extend(
+                pkg::local::get_all_version_manifests_in_scope(scope)
+                    .map_err(|e| format!("Error: {}", e))?
+                    .into_iter()
+                    .filter(|m| pkg::switch::version_matches(&m.version, version)),
+            );
+        }
+        all_versions = manifests;
+        &all_versions[..]
+    } else {
+        installed_packages
+    };
+
+    let mut candidates: Vec<_> = search_in
         .iter()
         .filter(|m| {
             let name_matches = m.name == request.name;
@@ -453,6 +464,7 @@ fn resolve_and_add_manifest(
                     && m.sub_package == candidates[0].sub_package
                     && m.repo == candidates[0].repo
                     && m.registry_handle == candidates[0].registry_handle
+                    && m.version == candidates[0].version
             }) {
                 manifests_to_uninstall.push(candidates[0].clone());
             }
@@ -473,6 +485,7 @@ fn resolve_and_add_manifest(
                     && m.repo == chosen.repo
                     && m.registry_handle == chosen.registry_handle
                     && m.scope == chosen.scope
+                    && m.version == chosen.version
             }) {
                 manifests_to_uninstall.push(chosen);
             }
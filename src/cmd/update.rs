@@ -1,6 +1,9 @@
 use crate::cmd::utils as cmd_utils;
 use crate::cmd::ux;
-use crate::pkg::{config, db, hooks, install, local, pin, resolve, transaction, types};
+use crate::pkg::{
+    backup, config, db, features, hooks, install, local, pin, preference, resolve, store,
+    transaction, types,
+};
 use anyhow::{Result, anyhow};
 use colored::*;
 use dialoguer::{MultiSelect, theme::ColorfulTheme};
@@ -249,8 +252,14 @@ fn run_update_single_logic(
         hooks::run_hooks(hooks, hooks::HookType::PreUpgrade)?;
     }
 
+    let update_source = if features::split_source(package_name).1.is_empty() {
+        features::with_features(package_name, &old_manifest.features)
+    } else {
+        package_name.to_string()
+    };
+
     let (graph, _) = install::resolver::resolve_dependency_graph(
-        &[package_name.to_string()],
+        &[update_source],
         Some(old_manifest.scope),
         true,
         yes,
@@ -317,53 +326,9 @@ fn run_update_single_logic(
             transaction::commit(&transaction.id)?;
         }
 
-        if let Some(backup_files) = &old_manifest.backup {
-            println!("Restoring configuration files...");
-            let old_version_dir = local::get_package_version_dir(
-                old_manifest.scope,
-                &old_manifest.registry_handle,
-                &old_manifest.repo,
-                &old_manifest.name,
-                &old_manifest.version,
-            )?;
-            let new_version_dir = local::get_package_version_dir(
-                new_manifest.scope,
-                &new_manifest.registry_handle,
-                &new_manifest.repo,
-                &new_manifest.name,
-                &new_manifest.version,
-            )?;
-
-            for backup_file_rel in backup_files {
-                let old_path = old_version_dir.join(backup_file_rel);
-                let new_path = new_version_dir.join(backup_file_rel);
-
-                if old_path.exists() {
-                    if new_path.exists() {
-                        let zoinew_path = new_path.with_extension(format!(
-                            "{}.zoinew",
-                            new_path
-                                .extension()
-                                .and_then(|s| s.to_str())
-                                .unwrap_or_default()
-                        ));
-                        println!(
-                            "Configuration file '{}' exists in new version. Saving as .zoinew",
-                            new_path.display()
-                        );
-                        if let Err(e) = fs::rename(&new_path, &zoinew_path) {
-                            eprintln!("Warning: failed to rename to .zoinew: {}", e);
-                            continue;
-                        }
-                    }
-                    if let Some(p) = new_path.parent() {
-                        fs::create_dir_all(p)?;
-                    }
-                    if let Err(e) = fs::rename(&old_path, &new_path) {
-                        eprintln!("Warning: failed to restore backup file: {}", e);
-                    }
-                }
-            }
+        match restore_backup_files(&old_manifest, &new_manifest) {
+            Ok(files) => print_pending_backups(&[(new_manifest.name.clone(), files)]),
+            Err(e) => eprintln!("Warning: failed to restore configuration files: {}", e),
         }
 
         cleanup_old_versions(
@@ -428,6 +393,9 @@ fn run_update_all_logic(
     }
 
     let installed_packages = local::get_installed_packages()?;
+    let preferences = config::read_config()
+        .map(|c| c.preferences)
+        .unwrap_or_default();
     let mut pinned_sources = Vec::new();
     let mut skipped_sources = Vec::new();
     let mut up_to_date_sources = Vec::new();
@@ -465,6 +433,17 @@ fn run_update_all_logic(
             continue;
         }
 
+        if let Some(reason) = preference::conflict_for(
+            &preferences,
+            &manifest.name,
+            &manifest.registry_handle,
+            &manifest.repo,
+        ) {
+            skipped_sources.push(format!("{} ({}; reinstall to switch)", source, reason));
+            pb.inc(1);
+            continue;
+        }
+
         let (new_pkg, new_version, _, _, _registry_handle, _) =
             match resolve::resolve_package_and_version(&source, true, false) {
                 Ok(result) => result,
@@ -726,7 +705,10 @@ fn run_update_all_logic(
         }
 
         let (graph, _) = match install::resolver::resolve_dependency_graph(
-            std::slice::from_ref(&candidate.source),
+            &[features::with_features(
+                &candidate.source,
+                &candidate.old_manifest.features,
+            )],
             Some(candidate.old_manifest.scope),
             true,
             yes,
@@ -876,55 +858,14 @@ fn run_update_all_logic(
 
     println!("\n{}", "Success:".green());
     let successful_upgrades = successful_upgrades.into_inner().expect("mutex poisoned");
+    let mut pending_backups = Vec::new();
     for (old_manifest, new_manifest, new_pkg) in &successful_upgrades {
-        if let Some(backup_files) = &old_manifest.backup {
-            println!(
-                "Restoring configuration for {}...",
-                old_manifest.name.cyan()
-            );
-            let old_version_dir = local::get_package_version_dir(
-                old_manifest.scope,
-                &old_manifest.registry_handle,
-                &old_manifest.repo,
-                &old_manifest.name,
-                &old_manifest.version,
-            )?;
-            let new_version_dir = local::get_package_version_dir(
-                new_manifest.scope,
-                &new_manifest.registry_handle,
-                &new_manifest.repo,
-                &new_manifest.name,
-                &new_manifest.version,
-            )?;
-            for backup_file_rel in backup_files {
-                let old_path = old_version_dir.join(backup_file_rel);
-                let new_path = new_version_dir.join(backup_file_rel);
-                if old_path.exists() {
-                    if new_path.exists() {
-                        let zoinew_path = new_path.with_extension(format!(
-                            "{}.zoinew",
-                            new_path
-                                .extension()
-                                .and_then(|s| s.to_str())
-                                .unwrap_or_default()
-                        ));
-                        println!(
-                            "Configuration file '{}' exists in new version. Saving as .zoinew",
-                            new_path.display()
-                        );
-                        if let Err(e) = fs::rename(&new_path, &zoinew_path) {
-                            eprintln!("Warning: failed to rename to .zoinew: {}", e);
-                            continue;
-                        }
-                    }
-                    if let Some(p) = new_path.parent() {
-                        fs::create_dir_all(p)?;
-                    }
-                    if let Err(e) = fs::rename(&old_path, &new_path) {
-                        eprintln!("Warning: failed to restore backup file: {}", e);
-                    }
-                }
-            }
+        match restore_backup_files(old_manifest, new_manifest) {
+            Ok(files) => pending_backups.push((new_manifest.name.clone(), files)),
+            Err(e) => eprintln!(
+                "Warning: failed to restore configuration for {}: {}",
+                old_manifest.name, e
+            ),
         }
 
         if let Err(e) = cleanup_old_versions(
@@ -962,6 +903,8 @@ fn run_update_all_logic(
         }
     }
 
+    print_pending_backups(&pending_backups);
+
     ux::print_transaction_summary(&ux::TransactionSummary {
         command: "update".to_string(),
         success: successful_upgrades.len(),
@@ -1000,6 +943,71 @@ fn advisory_counts(
     Ok((old_count, new_count))
 }
 
+/// Carries the user's `backup` files over to the new version and returns
+/// the `.zoinew` files left for the user to resolve.
+fn restore_backup_files(
+    old_manifest: &types::InstallManifest,
+    new_manifest: &types::InstallManifest,
+) -> Result<Vec<std::path::PathBuf>> {
+    let Some(backup_files) = &old_manifest.backup else {
+        return Ok(Vec::new());
+    };
+    println!(
+        "Restoring configuration for {}...",
+        old_manifest.name.cyan()
+    );
+    let old_version_dir = local::get_package_version_dir(
+        old_manifest.scope,
+        &old_manifest.registry_handle,
+        &old_manifest.repo,
+        &old_manifest.name,
+        &old_manifest.version,
+    )?;
+    let new_version_dir = local::get_package_version_dir(
+        new_manifest.scope,
+        &new_manifest.registry_handle,
+        &new_manifest.repo,
+        &new_manifest.name,
+        &new_manifest.version,
+    )?;
+
+    let mut zoinew_files = Vec::new();
+    for change in backup::carry_over(&old_version_dir, &new_version_dir, backup_files)? {
+        match change.outcome {
+            backup::Outcome::Unchanged => {}
+            backup::Outcome::Updated => {
+                println!(" - {} updated to the new default", change.path.display())
+            }
+            backup::Outcome::Kept => {
+                println!(" - {} kept with your changes", change.path.display())
+            }
+            backup::Outcome::Conflict { zoinew } => zoinew_files.push(zoinew),
+        }
+    }
+    Ok(zoinew_files)
+}
+
+fn print_pending_backups(pending: &[(String, Vec<std::path::PathBuf>)]) {
+    if pending.iter().all(|(_, files)| files.is_empty()) {
+        return;
+    }
+    println!(
+        "\n{} Both you and the new version changed these configuration files. The new versions were saved next to yours:",
+        "::".bold().yellow()
+    );
+    for (name, files) in pending {
+        for file in files {
+            println!("  {}", file.display().to_string().yellow());
+        }
+        if !files.is_empty() {
+            println!(
+                "  Review with 'zoi backup diff {}' and resolve with 'zoi backup merge {}'.",
+                name, name
+            );
+        }
+    }
+}
+
 fn cleanup_old_versions(
     package_name: &str,
     scope: types::Scope,
@@ -1018,6 +1026,7 @@ fn cleanup_old_versions(
             if path.is_dir()
                 && let Some(version_str) = path.file_name().and_then(|s| s.to_str())
                 && version_str != "latest"
+                && !local::is_version_kept(&path)
                 && let Ok(version) = Version::parse(version_str)
             {
                 versions.push(version);
@@ -1042,7 +1051,8 @@ fn cleanup_old_versions(
             let version_dir_to_delete = package_dir.join(version.to_string());
             println!(" - Removing {}", version_dir_to_delete.display());
             if version_dir_to_delete.exists() {
-                fs::remove_dir_all(version_dir_to_delete)?;
+                fs::remove_dir_all(&version_dir_to_delete)?;
+                let _ = store::release(scope, &version_dir_to_delete);
             }
         }
     }
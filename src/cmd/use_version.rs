@@ -0,0 +1,83 @@
+use crate::pkg::plugin::PluginManager;
+use crate::pkg::{config, local, resolve, types, version_files};
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::env;
+
+fn is_installed(request: &resolve::PackageRequest, version: &str) -> Result<bool> {
+    for scope in [
+        types::Scope::User,
+        types::Scope::System,
+        types::Scope::Project,
+    ] {
+        let installed = local::get_all_version_manifests_in_scope(scope)?
+            .into_iter()
+            .any(|m| {
+                m.name == request.name
+                    && m.sub_package == request.sub_package
+                    && (m.version == version || m.version.starts_with(&format!("{}.", version)))
+            });
+        if installed {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+pub fn run(source: &str, global: bool, yes: bool, plugin_manager: &PluginManager) -> Result<()> {
+    let request = resolve::parse_source_string(source)?;
+    let version = request.version_spec.clone().ok_or_else(|| {
+        anyhow!(
+            "No version given. Use '{}@<version>', e.g. 'zoi use node@20'.",
+            request.name
+        )
+    })?;
+
+    if global {
+        let mut user_config = config::read_user_config()?;
+        user_config
+            .versions
+            .insert(request.name.clone(), version.clone());
+        config::write_user_config(&user_config)?;
+        println!(
+            "Using {} {} by default.",
+            request.name.cyan(),
+            version.yellow()
+        );
+    } else {
+        let file = version_files::write_local(&env::current_dir()?, &request.name, &version)?;
+        println!(
+            "Using {} {} in this directory ({}).",
+            request.name.cyan(),
+            version.yellow(),
+            file.display()
+        );
+    }
+
+    if is_installed(&request, &version)? {
+        return Ok(());
+    }
+    println!("{} {} is not installed yet.", request.name, version);
+    crate::cmd::install::run(
+        &[source.to_string()],
+        None,
+        false,
+        false,
+        yes,
+        Some(crate::cli::InstallScope::User),
+        false,
+        false,
+        false,
+        None,
+        false,
+        plugin_manager,
+        false,
+        false,
+        false,
+        false,
+        3,
+        false,
+        false,
+        true,
+    )
+}
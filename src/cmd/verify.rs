@@ -0,0 +1,94 @@
+use crate::pkg::verify::{self, PackageReport, Problem};
+use crate::pkg::{local, resolve, types};
+use anyhow::{Result, anyhow};
+use colored::*;
+
+pub fn run(packages: &[String], json: bool) -> Result<()> {
+    let manifests = if packages.is_empty() {
+        local::get_installed_packages()?
+    } else {
+        let mut manifests = Vec::new();
+        for package in packages {
+            let request = resolve::parse_source_string(package)?;
+            let mut found = Vec::new();
+            for scope in [
+                types::Scope::User,
+                types::Scope::System,
+                types::Scope::Project,
+            ] {
+                found.extend(local::find_installed_manifests_matching(&request, scope)?);
+            }
+            if found.is_empty() {
+                return Err(anyhow!("Package '{}' is not installed.", package));
+            }
+            manifests.extend(found);
+        }
+        manifests
+    };
+
+    let reports: Vec<PackageReport> = manifests.iter().map(verify::verify_package).collect();
+    let failed = reports.iter().filter(|r| !r.is_ok()).count();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        print_reports(&reports);
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} package{} failed verification.",
+            failed,
+            if failed == 1 { "" } else { "s" }
+        ));
+    }
+    Ok(())
+}
+
+fn print_reports(reports: &[PackageReport]) {
+    println!("{} Verifying installed files...", "::".bold().blue());
+    for report in reports {
+        let name = match &report.sub_package {
+            Some(sub) => format!("{}:{}", report.name, sub),
+            None => report.name.clone(),
+        };
+        let label = format!("{} {}", name.cyan(), report.version.yellow());
+
+        if report.unrecorded {
+            println!(
+                "{} {}: no file records, reinstall to enable verification",
+                "?".yellow(),
+                label
+            );
+            continue;
+        }
+        if report.problems.is_empty() {
+            println!(
+                "{} {}: {} files ok",
+                "✓".green(),
+                label,
+                report.checked_files
+            );
+            continue;
+        }
+
+        let marker = if report.is_ok() {
+            "!".yellow()
+        } else {
+            "✗".red()
+        };
+        println!("{} {}", marker, label);
+        for file in &report.problems {
+            let what = match &file.problem {
+                Problem::Missing => "missing".red(),
+                Problem::Modified => "modified".red(),
+                Problem::ModifiedConfig => "modified config".yellow(),
+                Problem::ModeChanged { expected, actual } => {
+                    format!("mode {:o} (expected {:o})", actual, expected).red()
+                }
+                Problem::Unreadable { error } => format!("unreadable: {}", error).red(),
+            };
+            println!("    {}: {}", what, file.path);
+        }
+    }
+}
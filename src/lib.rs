@@ -71,6 +71,8 @@ pub struct BuildOptions<'a> {
     pub image: Option<&'a str>,
     /// Optional package version override.
     pub version_override: Option<&'a str>,
+    /// Feature selection such as `+vaapi` or `-x11`. Empty builds the defaults.
+    pub features: Vec<String>,
 }
 
 impl<'a> Default for BuildOptions<'a> {
@@ -83,6 +85,7 @@ impl<'a> Default for BuildOptions<'a> {
             method: "native",
             image: None,
             version_override: None,
+            features: Vec::new(),
         }
     }
 }
@@ -199,6 +202,8 @@ pub fn build_with_options(package_file: &Path, options: &BuildOptions<'_>) -> Re
         None,
         options.version_override,
         None,
+        &options.features,
+        &[],
         false,
         options.install_deps,
         options.method,
@@ -244,6 +249,7 @@ pub fn install_sources(sources: &[String], options: &SourceInstallOptions) -> Re
         3,
         false,
         false,
+        false,
     )
 }
 
@@ -329,6 +335,7 @@ pub fn build(
         method,
         image,
         version_override,
+        features: Vec::new(),
     };
     build_with_options(package_file, &options)
 }
@@ -0,0 +1,213 @@
+use crate::pkg::{local, store, types::InstallManifest};
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Pristine copies of a version's `backup` files, as the package shipped
+/// them, relative to the version directory.
+const SHIPPED_DIR: &str = ".zoi-shipped";
+/// The previous version's shipped copies of files left with a `.zoinew`,
+/// kept as the common ancestor for `zoi backup merge`.
+const BASE_DIR: &str = ".zoi-merge-base";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Neither the user nor the package changed the file.
+    Unchanged,
+    /// Only the package changed it, so the new version was installed.
+    Updated,
+    /// Only the user changed it, so their copy was kept.
+    Kept,
+    /// Both changed it. The user's copy was kept and the new one written
+    /// next to it.
+    Conflict { zoinew: PathBuf },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupChange {
+    pub path: PathBuf,
+    pub outcome: Outcome,
+}
+
+/// A `backup` file with a `.zoinew` waiting to be resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pending {
+    pub path: PathBuf,
+    pub zoinew: PathBuf,
+    /// The previously shipped copy, when it is known.
+    pub base: Option<PathBuf>,
+}
+
+pub fn zoinew_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}.zoinew", name))
+}
+
+pub fn shipped_path(version_dir: &Path, rel: &str) -> PathBuf {
+    version_dir.join(SHIPPED_DIR).join(rel)
+}
+
+fn base_path(version_dir: &Path, rel: &str) -> PathBuf {
+    version_dir.join(BASE_DIR).join(rel)
+}
+
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(from, to)?;
+    Ok(())
+}
+
+pub fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    Ok(fs::metadata(a)?.len() == fs::metadata(b)?.len()
+        && store::hash_file(a)? == store::hash_file(b)?)
+}
+
+/// Keeps the shipped copies of `files` so later upgrades can tell whether
+/// the user edited them.
+pub fn save_shipped(version_dir: &Path, files: &[String]) -> Result<()> {
+    for rel in files {
+        let path = version_dir.join(rel);
+        if path.is_file() {
+            copy_file(&path, &shipped_path(version_dir, rel))?;
+        }
+    }
+    Ok(())
+}
+
+/// Carries the user's `backup` files from the old version directory into
+/// the freshly installed new one, comparing the old shipped copy, the
+/// user's copy and the new shipped copy.
+pub fn carry_over(
+    old_version_dir: &Path,
+    new_version_dir: &Path,
+    files: &[String],
+) -> Result<Vec<BackupChange>> {
+    let mut changes = Vec::new();
+    for rel in files {
+        let current = old_version_dir.join(rel);
+        if !current.is_file() {
+            continue;
+        }
+        let path = new_version_dir.join(rel);
+        let old_shipped = shipped_path(old_version_dir, rel);
+        let old_shipped = old_shipped.is_file().then_some(old_shipped);
+
+        let outcome = if !path.is_file() {
+            // No longer shipped; the user's copy is all there is.
+            copy_file(&current, &path)?;
+            Outcome::Kept
+        } else if same_content(&current, &path)? {
+            Outcome::Unchanged
+        } else if let Some(base) = &old_shipped
+            && same_content(base, &current)?
+        {
+            Outcome::Updated
+        } else if let Some(base) = &old_shipped
+            && same_content(base, &path)?
+        {
+            copy_file(&current, &path)?;
+            Outcome::Kept
+        } else {
+            let zoinew = zoinew_path(&path);
+            fs::rename(&path, &zoinew)?;
+            copy_file(&current, &path)?;
+            if let Some(base) = &old_shipped {
+                copy_file(base, &base_path(new_version_dir, rel))?;
+            }
+            Outcome::Conflict { zoinew }
+        };
+        changes.push(BackupChange { path, outcome });
+    }
+    Ok(changes)
+}
+
+fn version_dir(manifest: &InstallManifest) -> Result<PathBuf> {
+    local::get_package_version_dir(
+        manifest.scope,
+        &manifest.registry_handle,
+        &manifest.repo,
+        &manifest.name,
+        &manifest.version,
+    )
+}
+
+/// The `backup` files of `manifest` with their current path and shipped
+/// copy, if it was kept.
+pub fn files(manifest: &InstallManifest) -> Result<Vec<(PathBuf, Option<PathBuf>)>> {
+    let version_dir = version_dir(manifest)?;
+    Ok(manifest
+        .backup
+        .iter()
+        .flatten()
+        .map(|rel| {
+            let shipped = shipped_path(&version_dir, rel);
+            (version_dir.join(rel), shipped.is_file().then_some(shipped))
+        })
+        .collect())
+}
+
+pub fn pending(manifest: &InstallManifest) -> Result<Vec<Pending>> {
+    let version_dir = version_dir(manifest)?;
+    Ok(manifest
+        .backup
+        .iter()
+        .flatten()
+        .filter_map(|rel| {
+            let path = version_dir.join(rel);
+            let zoinew = zoinew_path(&path);
+            let base = base_path(&version_dir, rel);
+            zoinew.is_file().then(|| Pending {
+                path,
+                zoinew,
+                base: base.is_file().then_some(base),
+            })
+        })
+        .collect())
+}
+
+fn read_text(path: &Path) -> Result<String> {
+    String::from_utf8(fs::read(path)?)
+        .map_err(|_| anyhow!("'{}' is not a text file.", path.display()))
+}
+
+/// Three-way merges the user's copy with the `.zoinew`. `Ok` holds a clean
+/// merge, `Err` the merge with conflict markers. Without a known base every
+/// differing line conflicts.
+pub fn merge(pending: &Pending) -> Result<std::result::Result<String, String>> {
+    let base = match &pending.base {
+        Some(base) => read_text(base)?,
+        None => String::new(),
+    };
+    let ours = read_text(&pending.path)?;
+    let theirs = read_text(&pending.zoinew)?;
+    Ok(diffy::merge(&base, &ours, &theirs))
+}
+
+/// Writes `content` over the user's copy and drops the `.zoinew`.
+pub fn resolve(pending: &Pending, content: &[u8]) -> Result<()> {
+    fs::write(&pending.path, content)?;
+    fs::remove_file(&pending.zoinew)?;
+    if let Some(base) = &pending.base {
+        let _ = fs::remove_file(base);
+    }
+    Ok(())
+}
+
+/// Unified diff from `from` to `to`, or `None` for files that are not text.
+pub fn diff(from: &Path, to: &Path, color: bool) -> Result<Option<String>> {
+    let (Ok(old), Ok(new)) = (read_text(from), read_text(to)) else {
+        return Ok(None);
+    };
+    let patch = diffy::create_patch(&old, &new);
+    let formatter = if color {
+        diffy::PatchFormatter::new().with_color()
+    } else {
+        diffy::PatchFormatter::new()
+    };
+    Ok(Some(formatter.fmt_patch(&patch).to_string()))
+}
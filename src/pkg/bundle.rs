@@ -0,0 +1,419 @@
+use crate::pkg::install::{resolver, util};
+use crate::pkg::{cache, config, pgp, resolve, snapshot, sync, types};
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder as TarBuilder};
+use tempfile::Builder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+/// Name of the manifest stored at the root of every bundle.
+pub const MANIFEST_FILE: &str = "bundle.json";
+
+/// Newest bundle format this version of Zoi can read.
+pub const BUNDLE_FORMAT: u32 = 1;
+
+/// Sidecars exported next to each archive, named `<archive>.<ext>` like the
+/// `.sig` files already kept in the archive cache.
+const SIDECARS: [&str; 4] = ["hash", "sig", "size", "files"];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundleManifest {
+    pub format: u32,
+    pub created: String,
+    pub platform: String,
+    #[serde(default)]
+    pub registries: Vec<BundledRegistry>,
+    #[serde(default)]
+    pub archives: Vec<String>,
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BundledRegistry {
+    pub handle: String,
+    pub url: String,
+    /// Snapshot archive inside the bundle, see [`snapshot::create_snapshot`].
+    pub snapshot: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub registries: Vec<String>,
+    pub archives: usize,
+    pub keys: usize,
+}
+
+fn configured_registries() -> Result<Vec<types::Registry>> {
+    let config = config::read_config()?;
+    let mut registries: Vec<_> = config.default_registry.into_iter().collect();
+    registries.extend(config.added_registries);
+    registries.retain(|r| !r.handle.is_empty());
+    Ok(registries)
+}
+
+fn fetch_sidecar(url: &str, dest: &Path, binary: bool) -> Result<()> {
+    if binary {
+        util::download_file_with_progress(url, dest, None, None)
+    } else {
+        let text =
+            util::get_text_from_candidate_urls(&cache::mirror_candidate_urls(url), "sidecar")?;
+        fs::write(dest, text)?;
+        Ok(())
+    }
+}
+
+/// Copies a package archive and its sidecars into `archives_dir`, taking them
+/// from the archive cache when present and downloading them otherwise.
+fn bundle_archive(node: &resolver::InstallNode, archives_dir: &Path) -> Result<Option<String>> {
+    let Some(info) = util::find_prebuilt_info(node)? else {
+        return Ok(None);
+    };
+    let archive_cache_root = cache::get_archive_cache_root()?;
+    let filename = util::get_filename_from_url(&info.final_url).to_string();
+    let dest = archives_dir.join(&filename);
+
+    let cached = archive_cache_root.join(&filename);
+    if cached.exists() {
+        fs::copy(&cached, &dest)?;
+    } else if crate::pkg::offline::is_offline() {
+        return Err(anyhow!(
+            "Archive {} is not cached and Zoi is in offline mode.",
+            filename
+        ));
+    } else {
+        let mut last_error = None;
+        for url in cache::mirror_candidate_urls(&info.final_url) {
+            match util::download_file_with_progress(&url, &dest, None, None) {
+                Ok(()) => {
+                    last_error = None;
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+
+    let sidecar_urls = [
+        ("hash", &info.hash_url),
+        ("sig", &info.pgp_url),
+        ("size", &info.size_url),
+        ("files", &info.files_url),
+    ];
+    for (ext, url) in sidecar_urls {
+        let sidecar_name = format!("{}.{}", filename, ext);
+        let cached = archive_cache_root.join(&sidecar_name);
+        let dest = archives_dir.join(&sidecar_name);
+        if cached.exists() {
+            fs::copy(&cached, &dest)?;
+        } else if let Some(url) = url
+            && !crate::pkg::offline::is_offline()
+            && let Err(e) = fetch_sidecar(url, &dest, ext == "sig")
+        {
+            eprintln!(
+                "{} Could not fetch {} for {}: {}",
+                "Warning:".yellow(),
+                ext,
+                filename,
+                e
+            );
+        }
+    }
+
+    Ok(Some(filename))
+}
+
+/// Writes a self-contained bundle with snapshots of every configured registry,
+/// the prebuilt archives for `packages` and their dependencies, and the PGP
+/// keys the registries declare, so it can be imported on a machine without
+/// network access. With `sign_key`, every snapshot gets a detached signature
+/// so registries with `authorities` accept it on import.
+pub fn export_bundle(
+    output: &Path,
+    packages: &[String],
+    sign_key: Option<&str>,
+) -> Result<BundleManifest> {
+    let db_root = resolve::get_db_root()?;
+    let staging = Builder::new().prefix("zoi-bundle-").tempdir()?;
+    let registries_dir = staging.path().join("registries");
+    let archives_dir = staging.path().join("archives");
+    let keys_dir = staging.path().join("keys");
+    fs::create_dir_all(&registries_dir)?;
+    fs::create_dir_all(&archives_dir)?;
+    fs::create_dir_all(&keys_dir)?;
+
+    let pgp_dir = pgp::get_pgp_dir()?;
+    let mut manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        created: Utc::now().to_rfc3339(),
+        platform: crate::utils::get_platform()?,
+        registries: Vec::new(),
+        archives: Vec::new(),
+        keys: Vec::new(),
+    };
+
+    for registry in configured_registries()? {
+        let path = db_root.join(&registry.handle);
+        if !path.join("repo.yaml").exists() {
+            continue;
+        }
+        println!("Packing registry {}...", registry.handle.cyan());
+        let snapshot_name = format!("{}.tar.zst", registry.handle);
+        // A registry synced from a snapshot must come out newer than it.
+        let serial = snapshot::next_serial(snapshot::read_index(&path).ok().map(|i| i.serial));
+        let snapshot_path = registries_dir.join(&snapshot_name);
+        snapshot::create_snapshot(&path, &snapshot_path, serial)?;
+        if let Some(key_id) = sign_key {
+            let signature_path = PathBuf::from(format!("{}.sig", snapshot_path.display()));
+            pgp::sign_detached(&snapshot_path, &signature_path, key_id)?;
+        }
+        manifest.registries.push(BundledRegistry {
+            handle: registry.handle.clone(),
+            url: registry.url.clone(),
+            snapshot: format!("registries/{}", snapshot_name),
+        });
+
+        for key in config::read_repo_config(&path)?.pgp {
+            let key_file = format!("{}.asc", key.name);
+            if manifest.keys.contains(&key.name) {
+                continue;
+            }
+            if pgp_dir.join(&key_file).exists() {
+                fs::copy(pgp_dir.join(&key_file), keys_dir.join(&key_file))?;
+                manifest.keys.push(key.name);
+            } else {
+                eprintln!(
+                    "{} PGP key '{}' is not imported locally; run 'zoi sync' first to include it.",
+                    "Warning:".yellow(),
+                    key.name
+                );
+            }
+        }
+    }
+
+    if manifest.registries.is_empty() {
+        return Err(anyhow!(
+            "No synced registries to export. Run 'zoi sync' first."
+        ));
+    }
+
+    if !packages.is_empty() {
+        let (graph, _) =
+            resolver::resolve_dependency_graph(packages, None, false, true, false, None, true)?;
+        for node in graph.nodes.values() {
+            println!("Adding {}@{}...", node.pkg.name.cyan(), node.version);
+            match bundle_archive(node, &archives_dir)? {
+                Some(filename) => {
+                    if !manifest.archives.contains(&filename) {
+                        manifest.archives.push(filename);
+                    }
+                }
+                None => eprintln!(
+                    "{} {} has no prebuilt archive and will have to be built from source.",
+                    "Warning:".yellow(),
+                    node.pkg.name
+                ),
+            }
+        }
+        manifest.archives.sort();
+    }
+
+    fs::write(
+        staging.path().join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )?;
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let encoder = ZstdEncoder::new(File::create(output)?, 0)?.auto_finish();
+    let mut tar_builder = TarBuilder::new(encoder);
+    tar_builder.append_dir_all(".", staging.path())?;
+    tar_builder.finish()?;
+
+    Ok(manifest)
+}
+
+fn register_bundled_registry(bundled: &BundledRegistry) -> Result<()> {
+    let mut user_config = config::read_user_config()?;
+    let known = configured_registries()?
+        .iter()
+        .any(|r| r.handle == bundled.handle);
+    if known {
+        return Ok(());
+    }
+    let registry = types::Registry {
+        handle: bundled.handle.clone(),
+        url: bundled.url.clone(),
+        advisory_prefix: None,
+        authorities: None,
+    };
+    if user_config.default_registry.is_none() && config::read_config()?.default_registry.is_none() {
+        user_config.default_registry = Some(registry);
+    } else {
+        user_config.added_registries.push(registry);
+    }
+    config::write_user_config(&user_config)
+}
+
+/// Loads a bundle written by [`export_bundle`] into the DB root, the archive
+/// cache and the PGP keyring.
+///
+/// A bundle is untrusted input: registries with `authorities` only take
+/// snapshots signed by them, git checkouts are only replaced with `replace`,
+/// and every key is confirmed before it is trusted.
+pub fn import_bundle(bundle: &Path, replace: bool, yes: bool) -> Result<ImportSummary> {
+    let staging = Builder::new().prefix("zoi-bundle-").tempdir()?;
+    Archive::new(ZstdDecoder::new(File::open(bundle)?)?).unpack(staging.path())?;
+
+    let manifest_path = staging.path().join(MANIFEST_FILE);
+    let manifest: BundleManifest = serde_json::from_str(
+        &fs::read_to_string(&manifest_path)
+            .map_err(|_| anyhow!("{} is not a Zoi bundle.", bundle.display()))?,
+    )?;
+    if manifest.format > BUNDLE_FORMAT {
+        return Err(anyhow!(
+            "Bundle uses format {}, but this version of Zoi only supports up to {}.",
+            manifest.format,
+            BUNDLE_FORMAT
+        ));
+    }
+    let platform = crate::utils::get_platform()?;
+    if !manifest.archives.is_empty() && manifest.platform != platform {
+        eprintln!(
+            "{} Bundle archives were built for {}, this machine is {}.",
+            "Warning:".yellow(),
+            manifest.platform,
+            platform
+        );
+    }
+
+    let mut summary = ImportSummary::default();
+
+    // Registries go first, so keys from the bundle can't vouch for them.
+    let db_root = resolve::get_db_root()?;
+    for bundled in &manifest.registries {
+        let archive = staging.path().join(safe_relative(&bundled.snapshot)?);
+        let signature = PathBuf::from(format!("{}.sig", archive.display()));
+        let signature = signature.exists().then_some(signature);
+        let authorities = configured_registries()?
+            .into_iter()
+            .find(|r| r.handle == bundled.handle)
+            .and_then(|r| r.authorities)
+            .unwrap_or_default();
+        let is_git = db_root.join(&bundled.handle).join(".git").exists();
+
+        let verified = !authorities.is_empty() && signature.is_some();
+        if !verified && (!authorities.is_empty() || is_git) {
+            let reason = if authorities.is_empty() {
+                "is a git checkout, which the bundle's snapshot would replace"
+            } else {
+                "only accepts snapshots signed by its authorities, but the bundle's is unsigned"
+            };
+            if !replace {
+                return Err(anyhow!(
+                    "Registry '{}' {}. Pass --replace to overwrite it anyway.",
+                    bundled.handle,
+                    reason
+                ));
+            }
+            let prompt = format!("Registry '{}' {}. Replace it?", bundled.handle, reason);
+            if !crate::utils::ask_for_confirmation(&prompt, yes) {
+                return Err(anyhow!("Import aborted."));
+            }
+        }
+        let (signature, authorities): (Option<&Path>, &[String]) = if verified {
+            (signature.as_deref(), &authorities)
+        } else {
+            (None, &[])
+        };
+        let installed = snapshot::install_snapshot(
+            &archive,
+            signature,
+            authorities,
+            &db_root,
+            Some(&bundled.handle),
+        )?;
+        let path = db_root.join(&installed.handle);
+        sync::index_snapshot_registry(&installed.handle, &path, false, None, false, None)?;
+        register_bundled_registry(bundled)?;
+        summary.registries.push(installed.handle);
+    }
+
+    for key in &manifest.keys {
+        if key.is_empty() || key.contains(['/', '\\']) || key.contains("..") {
+            return Err(anyhow!("Bundle contains an unsafe key name: {}", key));
+        }
+        let path = staging.path().join("keys").join(format!("{}.asc", key));
+        let bytes = fs::read(&path)?;
+        let existing = pgp::read_key_bytes(key)?;
+        if existing.as_deref() == Some(bytes.as_slice()) {
+            continue;
+        }
+        if existing.is_some() && !replace {
+            eprintln!(
+                "{} Skipping key '{}': a different key with that name is already trusted. Pass --replace to overwrite it.",
+                "Warning:".yellow(),
+                key
+            );
+            continue;
+        }
+        let prompt = format!(
+            "Trust PGP key '{}' ({}) from this bundle?",
+            key,
+            pgp::fingerprint_from_bytes(&bytes)?
+        );
+        if !crate::utils::ask_for_confirmation(&prompt, yes) {
+            println!("Skipping key '{}'.", key);
+            continue;
+        }
+        pgp::add_key_from_bytes(&bytes, key, true)?;
+        summary.keys += 1;
+    }
+
+    let archive_cache_root = cache::get_archive_cache_root()?;
+    fs::create_dir_all(&archive_cache_root)?;
+    let archives_dir = staging.path().join("archives");
+    for filename in &manifest.archives {
+        let filename = safe_relative(filename)?;
+        fs::copy(
+            archives_dir.join(&filename),
+            archive_cache_root.join(&filename),
+        )?;
+        for ext in SIDECARS {
+            let sidecar = PathBuf::from(format!("{}.{}", filename.display(), ext));
+            if archives_dir.join(&sidecar).exists() {
+                fs::copy(
+                    archives_dir.join(&sidecar),
+                    archive_cache_root.join(&sidecar),
+                )?;
+            }
+        }
+        summary.archives += 1;
+    }
+
+    Ok(summary)
+}
+
+fn safe_relative(path: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(path);
+    if path.is_absolute()
+        || path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+    {
+        return Err(anyhow!(
+            "Bundle contains an unsafe path: {}",
+            path.display()
+        ));
+    }
+    Ok(path)
+}
@@ -13,6 +13,29 @@ pub fn get_archive_cache_root() -> Result<PathBuf> {
     Ok(cache_root.join("archives"))
 }
 
+/// Reads a sidecar stored next to a cached archive, e.g. `<archive>.hash`.
+/// Bundles imported with `zoi repo import` provide these for offline installs.
+pub fn read_cached_sidecar(archive_filename: &str, extension: &str) -> Option<String> {
+    let path = get_archive_cache_root()
+        .ok()?
+        .join(format!("{}.{}", archive_filename, extension));
+    fs::read_to_string(path).ok()
+}
+
+/// Keeps a fetched sidecar next to the cached archive so offline installs and
+/// `zoi serve` can reuse it. Failures are ignored, the cache is best effort.
+pub fn write_cached_sidecar(archive_filename: &str, extension: &str, content: &str) {
+    let Ok(root) = get_archive_cache_root() else {
+        return;
+    };
+    if fs::create_dir_all(&root).is_ok() {
+        let _ = fs::write(
+            root.join(format!("{}.{}", archive_filename, extension)),
+            content,
+        );
+    }
+}
+
 pub fn get_pkgdef_cache_root() -> Result<PathBuf> {
     let cache_root = get_cache_root()?;
     Ok(cache_root.join("pkgdefs"))
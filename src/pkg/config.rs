@@ -40,8 +40,8 @@ fn get_user_config_path() -> Result<PathBuf> {
 }
 
 fn get_project_config_path() -> Result<PathBuf> {
-    let current_dir = std::env::current_dir()?;
-    Ok(current_dir.join(".zoi").join("pkgs").join("config.yaml"))
+    let project_root = crate::project::config::project_root()?;
+    Ok(project_root.join(".zoi").join("pkgs").join("config.yaml"))
 }
 
 fn get_git_root() -> Result<PathBuf> {
@@ -210,6 +210,14 @@ pub fn read_config() -> Result<Config> {
         merged_cfg.offline_mode = system_cfg.offline_mode;
     }
 
+    if project_val.get("sandbox").is_some() && !system_policy.sandbox_unoverridable {
+        merged_cfg.sandbox = project_cfg.sandbox;
+    } else if user_val.get("sandbox").is_some() && !system_policy.sandbox_unoverridable {
+        merged_cfg.sandbox = user_cfg.sandbox;
+    } else {
+        merged_cfg.sandbox = system_cfg.sandbox;
+    }
+
     if project_val
         .get("policy")
         .and_then(|p| p.get("advisory_enforcement_unoverridable"))
@@ -235,6 +243,10 @@ pub fn read_config() -> Result<Config> {
     merged_cfg.versions.extend(user_cfg.versions);
     merged_cfg.versions.extend(project_cfg.versions);
 
+    merged_cfg.providers = system_cfg.providers;
+    merged_cfg.providers.extend(user_cfg.providers);
+    merged_cfg.providers.extend(project_cfg.providers);
+
     merged_cfg.pkg_dirs = system_cfg.pkg_dirs;
     if !system_policy.pkg_dirs_unoverridable {
         merged_cfg.pkg_dirs.extend(user_cfg.pkg_dirs);
@@ -251,6 +263,12 @@ pub fn read_config() -> Result<Config> {
     merged_cfg.cache_mirrors.sort();
     merged_cfg.cache_mirrors.dedup();
 
+    if !system_policy.preferences_unoverridable {
+        merged_cfg.preferences.extend(project_cfg.preferences);
+        merged_cfg.preferences.extend(user_cfg.preferences);
+    }
+    merged_cfg.preferences.extend(system_cfg.preferences);
+
     if !system_policy.allow_deny_lists_unoverridable {
         if project_cfg.policy.allowed_licenses.is_some() {
             merged_cfg.policy.allowed_licenses = project_cfg.policy.allowed_licenses;
@@ -595,6 +613,36 @@ pub fn add_added_registry(url: &str) -> Result<()> {
     write_user_config(&config)
 }
 
+/// Registers a local directory as a registry. The directory is indexed in
+/// place on every sync instead of being cloned. Returns the stored URL.
+pub fn add_local_registry(url: &str) -> Result<String> {
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    let dir = fs::canonicalize(path)
+        .map_err(|e| anyhow!("Cannot access local registry '{}': {}", path, e))?;
+    if !dir.join("repo.yaml").exists() {
+        return Err(anyhow!(
+            "'{}' is not a registry: repo.yaml not found.",
+            dir.display()
+        ));
+    }
+    let name = read_repo_config(&dir)?.name;
+    let config = read_config()?;
+    let taken = config
+        .default_registry
+        .iter()
+        .chain(config.added_registries.iter())
+        .any(|r| r.handle == name);
+    if taken || get_db_root()?.join(&name).exists() {
+        return Err(anyhow!(
+            "A registry named '{}' already exists; rename the local registry in its repo.yaml.",
+            name
+        ));
+    }
+    let url = format!("file://{}", dir.display());
+    add_added_registry(&url)?;
+    Ok(url)
+}
+
 pub fn remove_added_registry(handle_or_url: &str) -> Result<()> {
     let mut config = read_config_from_path(&get_user_config_path()?)?;
     if let Some(pos) = config
@@ -606,7 +654,9 @@ pub fn remove_added_registry(handle_or_url: &str) -> Result<()> {
         if !removed_registry.handle.is_empty() {
             let db_root = get_db_root()?;
             let repo_path = db_root.join(removed_registry.handle);
-            if repo_path.exists() {
+            if repo_path.is_symlink() {
+                fs::remove_file(&repo_path).or_else(|_| fs::remove_dir(&repo_path))?;
+            } else if repo_path.exists() {
                 fs::remove_dir_all(repo_path)?;
             }
         }
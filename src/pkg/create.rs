@@ -99,6 +99,8 @@ pub fn run(
         Some(build_dir.path()),
         pkg.version.as_deref(),
         None,
+        &[],
+        &[],
         false,
         false,
         "native",
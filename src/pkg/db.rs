@@ -1,7 +1,8 @@
 use crate::pkg::types;
 use anyhow::Result;
-use rusqlite::{Connection, params};
-use std::path::PathBuf;
+use rusqlite::{Connection, OpenFlags, Transaction, TransactionBehavior, params};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
 
 pub fn get_db_path(registry_handle: &str) -> Result<PathBuf> {
     let db_root = crate::pkg::resolve::get_db_root()?;
@@ -33,8 +34,126 @@ pub fn open_connection_no_setup(registry_handle: &str) -> Result<Connection> {
     Ok(conn)
 }
 
+/// Schema version this build of Zoi writes, stored in `PRAGMA user_version`.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+type Migration = fn(&Connection) -> Result<()>;
+
+/// Ordered schema migrations. Entry `n` upgrades a database from version `n`
+/// to `n + 1`. Released entries must never be edited or reordered, only
+/// appended to.
+const MIGRATIONS: &[Migration] = &[
+    migrate_base_tables,
+    migrate_package_columns,
+    migrate_search_index,
+    migrate_index_sources,
+    migrate_provides_column,
+    migrate_features_column,
+];
+
+#[derive(Error, Debug)]
+pub enum SchemaError {
+    #[error(
+        "database '{path}' uses schema version {found}, but this version of Zoi only supports up to {supported}. It was written by a newer Zoi"
+    )]
+    TooNew {
+        path: String,
+        found: i64,
+        supported: i64,
+    },
+}
+
+pub fn schema_version(conn: &Connection) -> Result<i64> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Reads the schema version of a database file without migrating it.
+pub fn probe_schema_version(db_path: &Path) -> Result<i64> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    schema_version(&conn)
+}
+
+/// Deletes a database file together with its WAL side files so that it can be
+/// rebuilt from scratch.
+pub fn remove_database(registry_handle: &str) -> Result<()> {
+    let db_path = get_db_path(registry_handle)?;
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = db_path.clone().into_os_string();
+        path.push(suffix);
+        let path = PathBuf::from(path);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
 fn setup_schema(conn: &Connection) -> Result<()> {
-    conn.execute(
+    migrate_to(conn, SCHEMA_VERSION)
+}
+
+/// Applies all pending migrations up to `target` inside one transaction, so a
+/// database is never left half-migrated.
+pub fn migrate_to(conn: &Connection, target: i64) -> Result<()> {
+    let target = target.min(SCHEMA_VERSION);
+    let current = schema_version(conn)?;
+    check_not_newer(conn, current)?;
+    if current >= target {
+        return Ok(());
+    }
+
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let current = schema_version(&tx)?;
+    check_not_newer(&tx, current)?;
+
+    for (idx, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .take(target as usize)
+        .skip(current.max(0) as usize)
+    {
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", idx as i64 + 1)?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+fn check_not_newer(conn: &Connection, version: i64) -> Result<()> {
+    if version > SCHEMA_VERSION {
+        let path = conn.path().unwrap_or_default().to_string();
+        return Err(SchemaError::TooNew {
+            path,
+            found: version,
+            supported: SCHEMA_VERSION,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn migrate_base_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS packages (
             id INTEGER PRIMARY KEY,
             name TEXT NOT NULL,
@@ -44,67 +163,26 @@ fn setup_schema(conn: &Connection) -> Result<()> {
             description TEXT,
             package_type TEXT,
             tags TEXT,
-            bins TEXT,
             license TEXT,
             registry TEXT,
             scope TEXT,
             reason TEXT,
-            dependencies TEXT,
             UNIQUE(name, sub_package, repo, scope)
-        )",
-        [],
-    )?;
-
-    let has_deps: bool = conn
-        .query_row(
-            "SELECT count(*) FROM pragma_table_info('packages') WHERE name='dependencies'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-        > 0;
-
-    if !has_deps {
-        let _ = conn.execute("ALTER TABLE packages ADD COLUMN dependencies TEXT", []);
-    }
-
-    let column_exists: bool = conn
-        .query_row(
-            "SELECT count(*) FROM pragma_table_info('packages') WHERE name='bins'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-        > 0;
-
-    if !column_exists {
-        let _ = conn.execute("ALTER TABLE packages ADD COLUMN bins TEXT", []);
-    }
-
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_packages_name ON packages(name)",
-        [],
-    )?;
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_packages_repo ON packages(repo)",
-        [],
-    )?;
+        );
+        CREATE INDEX IF NOT EXISTS idx_packages_name ON packages(name);
+        CREATE INDEX IF NOT EXISTS idx_packages_repo ON packages(repo);
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS package_files (
+        CREATE TABLE IF NOT EXISTS package_files (
             id INTEGER PRIMARY KEY,
             package_id INTEGER,
             path TEXT NOT NULL,
             FOREIGN KEY(package_id) REFERENCES packages(id) ON DELETE CASCADE
-        )",
-        [],
-    )?;
+        );
+        CREATE INDEX IF NOT EXISTS idx_package_files_path ON package_files(path);
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS package_advisories (
+        CREATE TABLE IF NOT EXISTS package_advisories (
             id TEXT PRIMARY KEY,
             package TEXT NOT NULL,
-            sub_package TEXT,
             summary TEXT NOT NULL,
             severity TEXT NOT NULL,
             cvss TEXT,
@@ -114,141 +192,86 @@ fn setup_schema(conn: &Connection) -> Result<()> {
             references_json TEXT,
             repo TEXT,
             registry TEXT
-        )",
-        [],
+        );",
     )?;
+    Ok(())
+}
 
-    let has_sub_pkg_adv: bool = conn
-        .query_row(
-            "SELECT count(*) FROM pragma_table_info('package_advisories') WHERE name='sub_package'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-        > 0;
-
-    if !has_sub_pkg_adv {
-        let _ = conn.execute(
-            "ALTER TABLE package_advisories ADD COLUMN sub_package TEXT",
-            [],
-        );
-    }
-
+fn migrate_package_columns(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "packages", "dependencies", "TEXT")?;
+    add_column_if_missing(conn, "packages", "bins", "TEXT")?;
+    add_column_if_missing(conn, "package_advisories", "sub_package", "TEXT")?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_package_advisories_package ON package_advisories(package, sub_package)",
         [],
     )?;
+    Ok(())
+}
+
+fn migrate_search_index(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DROP TRIGGER IF EXISTS packages_ai;
+        DROP TRIGGER IF EXISTS packages_ad;
+        DROP TRIGGER IF EXISTS packages_au;
+        DROP TABLE IF EXISTS packages_fts;
+
+        CREATE VIRTUAL TABLE packages_fts USING fts5(name, description, tags, bins, content='packages', content_rowid='id');
+        INSERT INTO packages_fts(rowid, name, description, tags, bins)
+            SELECT id, name, description, tags, bins FROM packages;
+
+        CREATE TRIGGER packages_ai AFTER INSERT ON packages BEGIN
+            INSERT INTO packages_fts(rowid, name, description, tags, bins) VALUES (new.id, new.name, new.description, new.tags, new.bins);
+        END;
+        CREATE TRIGGER packages_ad AFTER DELETE ON packages BEGIN
+            INSERT INTO packages_fts(packages_fts, rowid, name, description, tags, bins) VALUES('delete', old.id, old.name, old.description, old.tags, old.bins);
+        END;
+        CREATE TRIGGER packages_au AFTER UPDATE ON packages BEGIN
+            INSERT INTO packages_fts(packages_fts, rowid, name, description, tags, bins) VALUES('delete', old.id, old.name, old.description, old.tags, old.bins);
+            INSERT INTO packages_fts(rowid, name, description, tags, bins) VALUES (new.id, new.name, new.description, new.tags, new.bins);
+        END;
+
+        DROP TRIGGER IF EXISTS package_files_ai;
+        DROP TRIGGER IF EXISTS package_files_ad;
+        DROP TRIGGER IF EXISTS package_files_au;
+        DROP TABLE IF EXISTS package_files_fts;
+
+        CREATE VIRTUAL TABLE package_files_fts USING fts5(path, content='package_files', content_rowid='id');
+        INSERT INTO package_files_fts(rowid, path) SELECT id, path FROM package_files;
+
+        CREATE TRIGGER package_files_ai AFTER INSERT ON package_files BEGIN
+            INSERT INTO package_files_fts(rowid, path) VALUES (new.id, new.path);
+        END;
+        CREATE TRIGGER package_files_ad AFTER DELETE ON package_files BEGIN
+            INSERT INTO package_files_fts(package_files_fts, rowid, path) VALUES('delete', old.id, old.path);
+        END;
+        CREATE TRIGGER package_files_au AFTER UPDATE ON package_files BEGIN
+            INSERT INTO package_files_fts(package_files_fts, rowid, path) VALUES('delete', old.id, old.path);
+            INSERT INTO package_files_fts(rowid, path) VALUES (new.id, new.path);
+        END;",
+    )?;
+    Ok(())
+}
 
+fn migrate_index_sources(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "packages", "source", "TEXT")?;
+    add_column_if_missing(conn, "package_advisories", "source", "TEXT")?;
     conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_package_files_path ON package_files(path)",
+        "CREATE TABLE IF NOT EXISTS registry_meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
         [],
     )?;
+    Ok(())
+}
 
-    let mut fts_needs_rebuild = false;
-    let fts_exists: bool = conn
-        .query_row(
-            "SELECT count(*) FROM sqlite_master WHERE type='table' AND name='packages_fts'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-        > 0;
-
-    if fts_exists {
-        let has_bins_fts: bool = conn
-            .query_row(
-                "SELECT count(*) FROM pragma_table_info('packages_fts') WHERE name='bins'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or(0)
-            > 0;
-        if !has_bins_fts {
-            fts_needs_rebuild = true;
-        }
-    } else {
-        fts_needs_rebuild = true;
-    }
-
-    if fts_needs_rebuild {
-        let _ = conn.execute("DROP TABLE IF EXISTS packages_fts", []);
-        let _ = conn.execute("DROP TRIGGER IF EXISTS packages_ai", []);
-        let _ = conn.execute("DROP TRIGGER IF EXISTS packages_ad", []);
-        let _ = conn.execute("DROP TRIGGER IF EXISTS packages_au", []);
-
-        let _ = conn.execute(
-            "CREATE VIRTUAL TABLE packages_fts USING fts5(name, description, tags, bins, content='packages', content_rowid='id')",
-            [],
-        );
-
-        let _ = conn.execute(
-            "INSERT INTO packages_fts(rowid, name, description, tags, bins) 
-             SELECT id, name, description, tags, bins FROM packages",
-            [],
-        );
-
-        let _ = conn.execute(
-            "CREATE TRIGGER packages_ai AFTER INSERT ON packages BEGIN
-                INSERT INTO packages_fts(rowid, name, description, tags, bins) VALUES (new.id, new.name, new.description, new.tags, new.bins);
-            END",
-            [],
-        );
-        let _ = conn.execute(
-            "CREATE TRIGGER packages_ad AFTER DELETE ON packages BEGIN
-                INSERT INTO packages_fts(packages_fts, rowid, name, description, tags, bins) VALUES('delete', old.id, old.name, old.description, old.tags, old.bins);
-            END",
-            [],
-        );
-        let _ = conn.execute(
-            "CREATE TRIGGER packages_au AFTER UPDATE ON packages BEGIN
-                INSERT INTO packages_fts(packages_fts, rowid, name, description, tags, bins) VALUES('delete', old.id, old.name, old.description, old.tags, old.bins);
-                INSERT INTO packages_fts(rowid, name, description, tags, bins) VALUES (new.id, new.name, new.description, new.tags, new.bins);
-            END",
-            [],
-        );
-    }
-
-    let files_fts_exists: bool = conn
-        .query_row(
-            "SELECT count(*) FROM sqlite_master WHERE type='table' AND name='package_files_fts'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-        > 0;
-
-    if !files_fts_exists {
-        let _ = conn.execute(
-            "CREATE VIRTUAL TABLE package_files_fts USING fts5(path, content='package_files', content_rowid='id')",
-            [],
-        );
-
-        let _ = conn.execute(
-            "INSERT INTO package_files_fts(rowid, path) SELECT id, path FROM package_files",
-            [],
-        );
-
-        let _ = conn.execute(
-            "CREATE TRIGGER package_files_ai AFTER INSERT ON package_files BEGIN
-                INSERT INTO package_files_fts(rowid, path) VALUES (new.id, new.path);
-            END",
-            [],
-        );
-        let _ = conn.execute(
-            "CREATE TRIGGER package_files_ad AFTER DELETE ON package_files BEGIN
-                INSERT INTO package_files_fts(package_files_fts, rowid, path) VALUES('delete', old.id, old.path);
-            END",
-            [],
-        );
-        let _ = conn.execute(
-            "CREATE TRIGGER package_files_au AFTER UPDATE ON package_files BEGIN
-                INSERT INTO package_files_fts(package_files_fts, rowid, path) VALUES('delete', old.id, old.path);
-                INSERT INTO package_files_fts(rowid, path) VALUES (new.id, new.path);
-            END",
-            [],
-        );
-    }
+fn migrate_provides_column(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "packages", "provides", "TEXT")?;
+    Ok(())
+}
 
+fn migrate_features_column(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "packages", "features", "TEXT")?;
     Ok(())
 }
 
@@ -276,10 +299,16 @@ pub fn update_package(
     } else {
         String::new()
     };
+    let provides_json =
+        serde_json::to_string(&pkg.provides.as_ref().unwrap_or(&vec![])).unwrap_or_default();
+    let features_json = pkg
+        .features
+        .as_ref()
+        .map(|f| serde_json::to_string(f).unwrap_or_default());
 
     conn.execute(
-        "INSERT INTO packages (name, sub_package, repo, version, description, package_type, tags, bins, license, registry, scope, reason, dependencies)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        "INSERT INTO packages (name, sub_package, repo, version, description, package_type, tags, bins, license, registry, scope, reason, dependencies, provides, features)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
          ON CONFLICT(name, sub_package, repo, scope) DO UPDATE SET
             version = excluded.version,
             description = excluded.description,
@@ -289,7 +318,9 @@ pub fn update_package(
             license = excluded.license,
             registry = excluded.registry,
             reason = COALESCE(excluded.reason, packages.reason),
-            dependencies = excluded.dependencies",
+            dependencies = excluded.dependencies,
+            provides = excluded.provides,
+            features = excluded.features",
         params![
             pkg.name,
             sub_package,
@@ -304,6 +335,8 @@ pub fn update_package(
             scope_str,
             reason_str,
             deps_json,
+            provides_json,
+            features_json,
         ],
     )?;
 
@@ -509,6 +542,84 @@ pub fn delete_package(
     Ok(())
 }
 
+pub fn set_package_source(conn: &Connection, package_id: i64, source: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE packages SET source = ?1 WHERE id = ?2",
+        params![source, package_id],
+    )?;
+    Ok(())
+}
+
+pub fn set_advisory_source(conn: &Connection, advisory_id: &str, source: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE package_advisories SET source = ?1 WHERE id = ?2",
+        params![source, advisory_id],
+    )?;
+    Ok(())
+}
+
+/// Removes every package row and advisory that was indexed from the given
+/// registry-relative file.
+pub fn delete_source(conn: &Connection, source: &str) -> Result<()> {
+    let mut stmt =
+        conn.prepare("SELECT name, sub_package, repo FROM packages WHERE source = ?1")?;
+    let rows = stmt.query_map(params![source], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        entries.push(row?);
+    }
+
+    for (name, sub_package, repo) in entries {
+        delete_package(conn, &name, sub_package.as_deref(), &repo, None)?;
+    }
+
+    conn.execute(
+        "DELETE FROM package_advisories WHERE source = ?1",
+        params![source],
+    )?;
+    Ok(())
+}
+
+/// Lists every registry file that currently has indexed packages or advisories.
+pub fn list_sources(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT source FROM packages WHERE source IS NOT NULL
+         UNION SELECT source FROM package_advisories WHERE source IS NOT NULL",
+    )?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    let mut sources = Vec::new();
+    for row in rows {
+        sources.push(row?);
+    }
+    Ok(sources)
+}
+
+pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT value FROM registry_meta WHERE key = ?1")?;
+    let mut rows = stmt.query(params![key])?;
+    if let Some(row) = rows.next()? {
+        Ok(Some(row.get(0)?))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO registry_meta (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
 pub fn clear_registry(conn: &Connection) -> Result<()> {
     conn.execute("DELETE FROM packages", [])?;
     conn.execute("DELETE FROM package_advisories", [])?;
@@ -625,6 +736,48 @@ pub fn find_provides(registry_handle: &str, term: &str) -> Result<Vec<(types::Pa
     Ok(results)
 }
 
+/// Packages that list the virtual package `name` in their `provides`.
+pub fn find_virtual_providers(registry_handle: &str, name: &str) -> Result<Vec<types::Package>> {
+    let conn = open_connection(registry_handle)?;
+    let mut stmt = conn.prepare(
+        "SELECT name, repo, version, description, sub_package, provides
+         FROM packages
+         WHERE provides LIKE ?1
+         ORDER BY name, repo",
+    )?;
+
+    let provides_like_query = format!("%\"{}\"%", name);
+    let rows = stmt.query_map(params![provides_like_query], |row| {
+        let provides_raw: Option<String> = row.get(5)?;
+        let provides: Vec<String> = provides_raw
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        Ok(types::Package {
+            name: row.get(0)?,
+            repo: row.get(1)?,
+            version: row.get(2)?,
+            description: row.get(3)?,
+            sub_package: row.get(4)?,
+            provides: Some(provides),
+            maintainer: types::Maintainer::default(),
+            ..Default::default()
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        let pkg = row?;
+        if pkg
+            .provides
+            .as_ref()
+            .is_some_and(|p| p.iter().any(|v| v == name))
+        {
+            results.push(pkg);
+        }
+    }
+    Ok(results)
+}
+
 pub fn search_packages(registry_handle: &str, term: &str) -> Result<Vec<types::Package>> {
     let conn = open_connection(registry_handle)?;
     let mut stmt = conn.prepare(
@@ -821,6 +974,28 @@ pub fn get_all_versions(registry_handle: &str, name: &str, repo: &str) -> Result
     Ok(versions)
 }
 
+/// Features declared by a package version, as stored JSON.
+pub fn get_package_features(
+    registry_handle: &str,
+    name: &str,
+    version: &str,
+    sub_package: Option<&str>,
+    repo: &str,
+) -> Result<Option<String>> {
+    let conn = open_connection(registry_handle)?;
+    let mut stmt = conn.prepare(
+        "SELECT features FROM packages
+         WHERE name = ?1 AND version = ?2 AND (sub_package IS ?3) AND repo = ?4",
+    )?;
+    let mut rows = stmt.query(params![name, version, sub_package, repo])?;
+    if let Some(row) = rows.next()? {
+        let features: Option<String> = row.get(0)?;
+        Ok(features)
+    } else {
+        Ok(None)
+    }
+}
+
 pub fn get_package_dependencies(
     registry_handle: &str,
     name: &str,
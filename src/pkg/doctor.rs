@@ -27,8 +27,8 @@ fn get_bin_root(scope: Scope) -> Result<PathBuf> {
             }
         }
         Scope::Project => {
-            let current_dir = std::env::current_dir()?;
-            Ok(current_dir.join(".zoi").join("pkgs").join("bin"))
+            let project_root = crate::project::config::project_root()?;
+            Ok(project_root.join(".zoi").join("pkgs").join("bin"))
         }
     }
 }
@@ -115,6 +115,30 @@ pub fn check_outdated_repos() -> Result<Option<String>> {
     Ok(None)
 }
 
+/// Returns metadata databases whose schema is newer than this Zoi understands,
+/// together with the version they were written with.
+pub fn check_database_schemas() -> Result<Vec<(PathBuf, i64)>> {
+    let db_root = crate::pkg::sysroot::apply_sysroot(crate::pkg::resolve::get_db_root()?);
+    if !db_root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut newer = Vec::new();
+    for entry in fs::read_dir(&db_root)?.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("db") {
+            continue;
+        }
+        if let Ok(version) = crate::pkg::db::probe_schema_version(&path)
+            && version > crate::pkg::db::SCHEMA_VERSION
+        {
+            newer.push((path, version));
+        }
+    }
+    newer.sort();
+    Ok(newer)
+}
+
 pub fn check_duplicate_packages() -> Result<Vec<(String, Vec<String>)>> {
     let db_root = crate::pkg::sysroot::apply_sysroot(crate::pkg::resolve::get_db_root()?);
     if !db_root.exists() {
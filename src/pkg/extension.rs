@@ -211,6 +211,9 @@ pub fn add(
         service: None,
         installed_files: vec![],
         installed_size: pkg.installed_size,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
     };
     let mut wrote_manifest = false;
     let mut applied_changes = Vec::new();
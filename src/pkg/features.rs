@@ -0,0 +1,203 @@
+use crate::pkg::types;
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::LazyLock;
+
+/// Stands for every default feature, as in `ffmpeg[-default,+vaapi]`.
+pub const DEFAULT_FEATURE: &str = "default";
+
+static SELECTION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[(?P<sel>\s*[+-]?[A-Za-z0-9_.-]+\s*(?:,\s*[+-]?[A-Za-z0-9_.-]+\s*)*)?\]$")
+        .expect("Static SELECTION_RE regex is valid")
+});
+
+/// Splits a trailing `[+feature,-feature]` selection off a source string.
+/// Returns the source without it and the selection as `+name`/`-name`
+/// entries; a bare `name` means `+name`. Paths and URLs may contain
+/// brackets of their own, so there every entry needs its `+` or `-`.
+pub fn split_source(source: &str) -> (String, Vec<String>) {
+    let Some(caps) = SELECTION_RE.captures(source) else {
+        return (source.to_string(), Vec::new());
+    };
+    let is_path = source.contains("://")
+        || source.starts_with(['.', '/', '~', '\\'])
+        || source.contains(".pkg.lua")
+        || source.contains(".manifest.yaml");
+    if is_path
+        && caps.name("sel").is_none_or(|sel| {
+            sel.as_str()
+                .split(',')
+                .any(|token| !token.trim().starts_with(['+', '-']))
+        })
+    {
+        return (source.to_string(), Vec::new());
+    }
+    let whole = caps.get(0).expect("Regex matched without a whole match");
+    let selection = caps
+        .name("sel")
+        .map(|m| {
+            m.as_str()
+                .split(',')
+                .map(|token| {
+                    let token = token.trim();
+                    if token.starts_with(['+', '-']) {
+                        token.to_string()
+                    } else {
+                        format!("+{}", token)
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let stripped = format!("{}{}", &source[..whole.start()], &source[whole.end()..]);
+    (stripped, selection)
+}
+
+/// Appends a selection that reproduces exactly `enabled` to `source`.
+pub fn with_features(source: &str, enabled: &[String]) -> String {
+    if enabled.is_empty() {
+        return source.to_string();
+    }
+    format!("{}[{}]", source, exact_selection(enabled).join(","))
+}
+
+/// Selection that turns on exactly `enabled`, regardless of defaults.
+pub fn exact_selection(enabled: &[String]) -> Vec<String> {
+    std::iter::once(format!("-{}", DEFAULT_FEATURE))
+        .chain(enabled.iter().map(|f| format!("+{}", f)))
+        .collect()
+}
+
+fn declared_or_err<'a>(
+    pkg_name: &str,
+    declared: &'a HashMap<String, types::PackageFeature>,
+    feature: &str,
+) -> Result<&'a types::PackageFeature> {
+    declared.get(feature).ok_or_else(|| {
+        let mut available: Vec<&str> = declared.keys().map(String::as_str).collect();
+        available.sort();
+        if available.is_empty() {
+            anyhow!(
+                "Package '{}' has no features (asked for '{}').",
+                pkg_name,
+                feature
+            )
+        } else {
+            anyhow!(
+                "Package '{}' has no feature '{}'. Available features: {}",
+                pkg_name,
+                feature,
+                available.join(", ")
+            )
+        }
+    })
+}
+
+/// Works out which features are enabled for `pkg_name` given its declared
+/// features and the `+name`/`-name` selection asked for. Defaults apply
+/// unless `-default` is given, and `requires` is followed transitively.
+pub fn resolve(
+    pkg_name: &str,
+    declared: Option<&HashMap<String, types::PackageFeature>>,
+    selection: &[String],
+) -> Result<Vec<String>> {
+    let empty = HashMap::new();
+    let declared = declared.unwrap_or(&empty);
+
+    let drop_defaults = selection
+        .iter()
+        .any(|s| s == &format!("-{}", DEFAULT_FEATURE));
+    let mut enabled: BTreeSet<String> = if drop_defaults {
+        BTreeSet::new()
+    } else {
+        declared
+            .iter()
+            .filter(|(_, f)| f.default)
+            .map(|(name, _)| name.clone())
+            .collect()
+    };
+    let mut disabled = BTreeSet::new();
+
+    for entry in selection {
+        let (on, name) = match entry.strip_prefix('-') {
+            Some(name) => (false, name),
+            None => (true, entry.strip_prefix('+').unwrap_or(entry)),
+        };
+        if name == DEFAULT_FEATURE {
+            if on {
+                enabled.extend(
+                    declared
+                        .iter()
+                        .filter(|(_, f)| f.default)
+                        .map(|(name, _)| name.clone()),
+                );
+            }
+            continue;
+        }
+        declared_or_err(pkg_name, declared, name)?;
+        if on {
+            disabled.remove(name);
+            enabled.insert(name.to_string());
+        } else {
+            enabled.remove(name);
+            disabled.insert(name.to_string());
+        }
+    }
+
+    let mut pending: Vec<String> = enabled.iter().cloned().collect();
+    while let Some(feature) = pending.pop() {
+        for required in &declared_or_err(pkg_name, declared, &feature)?.requires {
+            declared_or_err(pkg_name, declared, required)?;
+            if disabled.contains(required) {
+                return Err(anyhow!(
+                    "Feature '{}' of '{}' requires '{}', which was disabled.",
+                    feature,
+                    pkg_name,
+                    required
+                ));
+            }
+            if enabled.insert(required.clone()) {
+                pending.push(required.clone());
+            }
+        }
+    }
+
+    Ok(enabled.into_iter().collect())
+}
+
+/// Enabled features of `pkg`, or its defaults when nothing was selected.
+pub fn resolve_for_package(pkg: &types::Package, selection: &[String]) -> Result<Vec<String>> {
+    resolve(&pkg.name, pkg.features.as_ref(), selection)
+}
+
+/// Whether `enabled` is what `pkg` gets without any selection, so a prebuilt
+/// archive can be used for it.
+pub fn is_default_set(pkg: &types::Package, enabled: &[String]) -> bool {
+    resolve_for_package(pkg, &[]).is_ok_and(|defaults| defaults == enabled)
+}
+
+/// Extra dependencies pulled in by the enabled features.
+pub fn dependencies(
+    declared: Option<&HashMap<String, types::PackageFeature>>,
+    enabled: &[String],
+    build: bool,
+) -> Vec<String> {
+    let Some(declared) = declared else {
+        return Vec::new();
+    };
+    let mut deps = Vec::new();
+    for feature in enabled.iter().filter_map(|name| declared.get(name)) {
+        let list = if build {
+            &feature.build_dependencies
+        } else {
+            &feature.dependencies
+        };
+        for dep in list {
+            if !deps.contains(dep) {
+                deps.push(dep.clone());
+            }
+        }
+    }
+    deps
+}
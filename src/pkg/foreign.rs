@@ -0,0 +1,1068 @@
+//! Packages built for other package managers: Debian `.deb`, `.rpm` and
+//! AppImage files. They are read and unpacked natively, so installing one
+//! needs neither dpkg nor rpm on the host.
+
+use crate::pkg::types;
+use crate::utils;
+use anyhow::{Result, anyhow};
+use ar::Archive as ArArchive;
+use flate2::read::GzDecoder;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+/// Directories whose executables a package puts on `PATH`.
+const BIN_DIRS: &[&str] = &["usr/bin", "usr/sbin", "usr/local/bin", "usr/local/sbin"];
+
+/// Terminates the heredoc a maintainer script is fed through.
+const SCRIPT_EOF: &str = "ZOI_MAINTAINER_SCRIPT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Deb,
+    Rpm,
+    AppImage,
+}
+
+impl Format {
+    /// Recognises a foreign package by its file extension.
+    pub fn detect(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "deb" => Some(Format::Deb),
+            "rpm" => Some(Format::Rpm),
+            "appimage" => Some(Format::AppImage),
+            _ => None,
+        }
+    }
+}
+
+/// A maintainer script and the argument its own package manager would pass
+/// it on a fresh install or a full removal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub interpreter: String,
+    pub arg: String,
+    pub body: String,
+}
+
+impl Script {
+    fn new(body: String, default_interpreter: Option<String>, arg: &str) -> Script {
+        let interpreter = body
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("#!"))
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .or(default_interpreter)
+            .unwrap_or_else(|| "/bin/sh".to_string());
+        Script {
+            interpreter,
+            arg: arg.to_string(),
+            body,
+        }
+    }
+
+    /// The script as a hook command that feeds it to its interpreter.
+    pub fn to_hook(&self) -> String {
+        format!(
+            "{} -s {} <<'{}'\n{}\n{}\n",
+            self.interpreter,
+            self.arg,
+            SCRIPT_EOF,
+            self.body.trim_end(),
+            SCRIPT_EOF
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scripts {
+    pub pre_install: Option<Script>,
+    pub post_install: Option<Script>,
+    pub pre_remove: Option<Script>,
+    pub post_remove: Option<Script>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForeignPackage {
+    pub format: Format,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub maintainer: Option<String>,
+    pub homepage: Option<String>,
+    pub license: Option<String>,
+    /// Package names this one depends on, as the format's own distribution
+    /// calls them.
+    pub depends: Vec<String>,
+    pub bins: Vec<String>,
+    pub scripts: Scripts,
+}
+
+/// Reads the metadata of a `.deb`, `.rpm` or AppImage file.
+pub fn read(path: &Path) -> Result<ForeignPackage> {
+    match Format::detect(path) {
+        Some(Format::Deb) => read_deb(path),
+        Some(Format::Rpm) => read_rpm(path),
+        Some(Format::AppImage) => read_appimage(path),
+        None => Err(unsupported(path)),
+    }
+}
+
+/// Unpacks the files a foreign package installs into `dest`, laid out as
+/// they would be under `/`.
+pub fn unpack(path: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)?;
+    match Format::detect(path) {
+        Some(Format::Deb) => unpack_deb(path, dest),
+        Some(Format::Rpm) => unpack_rpm(path, dest),
+        Some(Format::AppImage) => {
+            let name = read_appimage(path)?.name;
+            let bin_dir = dest.join("usr/bin");
+            fs::create_dir_all(&bin_dir)?;
+            let target = bin_dir.join(name);
+            fs::copy(path, &target)?;
+            set_mode(&target, 0o755)?;
+            Ok(())
+        }
+        None => Err(unsupported(path)),
+    }
+}
+
+/// Unpacks a foreign package into the staging layout: files under `/usr`
+/// and `/opt` go to `dest/pkgstore`, everything else to `dest/usrroot`.
+///
+/// Absolute symlinks into the package are rewritten to relative ones so they
+/// survive the move; links that point outside the package are dropped.
+pub fn stage(path: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)?;
+    let root = tempfile::Builder::new()
+        .prefix(".unpack-")
+        .tempdir_in(dest)?;
+    unpack(path, root.path())?;
+
+    let entries: Vec<_> = WalkDir::new(root.path())
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .collect();
+    let contents: HashSet<PathBuf> = entries
+        .iter()
+        .filter_map(|e| e.path().strip_prefix(root.path()).ok())
+        .map(Path::to_path_buf)
+        .collect();
+
+    let mut dropped = Vec::new();
+    for entry in entries.iter().filter(|e| !e.file_type().is_dir()) {
+        let relative = entry.path().strip_prefix(root.path())?;
+        let staged = staged_path(relative);
+        let target = dest.join(&staged);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if entry.path_is_symlink() {
+            let link = fs::read_link(entry.path())?;
+            match relink(&contents, relative, &staged, &link) {
+                Some(new_link) => utils::symlink_file(&new_link, &target)?,
+                None => dropped.push(format!("/{} -> {}", relative.display(), link.display())),
+            }
+        } else {
+            fs::rename(entry.path(), &target)?;
+        }
+    }
+
+    if !dropped.is_empty() {
+        eprintln!(
+            "Warning: skipped symlinks that point outside the package:\n  {}",
+            dropped.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Translates a foreign package into Zoi package metadata.
+///
+/// Dependencies keep the distribution's package names. They are required
+/// when the host runs that distribution's package manager and optional
+/// otherwise, since the names mean nothing elsewhere.
+pub fn to_package(foreign: &ForeignPackage) -> types::Package {
+    let host_manager = utils::get_native_package_manager();
+    let manager = match foreign.format {
+        Format::Deb => Some("apt"),
+        Format::Rpm => match host_manager.as_deref() {
+            Some(m @ ("dnf" | "yum" | "zypper")) => Some(m),
+            _ => Some("dnf"),
+        },
+        Format::AppImage => None,
+    };
+
+    let dependencies = manager
+        .filter(|_| !foreign.depends.is_empty())
+        .map(|manager| {
+            let deps: Vec<String> = foreign
+                .depends
+                .iter()
+                .map(|dep| format!("{}:{}", manager, dep))
+                .collect();
+            let group = if host_manager.as_deref() == Some(manager) {
+                types::ComplexDependencyGroup {
+                    required: deps,
+                    ..Default::default()
+                }
+            } else {
+                types::ComplexDependencyGroup {
+                    optional: deps,
+                    ..Default::default()
+                }
+            };
+            types::Dependencies {
+                runtime: Some(types::DependencyGroup::Complex(group)),
+                build: None,
+            }
+        });
+
+    let hook = |script: &Option<Script>| {
+        script
+            .as_ref()
+            .map(|s| types::PlatformOrStringVec::StringVec(vec![s.to_hook()]))
+    };
+    let scripts = &foreign.scripts;
+    let hooks = (*scripts != Scripts::default()).then(|| types::Hooks {
+        pre_install: hook(&scripts.pre_install),
+        post_install: hook(&scripts.post_install),
+        pre_remove: hook(&scripts.pre_remove),
+        post_remove: hook(&scripts.post_remove),
+        ..Default::default()
+    });
+
+    let (maintainer_name, maintainer_email) = foreign
+        .maintainer
+        .as_deref()
+        .map(split_maintainer)
+        .unwrap_or_else(|| ("Unknown".to_string(), String::new()));
+
+    types::Package {
+        name: foreign.name.clone(),
+        repo: "local".to_string(),
+        version: Some(foreign.version.clone()),
+        description: foreign.description.clone(),
+        website: foreign.homepage.clone(),
+        license: foreign
+            .license
+            .clone()
+            .unwrap_or_else(|| "NOASSERTION".to_string()),
+        maintainer: types::Maintainer {
+            name: maintainer_name,
+            email: maintainer_email,
+            website: None,
+        },
+        types: vec!["source".to_string()],
+        bins: (!foreign.bins.is_empty()).then(|| foreign.bins.clone()),
+        dependencies,
+        hooks,
+        ..Default::default()
+    }
+}
+
+/// Renders a `.pkg.lua` that stages the foreign package at `source` with
+/// the metadata of `pkg`.
+pub fn render_pkg_lua(source: &Path, pkg: &types::Package) -> String {
+    let mut out = String::new();
+    out.push_str("metadata({\n");
+    out.push_str(&format!("  name = {},\n", lua_string(&pkg.name)));
+    out.push_str(&format!("  repo = {},\n", lua_string(&pkg.repo)));
+    if let Some(version) = &pkg.version {
+        out.push_str(&format!("  version = {},\n", lua_string(version)));
+    }
+    out.push_str(&format!(
+        "  description = {},\n",
+        lua_string(&pkg.description)
+    ));
+    if let Some(website) = &pkg.website {
+        out.push_str(&format!("  website = {},\n", lua_string(website)));
+    }
+    out.push_str(&format!("  license = {},\n", lua_string(&pkg.license)));
+    out.push_str(&format!(
+        "  maintainer = {{ name = {}, email = {} }},\n",
+        lua_string(&pkg.maintainer.name),
+        lua_string(&pkg.maintainer.email)
+    ));
+    out.push_str("  types = { \"source\" },\n");
+    if let Some(bins) = &pkg.bins {
+        out.push_str(&format!("  bins = {},\n", lua_list(bins)));
+    }
+    out.push_str("})\n\n");
+
+    if let Some(types::DependencyGroup::Complex(group)) = pkg
+        .dependencies
+        .as_ref()
+        .and_then(|deps| deps.runtime.as_ref())
+    {
+        out.push_str("dependencies({\n  runtime = {\n");
+        if !group.required.is_empty() {
+            out.push_str(&format!("    required = {},\n", lua_list(&group.required)));
+        }
+        if !group.optional.is_empty() {
+            out.push_str(&format!("    optional = {},\n", lua_list(&group.optional)));
+        }
+        out.push_str("  },\n})\n\n");
+    }
+
+    if let Some(hooks) = &pkg.hooks {
+        out.push_str("hooks({\n");
+        for (name, hook) in [
+            ("pre_install", &hooks.pre_install),
+            ("post_install", &hooks.post_install),
+            ("pre_remove", &hooks.pre_remove),
+            ("post_remove", &hooks.post_remove),
+        ] {
+            if let Some(types::PlatformOrStringVec::StringVec(commands)) = hook {
+                let commands: Vec<String> = commands.iter().map(|c| lua_long_string(c)).collect();
+                out.push_str(&format!("  {} = {{ {} }},\n", name, commands.join(", ")));
+            }
+        }
+        out.push_str("})\n\n");
+    }
+
+    out.push_str("function prepare()\n");
+    out.push_str(&format!(
+        "  UTILS.FOREIGN.stage({}, \"foreign\")\n",
+        lua_string(&source.to_string_lossy())
+    ));
+    out.push_str("end\n\n");
+    out.push_str("function package()\n");
+    out.push_str("  for _, dir in ipairs({ \"pkgstore\", \"usrroot\" }) do\n");
+    out.push_str("    if UTILS.FS.exists(BUILD_DIR .. \"/foreign/\" .. dir) then\n");
+    out.push_str("      zcp(\"foreign/\" .. dir, \"${\" .. dir .. \"}\")\n");
+    out.push_str("    end\n");
+    out.push_str("  end\n");
+    out.push_str("end\n");
+    out
+}
+
+/// Writes `<name>.pkg.lua` for the foreign package at `path` into `dir`,
+/// so it can be installed like any local package file.
+pub fn write_pkg_lua(path: &Path, dir: &Path) -> Result<PathBuf> {
+    let source = path
+        .canonicalize()
+        .map_err(|e| anyhow!("Failed to read '{}': {}", path.display(), e))?;
+    let foreign = read(&source)?;
+    println!(
+        "=> Importing {} {} from {}",
+        foreign.name,
+        foreign.version,
+        path.display()
+    );
+    let pkg = to_package(&foreign);
+
+    let pkg_lua = dir.join(format!("{}.pkg.lua", pkg.name));
+    fs::write(&pkg_lua, render_pkg_lua(&source, &pkg))?;
+    Ok(pkg_lua)
+}
+
+fn unsupported(path: &Path) -> anyhow::Error {
+    anyhow!("'{}' is not a .deb, .rpm or AppImage file", path.display())
+}
+
+fn read_deb(path: &Path) -> Result<ForeignPackage> {
+    let mut control = None;
+    let mut scripts = HashMap::new();
+    let mut bins = Vec::new();
+
+    for_each_deb_member(path, |name, member| {
+        if name.starts_with("control.tar") {
+            let mut archive = tar::Archive::new(decompress(name, member)?);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let file_name = entry
+                    .path()?
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if matches!(
+                    file_name.as_str(),
+                    "control" | "preinst" | "postinst" | "prerm" | "postrm"
+                ) {
+                    let mut content = String::new();
+                    entry.read_to_string(&mut content)?;
+                    if file_name == "control" {
+                        control = Some(content);
+                    } else {
+                        scripts.insert(file_name, content);
+                    }
+                }
+            }
+        } else if name.starts_with("data.tar") {
+            let mut archive = tar::Archive::new(decompress(name, member)?);
+            for entry in archive.entries()? {
+                let entry = entry?;
+                let header = entry.header();
+                let kind = header.entry_type();
+                let executable = kind.is_file() && header.mode()? & 0o111 != 0;
+                if (executable || kind.is_symlink())
+                    && let Some(bin) = bin_name(&entry.path()?)
+                {
+                    bins.push(bin);
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    let control = control.ok_or_else(|| anyhow!("'{}' has no control file", path.display()))?;
+    let fields = parse_control(&control);
+    let field = |key: &str| fields.get(key).filter(|v| !v.is_empty()).cloned();
+
+    let name = field("package")
+        .ok_or_else(|| anyhow!("'{}' does not name its package", path.display()))?;
+    let version = field("version").ok_or_else(|| anyhow!("'{}' has no version", path.display()))?;
+    let version = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.chars().all(|c| c.is_ascii_digit()) => rest.to_string(),
+        _ => version,
+    };
+
+    let mut depends = Vec::new();
+    for key in ["pre-depends", "depends"] {
+        if let Some(value) = field(key) {
+            depends.extend(deb_depends(&value));
+        }
+    }
+    dedup(&mut depends);
+    dedup(&mut bins);
+
+    let mut script = |file: &str, arg: &str| {
+        scripts
+            .remove(file)
+            .map(|body| Script::new(body, None, arg))
+    };
+    let scripts = Scripts {
+        pre_install: script("preinst", "install"),
+        post_install: script("postinst", "configure"),
+        pre_remove: script("prerm", "remove"),
+        post_remove: script("postrm", "remove"),
+    };
+
+    Ok(ForeignPackage {
+        format: Format::Deb,
+        description: field("description")
+            .and_then(|d| d.lines().next().map(str::to_string))
+            .unwrap_or_default(),
+        name,
+        version,
+        maintainer: field("maintainer"),
+        homepage: field("homepage"),
+        license: None,
+        depends,
+        bins,
+        scripts,
+    })
+}
+
+fn unpack_deb(path: &Path, dest: &Path) -> Result<()> {
+    let mut found = false;
+    for_each_deb_member(path, |name, member| {
+        if name.starts_with("data.tar") {
+            let mut archive = tar::Archive::new(decompress(name, member)?);
+            archive.set_preserve_permissions(true);
+            archive.unpack(dest)?;
+            found = true;
+        }
+        Ok(())
+    })?;
+    if !found {
+        return Err(anyhow!("'{}' has no data archive", path.display()));
+    }
+    Ok(())
+}
+
+fn for_each_deb_member(
+    path: &Path,
+    mut f: impl FnMut(&str, &mut dyn Read) -> Result<()>,
+) -> Result<()> {
+    let mut archive = ArArchive::new(File::open(path)?);
+    while let Some(member) = archive.next_entry() {
+        let mut member =
+            member.map_err(|e| anyhow!("'{}' is not a valid .deb: {}", path.display(), e))?;
+        let name = String::from_utf8_lossy(member.header().identifier())
+            .trim()
+            .trim_end_matches('/')
+            .to_string();
+        f(&name, &mut member)?;
+    }
+    Ok(())
+}
+
+fn decompress<'a>(name: &str, reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    Ok(if name.ends_with(".gz") {
+        Box::new(GzDecoder::new(reader))
+    } else if name.ends_with(".xz") {
+        Box::new(XzDecoder::new(reader))
+    } else if name.ends_with(".zst") {
+        Box::new(ZstdDecoder::new(reader)?)
+    } else if name.ends_with(".tar") {
+        Box::new(reader)
+    } else {
+        return Err(anyhow!("Unsupported compression for '{}'", name));
+    })
+}
+
+/// Parses a Debian control file into lowercased field names and values,
+/// with continuation lines joined by newlines.
+fn parse_control(content: &str) -> HashMap<String, String> {
+    let mut fields: HashMap<String, String> = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in content.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some(value) = current.as_ref().and_then(|key| fields.get_mut(key)) {
+                value.push('\n');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            let key = key.trim().to_ascii_lowercase();
+            fields.insert(key.clone(), value.trim().to_string());
+            current = Some(key);
+        }
+    }
+    fields
+}
+
+/// Package names from a `Depends` field. Of alternatives (`a | b`) the first
+/// is kept; versions, architecture qualifiers and restrictions are dropped.
+fn deb_depends(field: &str) -> Vec<String> {
+    field
+        .split(',')
+        .filter_map(|dep| {
+            let first = dep.split('|').next()?.trim();
+            let name = first
+                .split(|c: char| c.is_whitespace() || c == '(' || c == '[' || c == '<')
+                .next()?
+                .split(':')
+                .next()?;
+            (!name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+const RPM_LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const RPM_HEADER_MAGIC: [u8; 3] = [0x8e, 0xad, 0xe8];
+
+const RPMTAG_NAME: u32 = 1000;
+const RPMTAG_VERSION: u32 = 1001;
+const RPMTAG_RELEASE: u32 = 1002;
+const RPMTAG_SUMMARY: u32 = 1004;
+const RPMTAG_VENDOR: u32 = 1011;
+const RPMTAG_LICENSE: u32 = 1014;
+const RPMTAG_PACKAGER: u32 = 1015;
+const RPMTAG_URL: u32 = 1020;
+const RPMTAG_PREIN: u32 = 1023;
+const RPMTAG_POSTIN: u32 = 1024;
+const RPMTAG_PREUN: u32 = 1025;
+const RPMTAG_POSTUN: u32 = 1026;
+const RPMTAG_FILEMODES: u32 = 1030;
+const RPMTAG_REQUIRENAME: u32 = 1049;
+const RPMTAG_PREINPROG: u32 = 1085;
+const RPMTAG_POSTINPROG: u32 = 1086;
+const RPMTAG_PREUNPROG: u32 = 1087;
+const RPMTAG_POSTUNPROG: u32 = 1088;
+const RPMTAG_DIRINDEXES: u32 = 1116;
+const RPMTAG_BASENAMES: u32 = 1117;
+const RPMTAG_DIRNAMES: u32 = 1118;
+
+const RPM_INT16: u32 = 3;
+const RPM_INT32: u32 = 4;
+const RPM_STRING: u32 = 6;
+const RPM_STRING_ARRAY: u32 = 8;
+const RPM_I18NSTRING: u32 = 9;
+
+/// An rpm header: the tag index and the data store it points into.
+struct RpmHeader {
+    index: HashMap<u32, (u32, usize, usize)>,
+    store: Vec<u8>,
+}
+
+impl RpmHeader {
+    /// Reads a header, returning it with the number of bytes it took.
+    fn read(reader: &mut impl Read) -> Result<(RpmHeader, usize)> {
+        let mut intro = [0u8; 16];
+        reader.read_exact(&mut intro)?;
+        if intro[..3] != RPM_HEADER_MAGIC {
+            return Err(anyhow!("Corrupt rpm header"));
+        }
+        let count = u32::from_be_bytes([intro[8], intro[9], intro[10], intro[11]]) as usize;
+        let size = u32::from_be_bytes([intro[12], intro[13], intro[14], intro[15]]) as usize;
+        if count > 0x10000 || size > 0x1000_0000 {
+            return Err(anyhow!("Corrupt rpm header"));
+        }
+
+        let mut entries = vec![0u8; count * 16];
+        reader.read_exact(&mut entries)?;
+        let mut store = vec![0u8; size];
+        reader.read_exact(&mut store)?;
+
+        let word = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let index = entries
+            .chunks_exact(16)
+            .map(|e| {
+                (
+                    word(&e[0..4]),
+                    (
+                        word(&e[4..8]),
+                        word(&e[8..12]) as usize,
+                        word(&e[12..16]) as usize,
+                    ),
+                )
+            })
+            .collect();
+        Ok((RpmHeader { index, store }, 16 + entries.len() + size))
+    }
+
+    fn strings(&self, tag: u32) -> Vec<String> {
+        let Some(&(kind, offset, count)) = self.index.get(&tag) else {
+            return Vec::new();
+        };
+        let count = match kind {
+            RPM_STRING => 1,
+            RPM_STRING_ARRAY | RPM_I18NSTRING => count,
+            _ => return Vec::new(),
+        };
+        let mut out = Vec::new();
+        let mut data = self.store.get(offset..).unwrap_or_default();
+        for _ in 0..count {
+            let Some(end) = data.iter().position(|&b| b == 0) else {
+                break;
+            };
+            out.push(String::from_utf8_lossy(&data[..end]).to_string());
+            data = &data[end + 1..];
+        }
+        out
+    }
+
+    fn string(&self, tag: u32) -> Option<String> {
+        self.strings(tag)
+            .into_iter()
+            .next()
+            .filter(|s| !s.is_empty())
+    }
+
+    fn ints(&self, tag: u32) -> Vec<u32> {
+        let Some(&(kind, offset, count)) = self.index.get(&tag) else {
+            return Vec::new();
+        };
+        let width = match kind {
+            RPM_INT16 => 2,
+            RPM_INT32 => 4,
+            _ => return Vec::new(),
+        };
+        let data = self
+            .store
+            .get(offset..offset.saturating_add(count.saturating_mul(width)))
+            .unwrap_or_default();
+        data.chunks_exact(width)
+            .map(|b| match width {
+                2 => u16::from_be_bytes([b[0], b[1]]) as u32,
+                _ => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+            })
+            .collect()
+    }
+}
+
+/// Opens an rpm, returning its main header and a reader positioned at the
+/// start of the payload.
+fn open_rpm(path: &Path) -> Result<(RpmHeader, BufReader<File>)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut lead = [0u8; 96];
+    reader.read_exact(&mut lead)?;
+    if lead[..4] != RPM_LEAD_MAGIC {
+        return Err(anyhow!("'{}' is not a valid .rpm", path.display()));
+    }
+    let (_, signature_len) = RpmHeader::read(&mut reader)?;
+    let mut padding = [0u8; 8];
+    reader.read_exact(&mut padding[..(8 - signature_len % 8) % 8])?;
+    let (header, _) = RpmHeader::read(&mut reader)?;
+    Ok((header, reader))
+}
+
+fn read_rpm(path: &Path) -> Result<ForeignPackage> {
+    let (header, _) = open_rpm(path)?;
+    let name = header
+        .string(RPMTAG_NAME)
+        .ok_or_else(|| anyhow!("'{}' does not name its package", path.display()))?;
+    let version = header
+        .string(RPMTAG_VERSION)
+        .ok_or_else(|| anyhow!("'{}' has no version", path.display()))?;
+    let version = match header.string(RPMTAG_RELEASE) {
+        Some(release) => format!("{}-{}", version, release),
+        None => version,
+    };
+
+    // rpmlib features, files and sonames are provided by the system rather
+    // than by packages one could install by name.
+    let mut depends: Vec<String> = header
+        .strings(RPMTAG_REQUIRENAME)
+        .into_iter()
+        .filter(|dep| !dep.starts_with('/') && !dep.contains('(') && *dep != name)
+        .collect();
+    dedup(&mut depends);
+
+    let dir_names = header.strings(RPMTAG_DIRNAMES);
+    let modes = header.ints(RPMTAG_FILEMODES);
+    let mut bins: Vec<String> = header
+        .strings(RPMTAG_BASENAMES)
+        .into_iter()
+        .zip(header.ints(RPMTAG_DIRINDEXES))
+        .zip(modes)
+        .filter(|(_, mode)| {
+            let kind = mode & 0o170000;
+            kind == 0o120000 || (kind == 0o100000 && mode & 0o111 != 0)
+        })
+        .filter_map(|((base, dir), _)| {
+            let dir = dir_names.get(dir as usize)?;
+            bin_name(&Path::new(dir).join(base))
+        })
+        .collect();
+    dedup(&mut bins);
+
+    let script = |body_tag: u32, prog_tag: u32, arg: &str| {
+        let body = header.string(body_tag)?;
+        let interpreter = header.strings(prog_tag);
+        if interpreter.first().is_some_and(|p| p == "<lua>") {
+            eprintln!(
+                "Warning: skipping an rpm scriptlet of '{}' written for rpm's built-in Lua.",
+                name
+            );
+            return None;
+        }
+        let interpreter = (!interpreter.is_empty()).then(|| interpreter.join(" "));
+        Some(Script::new(body, interpreter, arg))
+    };
+    let scripts = Scripts {
+        pre_install: script(RPMTAG_PREIN, RPMTAG_PREINPROG, "1"),
+        post_install: script(RPMTAG_POSTIN, RPMTAG_POSTINPROG, "1"),
+        pre_remove: script(RPMTAG_PREUN, RPMTAG_PREUNPROG, "0"),
+        post_remove: script(RPMTAG_POSTUN, RPMTAG_POSTUNPROG, "0"),
+    };
+
+    Ok(ForeignPackage {
+        format: Format::Rpm,
+        description: header.string(RPMTAG_SUMMARY).unwrap_or_default(),
+        maintainer: header
+            .string(RPMTAG_PACKAGER)
+            .or_else(|| header.string(RPMTAG_VENDOR)),
+        homepage: header.string(RPMTAG_URL),
+        license: header.string(RPMTAG_LICENSE),
+        name,
+        version,
+        depends,
+        bins,
+        scripts,
+    })
+}
+
+fn unpack_rpm(path: &Path, dest: &Path) -> Result<()> {
+    let (_, mut reader) = open_rpm(path)?;
+    let magic = reader.fill_buf()?;
+    let mut payload: Box<dyn Read> = if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(GzDecoder::new(reader))
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+        Box::new(XzDecoder::new(reader))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(ZstdDecoder::new(reader)?)
+    } else if magic.starts_with(b"0707") {
+        Box::new(reader)
+    } else {
+        return Err(anyhow!(
+            "'{}' uses a payload compression Zoi cannot read",
+            path.display()
+        ));
+    };
+    unpack_cpio(&mut payload, dest)
+}
+
+/// Longest entry name or symlink target accepted in a cpio payload.
+const MAX_CPIO_PATH: u64 = 4096;
+
+/// Unpacks a `newc` cpio archive, the payload format of rpm.
+fn unpack_cpio(reader: &mut dyn Read, dest: &Path) -> Result<()> {
+    let padding = |len: u64| (4 - len % 4) % 4;
+    let skip = |reader: &mut dyn Read, len: u64| -> Result<()> {
+        io::copy(&mut (&mut *reader).take(len), &mut io::sink())?;
+        Ok(())
+    };
+    // Hard-linked files carry their data on the last link only.
+    let mut pending_links: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+
+    loop {
+        let mut header = [0u8; 110];
+        reader.read_exact(&mut header)?;
+        if &header[..5] != b"07070" {
+            return Err(anyhow!("Corrupt cpio payload"));
+        }
+        let field = |i: usize| -> Result<u64> {
+            let hex = std::str::from_utf8(&header[6 + i * 8..14 + i * 8])?;
+            Ok(u64::from_str_radix(hex, 16)?)
+        };
+        let (inode, mode, links, size, name_len) =
+            (field(0)?, field(1)?, field(4)?, field(6)?, field(11)?);
+
+        if name_len > MAX_CPIO_PATH {
+            return Err(anyhow!("Corrupt cpio payload: entry name too long"));
+        }
+        let mut name = vec![0u8; name_len as usize];
+        reader.read_exact(&mut name)?;
+        skip(reader, padding(110 + name_len))?;
+        let name = String::from_utf8_lossy(&name[..name.len().saturating_sub(1)]).to_string();
+        if name == "TRAILER!!!" {
+            break;
+        }
+
+        let target = normalize(Path::new(&name))
+            .map(|relative| contained_path(dest, &relative))
+            .transpose()?;
+        match (mode & 0o170000, target) {
+            (0o040000, Some(target)) => fs::create_dir_all(&target)?,
+            (0o100000, Some(target)) => {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                remove_entry(&target)?;
+                let mut file = File::create_new(&target)?;
+                if io::copy(&mut (&mut *reader).take(size), &mut file)? != size {
+                    return Err(anyhow!("Truncated cpio payload"));
+                }
+                drop(file);
+                set_mode(&target, (mode & 0o777) as u32)?;
+                if links > 1 {
+                    if size == 0 {
+                        pending_links.entry(inode).or_default().push(target);
+                    } else {
+                        for link in pending_links.remove(&inode).unwrap_or_default() {
+                            remove_entry(&link)?;
+                            fs::copy(&target, link)?;
+                        }
+                    }
+                }
+            }
+            (0o120000, Some(target)) => {
+                if size > MAX_CPIO_PATH {
+                    return Err(anyhow!("Corrupt cpio payload: symlink target too long"));
+                }
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                remove_entry(&target)?;
+                let mut link = String::new();
+                (&mut *reader).take(size).read_to_string(&mut link)?;
+                utils::symlink_file(Path::new(&link), &target)?;
+            }
+            _ => skip(reader, size)?,
+        }
+        skip(reader, padding(size))?;
+    }
+    Ok(())
+}
+
+/// Removes a file or symlink an earlier entry left at `path`, so writing the
+/// new entry can never follow a symlink out of the destination.
+fn remove_entry(path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if !meta.is_dir() => Ok(fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+/// Joins `relative` onto `dest`, refusing paths that would pass through a
+/// symlink the archive created earlier.
+fn contained_path(dest: &Path, relative: &Path) -> Result<PathBuf> {
+    let mut path = dest.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        path.push(component);
+        if components.peek().is_some()
+            && fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink())
+        {
+            return Err(anyhow!(
+                "Refusing to unpack '{}' through a symlink",
+                relative.display()
+            ));
+        }
+    }
+    Ok(path)
+}
+
+fn read_appimage(path: &Path) -> Result<ForeignPackage> {
+    let mut magic = [0u8; 11];
+    File::open(path)?.read_exact(&mut magic).ok();
+    if magic[..4] != *b"\x7fELF" || !matches!(&magic[8..11], b"AI\x01" | b"AI\x02") {
+        return Err(anyhow!("'{}' is not an AppImage", path.display()));
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (name, version) = appimage_name(&stem);
+    if name.is_empty() {
+        return Err(anyhow!(
+            "Cannot tell a package name from '{}'",
+            path.display()
+        ));
+    }
+
+    Ok(ForeignPackage {
+        format: Format::AppImage,
+        description: format!("{} (AppImage)", stem),
+        bins: vec![name.clone()],
+        name,
+        version,
+        maintainer: None,
+        homepage: None,
+        license: None,
+        depends: Vec::new(),
+        scripts: Scripts::default(),
+    })
+}
+
+/// Splits an AppImage file name like `Editor-1.2.3-x86_64` into a package
+/// name and version. The version is the first dash-separated part that
+/// starts with a digit; anything after it is taken to be the architecture.
+fn appimage_name(stem: &str) -> (String, String) {
+    let mut name = Vec::new();
+    let mut version = None;
+    for part in stem.split('-') {
+        let candidate = part.strip_prefix(['v', 'V']).unwrap_or(part);
+        if candidate.starts_with(|c: char| c.is_ascii_digit()) {
+            version = Some(candidate.to_string());
+            break;
+        }
+        name.push(part);
+    }
+    (
+        name.join("-").to_ascii_lowercase(),
+        version.unwrap_or_else(|| "0.0.0".to_string()),
+    )
+}
+
+/// Where a file installed at `/<path>` goes in the staging layout.
+fn staged_path(path: &Path) -> PathBuf {
+    if let Ok(rest) = path
+        .strip_prefix("usr/local")
+        .or_else(|_| path.strip_prefix("usr"))
+    {
+        Path::new("pkgstore").join(rest)
+    } else if path.starts_with("opt") {
+        Path::new("pkgstore").join(path)
+    } else {
+        Path::new("usrroot").join(path)
+    }
+}
+
+/// The target a symlink at `relative` (staged at `staged`) should have
+/// after staging, or `None` if it points outside the package's `contents`.
+fn relink(
+    contents: &HashSet<PathBuf>,
+    relative: &Path,
+    staged: &Path,
+    link: &Path,
+) -> Option<PathBuf> {
+    let resolved = if link.is_absolute() {
+        normalize(link)?
+    } else {
+        normalize(&relative.parent()?.join(link))?
+    };
+    if !contents.contains(&resolved) {
+        return None;
+    }
+
+    let target = staged_path(&resolved);
+    let from: Vec<_> = staged.parent()?.components().collect();
+    let to: Vec<_> = target.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut out = PathBuf::new();
+    for _ in common..from.len() {
+        out.push("..");
+    }
+    for component in &to[common..] {
+        out.push(component);
+    }
+    Some(out)
+}
+
+/// Resolves `.` and `..` in a path taken relative to `/`, or `None` if it
+/// climbs out of it.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::ParentDir if !out.pop() => return None,
+            _ => {}
+        }
+    }
+    (!out.as_os_str().is_empty()).then_some(out)
+}
+
+fn bin_name(path: &Path) -> Option<String> {
+    let path = normalize(path)?;
+    let parent = path.parent()?;
+    BIN_DIRS
+        .iter()
+        .any(|dir| parent == Path::new(dir))
+        .then(|| path.file_name().map(|n| n.to_string_lossy().to_string()))
+        .flatten()
+}
+
+/// Splits `Name <email>` into its parts.
+fn split_maintainer(maintainer: &str) -> (String, String) {
+    match maintainer.split_once('<') {
+        Some((name, email)) => (
+            name.trim().to_string(),
+            email.trim_end().trim_end_matches('>').trim().to_string(),
+        ),
+        None => (maintainer.trim().to_string(), String::new()),
+    }
+}
+
+fn dedup(values: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+    values.retain(|v| seen.insert(v.clone()));
+}
+
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+fn lua_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Quotes `value` as a Lua long string, which keeps scripts readable.
+fn lua_long_string(value: &str) -> String {
+    let mut level = 0;
+    while value.contains(&format!("]{}]", "=".repeat(level))) {
+        level += 1;
+    }
+    let equals = "=".repeat(level);
+    format!("[{}[\n{}]{}]", equals, value, equals)
+}
+
+fn lua_list(values: &[String]) -> String {
+    let items: Vec<String> = values.iter().map(|v| lua_string(v)).collect();
+    format!("{{ {} }}", items.join(", "))
+}
@@ -2,9 +2,22 @@ use anyhow::{Result, anyhow};
 use sha2::{Digest, Sha512};
 use std::fs;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+fn update_with_file(hasher: &mut Sha512, path: &Path) -> Result<()> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = [0; 8192];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(())
+}
+
 pub fn calculate_dir_hash(path: &Path) -> Result<String> {
     if !path.is_dir() {
         return Err(anyhow!("Path is not a directory"));
@@ -22,15 +35,26 @@ pub fn calculate_dir_hash(path: &Path) -> Result<String> {
     paths.sort();
 
     for file_path in paths {
-        let mut file = fs::File::open(&file_path)?;
-        let mut buffer = [0; 8192];
-        loop {
-            let bytes_read = file.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
-            }
-            hasher.update(&buffer[..bytes_read]);
-        }
+        update_with_file(&mut hasher, &file_path)?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Hashes the content of `files` together with their paths relative to
+/// `root`, so renaming a file changes the hash too.
+pub fn calculate_files_hash(root: &Path, files: &[PathBuf]) -> Result<String> {
+    let mut paths = files.to_vec();
+    paths.sort();
+    paths.dedup();
+
+    let mut hasher = Sha512::new();
+    for file_path in paths {
+        let rel = file_path.strip_prefix(root).unwrap_or(&file_path);
+        hasher.update(rel.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        update_with_file(&mut hasher, &file_path)?;
+        hasher.update([0]);
     }
 
     Ok(hex::encode(hasher.finalize()))
@@ -1,8 +1,9 @@
+use crate::pkg::sandbox;
 use crate::pkg::types::{Hooks, PlatformOrStringVec};
 use crate::utils;
 use anyhow::{Result, anyhow};
 use colored::*;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 pub mod global;
 
@@ -15,14 +16,52 @@ pub enum HookType {
     PostRemove,
 }
 
+fn shell_command(cmd_str: &str) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut command = Command::new("pwsh");
+        command.arg("-Command").arg(cmd_str);
+        command
+    } else {
+        let mut command = Command::new("bash");
+        command.arg("-c").arg(cmd_str);
+        command
+    }
+}
+
+/// Runs a hook command in the sandbox, with a scratch directory as the only
+/// writable place and no network.
+fn execute_sandboxed(cmd_str: &str) -> Result<()> {
+    let scratch = tempfile::Builder::new().prefix("zoi-hook-").tempdir()?;
+    let sandbox = sandbox::Sandbox {
+        writable: vec![scratch.path().to_path_buf()],
+        network: false,
+    };
+    let mut command = shell_command(cmd_str);
+    command
+        .current_dir(scratch.path())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::piped());
+    sandbox.apply(&mut command)?;
+
+    let output = sandbox::spawn_output(&mut command)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    eprint!("{}", stderr);
+    if !output.status.success() {
+        sandbox::report_violation(&sandbox, cmd_str, &stderr);
+        return Err(anyhow!("Hook command failed: {}", cmd_str));
+    }
+    Ok(())
+}
+
 fn execute_commands(commands: &[String]) -> Result<()> {
+    let sandboxed = sandbox::is_enabled();
     for cmd_str in commands {
         println!("> {}", cmd_str.cyan());
-        let status = if cfg!(target_os = "windows") {
-            Command::new("pwsh").arg("-Command").arg(cmd_str).status()?
-        } else {
-            Command::new("bash").arg("-c").arg(cmd_str).status()?
-        };
+        if sandboxed {
+            execute_sandboxed(cmd_str)?;
+            continue;
+        }
+        let status = shell_command(cmd_str).status()?;
 
         if !status.success() {
             return Err(anyhow!("Hook command failed: {}", cmd_str));
@@ -0,0 +1,492 @@
+use crate::pkg::install::pubgrub::{PkgName, SemVersion, ZoiDependencyProvider};
+use pubgrub::{DerivationTree, External, Ranges};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::Bound;
+
+pub type ConflictTree = DerivationTree<PkgName, Ranges<SemVersion>, String>;
+
+/// Extra facts about the packages in a failed resolution that the derivation
+/// tree does not carry, used to make suggestions more specific.
+#[derive(Debug, Default)]
+pub struct ConflictHints {
+    /// Versions known for each package.
+    pub available: HashMap<String, Vec<SemVersion>>,
+    /// `(parent, dependency)` pairs where the dependency was an optional one
+    /// the user opted into.
+    pub optional: HashSet<(String, String)>,
+}
+
+impl ConflictHints {
+    pub fn from_provider(provider: &ZoiDependencyProvider, tree: &ConflictTree) -> Self {
+        let mut hints = Self::default();
+        for package in tree.packages() {
+            if is_root(package) {
+                continue;
+            }
+            if let Ok(versions) = provider.get_versions(package) {
+                hints.available.insert(display_name(package), versions);
+            }
+        }
+        for ((parent, _), (_, optionals, _)) in provider.chosen_cache.borrow().iter() {
+            for optional in optionals {
+                let Ok(dep) = crate::pkg::dependencies::parse_dependency_string(optional) else {
+                    continue;
+                };
+                if dep.manager != "zoi" {
+                    continue;
+                }
+                if let Ok(req) = crate::pkg::resolve::parse_source_string(dep.package) {
+                    let name = match req.sub_package {
+                        Some(sub) => format!("{}:{}", req.name, sub),
+                        None => req.name,
+                    };
+                    hints.optional.insert((display_name(parent), name));
+                }
+            }
+        }
+        hints
+    }
+}
+
+/// Who placed a requirement: the install request itself (command line or
+/// `zoi.yaml`) or a range of versions of another package.
+#[derive(Clone)]
+enum Requirer {
+    Request,
+    Package(Box<(PkgName, Ranges<SemVersion>)>),
+}
+
+#[derive(Clone)]
+enum Fact {
+    Requires {
+        by: Requirer,
+        dep: PkgName,
+        range: Ranges<SemVersion>,
+    },
+    NoVersions(PkgName, Ranges<SemVersion>),
+    /// A `NoVersions` incompatibility that leaves a single candidate within
+    /// what was asked for, which reads better stated positively.
+    OnlyVersion {
+        package: PkgName,
+        version: SemVersion,
+        within: Ranges<SemVersion>,
+    },
+    Unavailable(PkgName, Ranges<SemVersion>, String),
+}
+
+fn is_root(package: &PkgName) -> bool {
+    package.name == "$root"
+}
+
+fn display_name(package: &PkgName) -> String {
+    if let Some(source) = &package.explicit_source {
+        return source.clone();
+    }
+    match &package.sub_package {
+        Some(sub) => format!("{}:{}", package.name, sub),
+        None => package.name.clone(),
+    }
+}
+
+/// Renders a range the way users write version specs, e.g. `2.x` instead of
+/// `>=2.0.0, <3.0.0`.
+fn describe_range(range: &Ranges<SemVersion>) -> String {
+    if range == &Ranges::full() {
+        return "any version".to_string();
+    }
+    if let Some(v) = range.as_singleton() {
+        return v.to_string();
+    }
+    let segments: Vec<_> = range.iter().collect();
+    if let [(Bound::Included(lo), Bound::Excluded(hi))] = segments.as_slice() {
+        let (lo, hi) = (&lo.0, &hi.0);
+        if lo.pre.is_empty() && hi.pre.is_empty() && lo.patch == 0 && hi.patch == 0 {
+            if lo.minor == 0 && hi.minor == 0 && hi.major == lo.major + 1 {
+                return format!("{}.x", lo.major);
+            }
+            if lo.major == hi.major && hi.minor == lo.minor + 1 {
+                return format!("{}.{}.x", lo.major, lo.minor);
+            }
+        }
+    }
+    range.to_string()
+}
+
+fn describe_package(package: &PkgName, range: &Ranges<SemVersion>) -> String {
+    if range == &Ranges::full() {
+        format!("every version of {}", display_name(package))
+    } else {
+        format!("{} {}", display_name(package), describe_range(range))
+    }
+}
+
+fn describe_requirement(by: &Requirer, dep: &PkgName, range: &Ranges<SemVersion>) -> String {
+    let dep = if range == &Ranges::full() {
+        display_name(dep)
+    } else {
+        format!("{} {}", display_name(dep), describe_range(range))
+    };
+    match by {
+        Requirer::Request => format!("you requested {}", dep),
+        Requirer::Package(parent) => {
+            let (parent, parent_range) = parent.as_ref();
+            format!(
+                "{} requires {}",
+                describe_package(parent, parent_range),
+                dep
+            )
+        }
+    }
+}
+
+/// Capitalizes the sentence openers used here, leaving package names as they
+/// are spelled.
+fn capitalize(text: &str) -> String {
+    for opener in ["you ", "every "] {
+        if let Some(rest) = text.strip_prefix(opener) {
+            let mut chars = opener.chars();
+            let first = chars.next().unwrap_or_default().to_uppercase();
+            return format!("{}{}{}", first, chars.as_str(), rest);
+        }
+    }
+    text.to_string()
+}
+
+impl Fact {
+    fn describe(&self, hints: &ConflictHints) -> String {
+        match self {
+            Fact::Requires { by, dep, range } => capitalize(&describe_requirement(by, dep, range)),
+            Fact::NoVersions(package, range) => {
+                let name = display_name(package);
+                let mut text = if range == &Ranges::full() {
+                    format!("No version of {} could be found", name)
+                } else {
+                    format!(
+                        "No available version of {} matches {}",
+                        name,
+                        describe_range(range)
+                    )
+                };
+                if let Some(versions) = hints.available.get(&name)
+                    && !versions.is_empty()
+                {
+                    let list: Vec<_> = versions.iter().map(|v| v.to_string()).collect();
+                    let _ = write!(text, " (available: {})", list.join(", "));
+                }
+                text
+            }
+            Fact::OnlyVersion {
+                package,
+                version,
+                within,
+            } => {
+                let name = display_name(package);
+                if within == &Ranges::full() {
+                    format!("{} {} is the only version of {}", name, version, name)
+                } else {
+                    format!(
+                        "{} {} is the only version of {} matching {}",
+                        name,
+                        version,
+                        name,
+                        describe_range(within)
+                    )
+                }
+            }
+            Fact::Unavailable(package, range, reason) => format!(
+                "{} cannot be used: {}",
+                capitalize(&describe_package(package, range)),
+                reason
+            ),
+        }
+    }
+}
+
+fn collect_facts(tree: &ConflictTree, facts: &mut Vec<Fact>, seen: &mut HashSet<String>) {
+    match tree {
+        DerivationTree::Derived(derived) => {
+            collect_facts(&derived.cause1, facts, seen);
+            collect_facts(&derived.cause2, facts, seen);
+        }
+        DerivationTree::External(external) => {
+            let fact = match external {
+                External::NotRoot(..) => return,
+                External::FromDependencyOf(parent, parent_range, dep, range) => Fact::Requires {
+                    by: if is_root(parent) {
+                        Requirer::Request
+                    } else {
+                        Requirer::Package(Box::new((parent.clone(), parent_range.clone())))
+                    },
+                    dep: dep.clone(),
+                    range: range.clone(),
+                },
+                External::NoVersions(package, range) => {
+                    Fact::NoVersions(package.clone(), range.clone())
+                }
+                External::Custom(package, range, reason) => {
+                    Fact::Unavailable(package.clone(), range.clone(), reason.clone())
+                }
+            };
+            let key = format!("{:?}", external);
+            if seen.insert(key) {
+                facts.push(fact);
+            }
+        }
+    }
+}
+
+/// Rewrites `NoVersions` facts that leave exactly one candidate as
+/// [`Fact::OnlyVersion`] and orders the facts so the request comes first,
+/// then package requirements, then what is available.
+fn simplify_facts(facts: Vec<Fact>) -> Vec<Fact> {
+    let wanted = |package: &PkgName| {
+        let mut ranges = facts.iter().filter_map(|f| match f {
+            Fact::Requires { dep, range, .. } if dep == package => Some(range.clone()),
+            _ => None,
+        });
+        let first = ranges.next().unwrap_or_else(Ranges::full);
+        ranges.fold(first, |acc, r| acc.union(&r))
+    };
+
+    let mut simplified: Vec<Fact> = facts
+        .iter()
+        .map(|fact| match fact {
+            Fact::NoVersions(package, range) => {
+                let within = wanted(package);
+                match within.intersection(&range.complement()).as_singleton() {
+                    Some(version) => Fact::OnlyVersion {
+                        package: package.clone(),
+                        version: version.clone(),
+                        within,
+                    },
+                    None => fact.clone(),
+                }
+            }
+            _ => fact.clone(),
+        })
+        .collect();
+
+    simplified.sort_by_key(|fact| match fact {
+        Fact::Requires {
+            by: Requirer::Request,
+            ..
+        } => 0,
+        Fact::Requires { .. } => 1,
+        Fact::OnlyVersion { .. } => 2,
+        Fact::NoVersions(..) | Fact::Unavailable(..) => 3,
+    });
+    simplified
+}
+
+/// What the suggestions need to know about the request.
+struct Request<'a> {
+    hints: &'a ConflictHints,
+    /// Requested packages and the range they were pinned to.
+    pins: HashMap<String, Ranges<SemVersion>>,
+    /// Packages with a single candidate version.
+    only: HashSet<String>,
+}
+
+fn suggest_for(
+    by: &Requirer,
+    dep: &PkgName,
+    range: &Ranges<SemVersion>,
+    request: &Request,
+    suggestions: &mut Vec<String>,
+) {
+    let dep_name = display_name(dep);
+    let suggestion = match by {
+        Requirer::Request if range != &Ranges::full() => format!(
+            "Relax the version you pinned for {} (currently {}).",
+            dep_name,
+            describe_range(range)
+        ),
+        Requirer::Request => return,
+        Requirer::Package(parent) => {
+            let (parent, parent_range) = parent.as_ref();
+            let parent_name = display_name(parent);
+            let pin = request.pins.get(&parent_name);
+            if request
+                .hints
+                .optional
+                .contains(&(parent_name.clone(), dep_name.clone()))
+            {
+                format!(
+                    "Skip the optional dependency {} of {}; it is not needed for {} to work.",
+                    dep_name, parent_name, parent_name
+                )
+            } else if let Some(pin) = pin
+                && request.only.contains(&parent_name)
+                && pin != &Ranges::full()
+            {
+                format!(
+                    "Relax the version you pinned for {} (currently {}); the only match needs {} {}.",
+                    parent_name,
+                    describe_range(pin),
+                    dep_name,
+                    describe_range(range)
+                )
+            } else if pin.is_some() && request.only.contains(&parent_name) {
+                format!(
+                    "Remove {} from the request; its only version needs {} {}.",
+                    parent_name,
+                    dep_name,
+                    describe_range(range)
+                )
+            } else if pin.is_some() && parent_range != &Ranges::full() {
+                let excluded = match parent_range.as_singleton() {
+                    Some(v) => format!("other than {}", v),
+                    None => format!("outside {}", describe_range(parent_range)),
+                };
+                format!(
+                    "Pin {} to a version {}; those need {} {}.",
+                    parent_name,
+                    excluded,
+                    dep_name,
+                    describe_range(range)
+                )
+            } else if pin.is_some() {
+                format!(
+                    "Remove {} from the request; every version of it needs {} {}.",
+                    parent_name,
+                    dep_name,
+                    describe_range(range)
+                )
+            } else {
+                format!(
+                    "Ask the maintainer of {} to widen its {} requirement ({}).",
+                    parent_name,
+                    dep_name,
+                    describe_range(range)
+                )
+            }
+        }
+    };
+    if !suggestions.contains(&suggestion) {
+        suggestions.push(suggestion);
+    }
+}
+
+/// Turns the solver's derivation tree into a numbered, plain-language
+/// explanation of why the requested packages cannot be installed together,
+/// followed by suggestions for which pin, version spec or optional to relax.
+pub fn explain_conflict(tree: &ConflictTree, hints: &ConflictHints) -> String {
+    let mut facts = Vec::new();
+    collect_facts(tree, &mut facts, &mut HashSet::new());
+    let facts = simplify_facts(facts);
+
+    let request = Request {
+        hints,
+        pins: facts
+            .iter()
+            .filter_map(|f| match f {
+                Fact::Requires {
+                    by: Requirer::Request,
+                    dep,
+                    range,
+                } => Some((display_name(dep), range.clone())),
+                _ => None,
+            })
+            .collect(),
+        only: facts
+            .iter()
+            .filter_map(|f| match f {
+                Fact::OnlyVersion { package, .. } => Some(display_name(package)),
+                _ => None,
+            })
+            .collect(),
+    };
+
+    let mut out = String::from("No set of package versions satisfies every requirement:\n");
+    for (i, fact) in facts.iter().enumerate() {
+        let _ = writeln!(out, "  {}. {}.", i + 1, fact.describe(hints));
+    }
+
+    let mut conclusions = Vec::new();
+    let mut suggestions = Vec::new();
+    for (i, a) in facts.iter().enumerate() {
+        let Fact::Requires {
+            by: by_a,
+            dep: dep_a,
+            range: range_a,
+        } = a
+        else {
+            continue;
+        };
+        for (j, b) in facts.iter().enumerate().skip(i + 1) {
+            let Fact::Requires {
+                by: by_b,
+                dep: dep_b,
+                range: range_b,
+            } = b
+            else {
+                continue;
+            };
+            if dep_a == dep_b && range_a.is_disjoint(range_b) {
+                conclusions.push(format!(
+                    "({}) and ({}) conflict: {}, but {}.",
+                    i + 1,
+                    j + 1,
+                    describe_requirement(by_a, dep_a, range_a),
+                    describe_requirement(by_b, dep_b, range_b)
+                ));
+                suggest_for(by_a, dep_a, range_a, &request, &mut suggestions);
+                suggest_for(by_b, dep_b, range_b, &request, &mut suggestions);
+            }
+        }
+    }
+
+    for (i, fact) in facts.iter().enumerate() {
+        let Fact::NoVersions(package, range) = fact else {
+            continue;
+        };
+        if !conclusions.is_empty() && range != &Ranges::full() {
+            continue;
+        }
+        let name = display_name(package);
+        conclusions.push(format!(
+            "({}) leaves nothing to install for {}.",
+            i + 1,
+            name
+        ));
+        for other in &facts {
+            if let Fact::Requires { by, dep, range: r } = other
+                && dep == package
+                && !r.is_disjoint(range)
+            {
+                suggest_for(by, dep, r, &request, &mut suggestions);
+            }
+        }
+        if range == &Ranges::full() {
+            let suggestion = format!(
+                "Check that {} exists in an enabled repo, or run 'zoi sync'.",
+                name
+            );
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+        }
+    }
+
+    if conclusions.is_empty() {
+        conclusions.push("Together these requirements rule out every candidate version.".into());
+        for fact in &facts {
+            if let Fact::Requires { by, dep, range } = fact {
+                suggest_for(by, dep, range, &request, &mut suggestions);
+            }
+        }
+    }
+
+    out.push('\n');
+    for conclusion in conclusions {
+        let _ = writeln!(out, "{}", conclusion);
+    }
+    if !suggestions.is_empty() {
+        out.push_str("\nTo resolve this, try one of:\n");
+        for suggestion in suggestions {
+            let _ = writeln!(out, "  - {}", suggestion);
+        }
+    }
+    out.trim_end().to_string()
+}
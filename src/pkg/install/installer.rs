@@ -1,16 +1,19 @@
 use crate::pkg::{
     cache, config, db, hooks,
     install::{manifest, plan, post_install, prebuilt, resolver::InstallNode, util},
-    local, pgp, pkgdir, recorder, resolve, types,
+    local,
+    package::delta,
+    pgp, pkgdir, recorder, resolve, types,
 };
 use anyhow::{Result, anyhow};
 use colored::Colorize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use semver::Version;
 use std::fs;
 use std::path::{Path, PathBuf};
 
 pub fn download_and_cache_archive(
-    _node: &InstallNode,
+    node: &InstallNode,
     details: &plan::PrebuiltDetails,
     pb: Option<&ProgressBar>,
 ) -> Result<PathBuf> {
@@ -51,8 +54,19 @@ pub fn download_and_cache_archive(
         let temp_archive_path = temp_dir.path().join(archive_filename);
 
         let mut last_error = None;
-        let candidate_urls = cache::mirror_candidate_urls(&details.info.final_url);
-        let mut downloaded = false;
+        let mut downloaded = try_delta_download(
+            node,
+            details,
+            archive_filename,
+            &archive_cache_root,
+            &temp_archive_path,
+            pb,
+        );
+        let candidate_urls = if downloaded {
+            Vec::new()
+        } else {
+            cache::mirror_candidate_urls(&details.info.final_url)
+        };
         for candidate_url in candidate_urls {
             match util::download_file_with_progress(
                 &candidate_url,
@@ -88,15 +102,15 @@ pub fn download_and_cache_archive(
     }
 
     let has_authorities = config.default_registry.as_ref().is_some_and(|r| {
-        r.handle == _node.registry_handle && r.authorities.as_ref().is_some_and(|a| !a.is_empty())
+        r.handle == node.registry_handle && r.authorities.as_ref().is_some_and(|a| !a.is_empty())
     }) || config.added_registries.iter().any(|r| {
-        r.handle == _node.registry_handle && r.authorities.as_ref().is_some_and(|a| !a.is_empty())
+        r.handle == node.registry_handle && r.authorities.as_ref().is_some_and(|a| !a.is_empty())
     });
 
     if has_authorities && details.info.pgp_url.is_none() {
         let msg = format!(
             "Warning: Installing unsigned package '{}' from a registry that claims to be secure.",
-            _node.pkg.name
+            node.pkg.name
         );
         if let Some(p) = pb {
             p.println(msg.yellow().to_string());
@@ -160,6 +174,94 @@ pub fn download_and_cache_archive(
     Ok(archive_path)
 }
 
+/// Rebuilds the archive from a cached archive of an installed version and a
+/// `.delta` published next to the new archive. Only used when the result can
+/// be checked against the `.hash` sidecar; any failure falls back to a full
+/// download.
+fn try_delta_download(
+    node: &InstallNode,
+    details: &plan::PrebuiltDetails,
+    archive_filename: &str,
+    archive_cache_root: &Path,
+    dest: &Path,
+    pb: Option<&ProgressBar>,
+) -> bool {
+    let Some(hash_url) = &details.info.hash_url else {
+        return false;
+    };
+    let Ok(platform) = crate::utils::get_platform() else {
+        return false;
+    };
+    let name = &node.pkg.name;
+    if archive_filename != delta::archive_filename(name, &node.version, &platform) {
+        return false;
+    }
+
+    let mut installed_versions: Vec<String> = local::get_installed_packages()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|m| {
+            m.name == *name
+                && m.repo == node.pkg.repo
+                && m.registry_handle == node.registry_handle
+                && m.version != node.version
+        })
+        .map(|m| m.version)
+        .collect();
+    installed_versions.sort_by(|a, b| match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => b.cmp(&a),
+        _ => b.cmp(a),
+    });
+    let Some((old_version, old_archive)) = installed_versions.into_iter().find_map(|v| {
+        let path = archive_cache_root.join(delta::archive_filename(name, &v, &platform));
+        path.exists().then_some((v, path))
+    }) else {
+        return false;
+    };
+
+    let delta_filename = delta::delta_filename(name, &old_version, &node.version, &platform);
+    let Some(base_url) = details
+        .info
+        .final_url
+        .rsplit_once('/')
+        .map(|(base, _)| base)
+    else {
+        return false;
+    };
+    let delta_url = format!("{}/{}", base_url, delta_filename);
+    let Ok(temp_dir) = tempfile::Builder::new().prefix("zoi-delta-").tempdir() else {
+        return false;
+    };
+    let delta_path = temp_dir.path().join(&delta_filename);
+
+    let fetched = cache::mirror_candidate_urls(&delta_url)
+        .into_iter()
+        .any(|url| util::download_file_with_progress(&url, &delta_path, pb, None).is_ok());
+    let patched = fetched
+        && delta::apply(&old_archive, &delta_path, dest).is_ok()
+        && util::get_expected_hash(hash_url, Some(archive_filename)).is_ok_and(|hash| {
+            !hash.is_empty() && util::verify_file_hash(dest, &hash, pb).is_ok_and(|ok| ok)
+        });
+
+    if patched {
+        let msg = format!(
+            "Updated {} from {} using a delta ({} instead of {}).",
+            name,
+            old_version,
+            crate::utils::format_bytes(fs::metadata(&delta_path).map(|m| m.len()).unwrap_or(0)),
+            crate::utils::format_bytes(details.download_size)
+        );
+        if let Some(p) = pb {
+            p.println(msg);
+        } else {
+            println!("{}", msg);
+        }
+    } else {
+        let _ = fs::remove_file(dest);
+    }
+    patched
+}
+
 pub fn install_node(
     node: &InstallNode,
     action: &plan::InstallAction,
@@ -267,6 +369,7 @@ pub fn install_node(
                 build_type,
                 yes,
                 sub_package_to_install.clone(),
+                &node.features,
                 pb_for_step,
             )?;
             (files, "source".to_string())
@@ -292,7 +395,7 @@ pub fn install_node(
         }
     }
 
-    let manifest = manifest::create_manifest(
+    let mut manifest = manifest::create_manifest(
         pkg,
         node.reason.clone(),
         node.dependencies.clone(),
@@ -303,6 +406,8 @@ pub fn install_node(
         &node.chosen_optionals,
         sub_package_to_install.clone(),
     )?;
+    manifest.provider_for = node.provider_for.clone();
+    manifest.features = node.features.clone();
 
     local::write_manifest(&manifest)?;
     local::persist_package_source(&manifest, Path::new(&node.source))?;
@@ -1,4 +1,4 @@
-use crate::pkg::{local, types};
+use crate::pkg::{local, types, verify};
 use anyhow::Result;
 
 pub fn create_manifest(
@@ -31,8 +31,11 @@ pub fn create_manifest(
         chosen_optionals: chosen_optionals.to_vec(),
         install_method,
         service: pkg.service.clone(),
+        file_records: verify::record_files(&installed_files),
         installed_files,
         installed_size: pkg.installed_size,
+        provider_for: vec![],
+        features: vec![],
     })
 }
 
@@ -1,3 +1,4 @@
+pub mod conflict;
 pub mod installer;
 pub mod lockfile;
 pub mod manifest;
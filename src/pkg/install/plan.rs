@@ -33,13 +33,19 @@ pub fn create_install_plan(
                 return (id.clone(), InstallAction::BuildAndInstall);
             }
 
+            if !crate::pkg::features::is_default_set(&node.pkg, &node.features) {
+                return (id.clone(), InstallAction::BuildAndInstall);
+            }
+
             let action = match util::find_prebuilt_info(node) {
                 Ok(Some(info)) => {
                     let (down_size, inst_size) = if let Some(size_url) = &info.size_url {
                         if crate::pkg::offline::is_offline() {
-                            (node.pkg.archive_size.unwrap_or(0), node.pkg.installed_size.unwrap_or(0))
+                            crate::pkg::cache::read_cached_sidecar(util::get_filename_from_url(&info.final_url), "size")
+                                .map(|s| util::parse_size_file(&s))
+                                .unwrap_or((node.pkg.archive_size.unwrap_or(0), node.pkg.installed_size.unwrap_or(0)))
                         } else {
-                            util::get_expected_size(size_url).unwrap_or_else(|e| {
+                            util::get_expected_size(size_url, Some(util::get_filename_from_url(&info.final_url))).unwrap_or_else(|e| {
                                 eprintln!(
                                     "Warning: could not fetch size for {}: {}. Falling back to metadata.",
                                     node.pkg.name,
@@ -12,6 +12,7 @@ pub fn try_build_install(
     build_type_override: Option<&str>,
     yes: bool,
     sub_package_to_install: Option<String>,
+    features: &[String],
     pb: Option<&indicatif::ProgressBar>,
 ) -> Result<Vec<String>> {
     let build_type = if let Some(t) = build_type_override {
@@ -54,6 +55,7 @@ pub fn try_build_install(
     let build_type_clone = build_type.to_string();
     let current_platform_clone = current_platform.clone();
     let version_clone = version.to_string();
+    let selection = crate::pkg::features::exact_selection(features);
 
     let build_handle = thread::spawn(move || {
         crate::pkg::package::build::run(
@@ -64,6 +66,8 @@ pub fn try_build_install(
             None,
             Some(&version_clone),
             sub_packages_vec,
+            &selection,
+            &[],
             true,
             true,
             "native",
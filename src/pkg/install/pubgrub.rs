@@ -1,4 +1,4 @@
-use crate::pkg::{db, resolve, types};
+use crate::pkg::{db, features, resolve, types};
 use pubgrub::{Dependencies, DependencyProvider, Ranges};
 use rusqlite::params;
 use rustc_hash::FxHashMap;
@@ -61,6 +61,14 @@ pub struct ZoiDependencyProvider {
         RefCell<FxHashMap<(PkgName, SemVersion), FxHashMap<PkgName, Ranges<SemVersion>>>>,
     pub chosen_cache:
         RefCell<FxHashMap<(PkgName, SemVersion), (Vec<String>, Vec<String>, Vec<String>)>>,
+    /// Provider picked for each virtual package that was depended on.
+    pub virtuals: RefCell<FxHashMap<String, PkgName>>,
+    /// `+name`/`-name` feature selections asked for each package.
+    pub requested_features: RefCell<FxHashMap<PkgName, Vec<String>>>,
+    /// Feature selection each cached dependency list was read with.
+    pub features_read: RefCell<FxHashMap<(PkgName, SemVersion), Vec<String>>>,
+    /// Features enabled for each package whose dependencies were read.
+    pub enabled_features: RefCell<FxHashMap<PkgName, Vec<String>>>,
 }
 
 pub fn semver_to_range(req_str: &str) -> Ranges<SemVersion> {
@@ -169,6 +177,10 @@ impl ZoiDependencyProvider {
             mini_index,
             deps_cache: RefCell::new(FxHashMap::default()),
             chosen_cache: RefCell::new(FxHashMap::default()),
+            virtuals: RefCell::new(FxHashMap::default()),
+            requested_features: RefCell::new(FxHashMap::default()),
+            features_read: RefCell::new(FxHashMap::default()),
+            enabled_features: RefCell::new(FxHashMap::default()),
         })
     }
 
@@ -176,6 +188,28 @@ impl ZoiDependencyProvider {
         semver_to_range(req_str)
     }
 
+    fn selection(&self, package: &PkgName) -> Vec<String> {
+        self.requested_features
+            .borrow()
+            .get(package)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether a dependent asked for more features of a solved package after
+    /// its dependencies were read. Those features may pull in dependencies
+    /// the solution lacks, so it has to be resolved again.
+    pub fn features_grew<'a>(
+        &self,
+        solved: impl IntoIterator<Item = (&'a PkgName, &'a SemVersion)>,
+    ) -> bool {
+        let read = self.features_read.borrow();
+        solved.into_iter().any(|(package, version)| {
+            read.get(&(package.clone(), version.clone()))
+                .is_some_and(|selection| *selection != self.selection(package))
+        })
+    }
+
     fn source_matches_package(&self, package: &PkgName, source: &str) -> bool {
         if let Some(explicit_source) = &package.explicit_source {
             let explicit_base = explicit_source
@@ -319,7 +353,10 @@ impl DependencyProvider for ZoiDependencyProvider {
         }
 
         let cache_key = (package.clone(), version.clone());
-        if let Some(cached) = self.deps_cache.borrow().get(&cache_key) {
+        let selection = self.selection(package);
+        if let Some(cached) = self.deps_cache.borrow().get(&cache_key)
+            && self.features_read.borrow().get(&cache_key) == Some(&selection)
+        {
             return Ok(Dependencies::Available(
                 pubgrub::DependencyConstraints::from_iter(cached.clone()),
             ));
@@ -337,10 +374,23 @@ impl DependencyProvider for ZoiDependencyProvider {
         .ok()
         .flatten();
 
-        let package_deps = if let Some(deps_json) = dependencies_opt
+        let (package_deps, declared_features) = if let Some(deps_json) = dependencies_opt
             && !deps_json.is_empty()
         {
-            serde_json::from_str::<types::Dependencies>(&deps_json).ok()
+            let declared = db::get_package_features(
+                &package.registry,
+                &package.name,
+                &version_str,
+                package.sub_package.as_deref(),
+                &package.repo,
+            )
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok());
+            (
+                serde_json::from_str::<types::Dependencies>(&deps_json).ok(),
+                declared,
+            )
         } else {
             let source = package
                 .explicit_source
@@ -349,21 +399,24 @@ impl DependencyProvider for ZoiDependencyProvider {
             let pkg_res = resolve::resolve_package_and_version(&source, self.quiet, self.yes);
 
             match pkg_res {
-                Ok((pkg, _, _, _, _, _)) => pkg.dependencies,
-                Err(_) => None,
+                Ok((pkg, _, _, _, _, _)) => (pkg.dependencies, pkg.features),
+                Err(_) => (None, None),
             }
         };
 
+        let enabled = features::resolve(&package.name, declared_features.as_ref(), &selection)
+            .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?;
+
         let mut deps = FxHashMap::default();
 
         let mut chosen_opts = Vec::new();
         let mut chosen_opts_opt = Vec::new();
-        let mut all_req = Vec::new();
+        let mut req_deps = Vec::new();
 
         if let Some(dependencies) = package_deps
             && let Some(runtime) = &dependencies.runtime
         {
-            let (req_deps, co, coo) =
+            let (group_deps, co, coo) =
                 crate::pkg::install::resolver::collect_dependencies_for_group(
                     runtime,
                     package.sub_package.as_deref(),
@@ -375,53 +428,81 @@ impl DependencyProvider for ZoiDependencyProvider {
 
             chosen_opts = co;
             chosen_opts_opt = coo;
-            all_req = req_deps.clone();
+            req_deps = group_deps;
+        }
 
-            for dep_str in req_deps {
-                let dep_req = crate::pkg::dependencies::parse_dependency_string(&dep_str)
-                    .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?;
+        for dep in features::dependencies(declared_features.as_ref(), &enabled, false) {
+            if !req_deps.contains(&dep) {
+                req_deps.push(dep);
+            }
+        }
+        self.enabled_features
+            .borrow_mut()
+            .insert(package.clone(), enabled);
+        let all_req = req_deps.clone();
 
-                if dep_req.manager == "zoi" {
-                    let req = resolve::parse_source_string(dep_req.package)
-                        .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?;
-                    let resolved_dep =
-                        resolve::resolve_source(dep_req.package, self.quiet, self.yes)
-                            .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?;
-
-                    let dep_name = PkgName {
-                        name: req.name,
-                        sub_package: req.sub_package,
-                        repo: resolved_dep.repo_name.unwrap_or_default(),
-                        registry: resolved_dep
-                            .registry_handle
-                            .unwrap_or_else(|| "zoidberg".to_string()),
-                        explicit_source: matches!(
-                            resolved_dep.source_type,
-                            resolve::SourceType::LocalFile
-                                | resolve::SourceType::Url
-                                | resolve::SourceType::GitRepo(_)
-                        )
-                        .then(|| dep_req.package.to_string()),
-                    };
+        for dep_str in req_deps {
+            let dep_req = crate::pkg::dependencies::parse_dependency_string(&dep_str)
+                .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?;
 
-                    let range = if req.version_spec.is_some() {
-                        let resolved_version =
-                            resolve::resolve_requested_version_spec(dep_req.package, true, true)
-                                .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?
-                                .expect("version spec presence was checked above");
-                        self.semver_to_range(&resolved_version)
-                    } else {
-                        Ranges::full()
-                    };
+            if dep_req.manager == "zoi" {
+                let req = resolve::parse_source_string(dep_req.package)
+                    .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?;
+                let resolved_dep = resolve::resolve_source(dep_req.package, self.quiet, self.yes)
+                    .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?;
 
-                    deps.insert(dep_name, range);
+                let provider = resolved_dep.provider.clone();
+                let dep_name = PkgName {
+                    name: provider.clone().unwrap_or_else(|| req.name.clone()),
+                    sub_package: req.sub_package.clone(),
+                    repo: resolved_dep.repo_name.unwrap_or_default(),
+                    registry: resolved_dep
+                        .registry_handle
+                        .unwrap_or_else(|| "zoidberg".to_string()),
+                    explicit_source: matches!(
+                        resolved_dep.source_type,
+                        resolve::SourceType::LocalFile
+                            | resolve::SourceType::Url
+                            | resolve::SourceType::GitRepo(_)
+                    )
+                    .then(|| dep_req.package.to_string()),
+                };
+
+                if !req.features.is_empty() {
+                    let mut requested = self.requested_features.borrow_mut();
+                    let selection = requested.entry(dep_name.clone()).or_default();
+                    for feature in &req.features {
+                        if !selection.contains(feature) {
+                            selection.push(feature.clone());
+                        }
+                    }
                 }
+
+                let range = if provider.is_some() {
+                    self.virtuals
+                        .borrow_mut()
+                        .insert(req.name.clone(), dep_name.clone());
+                    Ranges::full()
+                } else if req.version_spec.is_some() {
+                    let resolved_version =
+                        resolve::resolve_requested_version_spec(dep_req.package, true, true)
+                            .map_err(|e| ZoiSolverError::Dependency(e.to_string()))?
+                            .expect("version spec presence was checked above");
+                    self.semver_to_range(&resolved_version)
+                } else {
+                    Ranges::full()
+                };
+
+                deps.insert(dep_name, range);
             }
         }
 
         self.deps_cache
             .borrow_mut()
             .insert(cache_key.clone(), deps.clone());
+        self.features_read
+            .borrow_mut()
+            .insert(cache_key.clone(), selection);
         self.chosen_cache
             .borrow_mut()
             .insert(cache_key, (chosen_opts, chosen_opts_opt, all_req));
@@ -1,12 +1,13 @@
 use crate::pkg::{
     dependencies,
+    install::conflict::{ConflictHints, explain_conflict},
     install::pubgrub::{PkgName, SemVersion, ZoiDependencyProvider},
     resolve,
     types::{self, InstallReason, Package},
 };
 use crate::project::lockfile::FrozenLockPackage;
 use anyhow::{Result, anyhow};
-use pubgrub::{DependencyProvider, Ranges, resolve as pubgrub_resolve};
+use pubgrub::{DependencyProvider, PubGrubError, Ranges, resolve as pubgrub_resolve};
 use rustc_hash::FxHashMap;
 use semver::Version;
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -70,6 +71,10 @@ pub struct InstallNode {
     pub chosen_optionals: Vec<String>,
     pub dependencies: Vec<String>,
     pub git_sha: Option<String>,
+    /// Virtual packages this node was picked to provide.
+    pub provider_for: Vec<String>,
+    /// Features enabled for this node.
+    pub features: Vec<String>,
 }
 
 #[derive(Default, Debug)]
@@ -185,6 +190,8 @@ pub fn build_graph_from_locked_packages(
                 chosen_optionals: locked.chosen_optionals.clone(),
                 dependencies: locked.dependencies.clone(),
                 git_sha: locked.git_sha.clone().or(git_sha),
+                provider_for: locked.provider_for.clone(),
+                features: locked.features.clone(),
             },
         );
     }
@@ -272,6 +279,8 @@ pub fn resolve_dependency_graph(
 
     let mut non_zoi_deps = Vec::new();
     let mut root_deps = FxHashMap::default();
+    let mut root_virtuals = FxHashMap::default();
+    let mut root_features: FxHashMap<PkgName, Vec<String>> = FxHashMap::default();
 
     for source in initial_sources {
         let parse_result = dependencies::parse_dependency_string(source);
@@ -284,10 +293,11 @@ pub fn resolve_dependency_graph(
 
         let request = resolve::parse_source_string(source)?;
         let resolved = resolve::resolve_source(source, quiet, yes)?;
+        let provider = resolved.provider.clone();
 
         let pkg_name = PkgName {
-            name: request.name,
-            sub_package: request.sub_package,
+            name: provider.clone().unwrap_or_else(|| request.name.clone()),
+            sub_package: request.sub_package.clone(),
             repo: resolved.repo_name.unwrap_or_default(),
             registry: resolved
                 .registry_handle
@@ -301,7 +311,10 @@ pub fn resolve_dependency_graph(
             .then(|| source.clone()),
         };
 
-        let range = if request.version_spec.is_some() {
+        let range = if provider.is_some() {
+            root_virtuals.insert(request.name.clone(), pkg_name.clone());
+            Ranges::full()
+        } else if request.version_spec.is_some() {
             let resolved_version = resolve::resolve_requested_version_spec(source, true, true)?
                 .expect("version spec presence was checked above");
             crate::pkg::install::pubgrub::semver_to_range(&resolved_version)
@@ -309,6 +322,12 @@ pub fn resolve_dependency_graph(
             Ranges::full()
         };
 
+        if !request.features.is_empty() {
+            root_features
+                .entry(pkg_name.clone())
+                .or_default()
+                .extend(request.features.iter().cloned());
+        }
         root_deps.insert(pkg_name, range);
     }
 
@@ -319,6 +338,11 @@ pub fn resolve_dependency_graph(
         yes,
         all_optional,
     )?;
+    provider.virtuals.borrow_mut().extend(root_virtuals);
+    provider
+        .requested_features
+        .borrow_mut()
+        .extend(root_features);
     let root_pkg = PkgName {
         name: "$root".to_string(),
         sub_package: None,
@@ -331,7 +355,21 @@ pub fn resolve_dependency_graph(
     let mut final_nodes = HashMap::new();
     let mut final_adj: HashMap<String, HashSet<String>> = HashMap::new();
 
-    match pubgrub_resolve::<ZoiDependencyProvider>(&provider, root_pkg, root_version) {
+    // Features a dependent asks for late are only applied by resolving again
+    // with the grown selection; selections only grow, so this ends.
+    let result = loop {
+        let result = pubgrub_resolve::<ZoiDependencyProvider>(
+            &provider,
+            root_pkg.clone(),
+            root_version.clone(),
+        );
+        match &result {
+            Ok(solution) if provider.features_grew(solution.iter()) => continue,
+            _ => break result,
+        }
+    };
+
+    match result {
         Ok(solution) => {
             for (name, version) in solution.iter() {
                 if name.name == "$root" {
@@ -372,6 +410,21 @@ pub fn resolve_dependency_graph(
                     }
                 }
 
+                let mut provider_for: Vec<String> = provider
+                    .virtuals
+                    .borrow()
+                    .iter()
+                    .filter(|(_, chosen)| *chosen == name)
+                    .map(|(virtual_name, _)| virtual_name.clone())
+                    .collect();
+                provider_for.sort();
+                let features = provider
+                    .enabled_features
+                    .borrow()
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_default();
+
                 let node = InstallNode {
                     pkg: pkg.clone(),
                     version: version_str,
@@ -383,6 +436,8 @@ pub fn resolve_dependency_graph(
                     chosen_optionals,
                     dependencies: all_req_deps,
                     git_sha,
+                    provider_for,
+                    features,
                 };
                 final_nodes.insert(pkg_id, node);
             }
@@ -485,6 +540,25 @@ pub fn resolve_dependency_graph(
                 node.dependencies = resolved_deps;
             }
         }
+        Err(PubGrubError::NoSolution(tree)) => {
+            let hints = ConflictHints::from_provider(&provider, &tree);
+            return Err(anyhow!(
+                "Dependency resolution failed.\n\n{}",
+                explain_conflict(&tree, &hints)
+            ));
+        }
+        Err(PubGrubError::ErrorRetrievingDependencies {
+            package,
+            version,
+            source,
+        }) => {
+            return Err(anyhow!(
+                "Dependency resolution failed: could not read the dependencies of {}@{}: {}",
+                package,
+                version,
+                source
+            ));
+        }
         Err(e) => return Err(anyhow!("Dependency resolution failed: {}", e)),
     }
 
@@ -496,3 +570,54 @@ pub fn resolve_dependency_graph(
         non_zoi_deps,
     ))
 }
+
+/// Resolves each source on its own and merges the results, so several
+/// versions of the same package can be installed side by side. Packages that
+/// resolve to the same version are shared between the requests.
+pub fn resolve_side_by_side(
+    initial_sources: &[String],
+    scope_override: Option<types::Scope>,
+    force: bool,
+    yes: bool,
+    all_optional: bool,
+    build_type: Option<&str>,
+    quiet: bool,
+) -> Result<(DependencyGraph, Vec<String>)> {
+    let mut graph = DependencyGraph::new();
+    let mut non_zoi_deps: Vec<String> = Vec::new();
+
+    for source in initial_sources {
+        let (sub_graph, sub_non_zoi) = resolve_dependency_graph(
+            std::slice::from_ref(source),
+            scope_override,
+            force,
+            yes,
+            all_optional,
+            build_type,
+            quiet,
+        )?;
+
+        for (pkg_id, node) in sub_graph.nodes {
+            match graph.nodes.get_mut(&pkg_id) {
+                Some(existing) => {
+                    if matches!(node.reason, InstallReason::Direct) {
+                        existing.reason = InstallReason::Direct;
+                    }
+                }
+                None => {
+                    graph.nodes.insert(pkg_id, node);
+                }
+            }
+        }
+        for (from, to_set) in sub_graph.adj {
+            graph.adj.entry(from).or_default().extend(to_set);
+        }
+        for dep in sub_non_zoi {
+            if !non_zoi_deps.contains(&dep) {
+                non_zoi_deps.push(dep);
+            }
+        }
+    }
+
+    Ok((graph, non_zoi_deps))
+}
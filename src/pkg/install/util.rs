@@ -475,12 +475,15 @@ pub fn get_filename_from_url(url: &str) -> &str {
     url.split('/').next_back().unwrap_or_default()
 }
 
-fn get_text_from_candidate_urls(urls: &[String], resource_name: &str) -> Result<String> {
+pub fn get_text_from_candidate_urls(urls: &[String], resource_name: &str) -> Result<String> {
     let client = crate::utils::get_http_client()?;
     let mut last_error = None;
 
     for candidate_url in urls {
         match client.get(candidate_url).send() {
+            Ok(response) if !response.status().is_success() => {
+                last_error = Some(format!("{} (HTTP {})", candidate_url, response.status()))
+            }
             Ok(response) => match response.text() {
                 Ok(text) => return Ok(text),
                 Err(e) => last_error = Some(format!("{} ({})", candidate_url, e)),
@@ -676,17 +679,22 @@ pub fn verify_file_hash(
     Ok(result)
 }
 
-pub fn get_remote_file_list(url: &str) -> Result<Vec<String>> {
+pub fn get_remote_file_list(url: &str, filename: Option<&str>) -> Result<Vec<String>> {
     if crate::pkg::offline::is_offline() {
         return Ok(Vec::new());
     }
     let resp = get_text_from_candidate_urls(&cache::mirror_candidate_urls(url), "files list")?;
+    if let Some(f) = filename {
+        cache::write_cached_sidecar(f, "files", &resp);
+    }
+    Ok(parse_file_list(&resp))
+}
 
-    Ok(resp
-        .lines()
+pub fn parse_file_list(resp: &str) -> Vec<String> {
+    resp.lines()
         .map(|l| l.trim().to_string())
         .filter(|l| !l.is_empty())
-        .collect())
+        .collect()
 }
 
 pub fn check_file_conflicts(
@@ -716,10 +724,14 @@ pub fn check_file_conflicts(
 
         if let Ok(Some(info)) = find_prebuilt_info(node) {
             let mut file_list = None;
+            let archive_filename = get_filename_from_url(&info.final_url);
             if let Some(files_url) = &info.files_url
-                && let Ok(list) = get_remote_file_list(files_url)
+                && let Ok(list) = get_remote_file_list(files_url, Some(archive_filename))
+                && !list.is_empty()
             {
                 file_list = Some(list);
+            } else if let Some(cached) = cache::read_cached_sidecar(archive_filename, "files") {
+                file_list = Some(parse_file_list(&cached));
             }
 
             if let Some(list) = file_list {
@@ -890,10 +902,19 @@ pub fn get_file_conflicts_from_archive(
 }
 
 pub fn get_expected_hash(hash_url: &str, filename: Option<&str>) -> Result<String> {
-    if crate::pkg::offline::is_offline() {
-        return Ok(String::new());
-    }
-    let resp = get_text_from_candidate_urls(&cache::mirror_candidate_urls(hash_url), "hash file")?;
+    let resp = if crate::pkg::offline::is_offline() {
+        match filename.and_then(|f| cache::read_cached_sidecar(f, "hash")) {
+            Some(resp) => resp,
+            None => return Ok(String::new()),
+        }
+    } else {
+        let resp =
+            get_text_from_candidate_urls(&cache::mirror_candidate_urls(hash_url), "hash file")?;
+        if let Some(f) = filename {
+            cache::write_cached_sidecar(f, "hash", &resp);
+        }
+        resp
+    };
 
     let is_sha512 = |s: &str| s.len() == 128 && s.chars().all(|c| c.is_ascii_hexdigit());
 
@@ -921,12 +942,18 @@ pub fn get_expected_hash(hash_url: &str, filename: Option<&str>) -> Result<Strin
         .to_string())
 }
 
-pub fn get_expected_size(size_url: &str) -> Result<(u64, u64)> {
+pub fn get_expected_size(size_url: &str, filename: Option<&str>) -> Result<(u64, u64)> {
     if crate::pkg::offline::is_offline() {
         return Ok((0, 0));
     }
     let resp = get_text_from_candidate_urls(&cache::mirror_candidate_urls(size_url), "size file")?;
+    if let Some(f) = filename {
+        cache::write_cached_sidecar(f, "size", &resp);
+    }
+    Ok(parse_size_file(&resp))
+}
 
+pub fn parse_size_file(resp: &str) -> (u64, u64) {
     let mut download_size = 0;
     let mut installed_size = 0;
     let mut found_fields = false;
@@ -953,7 +980,7 @@ pub fn get_expected_size(size_url: &str) -> Result<(u64, u64)> {
         download_size = num;
     }
 
-    Ok((download_size, installed_size))
+    (download_size, installed_size)
 }
 
 pub fn resolve_url_placeholders(
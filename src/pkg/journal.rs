@@ -0,0 +1,414 @@
+use crate::pkg::{local, store, transaction, types, uninstall};
+use anyhow::{Result, anyhow};
+use colored::*;
+use dialoguer::{Select, theme::ColorfulTheme};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+/// Transaction whose file operations are currently being journaled.
+static ACTIVE: LazyLock<Mutex<Option<String>>> = LazyLock::new(|| Mutex::new(None));
+
+/// A file operation, written to the journal before it is carried out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Entry {
+    BeginInstall {
+        scope: types::Scope,
+    },
+    CreateDir {
+        path: PathBuf,
+    },
+    CreateFiles {
+        paths: Vec<PathBuf>,
+    },
+    BeginUninstall {
+        manifest: Box<types::InstallManifest>,
+    },
+    Remove {
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// A journal line. `target` is the version directory the operation belongs
+/// to, which keeps the entries of parallel installs apart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub target: PathBuf,
+    #[serde(flatten)]
+    pub entry: Entry,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Keep what finished, complete interrupted uninstalls and drop
+    /// half-written installs, then commit.
+    Forward,
+    /// Undo the whole transaction with `transaction::rollback`.
+    Back,
+}
+
+fn get_journal_path(transaction_id: &str) -> Result<PathBuf> {
+    Ok(transaction::get_transactions_dir()?.join(format!("{}.journal", transaction_id)))
+}
+
+/// Starts journaling file operations for `transaction_id`.
+pub fn activate(transaction_id: &str) {
+    *ACTIVE.lock().expect("Journal mutex poisoned") = Some(transaction_id.to_string());
+}
+
+/// Stops journaling. Used by rollbacks, which must not journal into the
+/// transaction they are undoing.
+pub fn deactivate() {
+    *ACTIVE.lock().expect("Journal mutex poisoned") = None;
+}
+
+/// Appends an entry for `target` to the active journal and syncs it to disk.
+/// Does nothing outside a transaction.
+pub fn record(target: &Path, entry: Entry) -> Result<()> {
+    let active = ACTIVE.lock().expect("Journal mutex poisoned");
+    let Some(transaction_id) = active.as_deref() else {
+        return Ok(());
+    };
+
+    let record = Record {
+        target: target.to_path_buf(),
+        entry,
+    };
+    let mut line = serde_json::to_string(&record)?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_journal_path(transaction_id)?)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Reads a journal. A torn last line from a crash mid-write is ignored.
+pub fn read(transaction_id: &str) -> Result<Vec<Record>> {
+    let path = get_journal_path(transaction_id)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Deletes the journal of a transaction that was committed or rolled back.
+pub fn discard(transaction_id: &str) -> Result<()> {
+    let mut active = ACTIVE.lock().expect("Journal mutex poisoned");
+    if active.as_deref() == Some(transaction_id) {
+        *active = None;
+    }
+    let path = get_journal_path(transaction_id)?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Transactions that were never committed or rolled back, oldest first.
+/// Only meaningful while holding the package lock.
+pub fn interrupted_transactions() -> Result<Vec<String>> {
+    let dir = transaction::get_transactions_dir()?;
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("json") => ids.push(id.to_string()),
+            Some("journal") if !dir.join(format!("{}.json", id)).exists() => {
+                let _ = fs::remove_file(&path);
+            }
+            _ => {}
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+#[derive(Default)]
+struct InterruptedInstall {
+    scope: Option<types::Scope>,
+    created_dirs: Vec<PathBuf>,
+    created_files: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct InterruptedUninstall {
+    manifest: Option<types::InstallManifest>,
+    removed: Vec<PathBuf>,
+}
+
+fn manifests_in(version_dir: &Path) -> Vec<types::InstallManifest> {
+    let Ok(entries) = fs::read_dir(version_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with("manifest") && name.ends_with(".yaml")
+        })
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_yaml::from_str(&content).ok())
+        .collect()
+}
+
+fn manifest_path(manifest: &types::InstallManifest) -> Result<PathBuf> {
+    let version_dir = local::get_package_version_dir(
+        manifest.scope,
+        &manifest.registry_handle,
+        &manifest.repo,
+        &manifest.name,
+        &manifest.version,
+    )?;
+    Ok(version_dir.join(match &manifest.sub_package {
+        Some(sub) => format!("manifest-{}.yaml", sub),
+        None => "manifest.yaml".to_string(),
+    }))
+}
+
+fn same_install(a: &types::InstallManifest, b: &types::InstallManifest) -> bool {
+    a.scope == b.scope
+        && a.registry_handle == b.registry_handle
+        && a.repo == b.repo
+        && a.name == b.name
+        && a.sub_package == b.sub_package
+        && a.version == b.version
+}
+
+fn remove_path(path: &Path) {
+    if path.is_dir() && !path.is_symlink() {
+        let _ = fs::remove_dir_all(path);
+    } else if path.symlink_metadata().is_ok() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Removes everything an interrupted install created.
+fn undo_install(target: &Path, install: &InterruptedInstall) {
+    println!("Removing partial install at {}...", target.display());
+    for file in install.created_files.iter().rev() {
+        remove_path(file);
+    }
+    for dir in install.created_dirs.iter().rev() {
+        remove_path(dir);
+        if let Some(scope) = install.scope {
+            let _ = store::release(scope, dir);
+        }
+    }
+}
+
+/// Brings the journal of an interrupted transaction to a consistent state,
+/// then commits (`Forward`) or rolls back (`Back`) the transaction.
+pub fn recover(transaction_id: &str, mode: Recovery) -> Result<()> {
+    let mut installs: Vec<(PathBuf, InterruptedInstall)> = Vec::new();
+    let mut uninstalls: Vec<(PathBuf, InterruptedUninstall)> = Vec::new();
+    let mut install_index = HashMap::new();
+    let mut uninstall_index = HashMap::new();
+
+    for record in read(transaction_id)? {
+        let target = record.target;
+        match record.entry {
+            Entry::BeginInstall { scope } => {
+                let i = *install_index.entry(target.clone()).or_insert_with(|| {
+                    installs.push((target.clone(), InterruptedInstall::default()));
+                    installs.len() - 1
+                });
+                installs[i].1.scope = Some(scope);
+            }
+            Entry::CreateDir { path } => {
+                if let Some(&i) = install_index.get(&target) {
+                    installs[i].1.created_dirs.push(path);
+                }
+            }
+            Entry::CreateFiles { paths } => {
+                if let Some(&i) = install_index.get(&target) {
+                    installs[i].1.created_files.extend(paths);
+                }
+            }
+            Entry::BeginUninstall { manifest } => {
+                let i = *uninstall_index.entry(target.clone()).or_insert_with(|| {
+                    uninstalls.push((target.clone(), InterruptedUninstall::default()));
+                    uninstalls.len() - 1
+                });
+                uninstalls[i].1.manifest = Some(*manifest);
+            }
+            Entry::Remove { paths } => {
+                if let Some(&i) = uninstall_index.get(&target) {
+                    uninstalls[i].1.removed.extend(paths);
+                }
+            }
+        }
+    }
+
+    let logged = transaction::read_transaction(transaction_id)
+        .map(|t| t.operations)
+        .unwrap_or_default();
+    let installed_in_log = |manifest: &types::InstallManifest| {
+        logged.iter().any(|op| match op {
+            types::TransactionOperation::Install { manifest: m } => same_install(m, manifest),
+            types::TransactionOperation::Upgrade { new_manifest, .. } => {
+                same_install(new_manifest, manifest)
+            }
+            types::TransactionOperation::Uninstall { .. } => false,
+        })
+    };
+    let uninstalled_in_log = |manifest: &types::InstallManifest| {
+        logged.iter().any(|op| {
+            matches!(op, types::TransactionOperation::Uninstall { manifest: m } if same_install(m, manifest))
+        })
+    };
+
+    let mut pending_ops = Vec::new();
+
+    for (target, install) in &installs {
+        let manifests = manifests_in(target);
+        if manifests.is_empty() {
+            undo_install(target, install);
+            continue;
+        }
+        // The manifest is the last thing an install writes, so the install
+        // itself finished. Rolling back has to know about it to undo it.
+        let created_version_dir = install.created_dirs.iter().any(|dir| dir == target);
+        if mode == Recovery::Back && created_version_dir {
+            for manifest in manifests {
+                if !installed_in_log(&manifest) {
+                    pending_ops.push(types::TransactionOperation::Install {
+                        manifest: Box::new(manifest),
+                    });
+                }
+            }
+        }
+    }
+
+    for (_, uninstall) in &uninstalls {
+        let Some(manifest) = &uninstall.manifest else {
+            continue;
+        };
+        let manifest_path = manifest_path(manifest)?;
+        match mode {
+            Recovery::Forward => {
+                if manifest_path.exists() {
+                    println!(
+                        "Finishing uninstall of {} v{}...",
+                        manifest.name.cyan(),
+                        manifest.version.yellow()
+                    );
+                    let source = local::installed_manifest_source(manifest);
+                    if let Err(e) = uninstall::run(&source, Some(manifest.scope), true) {
+                        eprintln!(
+                            "{} Failed to finish uninstall of '{}': {}",
+                            "Error:".red().bold(),
+                            manifest.name,
+                            e
+                        );
+                    }
+                } else {
+                    for path in &uninstall.removed {
+                        remove_path(path);
+                        let _ = store::release(manifest.scope, path);
+                    }
+                }
+            }
+            Recovery::Back => {
+                if uninstalled_in_log(manifest) {
+                    continue;
+                }
+                let untouched = manifest_path.exists()
+                    && manifest
+                        .installed_files
+                        .iter()
+                        .all(|file| Path::new(file).exists());
+                if untouched {
+                    continue;
+                }
+                // Some files are already gone: drop the manifest so the
+                // rollback re-installs the package instead of trusting it.
+                if manifest_path.exists() {
+                    fs::remove_file(&manifest_path)?;
+                }
+                pending_ops.push(types::TransactionOperation::Uninstall {
+                    manifest: Box::new(manifest.clone()),
+                });
+            }
+        }
+    }
+
+    match mode {
+        Recovery::Forward => transaction::commit(transaction_id),
+        Recovery::Back => {
+            for op in pending_ops {
+                transaction::record_operation(transaction_id, op)?;
+            }
+            transaction::rollback(transaction_id)
+        }
+    }
+}
+
+fn is_empty(transaction_id: &str) -> Result<bool> {
+    let transaction = transaction::read_transaction(transaction_id)?;
+    Ok(transaction.operations.is_empty() && read(transaction_id)?.is_empty())
+}
+
+/// Looks for transactions left behind by a Zoi process that was killed and
+/// offers to roll each one forward or back. Without a terminal to ask on,
+/// they are rolled back.
+pub fn recover_interrupted() -> Result<()> {
+    for id in interrupted_transactions()? {
+        if is_empty(&id).unwrap_or(false) {
+            transaction::delete_log(&id)?;
+            continue;
+        }
+
+        let start_time = transaction::read_transaction(&id)
+            .map(|t| t.start_time)
+            .unwrap_or_default();
+        eprintln!(
+            "{}: Transaction {} (started {}) was interrupted and never committed.",
+            "Warning".yellow().bold(),
+            id.cyan(),
+            start_time
+        );
+
+        let mode = if std::io::stdin().is_terminal() {
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("How should it be recovered?")
+                .items([
+                    "Roll back (undo every change of the transaction)",
+                    "Roll forward (keep finished changes, drop partial ones)",
+                ])
+                .default(0)
+                .interact()?;
+            if choice == 0 {
+                Recovery::Back
+            } else {
+                Recovery::Forward
+            }
+        } else {
+            println!("No terminal to ask on, rolling it back.");
+            Recovery::Back
+        };
+
+        if let Err(e) = recover(&id, mode) {
+            return Err(anyhow!(
+                "Could not recover transaction {}: {}. Inspect it with 'zoi transaction show {}'.",
+                id,
+                e,
+                id
+            ));
+        }
+    }
+    Ok(())
+}
@@ -4,7 +4,7 @@ use crate::pkg::resolve::get_db_root;
 use crate::pkg::sysroot::apply_sysroot;
 use crate::pkg::types::{self, InstallManifest, Scope};
 use crate::pkg::utils;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
@@ -28,8 +28,8 @@ pub fn get_store_base_dir(scope: Scope) -> Result<PathBuf> {
             }
         }
         Scope::Project => {
-            let current_dir = std::env::current_dir()?;
-            Ok(current_dir.join(".zoi").join("pkgs").join("store"))
+            let project_root = crate::project::config::project_root()?;
+            Ok(project_root.join(".zoi").join("pkgs").join("store"))
         }
     }
 }
@@ -175,6 +175,29 @@ pub fn is_package_installed(
     Ok(None)
 }
 
+fn read_manifests_in_dir(dir: &Path, manifests: &mut Vec<InstallManifest>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !file_name.starts_with("manifest") || !file_name.ends_with(".yaml") {
+            continue;
+        }
+
+        let manifest_path = entry.path();
+        if !manifest_path.exists() {
+            continue;
+        }
+
+        let content = fs::read_to_string(manifest_path)?;
+        let manifest: InstallManifest = serde_yaml::from_str(&content)?;
+        manifests.push(manifest);
+    }
+    Ok(())
+}
+
 pub fn get_installed_manifests_in_scope(scope: Scope) -> Result<Vec<InstallManifest>> {
     let store_root = get_store_base_dir(scope)?;
     if !store_root.exists() {
@@ -194,35 +217,52 @@ pub fn get_installed_manifests_in_scope(scope: Scope) -> Result<Vec<InstallManif
             continue;
         }
 
-        let Ok(entries) = fs::read_dir(&latest_path) else {
-            continue;
-        };
+        read_manifests_in_dir(&latest_path, &mut manifests)?;
+    }
 
-        for entry in entries.filter_map(Result::ok) {
-            let file_name = entry.file_name().to_string_lossy().to_string();
-            if !file_name.starts_with("manifest") || !file_name.ends_with(".yaml") {
-                continue;
-            }
+    Ok(manifests)
+}
 
-            let manifest_path = entry.path();
-            if !manifest_path.exists() {
-                continue;
-            }
+/// Like [`get_installed_manifests_in_scope`], but returns the manifests of every
+/// installed version instead of only the default one.
+pub fn get_all_version_manifests_in_scope(scope: Scope) -> Result<Vec<InstallManifest>> {
+    let store_root = get_store_base_dir(scope)?;
+    if !store_root.exists() {
+        return Ok(Vec::new());
+    }
 
-            let content = fs::read_to_string(manifest_path)?;
-            let manifest: InstallManifest = serde_yaml::from_str(&content)?;
-            manifests.push(manifest);
+    let mut manifests = Vec::new();
+    for entry in fs::read_dir(store_root)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        for version_entry in fs::read_dir(&path)?.flatten() {
+            let name = version_entry.file_name().to_string_lossy().to_string();
+            let version_path = version_entry.path();
+            if is_version_dir_name(&name) && version_path.is_dir() {
+                read_manifests_in_dir(&version_path, &mut manifests)?;
+            }
         }
     }
 
     Ok(manifests)
 }
 
+/// Finds installed manifests matching `request`. Without a version in the
+/// request only the default version of each package is considered; with one,
+/// every installed version is searched so non-default versions can be targeted.
 pub fn find_installed_manifests_matching(
     request: &PackageRequest,
     scope: Scope,
 ) -> Result<Vec<InstallManifest>> {
-    let manifests = get_installed_manifests_in_scope(scope)?;
+    let manifests = if request.version_spec.is_some() {
+        get_all_version_manifests_in_scope(scope)?
+    } else {
+        get_installed_manifests_in_scope(scope)?
+    };
     Ok(manifests
         .into_iter()
         .filter(|manifest| {
@@ -244,6 +284,137 @@ pub fn find_installed_manifests_matching(
         .collect())
 }
 
+fn is_version_dir_name(name: &str) -> bool {
+    name != "latest" && name != "dependents" && !name.starts_with('.')
+}
+
+fn has_manifest(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with("manifest") && name.ends_with(".yaml")
+        })
+    })
+}
+
+/// Kept versions are marked next to their directory, not inside it, so the
+/// marker does not change the hash the lockfile records for the version.
+fn keep_marker(version_dir: &Path) -> Option<PathBuf> {
+    let version = version_dir.file_name()?.to_string_lossy();
+    Some(version_dir.parent()?.join(format!(".keep-{}", version)))
+}
+
+/// Lists the versions of a package present in the store, newest first.
+pub fn get_installed_versions(
+    scope: Scope,
+    registry_handle: &str,
+    repo_path: &str,
+    package_name: &str,
+) -> Result<Vec<String>> {
+    let package_dir = get_package_dir(scope, registry_handle, repo_path, package_name)?;
+    let mut versions = Vec::new();
+    if let Ok(entries) = fs::read_dir(&package_dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_version_dir_name(&name) && has_manifest(&entry.path()) {
+                versions.push(name);
+            }
+        }
+    }
+    versions.sort_by(|a, b| compare_versions(b, a));
+    Ok(versions)
+}
+
+/// Orders version strings by semver, falling back to plain string order.
+pub fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+    match (semver::Version::parse(a), semver::Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// Returns the version the package's `latest` link points at.
+pub fn get_default_version(
+    scope: Scope,
+    registry_handle: &str,
+    repo_path: &str,
+    package_name: &str,
+) -> Result<Option<String>> {
+    let package_dir = get_package_dir(scope, registry_handle, repo_path, package_name)?;
+    let Ok(target) = fs::read_link(package_dir.join("latest")) else {
+        return Ok(None);
+    };
+    Ok(target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|version| package_dir.join(version).is_dir()))
+}
+
+/// Points the package's `latest` link at an installed version.
+pub fn set_default_version(
+    scope: Scope,
+    registry_handle: &str,
+    repo_path: &str,
+    package_name: &str,
+    version: &str,
+) -> Result<()> {
+    let version_dir =
+        get_package_version_dir(scope, registry_handle, repo_path, package_name, version)?;
+    if !version_dir.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Version {} of '{}' is not installed.",
+            version,
+            package_name
+        ));
+    }
+    let package_dir = get_package_dir(scope, registry_handle, repo_path, package_name)?;
+    crate::utils::symlink_dir(&version_dir, &package_dir.join("latest"))?;
+    Ok(())
+}
+
+/// Reads the manifest of one installed version of a package.
+pub fn read_version_manifest(
+    scope: Scope,
+    registry_handle: &str,
+    repo_path: &str,
+    package_name: &str,
+    sub_package: Option<&str>,
+    version: &str,
+) -> Result<Option<InstallManifest>> {
+    let version_dir =
+        get_package_version_dir(scope, registry_handle, repo_path, package_name, version)?;
+    let manifest_filename = match sub_package {
+        Some(sub) => format!("manifest-{}.yaml", sub),
+        None => "manifest.yaml".to_string(),
+    };
+    let manifest_path = version_dir.join(manifest_filename);
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(manifest_path)?;
+    Ok(Some(serde_yaml::from_str(&content)?))
+}
+
+/// Marks an installed version as kept, so updates leave it in place next to
+/// the new default version.
+pub fn mark_version_kept(version_dir: &Path) -> Result<()> {
+    let marker = keep_marker(version_dir)
+        .ok_or_else(|| anyhow!("Invalid version directory: {}", version_dir.display()))?;
+    fs::write(marker, "")?;
+    Ok(())
+}
+
+/// Drops the keep marker of a version that is being removed.
+pub fn unmark_version_kept(version_dir: &Path) {
+    if let Some(marker) = keep_marker(version_dir) {
+        let _ = fs::remove_file(marker);
+    }
+}
+
+pub fn is_version_kept(version_dir: &Path) -> bool {
+    keep_marker(version_dir).is_some_and(|marker| marker.exists())
+}
+
 pub fn package_source_string(
     registry_handle: &str,
     repo: &str,
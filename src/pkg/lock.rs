@@ -62,10 +62,12 @@ pub fn acquire_lock() -> Result<LockGuard> {
     let _ = write!(file, "{}", std::process::id());
     let _ = file.flush();
 
-    Ok(LockGuard {
+    let guard = LockGuard {
         path: Some(lock_path),
         _file: Some(file),
-    })
+    };
+    crate::pkg::journal::recover_interrupted()?;
+    Ok(guard)
 }
 
 pub fn release_lock() -> Result<()> {
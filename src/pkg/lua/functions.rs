@@ -1,3 +1,4 @@
+use crate::pkg::foreign;
 use crate::utils;
 use ar::Archive as ArArchive;
 use flate2::read::GzDecoder;
@@ -60,7 +61,8 @@ fn add_parse_util(lua: &Lua) -> Result<(), mlua::Error> {
 fn add_fetch_util(lua: &Lua) -> Result<(), mlua::Error> {
     let fetch_table = lua.create_table()?;
 
-    let fetch_fn = lua.create_function(|_, url: String| -> Result<String, mlua::Error> {
+    let fetch_fn = lua.create_function(|lua, url: String| -> Result<String, mlua::Error> {
+        check_network(lua)?;
         let client =
             utils::get_http_client().map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
         let response = client
@@ -87,7 +89,8 @@ struct GitArgs {
     branch: Option<String>,
 }
 
-fn fetch_json(url: &str) -> Result<serde_json::Value, mlua::Error> {
+fn fetch_json(lua: &Lua, url: &str) -> Result<serde_json::Value, mlua::Error> {
+    check_network(lua)?;
     let client = utils::get_http_client().map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
 
     let response = client
@@ -187,7 +190,7 @@ fn add_git_fetch_util(lua: &Lua) -> Result<(), mlua::Error> {
                     _ => unreachable!(),
                 };
 
-                let json = fetch_json(&url)?;
+                let json = fetch_json(lua, &url)?;
 
                 let result = match (provider, what) {
                     ("GITHUB", "tag") | ("GITEA", "tag") | ("FORGEJO", "tag") => json
@@ -235,7 +238,9 @@ fn add_git_fetch_util(lua: &Lua) -> Result<(), mlua::Error> {
 
 fn add_file_util(lua: &Lua) -> Result<(), mlua::Error> {
     let file_fn = lua.create_function(
-        |_, (url, path): (String, String)| -> Result<(), mlua::Error> {
+        |lua, (url, path): (String, String)| -> Result<(), mlua::Error> {
+            check_network(lua)?;
+            let path = sandboxed_path(lua, &path)?;
             let client =
                 utils::get_http_client().map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
             let response = client
@@ -528,6 +533,69 @@ fn add_zrm(lua: &Lua) -> Result<(), mlua::Error> {
     Ok(())
 }
 
+/// Whether a sandboxed build may use the network. Kept as app data
+/// rather than a global so package scripts cannot change it. Its presence
+/// also marks that a build is running, which confines the file helpers.
+struct SandboxNetwork(bool);
+
+/// Allows or denies network access to sandboxed `cmd` calls and the
+/// `UTILS` fetch helpers from now on, e.g. only while `prepare()` fetches
+/// sources.
+pub fn set_sandbox_network(lua: &Lua, allowed: bool) {
+    lua.set_app_data(SandboxNetwork(allowed));
+}
+
+/// Fails while a sandboxed build has the network turned off, so the
+/// `UTILS` helpers cannot reach it from outside `cmd` either.
+fn check_network(lua: &Lua) -> Result<(), mlua::Error> {
+    if lua
+        .app_data_ref::<SandboxNetwork>()
+        .is_some_and(|network| !network.0)
+        && crate::pkg::sandbox::is_enabled()
+    {
+        return Err(mlua::Error::RuntimeError(
+            "Network access is disabled by the sandbox outside of prepare().".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Resolves a path handed to a file helper. While a sandboxed build is
+/// running only paths inside `BUILD_DIR` or `STAGING_DIR` are allowed.
+fn sandboxed_path(lua: &Lua, path: &str) -> Result<PathBuf, mlua::Error> {
+    let path = PathBuf::from(path);
+    if lua.app_data_ref::<SandboxNetwork>().is_none() || !crate::pkg::sandbox::is_enabled() {
+        return Ok(path);
+    }
+    let resolved = fs::canonicalize(&path).or_else(|e| match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            fs::canonicalize(parent).map(|p| p.join(name))
+        }
+        _ => Err(e),
+    });
+    let allowed = resolved.is_ok_and(|resolved| {
+        ["BUILD_DIR", "STAGING_DIR"].iter().any(|var| {
+            lua.globals()
+                .get::<String>(*var)
+                .ok()
+                .and_then(|dir| fs::canonicalize(dir).ok())
+                .is_some_and(|dir| resolved.starts_with(dir))
+        })
+    });
+    if !allowed {
+        return Err(mlua::Error::RuntimeError(format!(
+            "The sandbox only allows access to BUILD_DIR and STAGING_DIR, not '{}'.",
+            path.display()
+        )));
+    }
+    Ok(path)
+}
+
 fn add_cmd_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
     let cmd_fn = lua.create_function(move |lua, command: String| {
         let build_dir: String = lua.globals().get("BUILD_DIR")?;
@@ -535,18 +603,37 @@ fn add_cmd_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
         if !quiet {
             println!("Executing: {}", command);
         }
-        let output = if cfg!(target_os = "windows") {
-            std::process::Command::new("pwsh")
-                .arg("-Command")
-                .arg(&command)
-                .current_dir(&build_dir)
-                .output()
+        let mut process = if cfg!(target_os = "windows") {
+            let mut process = std::process::Command::new("pwsh");
+            process.arg("-Command").arg(&command);
+            process
         } else {
-            std::process::Command::new("bash")
-                .arg("-c")
-                .arg(&command)
-                .current_dir(&build_dir)
-                .output()
+            let mut process = std::process::Command::new("bash");
+            process.arg("-c").arg(&command);
+            process
+        };
+        process.current_dir(&build_dir);
+
+        let sandbox = if crate::pkg::sandbox::is_enabled() {
+            let sandbox = crate::pkg::sandbox::Sandbox {
+                writable: vec![PathBuf::from(&build_dir)],
+                network: lua
+                    .app_data_ref::<SandboxNetwork>()
+                    .is_some_and(|network| network.0),
+            };
+            sandbox
+                .apply(&mut process)
+                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+            Some(sandbox)
+        } else {
+            None
+        };
+
+        let output = if sandbox.is_some() {
+            Ok(crate::pkg::sandbox::spawn_output(&mut process)
+                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?)
+        } else {
+            process.output()
         };
 
         match output {
@@ -561,6 +648,11 @@ fn add_cmd_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
                 if !out.status.success() && !quiet {
                     eprintln!("[cmd] {}", stderr);
                 }
+                if !out.status.success()
+                    && let Some(sandbox) = &sandbox
+                {
+                    crate::pkg::sandbox::report_violation(sandbox, &command, &stderr);
+                }
 
                 Ok((stdout, stderr, exit_code))
             }
@@ -593,26 +685,30 @@ fn add_fs_util(lua: &Lua) -> Result<(), mlua::Error> {
     })?;
     fs_table.set("exists", exists_fn)?;
 
-    let copy_fn = lua.create_function(|_, (src, dest): (String, String)| {
-        let src_path = Path::new(&src);
-        let dest_path = Path::new(&dest);
+    let copy_fn = lua.create_function(|lua, (src, dest): (String, String)| {
+        let src_path = sandboxed_path(lua, &src)?;
+        let dest_path = sandboxed_path(lua, &dest)?;
         if src_path.is_dir() {
-            utils::copy_dir_all(src_path, dest_path)
+            utils::copy_dir_all(&src_path, &dest_path)
                 .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
         } else {
-            fs::copy(src_path, dest_path).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+            fs::copy(&src_path, &dest_path)
+                .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
         }
         Ok(true)
     })?;
     fs_table.set("copy", copy_fn)?;
 
-    let move_fn = lua.create_function(|_, (src, dest): (String, String)| {
+    let move_fn = lua.create_function(|lua, (src, dest): (String, String)| {
+        let src = sandboxed_path(lua, &src)?;
+        let dest = sandboxed_path(lua, &dest)?;
         fs::rename(src, dest).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
         Ok(true)
     })?;
     fs_table.set("move", move_fn)?;
 
-    let chmod_fn = lua.create_function(|_, (path, mode): (String, u32)| {
+    let chmod_fn = lua.create_function(|lua, (path, mode): (String, u32)| {
+        let path = sandboxed_path(lua, &path)?;
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
@@ -662,6 +758,35 @@ fn add_find_util(lua: &Lua) -> Result<(), mlua::Error> {
     Ok(())
 }
 
+/// Downloads `url` into `dir`, keeping its file name.
+fn download_to(lua: &Lua, url: &str, dir: &Path, quiet: bool) -> Result<PathBuf, mlua::Error> {
+    check_network(lua)?;
+    if !quiet {
+        println!("Downloading: {}", url);
+    }
+    let file_name = url.split('/').next_back().unwrap_or("download.tmp");
+    let temp_path = dir.join(file_name);
+    let client = utils::get_http_client().map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+    let mut response = client
+        .get(url)
+        .send()
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(mlua::Error::RuntimeError(format!(
+            "Failed to download {}: {}",
+            url,
+            response.status()
+        )));
+    }
+
+    let mut temp_file =
+        fs::File::create(&temp_path).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+    std::io::copy(&mut response, &mut temp_file)
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+
+    Ok(temp_path)
+}
+
 fn add_extract_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
     let extract_fn =
         lua.create_function(move |lua, (source, out_name): (String, Option<String>)| {
@@ -669,27 +794,7 @@ fn add_extract_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
             let build_dir = Path::new(&build_dir_str);
 
             let archive_file = if source.starts_with("http") {
-                if !quiet {
-                    println!("Downloading: {}", source);
-                }
-                let file_name = source.split('/').next_back().unwrap_or("download.tmp");
-                let temp_path = build_dir.join(file_name);
-                let client = utils::get_http_client()
-                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
-                let mut response = client
-                    .get(&source)
-                    .send()
-                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
-                if !response.status().is_success() {
-                    return Err(mlua::Error::RuntimeError(format!("Failed to download {}: {}", source, response.status())));
-                }
-
-                let mut temp_file = fs::File::create(&temp_path)
-                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
-                std::io::copy(&mut response, &mut temp_file)
-                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
-
-                temp_path
+                download_to(lua, &source, build_dir, quiet)?
             } else {
                 PathBuf::from(source)
             };
@@ -805,6 +910,9 @@ fn add_extract_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
                         fs::remove_file(temp_data_path).ok();
                     }
                 }
+            } else if archive_path_str.ends_with(".rpm") {
+                foreign::unpack(&archive_file, &out_dir)
+                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
             } else {
                 return Err(mlua::Error::RuntimeError(format!(
                     "Unsupported archive format for file: {}",
@@ -821,6 +929,64 @@ fn add_extract_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
     Ok(())
 }
 
+fn add_foreign_util(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
+    let foreign_table = lua.create_table()?;
+
+    let resolve_source = move |lua: &Lua, source: &str| -> Result<PathBuf, mlua::Error> {
+        let build_dir_str: String = lua.globals().get("BUILD_DIR")?;
+        let build_dir = Path::new(&build_dir_str);
+        if source.starts_with("http") {
+            return download_to(lua, source, build_dir, quiet);
+        }
+        let path = Path::new(source);
+        if !path.exists() && build_dir.join(path).exists() {
+            Ok(build_dir.join(path))
+        } else {
+            Ok(path.to_path_buf())
+        }
+    };
+
+    let info_fn = lua.create_function(move |lua, source: String| {
+        let path = resolve_source(lua, &source)?;
+        let package = foreign::read(&path).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        let info = lua.create_table()?;
+        info.set("name", package.name)?;
+        info.set("version", package.version)?;
+        info.set("description", package.description)?;
+        info.set("maintainer", package.maintainer)?;
+        info.set("homepage", package.homepage)?;
+        info.set("license", package.license)?;
+        info.set("depends", package.depends)?;
+        info.set("bins", package.bins)?;
+        Ok(info)
+    })?;
+    foreign_table.set("info", info_fn)?;
+
+    let stage_fn = lua.create_function(move |lua, (source, out_name): (String, String)| {
+        let path = resolve_source(lua, &source)?;
+        let build_dir_str: String = lua.globals().get("BUILD_DIR")?;
+        let build_dir = Path::new(&build_dir_str);
+        let out_dir = build_dir.join(&out_name);
+        if !out_dir.starts_with(build_dir) || out_dir == build_dir {
+            return Err(mlua::Error::RuntimeError(format!(
+                "Invalid output directory: {}. Staging must be into a subdirectory of the build directory.",
+                out_name
+            )));
+        }
+        if !quiet {
+            println!("Staging {} to {}", path.display(), out_dir.display());
+        }
+        foreign::stage(&path, &out_dir).map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        Ok(())
+    })?;
+    foreign_table.set("stage", stage_fn)?;
+
+    let utils_table: Table = lua.globals().get("UTILS")?;
+    utils_table.set("FOREIGN", foreign_table)?;
+
+    Ok(())
+}
+
 fn add_verify_signature(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
     let verify_sig_fn = lua.create_function(
         move |lua, (file_path, sig_path, key_source): (String, String, String)| {
@@ -836,6 +1002,7 @@ fn add_verify_signature(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
             };
 
             let key_bytes: Vec<u8> = if key_source.starts_with("http") {
+                check_network(lua)?;
                 let client = utils::get_http_client()
                     .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
                 match client.get(&key_source).send().and_then(|r| r.bytes()) {
@@ -920,6 +1087,7 @@ fn add_verify_signature(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
 fn add_add_pgp_key(lua: &Lua, quiet: bool) -> Result<(), mlua::Error> {
     let add_pgp_key_fn = lua.create_function(move |lua, (source, name): (String, String)| {
         let result = if source.starts_with("http") {
+            check_network(lua)?;
             crate::pkg::pgp::add_key_from_url(&source, &name, quiet)
         } else {
             let p = Path::new(&source);
@@ -1149,7 +1317,7 @@ pub fn add_package_lifecycle_functions(lua: &Lua) -> Result<(), mlua::Error> {
     lua.globals().set("prepare", prepare_fn)?;
     let package_fn = lua.create_function(|_, _: Table| Ok(()))?;
     lua.globals().set("package", package_fn)?;
-    let verify_fn = lua.create_function(|_, _: Table| Ok(()))?;
+    let verify_fn = lua.create_function(|_, _: Table| Ok(true))?;
     lua.globals().set("verify", verify_fn)?;
     let test_fn = lua.create_function(|_, _: Table| Ok(true))?;
     lua.globals().set("test", test_fn)?;
@@ -1281,6 +1449,7 @@ pub fn setup_lua_environment(
     add_find_util(lua)?;
     add_archive_util(lua)?;
     add_extract_util(lua, quiet)?;
+    add_foreign_util(lua, quiet)?;
     add_verify_signature(lua, quiet)?;
     add_add_pgp_key(lua, quiet)?;
     add_package_lifecycle_functions(lua)?;
@@ -1,5 +1,7 @@
 pub mod audit;
 pub mod autoremove;
+pub mod backup;
+pub mod bundle;
 pub mod cache;
 pub mod config;
 pub mod create;
@@ -8,10 +10,13 @@ pub mod dependencies;
 pub mod doctor;
 pub mod exec;
 pub mod extension;
+pub mod features;
+pub mod foreign;
 pub mod hash;
 pub mod helper;
 pub mod hooks;
 pub mod install;
+pub mod journal;
 pub mod local;
 pub mod lock;
 pub mod lua;
@@ -23,13 +28,20 @@ pub mod pin;
 pub mod pkgdir;
 pub mod plugin;
 pub mod pm;
+pub mod preference;
+pub mod provider;
 pub mod purl;
 pub mod recorder;
 pub mod repo_install;
 pub mod resolve;
 pub mod rollback;
+pub mod sandbox;
+pub mod serve;
 pub mod service;
 pub mod shim;
+pub mod snapshot;
+pub mod store;
+pub mod switch;
 pub mod sync;
 pub mod sysroot;
 pub mod system;
@@ -39,3 +51,5 @@ pub mod types;
 pub mod uninstall;
 pub mod upgrade;
 pub mod utils;
+pub mod verify;
+pub mod version_files;
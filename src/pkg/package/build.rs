@@ -1,9 +1,10 @@
+use super::delta;
 use crate::{pkg, utils};
 use anyhow::{Result, anyhow};
 use colored::*;
 use mlua::{Lua, LuaSerdeExt, Table};
 use std::fs::{self, File};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tar::Builder as TarBuilder;
 use tempfile::Builder;
 use walkdir::WalkDir;
@@ -48,6 +49,8 @@ fn build_for_platform(
     output_dir: Option<&Path>,
     version_override: Option<&str>,
     sub_packages: Option<&Vec<String>>,
+    features: &[String],
+    delta_from: &[PathBuf],
     quiet: bool,
     install_deps: bool,
 ) -> Result<()> {
@@ -81,26 +84,38 @@ fn build_for_platform(
     let resolved_build_type =
         resolve_build_type(build_type, &pkg_for_meta.types, &pkg_for_meta.name)?;
 
-    if install_deps
-        && let Some(deps) = &pkg_for_meta.dependencies
-        && let Some(build_deps) = &deps.build
-    {
-        let group = match build_deps {
-            pkg::types::BuildDependencies::Group(g) => Some(g),
-            pkg::types::BuildDependencies::Typed(t) => t.types.get(&resolved_build_type),
-        };
-
-        if let Some(g) = group {
+    let enabled_features = pkg::features::resolve_for_package(&pkg_for_meta, features)?;
+
+    if install_deps {
+        let group = pkg_for_meta
+            .dependencies
+            .as_ref()
+            .and_then(|deps| deps.build.as_ref())
+            .and_then(|build_deps| match build_deps {
+                pkg::types::BuildDependencies::Group(g) => Some(g),
+                pkg::types::BuildDependencies::Typed(t) => t.types.get(&resolved_build_type),
+            });
+        let feature_deps =
+            pkg::features::dependencies(pkg_for_meta.features.as_ref(), &enabled_features, true);
+
+        if group.is_some() || !feature_deps.is_empty() {
             if !quiet {
                 println!("{} Installing build dependencies...", "::".bold().blue());
             }
-            let (req_deps, _, _) = pkg::install::resolver::collect_dependencies_for_group(
-                g,
-                None,
-                Some("build"),
-                true,
-                true,
-            )?;
+            let mut req_deps = match group {
+                Some(g) => {
+                    pkg::install::resolver::collect_dependencies_for_group(
+                        g,
+                        None,
+                        Some("build"),
+                        true,
+                        true,
+                    )?
+                    .0
+                }
+                None => Vec::new(),
+            };
+            req_deps.extend(feature_deps);
 
             let processed = std::sync::Mutex::new(std::collections::HashSet::new());
             let mut installed = Vec::new();
@@ -216,7 +231,17 @@ fn build_for_platform(
             args.set("sub", sub_package.clone())
                 .map_err(|e| anyhow!(e.to_string()))?;
         }
+        let features_table = lua.create_table().map_err(|e| anyhow!(e.to_string()))?;
+        for name in pkg_for_meta.features.iter().flat_map(|f| f.keys()) {
+            features_table
+                .set(name.as_str(), enabled_features.contains(name))
+                .map_err(|e| anyhow!(e.to_string()))?;
+        }
+        args.set("features", features_table)
+            .map_err(|e| anyhow!(e.to_string()))?;
 
+        // Sandboxed commands may only fetch sources in prepare().
+        pkg::lua::functions::set_sandbox_network(&lua, true);
         if let Ok(prepare_fn) = lua.globals().get::<mlua::Function>("prepare") {
             if !quiet {
                 println!("Running prepare()...");
@@ -231,6 +256,7 @@ fn build_for_platform(
             })?;
         }
 
+        pkg::lua::functions::set_sandbox_network(&lua, false);
         if let Ok(package_fn) = lua.globals().get::<mlua::Function>("package") {
             if !quiet {
                 println!("Running package()...");
@@ -294,6 +320,12 @@ fn build_for_platform(
                                     dest_path.join(entry.path().strip_prefix(&source_path)?);
                                 if entry.file_type().is_dir() {
                                     fs::create_dir_all(&target_path)?;
+                                } else if entry.path_is_symlink() {
+                                    if let Some(p) = target_path.parent() {
+                                        fs::create_dir_all(p)?;
+                                    }
+                                    let link = fs::read_link(entry.path())?;
+                                    utils::symlink_file(&link, &target_path)?;
                                 } else {
                                     if let Some(p) = target_path.parent() {
                                         fs::create_dir_all(p)?;
@@ -525,6 +557,43 @@ fn build_for_platform(
         );
     }
 
+    for old_archive in delta_from {
+        let old_version = old_archive
+            .file_name()
+            .and_then(|f| f.to_str())
+            .and_then(|f| delta::archive_version(f, &pkg_for_meta.name, platform));
+        let Some(old_version) = old_version.filter(|v| *v != version) else {
+            if !quiet {
+                println!(
+                    "Skipping delta from '{}': not an older {} archive of {}.",
+                    old_archive.display(),
+                    platform,
+                    pkg_for_meta.name
+                );
+            }
+            continue;
+        };
+        let delta_path = output_base.join(delta::delta_filename(
+            &pkg_for_meta.name,
+            old_version,
+            &version,
+            platform,
+        ));
+        delta::create(old_archive, &output_path, &delta_path)?;
+        if !quiet {
+            println!(
+                "{}",
+                format!(
+                    "Created delta from {}: {} ({})",
+                    old_version,
+                    delta_path.display(),
+                    crate::utils::format_bytes(fs::metadata(&delta_path)?.len())
+                )
+                .green()
+            );
+        }
+    }
+
     if let Some(key_id) = sign_key {
         if !quiet {
             println!("Signing package with key '{}'...", key_id.cyan());
@@ -557,6 +626,8 @@ pub fn run(
     output_dir: Option<&Path>,
     version_override: Option<&str>,
     sub_packages: Option<Vec<String>>,
+    features: &[String],
+    delta_from: &[PathBuf],
     quiet: bool,
     install_deps: bool,
     method: &str,
@@ -566,6 +637,11 @@ pub fn run(
         let docker_image = image.ok_or_else(|| {
             anyhow!("An image must be specified when using the 'docker' build method.")
         })?;
+        if !delta_from.is_empty() {
+            return Err(anyhow!(
+                "Deltas can only be generated with the 'native' build method."
+            ));
+        }
         return super::docker::run(
             package_file,
             build_type,
@@ -574,6 +650,7 @@ pub fn run(
             output_dir,
             version_override,
             sub_packages,
+            features,
             docker_image,
             install_deps,
         );
@@ -614,6 +691,8 @@ pub fn run(
             output_dir,
             version_override,
             sub_packages.as_ref(),
+            features,
+            delta_from,
             quiet,
             install_deps,
         ) {
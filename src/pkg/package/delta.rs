@@ -0,0 +1,76 @@
+use anyhow::{Result, anyhow};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use zstd::stream::read::Decoder as ZstdDecoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+const ARCHIVE_SUFFIX: &str = ".pkg.tar.zst";
+const DELTA_LEVEL: i32 = 19;
+const MAX_WINDOW_LOG: u32 = 30;
+
+/// Name of the delta that turns the `old` archive of a package into `new`,
+/// e.g. `hello-1.0.0-1.1.0-linux-amd64.pkg.tar.zst.delta`.
+pub fn delta_filename(name: &str, old: &str, new: &str, platform: &str) -> String {
+    format!(
+        "{}-{}-{}-{}{}.delta",
+        name, old, new, platform, ARCHIVE_SUFFIX
+    )
+}
+
+/// Standard archive name, `name-version-platform.pkg.tar.zst`.
+pub fn archive_filename(name: &str, version: &str, platform: &str) -> String {
+    format!("{}-{}-{}{}", name, version, platform, ARCHIVE_SUFFIX)
+}
+
+/// Version in a standard archive name of `name` for `platform`. Versions
+/// start with a digit, which keeps `hello` from matching `hello-world`.
+pub fn archive_version<'a>(filename: &'a str, name: &str, platform: &str) -> Option<&'a str> {
+    let version = filename
+        .strip_prefix(name)?
+        .strip_prefix('-')?
+        .strip_suffix(ARCHIVE_SUFFIX)?
+        .strip_suffix(platform)?
+        .strip_suffix('-')?;
+    version
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then_some(version)
+}
+
+/// Window large enough for matches to reach anywhere in the old archive.
+fn window_log(size: u64) -> u32 {
+    let bits = u64::BITS - size.max(1).leading_zeros();
+    (bits + 1).clamp(10, MAX_WINDOW_LOG)
+}
+
+/// Writes a zstd `--patch-from` style delta: `new` compressed with `old` as
+/// a raw-content dictionary.
+pub fn create(old: &Path, new: &Path, out: &Path) -> Result<()> {
+    let dictionary = fs::read(old)?;
+    let new_size = fs::metadata(new)?.len();
+
+    let writer = BufWriter::new(File::create(out)?);
+    let mut encoder = ZstdEncoder::with_dictionary(writer, DELTA_LEVEL, &dictionary)?;
+    encoder.long_distance_matching(true)?;
+    encoder.window_log(window_log(new_size.max(dictionary.len() as u64)))?;
+    encoder.set_pledged_src_size(Some(new_size))?;
+
+    io::copy(&mut File::open(new)?, &mut encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Rebuilds the new archive from `old` and a delta made by [`create`] (or
+/// `zstd --patch-from`).
+pub fn apply(old: &Path, delta: &Path, out: &Path) -> Result<()> {
+    let dictionary = fs::read(old)?;
+    let reader = BufReader::new(File::open(delta)?);
+    let mut decoder = ZstdDecoder::with_dictionary(reader, &dictionary)?;
+    decoder.window_log_max(MAX_WINDOW_LOG + 1)?;
+
+    let mut writer = BufWriter::new(File::create(out)?);
+    io::copy(&mut decoder, &mut writer)
+        .map_err(|e| anyhow!("Failed to apply delta '{}': {}", delta.display(), e))?;
+    writer.flush()?;
+    Ok(())
+}
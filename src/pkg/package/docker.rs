@@ -12,6 +12,7 @@ pub fn run(
     output_dir: Option<&Path>,
     version_override: Option<&str>,
     sub_packages: Option<Vec<String>>,
+    features: &[String],
     image: &str,
     install_deps: bool,
 ) -> Result<()> {
@@ -131,6 +132,10 @@ pub fn run(
         }
     }
 
+    if !features.is_empty() {
+        inner_cmd.push_str(&format!(" --features={}", features.join(",")));
+    }
+
     if install_deps {
         inner_cmd.push_str(" --install-deps");
     }
@@ -1,4 +1,5 @@
-use crate::pkg::{local, lua, types};
+use crate::pkg::journal::{self, Entry};
+use crate::pkg::{backup, local, lua, store, types};
 use crate::utils::{self, copy_dir_all};
 use anyhow::{Result, anyhow};
 use colored::*;
@@ -11,7 +12,7 @@ use tempfile::Builder;
 use walkdir::WalkDir;
 use zstd::stream::read::Decoder as ZstdDecoder;
 
-fn get_bin_root(scope: types::Scope) -> Result<PathBuf> {
+pub fn get_bin_root(scope: types::Scope) -> Result<PathBuf> {
     match scope {
         types::Scope::User => {
             let home_dir =
@@ -32,8 +33,8 @@ fn get_bin_root(scope: types::Scope) -> Result<PathBuf> {
             }
         }
         types::Scope::Project => {
-            let current_dir = std::env::current_dir()?;
-            Ok(current_dir.join(".zoi").join("pkgs").join("bin"))
+            let project_root = crate::project::config::project_root()?;
+            Ok(project_root.join(".zoi").join("pkgs").join("bin"))
         }
     }
 }
@@ -85,6 +86,21 @@ fn check_and_handle_file_conflicts(
     Ok(())
 }
 
+/// Journals the files under `dest` that copying `src` over it will create.
+fn journal_new_files(version_dir: &Path, src: &Path, dest: &Path) -> Result<()> {
+    let mut paths = Vec::new();
+    for entry in WalkDir::new(src).into_iter().filter_map(|e| e.ok()) {
+        let dest_path = dest.join(entry.path().strip_prefix(src)?);
+        if !entry.file_type().is_dir() && dest_path.symlink_metadata().is_err() {
+            paths.push(dest_path);
+        }
+    }
+    if paths.is_empty() {
+        return Ok(());
+    }
+    journal::record(version_dir, Entry::CreateFiles { paths })
+}
+
 pub fn run(
     package_file: &Path,
     scope_override: Option<types::Scope>,
@@ -194,14 +210,29 @@ pub fn run(
 
     let package_dir =
         local::get_package_dir(scope, registry_handle, &metadata.repo, &metadata.name)?;
+    let version_dir = package_dir.join(version);
+    journal::record(&version_dir, Entry::BeginInstall { scope })?;
+    if !package_dir.exists() {
+        journal::record(
+            &version_dir,
+            Entry::CreateDir {
+                path: package_dir.clone(),
+            },
+        )?;
+    }
     fs::create_dir_all(&package_dir)?;
 
     let staging_dir = tempfile::Builder::new()
         .prefix(".tmp-install-")
         .tempdir_in(&package_dir)?;
+    journal::record(
+        &version_dir,
+        Entry::CreateDir {
+            path: staging_dir.path().to_path_buf(),
+        },
+    )?;
 
     let mut installed_files: Vec<String> = Vec::new();
-    let version_dir = package_dir.join(version);
 
     let data_dir = temp_dir.path().join("data");
     if data_dir.exists() {
@@ -268,6 +299,7 @@ pub fn run(
                 }
                 let root_dest = crate::pkg::sysroot::apply_sysroot(PathBuf::from("/"));
                 check_and_handle_file_conflicts(&usrroot_src, &root_dest, &owned_files, yes)?;
+                journal_new_files(&version_dir, &usrroot_src, &root_dest)?;
                 copy_dir_all(&usrroot_src, &root_dest)?;
                 for entry in WalkDir::new(&usrroot_src)
                     .into_iter()
@@ -285,6 +317,7 @@ pub fn run(
                 let home_dest =
                     home::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
                 check_and_handle_file_conflicts(&usrhome_src, &home_dest, &owned_files, yes)?;
+                journal_new_files(&version_dir, &usrhome_src, &home_dest)?;
                 copy_dir_all(&usrhome_src, &home_dest)?;
                 for entry in WalkDir::new(&usrhome_src)
                     .into_iter()
@@ -303,6 +336,7 @@ pub fn run(
         p.set_position(60);
     }
 
+    let mut staged_files = Vec::new();
     for entry in WalkDir::new(staging_dir.path())
         .into_iter()
         .filter_map(|e| e.ok())
@@ -310,11 +344,35 @@ pub fn run(
         if entry.file_type().is_file() {
             let rel_path = entry.path().strip_prefix(staging_dir.path())?;
             installed_files.push(version_dir.join(rel_path).to_string_lossy().to_string());
+            staged_files.push(rel_path.to_path_buf());
         }
     }
 
+    if version_dir.exists() {
+        store::unshare(&version_dir, &staged_files)?;
+    } else {
+        journal::record(
+            &version_dir,
+            Entry::CreateDir {
+                path: version_dir.clone(),
+            },
+        )?;
+    }
     fs::create_dir_all(&version_dir)?;
     copy_dir_all(staging_dir.path(), &version_dir)?;
+    backup::save_shipped(&version_dir, metadata.backup.as_deref().unwrap_or_default())?;
+    if let Err(e) = store::ingest(
+        scope,
+        &version_dir,
+        &staged_files,
+        metadata.backup.as_deref().unwrap_or_default(),
+    ) && pb.is_none()
+    {
+        eprintln!(
+            "Warning: could not deduplicate files into the package store: {}",
+            e
+        );
+    }
 
     if link_bins && let Some(bins) = &metadata.bins {
         let bin_root = get_bin_root(scope)?;
@@ -331,6 +389,14 @@ pub fn run(
             {
                 if entry.file_type().is_file() && entry.file_name().to_string_lossy() == *bin_name {
                     let link_path = bin_root.join(bin_name);
+                    if link_path.symlink_metadata().is_err() {
+                        journal::record(
+                            &version_dir,
+                            Entry::CreateFiles {
+                                paths: vec![link_path.clone()],
+                            },
+                        )?;
+                    }
 
                     if let Err(e) = crate::pkg::shim::create_shim(&link_path) {
                         link_error = Some(e);
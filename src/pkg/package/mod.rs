@@ -1,4 +1,5 @@
 pub mod build;
+pub mod delta;
 pub mod docker;
 pub mod doctor;
 pub mod install;
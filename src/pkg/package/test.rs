@@ -30,6 +30,8 @@ pub fn run(args: &cmd::package::build::BuildCommand) -> Result<()> {
         pkg::resolve::get_default_version(&pkg_for_meta, None)?
     };
 
+    let enabled_features = pkg::features::resolve_for_package(&pkg_for_meta, &args.features)?;
+
     let resolved_build_type = crate::pkg::package::build::resolve_build_type(
         args.r#type.as_deref(),
         &pkg_for_meta.types,
@@ -116,7 +118,17 @@ pub fn run(args: &cmd::package::build::BuildCommand) -> Result<()> {
                 .set("sub", sub_package.clone())
                 .map_err(|e| anyhow!(e.to_string()))?;
         }
+        let features_table = lua.create_table().map_err(|e| anyhow!(e.to_string()))?;
+        for name in pkg_for_meta.features.iter().flat_map(|f| f.keys()) {
+            features_table
+                .set(name.as_str(), enabled_features.contains(name))
+                .map_err(|e| anyhow!(e.to_string()))?;
+        }
+        lua_args
+            .set("features", features_table)
+            .map_err(|e| anyhow!(e.to_string()))?;
 
+        pkg::lua::functions::set_sandbox_network(&lua, true);
         if let Ok(prepare_fn) = lua.globals().get::<mlua::Function>("prepare") {
             println!("Running prepare()...");
             prepare_fn
@@ -124,6 +136,7 @@ pub fn run(args: &cmd::package::build::BuildCommand) -> Result<()> {
                 .map_err(|e| anyhow!(e.to_string()))?;
         }
 
+        pkg::lua::functions::set_sandbox_network(&lua, false);
         if let Ok(package_fn) = lua.globals().get::<mlua::Function>("package") {
             println!("Running package()...");
             package_fn
@@ -80,6 +80,21 @@ pub fn get_pgp_dir() -> Result<PathBuf> {
     Ok(pgp_dir)
 }
 
+/// Fingerprint of an armored or binary key, for asking before trusting it.
+pub fn fingerprint_from_bytes(key_bytes: &[u8]) -> Result<String> {
+    Ok(Cert::from_bytes(key_bytes)?.fingerprint().to_string())
+}
+
+/// The stored key with `name`, if there is one.
+pub fn read_key_bytes(name: &str) -> Result<Option<Vec<u8>>> {
+    let path = get_pgp_dir()?.join(format!("{}.asc", name));
+    if path.exists() {
+        Ok(Some(fs::read(path)?))
+    } else {
+        Ok(None)
+    }
+}
+
 pub fn add_key_from_bytes(key_bytes: &[u8], name: &str, quiet: bool) -> Result<()> {
     let pgp_dir = get_pgp_dir()?;
     let dest_path = pgp_dir.join(format!("{}.asc", name));
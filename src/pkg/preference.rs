@@ -0,0 +1,109 @@
+use crate::pkg::types::PackagePreference;
+
+/// Priority of a candidate that no preference rule matches.
+pub const DEFAULT_PRIORITY: i32 = 500;
+
+fn matches_name(rule: &PackagePreference, name: &str) -> bool {
+    let pattern = rule.package.trim().to_ascii_lowercase();
+    let name = name.to_ascii_lowercase();
+    if pattern == name {
+        return true;
+    }
+    glob::Pattern::new(&pattern).is_ok_and(|p| p.matches(&name))
+}
+
+fn is_exact(rule: &PackagePreference) -> bool {
+    !rule.package.contains(['*', '?', '['])
+}
+
+fn matches_repo(rule_repo: &str, repo: &str) -> bool {
+    let rule_repo = rule_repo
+        .trim()
+        .trim_start_matches('@')
+        .to_ascii_lowercase();
+    let repo = repo.to_ascii_lowercase();
+    if rule_repo.contains('/') {
+        repo == rule_repo
+    } else {
+        repo.split('/').next() == Some(rule_repo.as_str())
+    }
+}
+
+fn matches_source(rule: &PackagePreference, registry: &str, repo: &str) -> bool {
+    rule.registry
+        .as_deref()
+        .is_none_or(|r| r.trim_start_matches('#').eq_ignore_ascii_case(registry))
+        && rule.repo.as_deref().is_none_or(|r| matches_repo(r, repo))
+}
+
+/// Rules that apply to a package, most specific first: rules naming the
+/// package exactly come before patterns, then in configuration order.
+fn rules_for<'a>(
+    preferences: &'a [PackagePreference],
+    name: &str,
+) -> impl Iterator<Item = &'a PackagePreference> {
+    let matching: Vec<_> = preferences
+        .iter()
+        .filter(|r| matches_name(r, name))
+        .collect();
+    let (exact, patterns): (Vec<_>, Vec<_>) = matching.into_iter().partition(|r| is_exact(r));
+    exact.into_iter().chain(patterns)
+}
+
+/// Priority of taking `name` from `#registry@repo`. Candidates below zero are
+/// never chosen unless the registry or repo was requested explicitly.
+pub fn priority_for(
+    preferences: &[PackagePreference],
+    name: &str,
+    registry: &str,
+    repo: &str,
+) -> i32 {
+    rules_for(preferences, name)
+        .find(|r| matches_source(r, registry, repo))
+        .map(|r| r.priority)
+        .unwrap_or(DEFAULT_PRIORITY)
+}
+
+/// Registries that preference rules ask to consider for `name`, in addition to
+/// the default registry.
+pub fn preferred_registries(preferences: &[PackagePreference], name: &str) -> Vec<String> {
+    let mut handles = Vec::new();
+    for rule in rules_for(preferences, name) {
+        if rule.priority >= 0
+            && let Some(handle) = &rule.registry
+        {
+            let handle = handle.trim_start_matches('#').to_string();
+            if !handles.contains(&handle) {
+                handles.push(handle);
+            }
+        }
+    }
+    handles
+}
+
+/// Explains why preferences rule out keeping `name` from `#registry@repo`,
+/// either because that source is excluded or because a higher priority rule
+/// pins the package elsewhere.
+pub fn conflict_for(
+    preferences: &[PackagePreference],
+    name: &str,
+    registry: &str,
+    repo: &str,
+) -> Option<String> {
+    let current = priority_for(preferences, name, registry, repo);
+    if current < 0 {
+        return Some(format!("#{}@{} is excluded by preferences", registry, repo));
+    }
+    rules_for(preferences, name)
+        .filter(|r| r.priority > current && (r.registry.is_some() || r.repo.is_some()))
+        .find(|r| !matches_source(r, registry, repo))
+        .map(|r| {
+            let target = match (&r.registry, &r.repo) {
+                (Some(h), Some(p)) => format!("#{}@{}", h.trim_start_matches('#'), p),
+                (Some(h), None) => format!("#{}", h.trim_start_matches('#')),
+                (None, Some(p)) => format!("@{}", p.trim_start_matches('@')),
+                (None, None) => unreachable!(),
+            };
+            format!("preferences pin it to {}", target)
+        })
+}
@@ -0,0 +1,193 @@
+use crate::pkg::{config, local, resolve, types};
+use anyhow::{Result, anyhow};
+use colored::*;
+use dialoguer::{Select, theme::ColorfulTheme};
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+/// Provider picked for each virtual package during this run, so every
+/// dependency on the same capability resolves to the same package.
+static CHOSEN_PROVIDERS: LazyLock<Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A package that lists a virtual package in its `provides`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderCandidate {
+    pub name: String,
+    pub repo: String,
+    pub registry: String,
+    pub conflicts: Vec<String>,
+    pub replaces: Vec<String>,
+}
+
+impl ProviderCandidate {
+    pub fn from_package(pkg: &types::Package, registry: &str) -> Self {
+        Self {
+            name: pkg.name.clone(),
+            repo: pkg.repo.clone(),
+            registry: registry.to_string(),
+            conflicts: pkg.conflicts.clone().unwrap_or_default(),
+            replaces: pkg.replaces.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn source(&self) -> String {
+        format!("#{}@{}/{}", self.registry, self.repo, self.name)
+    }
+
+    /// Whether `source` (e.g. `openjdk`, `@core/openjdk` or
+    /// `#zoidberg@core/openjdk`) names this candidate.
+    fn matches_source(&self, source: &str) -> bool {
+        let Ok(request) = resolve::parse_source_string(source) else {
+            return false;
+        };
+        request.name == self.name
+            && request
+                .handle
+                .as_ref()
+                .is_none_or(|handle| *handle == self.registry)
+            && request.repo.as_ref().is_none_or(|repo| *repo == self.repo)
+    }
+
+    fn is_installed_as(&self, manifest: &types::InstallManifest) -> bool {
+        manifest.name == self.name
+            && manifest.repo == self.repo
+            && manifest.registry_handle == self.registry
+    }
+
+    fn conflicts_with(&self, manifest: &types::InstallManifest) -> bool {
+        if manifest.name == self.name || self.replaces.contains(&manifest.name) {
+            return false;
+        }
+        self.conflicts.contains(&manifest.name)
+            || manifest
+                .conflicts
+                .as_ref()
+                .is_some_and(|c| c.contains(&self.name))
+    }
+}
+
+/// Picks which of `candidates` satisfies the virtual package `virtual_name`.
+///
+/// An installed provider is kept unless another candidate replaces it, then
+/// the provider configured under `providers` is used, and otherwise the user
+/// is asked. Candidates that conflict with an installed package are skipped.
+pub fn select_provider(
+    virtual_name: &str,
+    candidates: &[ProviderCandidate],
+    yes: bool,
+) -> Result<Option<usize>> {
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    if let Some(source) = CHOSEN_PROVIDERS
+        .lock()
+        .map_err(|e| anyhow!("Failed to lock provider cache: {}", e))?
+        .get(virtual_name)
+        && let Some(idx) = candidates.iter().position(|c| c.source() == *source)
+    {
+        return Ok(Some(idx));
+    }
+
+    let installed = local::get_installed_packages().unwrap_or_default();
+    let viable: Vec<usize> = (0..candidates.len())
+        .filter(|&i| !installed.iter().any(|m| candidates[i].conflicts_with(m)))
+        .collect();
+
+    if viable.is_empty() {
+        let names: Vec<String> = candidates.iter().map(|c| c.name.clone()).collect();
+        return Err(anyhow!(
+            "Every provider of '{}' ({}) conflicts with an installed package.",
+            virtual_name,
+            names.join(", ")
+        ));
+    }
+
+    let chosen = choose_viable(virtual_name, candidates, &viable, &installed, yes)?;
+    CHOSEN_PROVIDERS
+        .lock()
+        .map_err(|e| anyhow!("Failed to lock provider cache: {}", e))?
+        .insert(virtual_name.to_string(), candidates[chosen].source());
+    Ok(Some(chosen))
+}
+
+fn choose_viable(
+    virtual_name: &str,
+    candidates: &[ProviderCandidate],
+    viable: &[usize],
+    installed: &[types::InstallManifest],
+    yes: bool,
+) -> Result<usize> {
+    let installed_providers: Vec<&types::InstallManifest> = installed
+        .iter()
+        .filter(|m| {
+            m.provides
+                .as_ref()
+                .is_some_and(|p| p.iter().any(|v| v == virtual_name))
+        })
+        .collect();
+
+    for manifest in &installed_providers {
+        if let Some(&idx) = viable
+            .iter()
+            .find(|&&i| candidates[i].replaces.contains(&manifest.name))
+        {
+            println!(
+                "Using '{}' for '{}': it replaces the installed '{}'.",
+                candidates[idx].name.cyan(),
+                virtual_name,
+                manifest.name
+            );
+            return Ok(idx);
+        }
+    }
+
+    for manifest in &installed_providers {
+        if let Some(&idx) = viable
+            .iter()
+            .find(|&&i| candidates[i].is_installed_as(manifest))
+        {
+            return Ok(idx);
+        }
+    }
+
+    let config = config::read_config().unwrap_or_default();
+    if let Some(preferred) = config.providers.get(virtual_name) {
+        if let Some(&idx) = viable
+            .iter()
+            .find(|&&i| candidates[i].matches_source(preferred))
+        {
+            return Ok(idx);
+        }
+        eprintln!(
+            "{}: preferred provider '{}' for '{}' is not available or conflicts with an installed package.",
+            "Warning".yellow().bold(),
+            preferred,
+            virtual_name
+        );
+    }
+
+    if viable.len() == 1 {
+        return Ok(viable[0]);
+    }
+
+    if yes {
+        let idx = viable[0];
+        println!(
+            "Using '{}' to provide '{}'. Set 'providers.{}' in your config to choose another.",
+            candidates[idx].source().cyan(),
+            virtual_name,
+            virtual_name
+        );
+        return Ok(idx);
+    }
+
+    let items: Vec<String> = viable.iter().map(|&i| candidates[i].source()).collect();
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Select a provider for '{}'", virtual_name))
+        .items(&items)
+        .default(0)
+        .interact()?;
+    Ok(viable[selection])
+}
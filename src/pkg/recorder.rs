@@ -7,7 +7,7 @@ use std::path::PathBuf;
 
 fn get_lockfile_path(scope: types::Scope) -> Result<PathBuf> {
     let path = if scope == types::Scope::Project {
-        std::env::current_dir()?
+        crate::project::config::project_root()?
             .join(".zoi")
             .join("pkgs")
             .join("zoi.pkgs.json")
@@ -124,6 +124,7 @@ pub fn run(
         3,
         false,
         false,
+        false,
     )?;
 
     Ok(())
@@ -1,4 +1,4 @@
-use crate::pkg::{cache, config, pin, types};
+use crate::pkg::{cache, config, features, pin, preference, provider, types};
 use anyhow::{Result, anyhow};
 use colored::*;
 use comfy_table::{Table, presets::UTF8_FULL};
@@ -29,6 +29,9 @@ pub struct ResolvedSource {
     pub registry_handle: Option<String>,
     pub sharable_manifest: Option<types::SharableInstallManifest>,
     pub git_sha: Option<String>,
+    /// Set when the request named a virtual package: the real package that
+    /// was chosen to provide it.
+    pub provider: Option<String>,
 }
 
 #[derive(Debug, Default)]
@@ -38,6 +41,8 @@ pub struct PackageRequest {
     pub name: String,
     pub sub_package: Option<String>,
     pub version_spec: Option<String>,
+    /// `+name`/`-name` feature selection from `name[+a,-b]`.
+    pub features: Vec<String>,
 }
 
 use std::sync::LazyLock;
@@ -116,6 +121,9 @@ pub fn get_db_root() -> Result<PathBuf> {
 }
 
 pub fn parse_source_string(source_str: &str) -> Result<PackageRequest> {
+    let (source_str, features) = features::split_source(source_str);
+    let source_str = source_str.as_str();
+
     if let Some((path_part, sub_package_from_path, version_spec)) =
         split_explicit_file_source(source_str)
     {
@@ -134,6 +142,7 @@ pub fn parse_source_string(source_str: &str) -> Result<PackageRequest> {
             name,
             sub_package: sub_package_from_path,
             version_spec,
+            features,
         });
     }
 
@@ -183,106 +192,117 @@ pub fn parse_source_string(source_str: &str) -> Result<PackageRequest> {
         name: name.to_lowercase(),
         sub_package,
         version_spec,
+        features,
     })
 }
 
-fn find_package_in_db(request: &PackageRequest, quiet: bool) -> Result<ResolvedSource> {
+fn registry_sub_repos(repo_path: &Path) -> Result<Vec<String>> {
+    if !repo_path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_dir(repo_path)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir() && entry.file_name() != ".git")
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect())
+}
+
+fn find_package_in_db(request: &PackageRequest, quiet: bool, yes: bool) -> Result<ResolvedSource> {
     let db_root = get_db_root()?;
     let config = config::read_config()?;
 
-    let (registry_db_path, search_repos, is_default_registry, registry_handle) =
-        if let Some(h) = &request.handle {
-            let is_default = config
-                .default_registry
-                .as_ref()
-                .is_some_and(|reg| reg.handle == *h);
-
-            if is_default {
-                let default_registry = config
-                    .default_registry
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("Default registry not found"))?;
-                (
-                    db_root.join(&default_registry.handle),
-                    config.repos,
-                    true,
-                    Some(default_registry.handle.clone()),
-                )
-            } else if let Some(registry) = config.added_registries.iter().find(|r| r.handle == *h) {
+    struct SearchRegistry {
+        db_path: PathBuf,
+        repos: Vec<String>,
+        is_default: bool,
+        handle: String,
+    }
+
+    let mut registries = Vec::new();
+    if let Some(h) = &request.handle {
+        let is_default = config
+            .default_registry
+            .as_ref()
+            .is_some_and(|reg| reg.handle == *h);
+
+        if is_default {
+            registries.push(SearchRegistry {
+                db_path: db_root.join(h),
+                repos: config.repos.clone(),
+                is_default: true,
+                handle: h.clone(),
+            });
+        } else if let Some(registry) = config.added_registries.iter().find(|r| r.handle == *h) {
+            let repo_path = db_root.join(&registry.handle);
+            registries.push(SearchRegistry {
+                repos: registry_sub_repos(&repo_path)?,
+                db_path: repo_path,
+                is_default: false,
+                handle: registry.handle.clone(),
+            });
+        } else {
+            return Err(anyhow!("Registry with handle '{}' not found.", h));
+        }
+    } else {
+        let default_registry = config
+            .default_registry
+            .as_ref()
+            .ok_or_else(|| anyhow!("No default registry set."))?;
+        registries.push(SearchRegistry {
+            db_path: db_root.join(&default_registry.handle),
+            repos: config.repos.clone(),
+            is_default: true,
+            handle: default_registry.handle.clone(),
+        });
+
+        for handle in preference::preferred_registries(&config.preferences, &request.name) {
+            if handle == default_registry.handle {
+                continue;
+            }
+            if let Some(registry) = config.added_registries.iter().find(|r| r.handle == handle) {
                 let repo_path = db_root.join(&registry.handle);
-                let all_sub_repos = if repo_path.exists() {
-                    fs::read_dir(&repo_path)?
-                        .filter_map(Result::ok)
-                        .filter(|entry| entry.path().is_dir() && entry.file_name() != ".git")
-                        .map(|entry| entry.file_name().to_string_lossy().into_owned())
-                        .collect()
-                } else {
-                    Vec::new()
-                };
-                (
-                    repo_path,
-                    all_sub_repos,
-                    false,
-                    Some(registry.handle.clone()),
-                )
-            } else {
-                return Err(anyhow!("Registry with handle '{}' not found.", h));
+                if repo_path.exists() {
+                    registries.push(SearchRegistry {
+                        repos: registry_sub_repos(&repo_path)?,
+                        db_path: repo_path,
+                        is_default: false,
+                        handle: registry.handle.clone(),
+                    });
+                }
             }
-        } else {
-            let default_registry = config
-                .default_registry
-                .as_ref()
-                .ok_or_else(|| anyhow!("No default registry set."))?;
-            (
-                db_root.join(&default_registry.handle),
-                config.repos,
-                true,
-                Some(default_registry.handle.clone()),
-            )
-        };
+        }
+    }
 
-    if !registry_db_path.exists() {
+    if !registries[0].db_path.exists() {
         return Err(anyhow!(
             "Registry '{}' is not synced. Please run 'zoi sync' to download the package database.",
-            registry_handle.unwrap_or_else(|| "default".to_string())
+            registries[0].handle
         ));
     }
 
-    let repos_to_search = if let Some(r) = &request.repo {
-        vec![r.clone()]
-    } else {
-        search_repos
-    };
-
     struct FoundPackage {
         path: PathBuf,
         source_type: SourceType,
         repo_name: String,
+        registry_handle: String,
         description: String,
         license: String,
         size: Option<u64>,
+        provider: Option<String>,
     }
 
-    fn process_found_package(
-        path: PathBuf,
+    fn source_type_for(
         repo_name: &str,
         is_default_registry: bool,
         registry_db_path: &Path,
-        quiet: bool,
-    ) -> Result<FoundPackage> {
-        let pkg: types::Package = crate::pkg::lua::parser::parse_lua_package(
-            path.to_str()
-                .ok_or_else(|| anyhow!("Path contains invalid UTF-8 characters: {:?}", path))?,
-            None,
-            quiet,
-        )?;
+    ) -> SourceType {
         let major_repo = repo_name
             .split('/')
             .next()
             .unwrap_or_default()
             .to_lowercase();
 
-        let source_type = if is_default_registry {
+        if is_default_registry {
             let repo_config = config::read_repo_config(registry_db_path).ok();
             if let Some(ref cfg) = repo_config {
                 if let Some(repo_entry) = cfg.repos.iter().find(|r| r.name == major_repo) {
@@ -299,140 +319,182 @@ fn find_package_in_db(request: &PackageRequest, quiet: bool) -> Result<ResolvedS
             }
         } else {
             SourceType::UntrustedRepo(repo_name.to_string())
-        };
+        }
+    }
+
+    fn process_found_package(
+        path: PathBuf,
+        repo_name: &str,
+        registry: &SearchRegistry,
+        quiet: bool,
+    ) -> Result<FoundPackage> {
+        let pkg: types::Package = crate::pkg::lua::parser::parse_lua_package(
+            path.to_str()
+                .ok_or_else(|| anyhow!("Path contains invalid UTF-8 characters: {:?}", path))?,
+            None,
+            quiet,
+        )?;
 
         Ok(FoundPackage {
             path,
-            source_type,
+            source_type: source_type_for(repo_name, registry.is_default, &registry.db_path),
             repo_name: pkg.repo.clone(),
+            registry_handle: registry.handle.clone(),
             description: pkg.description,
             license: pkg.license,
             size: pkg.installed_size,
+            provider: None,
         })
     }
 
+    let repos_for = |registry: &SearchRegistry| -> Vec<String> {
+        if let Some(r) = &request.repo {
+            vec![r.clone()]
+        } else {
+            registry.repos.clone()
+        }
+    };
+
     let mut found_packages = Vec::new();
 
-    if request.name.contains('/') {
-        let pkg_name = Path::new(&request.name)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| anyhow!("Invalid package path: {}", request.name))?;
-
-        for repo_name in &repos_to_search {
-            let path = registry_db_path
-                .join(repo_name)
-                .join(&request.name)
-                .join(format!("{}.pkg.lua", pkg_name));
-
-            if path.exists()
-                && let Ok(found) = process_found_package(
-                    path,
-                    repo_name,
-                    is_default_registry,
-                    &registry_db_path,
-                    quiet,
-                )
-            {
-                found_packages.push(found);
-            }
-        }
-    } else {
-        for repo_name in &repos_to_search {
-            let repo_path = registry_db_path.join(repo_name);
-            if !repo_path.is_dir() {
-                continue;
+    for registry in &registries {
+        let registry_db_path = &registry.db_path;
+        let repos_to_search = repos_for(registry);
+
+        if request.name.contains('/') {
+            let pkg_name = Path::new(&request.name)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .ok_or_else(|| anyhow!("Invalid package path: {}", request.name))?;
+
+            for repo_name in &repos_to_search {
+                let path = registry_db_path
+                    .join(repo_name)
+                    .join(&request.name)
+                    .join(format!("{}.pkg.lua", pkg_name));
+
+                if path.exists()
+                    && let Ok(found) = process_found_package(path, repo_name, registry, quiet)
+                {
+                    found_packages.push(found);
+                }
             }
-            for entry in WalkDir::new(&repo_path)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().is_dir() && e.file_name() == request.name.as_str())
-            {
-                let pkg_dir_path = entry.path();
+        } else {
+            for repo_name in &repos_to_search {
+                let repo_path = registry_db_path.join(repo_name);
+                if !repo_path.is_dir() {
+                    continue;
+                }
+                for entry in WalkDir::new(&repo_path)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_dir() && e.file_name() == request.name.as_str())
+                {
+                    let pkg_dir_path = entry.path();
 
-                if let Ok(relative_path) = pkg_dir_path.strip_prefix(&repo_path) {
-                    if relative_path.components().count() > 1 {
+                    if let Ok(relative_path) = pkg_dir_path.strip_prefix(&repo_path) {
+                        if relative_path.components().count() > 1 {
+                            continue;
+                        }
+                    } else {
                         continue;
                     }
-                } else {
-                    continue;
-                }
 
-                let pkg_file_path = pkg_dir_path.join(format!("{}.pkg.lua", request.name));
+                    let pkg_file_path = pkg_dir_path.join(format!("{}.pkg.lua", request.name));
 
-                if pkg_file_path.exists()
-                    && let Ok(found) = process_found_package(
-                        pkg_file_path,
-                        repo_name,
-                        is_default_registry,
-                        &registry_db_path,
-                        quiet,
-                    )
-                {
-                    found_packages.push(found);
+                    if pkg_file_path.exists()
+                        && let Ok(found) =
+                            process_found_package(pkg_file_path, repo_name, registry, quiet)
+                    {
+                        found_packages.push(found);
+                    }
                 }
             }
         }
     }
 
     if found_packages.is_empty() {
-        for repo_name in &repos_to_search {
-            let repo_path = registry_db_path.join(repo_name);
-            if !repo_path.is_dir() {
-                continue;
-            }
-            for entry in WalkDir::new(&repo_path)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|e| {
-                    e.file_type().is_file() && e.file_name().to_string_lossy().ends_with(".pkg.lua")
-                })
-            {
-                if let Ok(pkg) = crate::pkg::lua::parser::parse_lua_package(
-                    entry.path().to_str().ok_or_else(|| {
-                        anyhow!("Path contains invalid UTF-8 characters: {:?}", entry.path())
-                    })?,
-                    None,
-                    true,
-                ) && let Some(provides) = &pkg.provides
-                    && provides.iter().any(|p| p == &request.name)
+        let mut providers = Vec::new();
+        for registry in &registries {
+            let registry_db_path = &registry.db_path;
+            for repo_name in &repos_for(registry) {
+                let repo_path = registry_db_path.join(repo_name);
+                if !repo_path.is_dir() {
+                    continue;
+                }
+                for entry in WalkDir::new(&repo_path)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| {
+                        e.file_type().is_file()
+                            && e.file_name().to_string_lossy().ends_with(".pkg.lua")
+                    })
                 {
-                    let major_repo = repo_name
-                        .split('/')
-                        .next()
-                        .unwrap_or_default()
-                        .to_lowercase();
-                    let source_type = if is_default_registry {
-                        let repo_config = config::read_repo_config(&registry_db_path).ok();
-                        if let Some(ref cfg) = repo_config {
-                            if let Some(repo_entry) =
-                                cfg.repos.iter().find(|r| r.name == major_repo)
-                            {
-                                if repo_entry.repo_type == "official" {
-                                    SourceType::OfficialRepo
-                                } else {
-                                    SourceType::UntrustedRepo(repo_name.clone())
-                                }
-                            } else {
-                                SourceType::UntrustedRepo(repo_name.clone())
-                            }
-                        } else {
-                            SourceType::UntrustedRepo(repo_name.clone())
-                        }
-                    } else {
-                        SourceType::UntrustedRepo(repo_name.clone())
-                    };
-                    found_packages.push(FoundPackage {
-                        path: entry.path().to_path_buf(),
-                        source_type,
-                        repo_name: pkg.repo.clone(),
-                        description: pkg.description,
-                        license: pkg.license,
-                        size: pkg.installed_size,
-                    });
+                    if let Ok(pkg) = crate::pkg::lua::parser::parse_lua_package(
+                        entry.path().to_str().ok_or_else(|| {
+                            anyhow!("Path contains invalid UTF-8 characters: {:?}", entry.path())
+                        })?,
+                        None,
+                        true,
+                    ) && let Some(provides) = &pkg.provides
+                        && provides.iter().any(|p| p == &request.name)
+                    {
+                        let candidate =
+                            provider::ProviderCandidate::from_package(&pkg, &registry.handle);
+                        providers.push((
+                            candidate,
+                            FoundPackage {
+                                path: entry.path().to_path_buf(),
+                                source_type: source_type_for(
+                                    repo_name,
+                                    registry.is_default,
+                                    registry_db_path,
+                                ),
+                                repo_name: pkg.repo.clone(),
+                                registry_handle: registry.handle.clone(),
+                                description: pkg.description,
+                                license: pkg.license,
+                                size: pkg.installed_size,
+                                provider: Some(pkg.name.clone()),
+                            },
+                        ));
+                    }
                 }
             }
         }
+
+        let candidates: Vec<_> = providers.iter().map(|(c, _)| c.clone()).collect();
+        if let Some(idx) = provider::select_provider(&request.name, &candidates, yes)? {
+            found_packages.push(providers.swap_remove(idx).1);
+        }
+    }
+
+    if !config.preferences.is_empty() && !found_packages.is_empty() {
+        let explicit = request.handle.is_some() || request.repo.is_some();
+        let mut ranked: Vec<(i32, FoundPackage)> = found_packages
+            .into_iter()
+            .map(|p| {
+                let priority = preference::priority_for(
+                    &config.preferences,
+                    &request.name,
+                    &p.registry_handle,
+                    &p.repo_name,
+                );
+                (priority, p)
+            })
+            .filter(|(priority, _)| explicit || *priority >= 0)
+            .collect();
+
+        if ranked.is_empty() {
+            return Err(anyhow!(
+                "Package '{}' is only available from sources excluded by your preferences. Request it with an explicit #registry or @repo to install it anyway.",
+                request.name
+            ));
+        }
+
+        let best = ranked.iter().map(|(p, _)| *p).max().unwrap_or_default();
+        ranked.retain(|(p, _)| *p == best);
+        found_packages = ranked.into_iter().map(|(_, p)| p).collect();
     }
 
     if found_packages.is_empty() {
@@ -455,9 +517,10 @@ fn find_package_in_db(request: &PackageRequest, quiet: bool) -> Result<ResolvedS
             path: chosen.path.clone(),
             source_type: chosen.source_type.clone(),
             repo_name: Some(chosen.repo_name.clone()),
-            registry_handle: registry_handle.clone(),
+            registry_handle: Some(chosen.registry_handle.clone()),
             sharable_manifest: None,
             git_sha: None,
+            provider: chosen.provider.clone(),
         })
     } else {
         println!(
@@ -465,14 +528,26 @@ fn find_package_in_db(request: &PackageRequest, quiet: bool) -> Result<ResolvedS
             request.name.cyan()
         );
 
+        let multiple_registries = found_packages
+            .iter()
+            .any(|p| p.registry_handle != found_packages[0].registry_handle);
+
         let mut table = Table::new();
         table.load_preset(UTF8_FULL);
         table.set_header(vec!["#", "Repo", "License", "Size", "Description"]);
 
+        let display_repo = |p: &FoundPackage| {
+            if multiple_registries {
+                format!("#{}@{}", p.registry_handle, p.repo_name)
+            } else {
+                p.repo_name.clone()
+            }
+        };
+
         for (i, p) in found_packages.iter().enumerate() {
             table.add_row(vec![
                 (i + 1).to_string(),
-                p.repo_name.clone(),
+                display_repo(p),
                 p.license.clone(),
                 p.size
                     .map(crate::utils::format_bytes)
@@ -484,7 +559,13 @@ fn find_package_in_db(request: &PackageRequest, quiet: bool) -> Result<ResolvedS
 
         let items: Vec<String> = found_packages
             .iter()
-            .map(|p| format!("@{}", p.repo_name.bold()))
+            .map(|p| {
+                if multiple_registries {
+                    display_repo(p).bold().to_string()
+                } else {
+                    format!("@{}", p.repo_name.bold())
+                }
+            })
             .collect();
 
         let selection = Select::with_theme(&ColorfulTheme::default())
@@ -503,9 +584,10 @@ fn find_package_in_db(request: &PackageRequest, quiet: bool) -> Result<ResolvedS
             path: chosen.path.clone(),
             source_type: chosen.source_type.clone(),
             repo_name: Some(chosen.repo_name.clone()),
-            registry_handle: registry_handle.clone(),
+            registry_handle: Some(chosen.registry_handle.clone()),
             sharable_manifest: None,
             git_sha: None,
+            provider: chosen.provider.clone(),
         })
     }
 }
@@ -527,6 +609,7 @@ fn download_from_url(url: &str) -> Result<ResolvedSource> {
             registry_handle: Some("local".to_string()),
             sharable_manifest: None,
             git_sha: None,
+            provider: None,
         });
     }
 
@@ -591,6 +674,7 @@ fn download_from_url(url: &str) -> Result<ResolvedSource> {
         registry_handle: Some("local".to_string()),
         sharable_manifest: None,
         git_sha: None,
+        provider: None,
     })
 }
 
@@ -890,7 +974,7 @@ pub fn resolve_requested_version_spec(
 pub fn resolve_source(source: &str, quiet: bool, yes: bool) -> Result<ResolvedSource> {
     let config = config::read_config().unwrap_or_default();
     let max_depth = config.max_resolution_depth.unwrap_or(7);
-    let resolved = resolve_source_recursive(source, 0, max_depth, quiet)?;
+    let resolved = resolve_source_recursive(source, 0, max_depth, quiet, yes)?;
 
     if !quiet {
         let confirmation_key = match &resolved.source_type {
@@ -1011,7 +1095,10 @@ fn resolve_source_recursive(
     depth: u8,
     max_depth: u8,
     quiet: bool,
+    yes: bool,
 ) -> Result<ResolvedSource> {
+    let (source, _) = features::split_source(source);
+    let source = source.as_str();
     if max_depth > 0 && depth > max_depth {
         let msg = format!(
             "Resolution depth {} exceeds limit {}. Potential circular 'alt' reference.",
@@ -1039,7 +1126,7 @@ fn resolve_source_recursive(
             sharable_manifest.version
         );
         let mut resolved_source =
-            resolve_source_recursive(&new_source, depth + 1, max_depth, quiet)?;
+            resolve_source_recursive(&new_source, depth + 1, max_depth, quiet, yes)?;
         resolved_source.sharable_manifest = Some(sharable_manifest);
         return Ok(resolved_source);
     }
@@ -1149,6 +1236,7 @@ fn resolve_source_recursive(
             registry_handle: None,
             sharable_manifest: None,
             git_sha: None,
+            provider: None,
         });
     }
 
@@ -1209,6 +1297,7 @@ fn resolve_source_recursive(
             registry_handle: Some("local".to_string()),
             sharable_manifest: None,
             git_sha,
+            provider: None,
         }
     } else if source.starts_with("http://") || source.starts_with("https://") {
         if crate::pkg::offline::is_offline() {
@@ -1230,6 +1319,7 @@ fn resolve_source_recursive(
             registry_handle: Some("local".to_string()),
             sharable_manifest: None,
             git_sha: None,
+            provider: None,
         }
     } else if crate::utils::is_mini_mode() {
         let index = crate::pkg::mini_resolve::fetch_registry_index()?;
@@ -1264,7 +1354,7 @@ fn resolve_source_recursive(
         };
         resolved
     } else {
-        find_package_in_db(&request, quiet)?
+        find_package_in_db(&request, quiet, yes)?
     };
 
     let pkg_for_alt_check = crate::pkg::lua::parser::parse_lua_package(
@@ -1339,14 +1429,18 @@ fn resolve_source_recursive(
                     depth + 1,
                     max_depth,
                     quiet,
+                    yes,
                 )?
             } else {
-                resolve_source_recursive(&alt_source, depth + 1, max_depth, quiet)?
+                resolve_source_recursive(&alt_source, depth + 1, max_depth, quiet, yes)?
             };
 
         if resolved_source.source_type == SourceType::OfficialRepo {
             alt_resolved_source.source_type = SourceType::OfficialRepo;
         }
+        if alt_resolved_source.provider.is_none() {
+            alt_resolved_source.provider = resolved_source.provider;
+        }
 
         return Ok(alt_resolved_source);
     }
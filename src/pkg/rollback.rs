@@ -145,7 +145,8 @@ pub fn run(package_name: &str, yes: bool) -> Result<()> {
             fs::remove_file(current_manifest_path)?;
         }
     } else {
-        fs::remove_dir_all(current_version_dir)?;
+        fs::remove_dir_all(&current_version_dir)?;
+        let _ = crate::pkg::store::release(scope, &current_version_dir);
     }
 
     println!(
@@ -178,8 +179,8 @@ fn get_bin_root(scope: types::Scope) -> Result<PathBuf> {
             }
         }
         types::Scope::Project => {
-            let current_dir = std::env::current_dir()?;
-            Ok(current_dir.join(".zoi").join("pkgs").join("bin"))
+            let project_root = crate::project::config::project_root()?;
+            Ok(project_root.join(".zoi").join("pkgs").join("bin"))
         }
     }
 }
@@ -0,0 +1,507 @@
+use crate::pkg::config;
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Restrictions for a command run on behalf of a package.
+///
+/// Inside the sandbox the whole filesystem is read-only except `writable`,
+/// `$HOME` is replaced by an empty directory (only Zoi's own package store
+/// stays visible), and so is the user's runtime directory. The command only
+/// inherits a few harmless environment variables, and without `network` it
+/// gets a network namespace with no interfaces.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    pub writable: Vec<PathBuf>,
+    pub network: bool,
+}
+
+/// Whether the `sandbox` setting is on for this invocation.
+pub fn is_enabled() -> bool {
+    config::read_config().is_ok_and(|c| c.sandbox)
+}
+
+/// Whether the system policy forces the sandbox on.
+fn is_enforced() -> bool {
+    config::read_config().is_ok_and(|c| c.sandbox && c.policy.sandbox_unoverridable)
+}
+
+impl Sandbox {
+    /// Makes `command` start inside the sandbox.
+    pub fn apply(&self, command: &mut Command) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            linux::apply(self, command)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = command;
+            if is_enforced() {
+                return Err(anyhow!(
+                    "The system policy requires sandboxed package commands, which are only supported on Linux."
+                ));
+            }
+            eprintln!(
+                "{}: Sandboxing is only supported on Linux, running the command unsandboxed.",
+                "Warning".yellow().bold()
+            );
+            Ok(())
+        }
+    }
+
+    /// Explains a failed command whose output shows it ran into the sandbox.
+    pub fn explain_failure(&self, output: &str) -> Option<String> {
+        let writable = self
+            .writable
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if output.contains("Read-only file system") {
+            return Some(format!(
+                "the command tried to write outside its sandbox; only {} is writable",
+                if writable.is_empty() {
+                    "nothing".to_string()
+                } else {
+                    writable
+                }
+            ));
+        }
+        const NETWORK_ERRORS: [&str; 5] = [
+            "Network is unreachable",
+            "Could not resolve host",
+            "Temporary failure in name resolution",
+            "Name or service not known",
+            "Failed to connect to",
+        ];
+        if !self.network && NETWORK_ERRORS.iter().any(|e| output.contains(e)) {
+            return Some(
+                "the command tried to use the network, which the sandbox only allows in prepare()"
+                    .to_string(),
+            );
+        }
+        None
+    }
+}
+
+/// Prints why a sandboxed command failed, if the sandbox is to blame.
+pub fn report_violation(sandbox: &Sandbox, command: &str, output: &str) {
+    if let Some(reason) = sandbox.explain_failure(output) {
+        eprintln!(
+            "{} '{}': {}.",
+            "Sandbox violation in".red().bold(),
+            command,
+            reason
+        );
+    }
+}
+
+fn setup_error(e: std::io::Error) -> anyhow::Error {
+    if is_enforced() {
+        anyhow!(
+            "Could not start the sandbox required by the system policy: {}",
+            e
+        )
+    } else {
+        anyhow!(
+            "Could not start the sandbox: {}. Unprivileged user namespaces may be disabled on this system; set 'sandbox: false' to run package commands unsandboxed.",
+            e
+        )
+    }
+}
+
+/// Spawns `command` and maps sandbox setup failures to a readable error.
+pub fn spawn_output(command: &mut Command) -> Result<std::process::Output> {
+    command.output().map_err(setup_error)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::Sandbox;
+    use anyhow::{Result, anyhow};
+    use nix::errno::Errno;
+    use nix::fcntl::{OFlag, open};
+    use nix::mount::{MsFlags, mount};
+    use nix::sched::{CloneFlags, unshare};
+    use nix::sys::stat::Mode;
+    use nix::sys::statvfs::{FsFlags, statvfs};
+    use nix::unistd::{Gid, Uid, chdir, mkdir, write};
+    use std::ffi::{CStr, CString, OsString};
+    use std::fs;
+    use std::os::fd::{AsRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    /// Mount points left alone: kernel interfaces and device nodes.
+    const SKIPPED_MOUNTS: [&str; 3] = ["/proc", "/sys", "/dev"];
+
+    /// Variables the command inherits; everything else, such as tokens and
+    /// agent sockets, is dropped. Variables prefixed with `LC_` or `ZOI_`
+    /// are kept too.
+    const KEPT_ENV: [&str; 9] = [
+        "PATH", "HOME", "USER", "LOGNAME", "SHELL", "LANG", "LANGUAGE", "TERM", "TZ",
+    ];
+
+    /// Everything the child needs, computed before `fork` so the child only
+    /// makes system calls.
+    struct Plan {
+        flags: CloneFlags,
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        /// Bind mounted onto themselves and left writable.
+        writable: Vec<CString>,
+        /// Existing mounts to make read-only, with the flags they must keep.
+        read_only: Vec<(CString, MsFlags)>,
+        /// Covered by an empty tmpfs.
+        hidden: Option<CString>,
+        /// Also covered by an empty tmpfs, with nothing exposed again.
+        emptied: Vec<CString>,
+        /// Directories to create in the tmpfs, parents first.
+        skeleton: Vec<CString>,
+        /// Bind mounted back into the hidden directory, then remounted with
+        /// the given flags.
+        exposed: Vec<(CString, MsFlags)>,
+        cwd: Option<CString>,
+    }
+
+    fn cstring(path: &Path) -> Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| anyhow!("Path contains a NUL byte: {}", path.display()))
+    }
+
+    /// Flags an unprivileged remount has to keep, as they are locked in a
+    /// user namespace.
+    fn kept_flags(path: &Path) -> MsFlags {
+        let Ok(stat) = statvfs(path) else {
+            return MsFlags::empty();
+        };
+        let flags = stat.flags();
+        let mut kept = MsFlags::empty();
+        for (fs_flag, ms_flag) in [
+            (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+            (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+            (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+            (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+            (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+            (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+        ] {
+            if flags.contains(fs_flag) {
+                kept |= ms_flag;
+            }
+        }
+        kept
+    }
+
+    /// Undoes the octal escapes (`\040` for a space) of `/proc/self/mountinfo`.
+    fn unescape(field: &str) -> PathBuf {
+        let bytes = field.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'\\'
+                && i + 3 < bytes.len()
+                && let Some(v) = std::str::from_utf8(&bytes[i + 1..i + 4])
+                    .ok()
+                    .and_then(|digits| u8::from_str_radix(digits, 8).ok())
+            {
+                out.push(v);
+                i += 4;
+                continue;
+            }
+            out.push(bytes[i]);
+            i += 1;
+        }
+        PathBuf::from(std::ffi::OsStr::from_bytes(&out))
+    }
+
+    fn mount_points() -> Result<Vec<PathBuf>> {
+        let content = fs::read_to_string("/proc/self/mountinfo")?;
+        Ok(content
+            .lines()
+            .filter_map(|line| line.split(' ').nth(4))
+            .map(unescape)
+            .collect())
+    }
+
+    fn plan(sandbox: &Sandbox, cwd: Option<&Path>) -> Result<Plan> {
+        let mut flags = CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS;
+        if !sandbox.network {
+            flags |= CloneFlags::CLONE_NEWNET;
+        }
+        let uid = Uid::current();
+        let gid = Gid::current();
+
+        let writable: Vec<PathBuf> = sandbox
+            .writable
+            .iter()
+            .filter_map(|p| p.canonicalize().ok())
+            .collect();
+        let hidden = home::home_dir()
+            .and_then(|h| h.canonicalize().ok())
+            .filter(|h| h != Path::new("/"));
+
+        // The user's runtime directory holds sockets of the session bus,
+        // keyring and agents.
+        let mut emptied: Vec<PathBuf> = Vec::new();
+        let runtime_dirs = [
+            Some(PathBuf::from(format!("/run/user/{}", uid.as_raw()))),
+            std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from),
+        ];
+        for dir in runtime_dirs.into_iter().flatten() {
+            let Ok(dir) = dir.canonicalize() else {
+                continue;
+            };
+            if dir == Path::new("/")
+                || hidden.as_ref().is_some_and(|h| dir.starts_with(h))
+                || writable.iter().any(|w| w.starts_with(&dir))
+                || emptied.contains(&dir)
+            {
+                continue;
+            }
+            emptied.push(dir);
+        }
+
+        let mut read_only = Vec::new();
+        for mount_point in mount_points()? {
+            if SKIPPED_MOUNTS
+                .iter()
+                .any(|skipped| mount_point.starts_with(skipped))
+                || writable.iter().any(|w| mount_point.starts_with(w))
+                || hidden.as_ref().is_some_and(|h| mount_point.starts_with(h))
+            {
+                continue;
+            }
+            let kept = kept_flags(&mount_point);
+            read_only.push((cstring(&mount_point)?, kept));
+        }
+
+        let mut skeleton = Vec::new();
+        let mut exposed = Vec::new();
+        if let Some(home) = &hidden {
+            let store = home.join(".zoi").join("pkgs");
+            let mut inside: Vec<(PathBuf, bool)> = writable
+                .iter()
+                .filter(|w| w.starts_with(home) && *w != home)
+                .map(|w| (w.clone(), true))
+                .collect();
+            if store.is_dir() && !inside.iter().any(|(w, _)| store.starts_with(w)) {
+                inside.push((store, false));
+            }
+            for (path, is_writable) in inside {
+                let mut dir = home.clone();
+                for component in path.strip_prefix(home)?.components() {
+                    dir.push(component);
+                    let dir = cstring(&dir)?;
+                    if !skeleton.contains(&dir) {
+                        skeleton.push(dir);
+                    }
+                }
+                let mut flags = MsFlags::MS_REMOUNT | MsFlags::MS_BIND | kept_flags(&path);
+                if !is_writable {
+                    flags |= MsFlags::MS_RDONLY;
+                }
+                exposed.push((cstring(&path)?, flags));
+            }
+        }
+
+        let map = |outer: u32| format!("{} {} 1\n", outer, outer).into_bytes();
+        Ok(Plan {
+            flags,
+            uid_map: map(uid.as_raw()),
+            gid_map: map(gid.as_raw()),
+            writable: writable
+                .iter()
+                .filter(|w| !hidden.as_ref().is_some_and(|h| w.starts_with(h)))
+                .map(|w| cstring(w))
+                .collect::<Result<_>>()?,
+            read_only,
+            hidden: hidden.as_deref().map(cstring).transpose()?,
+            emptied: emptied.iter().map(|d| cstring(d)).collect::<Result<_>>()?,
+            skeleton,
+            exposed,
+            cwd: cwd.map(cstring).transpose()?,
+        })
+    }
+
+    fn write_file(path: &CStr, content: &[u8]) -> nix::Result<()> {
+        let fd = open(path, OFlag::O_WRONLY, Mode::empty())?;
+        write(&fd, content)?;
+        Ok(())
+    }
+
+    /// `/proc/self/fd/<fd>` without allocating.
+    fn fd_path<'a>(fd: &OwnedFd, buf: &'a mut [u8; 32]) -> &'a CStr {
+        const PREFIX: &[u8] = b"/proc/self/fd/";
+        buf[..PREFIX.len()].copy_from_slice(PREFIX);
+        let mut digits = [0u8; 10];
+        let mut n = fd.as_raw_fd() as u32;
+        let mut len = 0;
+        loop {
+            digits[len] = b'0' + (n % 10) as u8;
+            len += 1;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        for i in 0..len {
+            buf[PREFIX.len() + i] = digits[len - 1 - i];
+        }
+        buf[PREFIX.len() + len] = 0;
+        CStr::from_bytes_with_nul(&buf[..PREFIX.len() + len + 1]).expect("NUL terminated")
+    }
+
+    const NONE: Option<&CStr> = None;
+
+    /// Runs in the forked child: only system calls on prepared data.
+    fn enter(plan: &Plan) -> nix::Result<()> {
+        unshare(plan.flags)?;
+        write_file(c"/proc/self/setgroups", b"deny")?;
+        write_file(c"/proc/self/uid_map", &plan.uid_map)?;
+        write_file(c"/proc/self/gid_map", &plan.gid_map)?;
+
+        mount(
+            NONE,
+            c"/",
+            NONE,
+            MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+            NONE,
+        )?;
+
+        // Keep handles on what is re-exposed before the tmpfs covers it.
+        let mut handles = [const { None::<OwnedFd> }; 16];
+        if plan.exposed.len() > handles.len() {
+            return Err(Errno::E2BIG);
+        }
+        for (i, (path, _)) in plan.exposed.iter().enumerate() {
+            handles[i] = Some(open(
+                path.as_c_str(),
+                OFlag::O_PATH | OFlag::O_DIRECTORY | OFlag::O_CLOEXEC,
+                Mode::empty(),
+            )?);
+        }
+
+        for dir in &plan.writable {
+            mount(
+                Some(dir.as_c_str()),
+                dir.as_c_str(),
+                NONE,
+                MsFlags::MS_BIND | MsFlags::MS_REC,
+                NONE,
+            )?;
+        }
+
+        for (mount_point, kept) in &plan.read_only {
+            let result = mount(
+                NONE,
+                mount_point.as_c_str(),
+                NONE,
+                MsFlags::MS_REMOUNT | MsFlags::MS_BIND | MsFlags::MS_RDONLY | *kept,
+                NONE,
+            );
+            // Mounts hidden below other mounts cannot be reached; the root
+            // itself must become read-only.
+            if result.is_err() && mount_point.as_bytes() == b"/" {
+                return result;
+            }
+        }
+
+        if let Some(home) = &plan.hidden {
+            mount(
+                Some(c"tmpfs"),
+                home.as_c_str(),
+                Some(c"tmpfs"),
+                MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                Some(c"mode=0755"),
+            )?;
+            for dir in &plan.skeleton {
+                match mkdir(dir.as_c_str(), Mode::from_bits_truncate(0o755)) {
+                    Ok(()) | Err(Errno::EEXIST) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            for (i, (target, flags)) in plan.exposed.iter().enumerate() {
+                let mut buf = [0u8; 32];
+                let handle = handles[i].as_ref().ok_or(Errno::EBADF)?;
+                mount(
+                    Some(fd_path(handle, &mut buf)),
+                    target.as_c_str(),
+                    NONE,
+                    MsFlags::MS_BIND | MsFlags::MS_REC,
+                    NONE,
+                )?;
+                // The bind copies the flags of the mount it came from, which
+                // is read-only by now.
+                mount(NONE, target.as_c_str(), NONE, *flags, NONE)?;
+            }
+            mount(
+                NONE,
+                home.as_c_str(),
+                NONE,
+                MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                NONE,
+            )?;
+        }
+
+        for dir in &plan.emptied {
+            mount(
+                Some(c"tmpfs"),
+                dir.as_c_str(),
+                Some(c"tmpfs"),
+                MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                Some(c"mode=0700"),
+            )?;
+        }
+
+        // Re-enter the working directory through the new mounts, or leave a
+        // hidden one entirely.
+        if let Some(cwd) = &plan.cwd
+            && chdir(cwd.as_c_str()).is_ok()
+        {
+            return Ok(());
+        }
+        chdir(c"/")
+    }
+
+    pub fn apply(sandbox: &Sandbox, command: &mut Command) -> Result<()> {
+        let cwd = command
+            .get_current_dir()
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok());
+        let plan = plan(sandbox, cwd.as_deref())?;
+
+        // Variables set on the command itself come from Zoi and are kept.
+        let explicit: Vec<(OsString, OsString)> = command
+            .get_envs()
+            .filter_map(|(key, value)| Some((key.to_os_string(), value?.to_os_string())))
+            .collect();
+        command.env_clear();
+        for (key, value) in std::env::vars_os() {
+            let name = key.to_string_lossy();
+            if KEPT_ENV.contains(&name.as_ref())
+                || name.starts_with("LC_")
+                || name.starts_with("ZOI_")
+            {
+                command.env(&key, value);
+            }
+        }
+        // `/tmp` is read-only in here, so temporary files go to the first
+        // writable directory instead.
+        if let Some(dir) = sandbox.writable.first() {
+            let tmp = dir.join(".tmp");
+            fs::create_dir_all(&tmp)?;
+            command.env("TMPDIR", tmp);
+        }
+        command.envs(explicit);
+
+        // SAFETY: `enter` only issues system calls on data prepared above and
+        // does not allocate, which keeps it safe between fork and exec.
+        unsafe {
+            command.pre_exec(move || enter(&plan).map_err(std::io::Error::from));
+        }
+        Ok(())
+    }
+}
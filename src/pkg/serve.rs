@@ -0,0 +1,349 @@
+use crate::pkg::{cache, resolve, snapshot, sync};
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Default port for `zoi serve`.
+pub const DEFAULT_PORT: u16 = 8080;
+
+/// Connections handled at the same time. Further connections wait in a
+/// queue of the same size, then in the listener's backlog.
+const WORKERS: usize = 16;
+
+struct CachedSnapshot {
+    key: String,
+    serial: u64,
+    path: PathBuf,
+}
+
+/// Snapshots generated on demand, keyed by registry handle. A snapshot is
+/// rebuilt when the registry's HEAD moves, or on every request for registries
+/// that are not git checkouts.
+struct SnapshotCache {
+    dir: TempDir,
+    entries: Mutex<HashMap<String, CachedSnapshot>>,
+}
+
+enum Route {
+    Index,
+    ArchiveList,
+    File(PathBuf),
+    Snapshot(String),
+}
+
+pub fn bind(address: &str, port: u16) -> Result<TcpListener> {
+    TcpListener::bind((address, port))
+        .map_err(|e| anyhow!("Failed to listen on {}:{}: {}", address, port, e))
+}
+
+/// Serves the archive cache and the synced registries over HTTP until the
+/// process is stopped. Connections are handled by a fixed pool of workers.
+///
+/// * `/archives/<file>` serves `<archive cache>/<file>`, so the URL can be
+///   used in `cache_mirrors` or as a registry `pkg` URL. Any directories in
+///   front of the file name are ignored, which lets `pkg` templates such as
+///   `{repo}/{os}/{arch}/{version}` resolve against the flat cache.
+/// * `/registries/<handle>.tar.zst` serves a snapshot of a registry that
+///   clients can sync from without git.
+/// * `/registries/<handle>/<path>` serves files from the registry tree.
+pub fn serve(listener: TcpListener) -> Result<()> {
+    let snapshots = Arc::new(SnapshotCache {
+        dir: tempfile::Builder::new().prefix("zoi-serve-").tempdir()?,
+        entries: Mutex::new(HashMap::new()),
+    });
+
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(WORKERS);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKERS {
+        let receiver = Arc::clone(&receiver);
+        let snapshots = Arc::clone(&snapshots);
+        std::thread::spawn(move || {
+            loop {
+                let next = match receiver.lock() {
+                    Ok(receiver) => receiver.recv(),
+                    Err(_) => return,
+                };
+                let Ok(stream) = next else { return };
+                if let Err(e) = handle_connection(stream, &snapshots) {
+                    eprintln!("zoi serve: {}", e);
+                }
+            }
+        });
+    }
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        if sender.send(stream).is_err() {
+            return Err(anyhow!("zoi serve: all workers stopped"));
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, snapshots: &SnapshotCache) -> Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return respond_text(&mut stream, 400, "Bad Request", "bad request\n", false);
+    };
+    let head_only = method == "HEAD";
+    if method != "GET" && !head_only {
+        return respond_text(
+            &mut stream,
+            405,
+            "Method Not Allowed",
+            "method not allowed\n",
+            false,
+        );
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = urlencoding::decode(path)
+        .map(|p| p.into_owned())
+        .unwrap_or_default();
+
+    let route = match route(&path) {
+        Ok(Some(route)) => route,
+        Ok(None) => return respond_text(&mut stream, 404, "Not Found", "not found\n", head_only),
+        Err(e) => {
+            return respond_text(
+                &mut stream,
+                500,
+                "Internal Server Error",
+                &format!("{}\n", e),
+                head_only,
+            );
+        }
+    };
+
+    let result = match route {
+        Route::Index => index_text().map(Body::Text),
+        Route::ArchiveList => archive_list_text().map(Body::Text),
+        Route::File(path) => Ok(Body::File(path)),
+        Route::Snapshot(handle) => registry_snapshot(snapshots, &handle).map(Body::File),
+    };
+
+    match result {
+        Ok(Body::Text(text)) => respond_text(&mut stream, 200, "OK", &text, head_only),
+        Ok(Body::File(path)) => respond_file(&mut stream, &path, head_only),
+        Err(e) => respond_text(
+            &mut stream,
+            500,
+            "Internal Server Error",
+            &format!("{}\n", e),
+            head_only,
+        ),
+    }
+}
+
+enum Body {
+    Text(String),
+    File(PathBuf),
+}
+
+/// Splits a request path into normal components, rejecting anything that
+/// could escape the served directories.
+fn safe_components(path: &str) -> Option<Vec<String>> {
+    let mut parts = Vec::new();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(parts)
+}
+
+fn route(path: &str) -> Result<Option<Route>> {
+    let Some(parts) = safe_components(path) else {
+        return Ok(None);
+    };
+    let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+
+    match parts.as_slice() {
+        [] => Ok(Some(Route::Index)),
+        ["archives"] => Ok(Some(Route::ArchiveList)),
+        ["archives", .., file] => {
+            let path = cache::get_archive_cache_root()?.join(file);
+            Ok(path.is_file().then_some(Route::File(path)))
+        }
+        ["registries", file] => {
+            let Some(handle) = file.strip_suffix(".tar.zst") else {
+                return Ok(None);
+            };
+            let tree = resolve::get_db_root()?.join(handle);
+            Ok(tree
+                .join("repo.yaml")
+                .is_file()
+                .then(|| Route::Snapshot(handle.to_string())))
+        }
+        ["registries", handle, rest @ ..] => {
+            if rest.contains(&".git") {
+                return Ok(None);
+            }
+            let mut path = resolve::get_db_root()?.join(handle);
+            path.extend(rest);
+            Ok(path.is_file().then_some(Route::File(path)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn served_registries() -> Result<Vec<String>> {
+    let db_root = resolve::get_db_root()?;
+    let mut handles = Vec::new();
+    if let Ok(entries) = fs::read_dir(&db_root) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with('.') && entry.path().join("repo.yaml").is_file() {
+                handles.push(name);
+            }
+        }
+    }
+    handles.sort();
+    Ok(handles)
+}
+
+fn archive_list_text() -> Result<String> {
+    let root = cache::get_archive_cache_root()?;
+    let mut names = Vec::new();
+    if let Ok(entries) = fs::read_dir(&root) {
+        for entry in entries.flatten() {
+            if entry.path().is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+    }
+    names.sort();
+    Ok(names.into_iter().map(|n| n + "\n").collect())
+}
+
+fn index_text() -> Result<String> {
+    let mut text = String::from("Zoi mirror\n\narchives:\n  /archives/\n\nregistries:\n");
+    for handle in served_registries()? {
+        text.push_str(&format!("  /registries/{}.tar.zst\n", handle));
+    }
+    Ok(text)
+}
+
+/// Returns a snapshot of the registry, building it when the registry changed
+/// since the last request.
+fn registry_snapshot(snapshots: &SnapshotCache, handle: &str) -> Result<PathBuf> {
+    let tree = resolve::get_db_root()?.join(handle);
+    let key = sync::head_commit_id(&tree);
+    let mut entries = snapshots
+        .entries
+        .lock()
+        .map_err(|_| anyhow!("snapshot cache lock poisoned"))?;
+
+    let cached = entries.get(handle);
+    if let Some(key) = &key
+        && let Some(cached) = cached
+        && cached.key == *key
+        && cached.path.is_file()
+    {
+        return Ok(cached.path.clone());
+    }
+
+    // Clients skip snapshots whose serial is not newer than the one they
+    // have, so every rebuild has to move past the previous one.
+    let previous = cached
+        .map(|c| c.serial)
+        .max(snapshot::read_index(&tree).ok().map(|i| i.serial));
+    let serial = snapshot::next_serial(previous);
+    let output = snapshots
+        .dir
+        .path()
+        .join(format!("{}-{}.tar.zst", handle, serial));
+    snapshot::create_snapshot(&tree, &output, serial)?;
+    let entry = CachedSnapshot {
+        key: key.unwrap_or_default(),
+        serial,
+        path: output.clone(),
+    };
+    if let Some(previous) = entries.insert(handle.to_string(), entry)
+        && previous.path != output
+    {
+        let _ = fs::remove_file(previous.path);
+    }
+    Ok(output)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let name = path.to_string_lossy();
+    if name.ends_with(".yaml") || name.ends_with(".yml") {
+        "application/yaml"
+    } else if [".hash", ".size", ".files", ".lua", ".txt", ".md"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+    {
+        "text/plain; charset=utf-8"
+    } else if name.ends_with(".json") {
+        "application/json"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+fn write_head(
+    stream: &mut TcpStream,
+    status: u16,
+    reason: &str,
+    content_type: &str,
+    length: u64,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, reason, content_type, length
+    )
+}
+
+fn respond_text(
+    stream: &mut TcpStream,
+    status: u16,
+    reason: &str,
+    body: &str,
+    head_only: bool,
+) -> Result<()> {
+    write_head(
+        stream,
+        status,
+        reason,
+        "text/plain; charset=utf-8",
+        body.len() as u64,
+    )?;
+    if !head_only {
+        stream.write_all(body.as_bytes())?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+fn respond_file(stream: &mut TcpStream, path: &Path, head_only: bool) -> Result<()> {
+    let mut file = File::open(path)?;
+    let length = file.metadata()?.len();
+    write_head(stream, 200, "OK", content_type(path), length)?;
+    if !head_only {
+        io::copy(&mut file, stream)?;
+    }
+    stream.flush()?;
+    Ok(())
+}
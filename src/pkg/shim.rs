@@ -1,4 +1,4 @@
-use crate::pkg::{config, db, local, resolve, types::Scope};
+use crate::pkg::{config, db, local, resolve, types::Scope, version_files};
 use crate::project;
 use anyhow::{Result, anyhow};
 use std::env;
@@ -120,8 +120,23 @@ fn get_desired_version(bin_name: &str, plugin_manager: &PluginManager) -> Result
         }
     }
 
+    let mut names = vec![bin_name.to_string()];
+    if let Ok(providers) = db::find_provides("local", bin_name) {
+        for (pkg, _) in providers {
+            if !names.contains(&pkg.name) {
+                names.push(pkg.name);
+            }
+        }
+    }
+
+    if let Ok(cwd) = env::current_dir()
+        && let Some(pinned) = version_files::find_version(&cwd, &names)
+    {
+        return Ok(Some(pinned.version));
+    }
+
     let cfg = config::read_config()?;
-    if let Some(v) = cfg.versions.get(bin_name) {
+    if let Some(v) = names.iter().find_map(|name| cfg.versions.get(name)) {
         return Ok(Some(v.clone()));
     }
 
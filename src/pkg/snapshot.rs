@@ -0,0 +1,374 @@
+use crate::pkg::{config, sync, types};
+use crate::utils;
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tar::{Archive, Builder as TarBuilder, Header};
+use tempfile::{Builder, TempDir};
+use walkdir::WalkDir;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+/// Name of the index stored at the root of every snapshot.
+pub const INDEX_FILE: &str = "index.json";
+
+/// Newest snapshot format this version of Zoi can read.
+pub const SNAPSHOT_FORMAT: u32 = 1;
+
+/// Prefix that marks a registry URL as a static snapshot location rather than
+/// a git remote, e.g. `snapshot+https://mirror.internal/zoidberg/`.
+pub const SNAPSHOT_PREFIX: &str = "snapshot+";
+
+fn default_format() -> u32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotIndex {
+    #[serde(default = "default_format")]
+    pub format: u32,
+    pub handle: String,
+    /// Monotonically increasing snapshot number, used to refuse rollbacks.
+    pub serial: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default)]
+    pub packages: Vec<IndexedPackage>,
+    #[serde(default)]
+    pub advisories: Vec<IndexedAdvisory>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexedPackage {
+    pub source: String,
+    pub package: types::Package,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexedAdvisory {
+    pub source: String,
+    pub repo: String,
+    pub advisory: types::Advisory,
+}
+
+pub struct DownloadedSnapshot {
+    _dir: TempDir,
+    pub archive: PathBuf,
+    pub signature: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct InstalledSnapshot {
+    pub handle: String,
+    pub serial: u64,
+    pub updated: bool,
+}
+
+fn is_archive_url(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    [".tar.zst", ".tar.gz", ".tgz", ".tar.xz"]
+        .iter()
+        .any(|ext| path.ends_with(ext))
+}
+
+/// Whether a registry URL points at a snapshot instead of a git repository.
+pub fn is_snapshot_url(url: &str) -> bool {
+    url.starts_with(SNAPSHOT_PREFIX) || is_archive_url(url)
+}
+
+/// Resolves the archive to download for a snapshot registry. A URL that is not
+/// an archive itself is treated as a static directory whose `repo.yaml` names
+/// the snapshot through its `db` field.
+pub fn resolve_snapshot_url(url: &str) -> Result<String> {
+    let url = url.strip_prefix(SNAPSHOT_PREFIX).unwrap_or(url);
+    if is_archive_url(url) {
+        return Ok(url.to_string());
+    }
+
+    let base = url.trim_end_matches('/');
+    let repo_yaml_url = format!("{}/repo.yaml", base);
+    let response = utils::get_http_client()?.get(&repo_yaml_url).send()?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to fetch {} (HTTP {})",
+            repo_yaml_url,
+            response.status()
+        ));
+    }
+    let repo_config: types::RepoConfig = serde_yaml::from_str(&response.text()?)?;
+    let db_template = repo_config.db.ok_or_else(|| {
+        anyhow!(
+            "repo.yaml at {} does not declare a snapshot in its 'db' field.",
+            repo_yaml_url
+        )
+    })?;
+
+    let platform = utils::get_platform().unwrap_or_default();
+    let db_url =
+        crate::pkg::install::util::resolve_url_placeholders(&db_template, "", "", "", &platform);
+    if !is_archive_url(&db_url) {
+        return Err(anyhow!(
+            "The 'db' URL '{}' is not a registry snapshot archive.",
+            db_url
+        ));
+    }
+    if db_url.contains("://") {
+        Ok(db_url)
+    } else {
+        Ok(format!("{}/{}", base, db_url.trim_start_matches('/')))
+    }
+}
+
+/// Downloads a snapshot archive and its detached `.sig`, if one is published.
+pub fn download_snapshot(url: &str, pb: Option<&ProgressBar>) -> Result<DownloadedSnapshot> {
+    let archive_url = resolve_snapshot_url(url)?;
+    let dir = Builder::new().prefix("zoi-snapshot-").tempdir()?;
+    let file_name = crate::pkg::install::util::get_filename_from_url(&archive_url).to_string();
+    let archive = dir.path().join(&file_name);
+    crate::pkg::install::util::download_file_with_progress(&archive_url, &archive, pb, None)?;
+
+    let sig_path = dir.path().join(format!("{}.sig", file_name));
+    let signature = crate::pkg::install::util::download_file_with_progress(
+        &format!("{}.sig", archive_url),
+        &sig_path,
+        pb,
+        None,
+    )
+    .ok()
+    .map(|_| sig_path);
+
+    Ok(DownloadedSnapshot {
+        _dir: dir,
+        archive,
+        signature,
+    })
+}
+
+/// Verifies a snapshot against the registry's authorities. Unsigned snapshots
+/// are only accepted when no authorities are configured, matching git
+/// registries.
+pub fn verify_snapshot(
+    archive: &Path,
+    signature: Option<&Path>,
+    authorities: &[String],
+) -> Result<()> {
+    if authorities.is_empty() {
+        return Ok(());
+    }
+    let sig_path = signature
+        .ok_or_else(|| anyhow!("Registry snapshot is not signed. Sync aborted for security."))?;
+    let data = fs::read(archive)?;
+    let sig = fs::read(sig_path)?;
+    if sync::verify_with_authorities(&data, &sig, authorities)? {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Registry snapshot was signed but not by any authorized authority. Sync aborted."
+        ))
+    }
+}
+
+fn open_archive(archive_path: &Path) -> Result<Box<dyn Read>> {
+    let file = File::open(archive_path)?;
+    let name = archive_path.to_string_lossy();
+    Ok(if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Box::new(GzDecoder::new(file))
+    } else if name.ends_with(".tar.xz") {
+        Box::new(XzDecoder::new(file))
+    } else {
+        Box::new(ZstdDecoder::new(file)?)
+    })
+}
+
+fn unpack_archive(archive_path: &Path, dest: &Path) -> Result<()> {
+    let reader = open_archive(archive_path)?;
+    fs::create_dir_all(dest)?;
+    Archive::new(reader).unpack(dest)?;
+    Ok(())
+}
+
+/// Reads the index of a snapshot archive without unpacking it.
+pub fn read_archive_index(archive_path: &Path) -> Result<SnapshotIndex> {
+    let mut archive = Archive::new(open_archive(archive_path)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? == Path::new(INDEX_FILE) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            return Ok(serde_json::from_str(&content)?);
+        }
+    }
+    Err(anyhow!(
+        "{} is not a registry snapshot: it has no {}",
+        archive_path.display(),
+        INDEX_FILE
+    ))
+}
+
+/// Serial for a new snapshot: the current time in seconds, but always past
+/// `previous` so rebuilds within the same second still count as newer.
+pub fn next_serial(previous: Option<u64>) -> u64 {
+    let now = Utc::now().timestamp().max(0) as u64;
+    match previous {
+        Some(previous) => now.max(previous + 1),
+        None => now,
+    }
+}
+
+pub fn read_index(registry_path: &Path) -> Result<SnapshotIndex> {
+    let path = registry_path.join(INDEX_FILE);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Failed to read snapshot index {}: {}", path.display(), e))?;
+    let index: SnapshotIndex = serde_json::from_str(&content)?;
+    if index.format > SNAPSHOT_FORMAT {
+        return Err(anyhow!(
+            "Snapshot uses format {}, but this version of Zoi only supports up to {}.",
+            index.format,
+            SNAPSHOT_FORMAT
+        ));
+    }
+    Ok(index)
+}
+
+/// Verifies and unpacks a snapshot archive into `<db_root>/<handle>`, the same
+/// layout a git registry checkout uses. Snapshots older than the installed one
+/// are refused.
+pub fn install_snapshot(
+    archive: &Path,
+    signature: Option<&Path>,
+    authorities: &[String],
+    db_root: &Path,
+    handle: Option<&str>,
+) -> Result<InstalledSnapshot> {
+    verify_snapshot(archive, signature, authorities)?;
+
+    fs::create_dir_all(db_root)?;
+    let staging = Builder::new()
+        .prefix(".zoi-snapshot-")
+        .tempdir_in(db_root)?;
+    let tree = staging.path().join("tree");
+    unpack_archive(archive, &tree)?;
+
+    let index = read_index(&tree)?;
+    let repo_config = config::read_repo_config(&tree)?;
+    let handle = match handle {
+        Some(h) if !h.is_empty() => h.to_string(),
+        _ => repo_config.name,
+    };
+
+    let target = db_root.join(&handle);
+    if let Ok(current) = read_index(&target) {
+        if current.serial > index.serial {
+            return Err(anyhow!(
+                "Refusing to roll back registry '{}' from snapshot {} to older snapshot {}.",
+                handle,
+                current.serial,
+                index.serial
+            ));
+        }
+        if current.serial == index.serial {
+            return Ok(InstalledSnapshot {
+                handle,
+                serial: index.serial,
+                updated: false,
+            });
+        }
+    }
+
+    let previous = staging.path().join("previous");
+    let had_previous = target.exists();
+    if had_previous {
+        fs::rename(&target, &previous)?;
+    }
+    if let Err(e) = fs::rename(&tree, &target) {
+        // Put the old registry back rather than leaving none at all.
+        if had_previous {
+            fs::rename(&previous, &target)?;
+        }
+        return Err(e.into());
+    }
+
+    Ok(InstalledSnapshot {
+        handle,
+        serial: index.serial,
+        updated: true,
+    })
+}
+
+/// Packs a registry tree into a snapshot archive with a prebuilt index, so
+/// that clients can sync it without git and without evaluating every package.
+/// The archive can then be signed with `gpg --detach-sign`.
+pub fn create_snapshot(registry_path: &Path, output: &Path, serial: u64) -> Result<SnapshotIndex> {
+    let repo_config = config::read_repo_config(registry_path)?;
+    let (pkg_files, sec_files) = sync::collect_index_files(registry_path);
+
+    let mut packages = Vec::new();
+    for path in &pkg_files {
+        if let Some(package) = sync::parse_registry_package(registry_path, path) {
+            packages.push(IndexedPackage {
+                source: sync::registry_source_path(registry_path, path).unwrap_or_default(),
+                package,
+            });
+        }
+    }
+
+    let mut advisories = Vec::new();
+    for path in &sec_files {
+        if let Some((advisory, repo)) = sync::parse_registry_advisory(registry_path, path) {
+            advisories.push(IndexedAdvisory {
+                source: sync::registry_source_path(registry_path, path).unwrap_or_default(),
+                repo,
+                advisory,
+            });
+        }
+    }
+
+    let index = SnapshotIndex {
+        format: SNAPSHOT_FORMAT,
+        handle: repo_config.name,
+        serial,
+        created: Some(Utc::now().to_rfc3339()),
+        packages,
+        advisories,
+    };
+    let index_json = serde_json::to_vec_pretty(&index)?;
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let encoder = ZstdEncoder::new(File::create(output)?, 0)?.auto_finish();
+    let mut tar_builder = TarBuilder::new(encoder);
+
+    for entry in WalkDir::new(registry_path)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+    {
+        let rel = entry.path().strip_prefix(registry_path)?;
+        if rel == Path::new(INDEX_FILE) {
+            continue;
+        }
+        if entry.file_type().is_dir() {
+            tar_builder.append_dir(rel, entry.path())?;
+        } else if entry.file_type().is_file() {
+            tar_builder.append_path_with_name(entry.path(), rel)?;
+        }
+    }
+
+    let mut header = Header::new_gnu();
+    header.set_size(index_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    tar_builder.append_data(&mut header, INDEX_FILE, index_json.as_slice())?;
+    tar_builder.finish()?;
+
+    Ok(index)
+}
@@ -0,0 +1,461 @@
+use crate::pkg::local;
+use crate::pkg::types::Scope;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use walkdir::WalkDir;
+
+const INDEX_FILE: &str = "index.json";
+
+/// Serialises index updates from the parallel installer.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Which object backs each file of each version directory, keyed by the
+/// version directory relative to the package store and then by file path
+/// relative to the version directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreIndex {
+    #[serde(default)]
+    versions: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl StoreIndex {
+    fn referenced(&self) -> BTreeSet<&str> {
+        self.versions
+            .values()
+            .flat_map(|files| files.values().map(String::as_str))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IngestStats {
+    /// Files that now share an object already in the store.
+    pub shared: usize,
+    /// Files that became new objects.
+    pub added: usize,
+    /// Bytes no longer stored twice thanks to `shared`.
+    pub saved_bytes: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GcReport {
+    /// Version directories dropped from the index because they are gone.
+    pub stale_versions: usize,
+    pub removed_objects: usize,
+    pub freed_bytes: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub checked_objects: usize,
+    /// Objects whose content no longer matches their hash, with the files
+    /// that use them.
+    pub corrupt: Vec<(String, Vec<PathBuf>)>,
+    /// Objects referenced by the index but missing from the store.
+    pub missing: Vec<String>,
+    /// Indexed files that exist but no longer share the object's content.
+    pub detached: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty() && self.missing.is_empty()
+    }
+}
+
+/// Directory holding the content-addressed objects for `scope`, next to the
+/// package store.
+pub fn get_objects_dir(scope: Scope) -> Result<PathBuf> {
+    let store = local::get_store_base_dir(scope)?;
+    let parent = store
+        .parent()
+        .ok_or_else(|| anyhow!("Package store '{}' has no parent.", store.display()))?;
+    Ok(parent.join("objects"))
+}
+
+fn object_path(objects_dir: &Path, object: &str) -> PathBuf {
+    objects_dir.join(&object[..2]).join(object)
+}
+
+fn index_key(scope: Scope, version_dir: &Path) -> Result<String> {
+    let store = local::get_store_base_dir(scope)?;
+    let rel = version_dir.strip_prefix(&store).map_err(|_| {
+        anyhow!(
+            "'{}' is not inside the package store '{}'.",
+            version_dir.display(),
+            store.display()
+        )
+    })?;
+    Ok(rel_to_key(rel))
+}
+
+fn rel_to_key(rel: &Path) -> String {
+    rel.components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn read_index(objects_dir: &Path) -> Result<StoreIndex> {
+    let path = objects_dir.join(INDEX_FILE);
+    if !path.exists() {
+        return Ok(StoreIndex::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse store index '{}': {}", path.display(), e))
+}
+
+fn write_index(objects_dir: &Path, index: &StoreIndex) -> Result<()> {
+    fs::create_dir_all(objects_dir)?;
+    let path = objects_dir.join(INDEX_FILE);
+    let tmp = objects_dir.join(format!("{}.tmp", INDEX_FILE));
+    fs::write(&tmp, serde_json::to_vec(index)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+pub(crate) fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Object name for `path`: its sha256, plus `.x` for executables since hard
+/// links share permissions.
+fn object_name(path: &Path, metadata: &fs::Metadata) -> Result<String> {
+    let hash = hash_file(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return Ok(format!("{}.x", hash));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    Ok(hash)
+}
+
+fn object_hash(object: &str) -> &str {
+    object.strip_suffix(".x").unwrap_or(object)
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    a.len() == b.len()
+}
+
+fn temp_sibling(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.zoi-store-tmp", name))
+}
+
+/// Points `file` at `object`, adding the object first if the store does not
+/// have it yet. Returns whether an existing object was reused.
+fn link_into_store(file: &Path, object: &Path) -> Result<bool> {
+    if object.exists() {
+        let tmp = temp_sibling(file);
+        let _ = fs::remove_file(&tmp);
+        fs::hard_link(object, &tmp)?;
+        if let Err(e) = fs::rename(&tmp, file) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e.into());
+        }
+        Ok(true)
+    } else {
+        if let Some(parent) = object.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = temp_sibling(object);
+        let _ = fs::remove_file(&tmp);
+        fs::hard_link(file, &tmp)?;
+        fs::rename(&tmp, object)?;
+        Ok(false)
+    }
+}
+
+/// Removes objects in `candidates` that nothing in `index` references any
+/// more, returning the bytes freed.
+fn free_unreferenced<'a>(
+    objects_dir: &Path,
+    index: &StoreIndex,
+    candidates: impl IntoIterator<Item = &'a String>,
+) -> u64 {
+    let referenced = index.referenced();
+    let mut freed = 0;
+    for object in candidates {
+        if referenced.contains(object.as_str()) {
+            continue;
+        }
+        let path = object_path(objects_dir, object);
+        if let Ok(meta) = fs::symlink_metadata(&path)
+            && fs::remove_file(&path).is_ok()
+        {
+            freed += meta.len();
+        }
+    }
+    freed
+}
+
+/// Moves the regular files in `files` (relative to `version_dir`) into the
+/// object store and hard-links them back, so identical files across versions
+/// are stored once. Paths in `skip` are left alone, which is how files users
+/// edit in place (such as `backup` configs) stay private to their version.
+///
+/// Files the filesystem cannot hard-link are kept as plain copies.
+pub fn ingest(
+    scope: Scope,
+    version_dir: &Path,
+    files: &[PathBuf],
+    skip: &[String],
+) -> Result<IngestStats> {
+    let objects_dir = get_objects_dir(scope)?;
+    let key = index_key(scope, version_dir)?;
+    let skip: BTreeSet<String> = skip.iter().map(|s| rel_to_key(Path::new(s))).collect();
+
+    let mut linked = BTreeMap::new();
+    let mut stats = IngestStats::default();
+    for rel in files {
+        let rel_key = rel_to_key(rel);
+        if rel_key.is_empty() || skip.contains(&rel_key) {
+            continue;
+        }
+        let path = version_dir.join(rel);
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if !metadata.file_type().is_file() {
+            continue;
+        }
+        let object = object_name(&path, &metadata)?;
+        match link_into_store(&path, &object_path(&objects_dir, &object)) {
+            Ok(true) => {
+                stats.shared += 1;
+                stats.saved_bytes += metadata.len();
+            }
+            Ok(false) => stats.added += 1,
+            Err(_) => continue,
+        }
+        linked.insert(rel_key, object);
+    }
+
+    let _guard = INDEX_LOCK
+        .lock()
+        .map_err(|e| anyhow!("Failed to lock store index: {}", e))?;
+    let mut index = read_index(&objects_dir)?;
+    let entry = index.versions.entry(key).or_default();
+    let mut replaced = Vec::new();
+    for (rel, object) in linked {
+        if let Some(old) = entry.insert(rel, object.clone())
+            && old != object
+        {
+            replaced.push(old);
+        }
+    }
+    write_index(&objects_dir, &index)?;
+    free_unreferenced(&objects_dir, &index, &replaced);
+    Ok(stats)
+}
+
+/// Removes the files in `files` (relative to `version_dir`) ahead of
+/// reinstalling over them. Copying onto a hard link would otherwise rewrite
+/// the shared object and every other version using it.
+pub fn unshare(version_dir: &Path, files: &[PathBuf]) -> Result<()> {
+    for rel in files {
+        let path = version_dir.join(rel);
+        if fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_file()) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Drops the references held by `path`, a version directory or a whole
+/// package directory that was just removed, and deletes the objects no other
+/// version shares. Returns the bytes freed.
+pub fn release(scope: Scope, path: &Path) -> Result<u64> {
+    let objects_dir = get_objects_dir(scope)?;
+    if !objects_dir.join(INDEX_FILE).exists() {
+        return Ok(0);
+    }
+    let key = index_key(scope, path)?;
+    let prefix = format!("{}/", key);
+
+    let _guard = INDEX_LOCK
+        .lock()
+        .map_err(|e| anyhow!("Failed to lock store index: {}", e))?;
+    let mut index = read_index(&objects_dir)?;
+    let released: Vec<String> = index
+        .versions
+        .keys()
+        .filter(|k| **k == key || k.starts_with(&prefix))
+        .cloned()
+        .collect();
+    if released.is_empty() {
+        return Ok(0);
+    }
+    let mut candidates = BTreeSet::new();
+    for k in released {
+        if let Some(files) = index.versions.remove(&k) {
+            candidates.extend(files.into_values());
+        }
+    }
+    write_index(&objects_dir, &index)?;
+    Ok(free_unreferenced(&objects_dir, &index, &candidates))
+}
+
+/// Drops index entries for version directories and files that no longer
+/// exist or were replaced, then deletes every object nothing references.
+pub fn gc(scope: Scope, dry_run: bool) -> Result<GcReport> {
+    let objects_dir = get_objects_dir(scope)?;
+    let mut report = GcReport::default();
+    if !objects_dir.exists() {
+        return Ok(report);
+    }
+    let store = local::get_store_base_dir(scope)?;
+
+    let _guard = INDEX_LOCK
+        .lock()
+        .map_err(|e| anyhow!("Failed to lock store index: {}", e))?;
+    let mut index = read_index(&objects_dir)?;
+
+    index.versions.retain(|key, files| {
+        let version_dir = store.join(key);
+        if !version_dir.is_dir() {
+            report.stale_versions += 1;
+            return false;
+        }
+        files.retain(|rel, object| {
+            let object_meta = fs::metadata(object_path(&objects_dir, object));
+            let file_meta = fs::symlink_metadata(version_dir.join(rel));
+            matches!((object_meta, file_meta), (Ok(o), Ok(f)) if same_file(&o, &f))
+        });
+        !files.is_empty()
+    });
+
+    let referenced: BTreeSet<String> = index.referenced().into_iter().map(String::from).collect();
+    for entry in WalkDir::new(&objects_dir)
+        .min_depth(2)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy();
+        if referenced.contains(name.as_ref()) {
+            continue;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        if dry_run || fs::remove_file(entry.path()).is_ok() {
+            report.removed_objects += 1;
+            report.freed_bytes += size;
+        }
+    }
+
+    if !dry_run {
+        write_index(&objects_dir, &index)?;
+        if let Ok(entries) = fs::read_dir(&objects_dir) {
+            for entry in entries.flatten() {
+                if entry.path().is_dir() {
+                    let _ = fs::remove_dir(entry.path());
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Re-hashes every object and checks that the indexed files still share it.
+/// With `repair`, corrupt objects are taken out of the store so reinstalling
+/// the packages that use them writes fresh copies.
+pub fn verify(scope: Scope, repair: bool) -> Result<VerifyReport> {
+    let objects_dir = get_objects_dir(scope)?;
+    let mut report = VerifyReport::default();
+    if !objects_dir.exists() {
+        return Ok(report);
+    }
+    let store = local::get_store_base_dir(scope)?;
+    let index = read_index(&objects_dir)?;
+
+    let mut users: BTreeMap<&str, Vec<PathBuf>> = BTreeMap::new();
+    for (key, files) in &index.versions {
+        for (rel, object) in files {
+            users
+                .entry(object.as_str())
+                .or_default()
+                .push(store.join(key).join(rel));
+        }
+    }
+
+    for entry in WalkDir::new(&objects_dir)
+        .min_depth(2)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".zoi-store-tmp") {
+            continue;
+        }
+        report.checked_objects += 1;
+        if hash_file(entry.path())? != object_hash(&name) {
+            // The files using it keep their (damaged) bytes through their own
+            // links; dropping the object stops reinstalls from reusing it.
+            if repair {
+                let _ = fs::remove_file(entry.path());
+            }
+            let files = users.get(name.as_str()).cloned().unwrap_or_default();
+            report.corrupt.push((name, files));
+        }
+    }
+
+    for (object, files) in &users {
+        let Ok(object_meta) = fs::metadata(object_path(&objects_dir, object)) else {
+            if !report.corrupt.iter().any(|(name, _)| name == object) {
+                report.missing.push(object.to_string());
+            }
+            continue;
+        };
+        for file in files {
+            if let Ok(file_meta) = fs::symlink_metadata(file)
+                && !same_file(&object_meta, &file_meta)
+            {
+                report.detached.push(file.clone());
+            }
+        }
+    }
+
+    Ok(report)
+}
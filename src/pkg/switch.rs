@@ -0,0 +1,146 @@
+use crate::pkg::{db, local, resolve, types};
+use anyhow::{Result, anyhow};
+use colored::*;
+use semver::{Version, VersionReq};
+
+/// Returns whether an installed version satisfies what the user asked for:
+/// either the exact version string or a requirement such as `20` or `^1.4`.
+pub fn version_matches(installed: &str, requested: &str) -> bool {
+    let requested = requested.trim_start_matches('v');
+    if installed == requested {
+        return true;
+    }
+    match (Version::parse(installed), VersionReq::parse(requested)) {
+        (Ok(version), Ok(req)) => req.matches(&version),
+        _ => false,
+    }
+}
+
+/// Makes the version described by `manifest` the default for its package:
+/// the `latest` link is retargeted, the bin shims are refreshed and the
+/// local database records it as the installed version.
+pub fn set_default(manifest: &types::InstallManifest, yes: bool) -> Result<()> {
+    local::set_default_version(
+        manifest.scope,
+        &manifest.registry_handle,
+        &manifest.repo,
+        &manifest.name,
+        &manifest.version,
+    )?;
+
+    if let Some(bins) = &manifest.bins {
+        let bin_root = crate::pkg::package::install::get_bin_root(manifest.scope)?;
+        std::fs::create_dir_all(&bin_root)?;
+        for bin in bins {
+            crate::pkg::shim::create_shim(&bin_root.join(bin))?;
+        }
+    }
+
+    let (pkg, _) = crate::pkg::uninstall::load_installed_package(manifest, yes)?;
+    if let Ok(conn) = db::open_connection("local") {
+        db::update_package(
+            &conn,
+            &pkg,
+            &manifest.registry_handle,
+            Some(manifest.scope),
+            manifest.sub_package.as_deref(),
+            Some(&manifest.reason),
+        )?;
+    }
+    Ok(())
+}
+
+/// Switches the default version of an installed package to the newest
+/// installed version matching `version`.
+pub fn run(
+    package_name: &str,
+    version: &str,
+    scope_override: Option<types::Scope>,
+    yes: bool,
+) -> Result<types::InstallManifest> {
+    let mut request = resolve::parse_source_string(package_name)?;
+    request.version_spec = None;
+
+    let scopes = if let Some(scope) = scope_override {
+        vec![scope]
+    } else {
+        vec![
+            types::Scope::Project,
+            types::Scope::User,
+            types::Scope::System,
+        ]
+    };
+
+    let mut candidates = Vec::new();
+    for scope in scopes {
+        candidates.extend(
+            local::get_all_version_manifests_in_scope(scope)?
+                .into_iter()
+                .filter(|m| {
+                    m.name == request.name
+                        && m.sub_package == request.sub_package
+                        && request
+                            .handle
+                            .as_ref()
+                            .is_none_or(|handle| m.registry_handle == *handle)
+                        && request.repo.as_ref().is_none_or(|repo| m.repo == *repo)
+                }),
+        );
+    }
+
+    if candidates.is_empty() {
+        return Err(anyhow!("Package '{}' is not installed.", package_name));
+    }
+
+    let first = &candidates[0];
+    if candidates.iter().any(|m| {
+        m.scope != first.scope || m.registry_handle != first.registry_handle || m.repo != first.repo
+    }) {
+        return Err(anyhow!(
+            "Package '{}' is installed from several sources. Use an explicit source like '#handle@repo/name[:sub]'.",
+            package_name
+        ));
+    }
+
+    let mut installed: Vec<String> = candidates.iter().map(|m| m.version.clone()).collect();
+    installed.sort_by(|a, b| local::compare_versions(b, a));
+
+    let Some(chosen) = candidates
+        .iter()
+        .filter(|m| version_matches(&m.version, version))
+        .max_by(|a, b| local::compare_versions(&a.version, &b.version))
+    else {
+        return Err(anyhow!(
+            "No installed version of '{}' matches '{}'. Installed versions: {}",
+            package_name,
+            version,
+            installed.join(", ")
+        ));
+    };
+
+    let current = local::get_default_version(
+        chosen.scope,
+        &chosen.registry_handle,
+        &chosen.repo,
+        &chosen.name,
+    )?;
+    if current.as_deref() == Some(chosen.version.as_str()) {
+        println!(
+            "'{}' already defaults to version {}.",
+            package_name.cyan(),
+            chosen.version.green()
+        );
+        return Ok(chosen.clone());
+    }
+
+    set_default(chosen, yes)?;
+    println!(
+        "Switched '{}' to version {}{}.",
+        package_name.cyan(),
+        chosen.version.green(),
+        current
+            .map(|v| format!(" (was {})", v.yellow()))
+            .unwrap_or_default()
+    );
+    Ok(chosen.clone())
+}
@@ -1,36 +1,431 @@
 use crate::{
-    pkg::{config, db, pgp, types},
+    pkg::{config, db, pgp, snapshot, types},
     utils,
 };
 use anyhow::{Result, anyhow};
 use colored::*;
 use git2::{
-    FetchOptions, RemoteCallbacks, Repository, ResetType,
+    Delta, FetchOptions, Oid, RemoteCallbacks, Repository, ResetType,
     build::{CheckoutBuilder, RepoBuilder},
 };
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use rayon::prelude::*;
+use rusqlite::Connection;
 use serde_yaml;
 use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tempfile::Builder;
 use walkdir::WalkDir;
 
-fn refresh_registry_db(
+const META_INDEXED_COMMIT: &str = "indexed_commit";
+const META_INDEX_SCHEMA: &str = "index_schema";
+const META_FILES_INDEXED: &str = "files_indexed";
+const META_SNAPSHOT_SERIAL: &str = "snapshot_serial";
+const META_INDEXED_MTIME: &str = "indexed_mtime";
+
+struct RegistryChanges {
+    updated: Vec<PathBuf>,
+    removed: Vec<String>,
+}
+
+fn is_index_file(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    name.ends_with(".pkg.lua") || name.ends_with(".sec.yaml")
+}
+
+pub fn registry_source_path(registry_path: &Path, path: &Path) -> Option<String> {
+    path.strip_prefix(registry_path)
+        .ok()
+        .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+}
+
+/// The commit currently checked out in a git registry.
+pub fn head_commit_id(registry_path: &Path) -> Option<String> {
+    let repo = Repository::open(registry_path).ok()?;
+    let head = repo.head().ok()?;
+    head.target().map(|oid| oid.to_string())
+}
+
+fn default_branch_ref(repo: &Repository) -> Option<String> {
+    let origin_head = repo.find_reference("refs/remotes/origin/HEAD").ok()?;
+    let target = origin_head.symbolic_target().ok()??;
+    let branch = target.strip_prefix("refs/remotes/origin/")?;
+    Some(format!("refs/heads/{}", branch))
+}
+
+fn branch_tip(repo: &Repository) -> Result<git2::Commit<'_>> {
+    if let Some(refname) = default_branch_ref(repo)
+        && let Ok(reference) = repo.find_reference(&refname)
+    {
+        return Ok(reference.peel_to_commit()?);
+    }
+    if let Ok(head) = repo.head()
+        && head.is_branch()
+    {
+        return Ok(head.peel_to_commit()?);
+    }
+    Ok(first_local_branch(repo)?.get().peel_to_commit()?)
+}
+
+fn first_local_branch(repo: &Repository) -> Result<git2::Branch<'_>> {
+    let (branch, _) = repo
+        .branches(Some(git2::BranchType::Local))?
+        .next()
+        .ok_or_else(|| anyhow!("Registry has no local branch."))??;
+    Ok(branch)
+}
+
+/// Resolves a commit SHA, tag, branch or `YYYY-MM-DD` date to a registry
+/// commit. A date selects the last commit on the default branch made on or
+/// before that day (UTC).
+pub fn resolve_registry_revision(registry_path: &Path, rev: &str) -> Result<Oid> {
+    let repo = Repository::open(registry_path)?;
+
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(rev, "%Y-%m-%d") {
+        let cutoff = date
+            .succ_opt()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|d| d.and_utc().timestamp())
+            .ok_or_else(|| anyhow!("Invalid date '{}'.", rev))?;
+        let mut walk = repo.revwalk()?;
+        walk.push(branch_tip(&repo)?.id())?;
+        walk.simplify_first_parent()?;
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            if commit.time().seconds() < cutoff {
+                return Ok(commit.id());
+            }
+        }
+        return Err(anyhow!("Registry has no commits on or before {}.", rev));
+    }
+
+    let object = repo
+        .revparse_single(rev)
+        .map_err(|_| anyhow!("Revision '{}' not found in registry.", rev))?;
+    Ok(object.peel_to_commit()?.id())
+}
+
+/// Checks out a registry at a historic revision with a detached HEAD. The next
+/// plain sync moves it back to the tip of its branch.
+pub fn checkout_registry_revision(registry_path: &Path, rev: &str) -> Result<String> {
+    let oid = resolve_registry_revision(registry_path, rev)?;
+    let repo = Repository::open(registry_path)?;
+    let object = repo.find_object(oid, None)?;
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+    repo.checkout_tree(&object, Some(&mut checkout))?;
+    repo.set_head_detached(oid)?;
+    Ok(oid.to_string())
+}
+
+/// Moves a registry left at a historic revision back onto its branch.
+pub fn reattach_registry_head(registry_path: &Path) -> Result<()> {
+    let repo = Repository::open(registry_path)?;
+    if !repo.head_detached()? {
+        return Ok(());
+    }
+    let tip = branch_tip(&repo)?;
+    let refname = match default_branch_ref(&repo) {
+        Some(r) if repo.find_reference(&r).is_ok() => r,
+        _ => first_local_branch(&repo)?.get().name()?.to_string(),
+    };
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+    repo.checkout_tree(tip.as_object(), Some(&mut checkout))?;
+    repo.set_head(&refname)?;
+    Ok(())
+}
+
+fn diff_registry_commits(
+    registry_path: &Path,
+    old_commit: &str,
+    new_commit: &str,
+) -> Result<Option<RegistryChanges>> {
+    let repo = Repository::open(registry_path)?;
+    let old_tree = repo.find_commit(Oid::from_str(old_commit)?)?.tree()?;
+    let new_tree = repo.find_commit(Oid::from_str(new_commit)?)?.tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+
+    let mut changes = RegistryChanges {
+        updated: Vec::new(),
+        removed: Vec::new(),
+    };
+
+    for delta in diff.deltas() {
+        let old_path = delta.old_file().path();
+        let new_path = delta.new_file().path();
+
+        if old_path == Some(Path::new("repo.yaml")) || new_path == Some(Path::new("repo.yaml")) {
+            // repo.yaml affects how every package is indexed.
+            return Ok(None);
+        }
+
+        if let Some(old) = old_path
+            && is_index_file(old)
+            && !matches!(delta.status(), Delta::Added | Delta::Copied)
+        {
+            changes
+                .removed
+                .push(old.to_string_lossy().replace('\\', "/"));
+        }
+
+        if let Some(new) = new_path
+            && is_index_file(new)
+            && delta.status() != Delta::Deleted
+        {
+            changes.updated.push(registry_path.join(new));
+        }
+    }
+
+    Ok(Some(changes))
+}
+
+fn incremental_changes(
+    conn: &Connection,
+    registry_path: &Path,
+    head: &str,
+    sync_files: bool,
+) -> Option<RegistryChanges> {
+    let schema = db::SCHEMA_VERSION.to_string();
+    if db::get_meta(conn, META_INDEX_SCHEMA).ok()?.as_deref() != Some(schema.as_str()) {
+        return None;
+    }
+    if sync_files && db::get_meta(conn, META_FILES_INDEXED).ok()?.as_deref() != Some("true") {
+        return None;
+    }
+    let previous = db::get_meta(conn, META_INDEXED_COMMIT).ok()??;
+    diff_registry_commits(registry_path, &previous, head)
+        .ok()
+        .flatten()
+}
+
+fn modified_secs(path: &Path) -> Option<u64> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
+
+fn local_changes(
+    conn: &Connection,
+    registry_path: &Path,
+    sync_files: bool,
+) -> Option<RegistryChanges> {
+    let schema = db::SCHEMA_VERSION.to_string();
+    if db::get_meta(conn, META_INDEX_SCHEMA).ok()?.as_deref() != Some(schema.as_str()) {
+        return None;
+    }
+    if sync_files && db::get_meta(conn, META_FILES_INDEXED).ok()?.as_deref() != Some("true") {
+        return None;
+    }
+    let since: u64 = db::get_meta(conn, META_INDEXED_MTIME).ok()??.parse().ok()?;
+    if modified_secs(&registry_path.join("repo.yaml")).is_none_or(|t| t >= since) {
+        return None;
+    }
+
+    let known: HashSet<String> = db::list_sources(conn).ok()?.into_iter().collect();
+    let (pkg_files, sec_files) = collect_index_files(registry_path);
+
+    let mut changes = RegistryChanges {
+        updated: Vec::new(),
+        removed: Vec::new(),
+    };
+    let mut present = HashSet::new();
+    for path in pkg_files.into_iter().chain(sec_files) {
+        let source = registry_source_path(registry_path, &path)?;
+        let is_known = known.contains(&source);
+        if !is_known || modified_secs(&path).is_none_or(|t| t >= since) {
+            if is_known {
+                changes.removed.push(source.clone());
+            }
+            changes.updated.push(path);
+        }
+        present.insert(source);
+    }
+    changes
+        .removed
+        .extend(known.into_iter().filter(|s| !present.contains(s)));
+
+    Some(changes)
+}
+
+/// Finds every package definition and advisory file in a registry tree.
+pub fn collect_index_files(registry_path: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut pkg_files = Vec::new();
+    let mut sec_files = Vec::new();
+    for entry in WalkDir::new(registry_path)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+        .filter_map(|e| e.ok())
+    {
+        if entry.file_type().is_file() {
+            let name = entry.file_name().to_string_lossy();
+            if name.ends_with(".pkg.lua") {
+                pkg_files.push(entry.path().to_path_buf());
+            } else if name.ends_with(".sec.yaml") {
+                sec_files.push(entry.path().to_path_buf());
+            }
+        }
+    }
+    (pkg_files, sec_files)
+}
+
+/// Parses a registry package file, deriving its repo from the file's location
+/// when the definition doesn't set one.
+pub fn parse_registry_package(registry_path: &Path, path: &Path) -> Option<types::Package> {
+    let path_str = path.to_string_lossy();
+    let mut pkg = crate::pkg::lua::parser::parse_lua_package(&path_str, None, true).ok()?;
+    if pkg.repo.is_empty()
+        && let Ok(rel_path) = path.strip_prefix(registry_path)
+        && let Some(parent) = rel_path.parent()
+    {
+        let mut repo_path = parent.to_string_lossy().to_string().replace('\\', "/");
+        let pkg_name_suffix = format!("/{}", pkg.name);
+        if repo_path.ends_with(&pkg_name_suffix) {
+            repo_path = repo_path[..repo_path.len() - pkg_name_suffix.len()].to_string();
+        } else if repo_path == pkg.name {
+            repo_path = String::new();
+        }
+        pkg.repo = repo_path;
+    }
+    Some(pkg)
+}
+
+pub fn parse_registry_advisory(
+    registry_path: &Path,
+    path: &Path,
+) -> Option<(types::Advisory, String)> {
+    let content = fs::read_to_string(path).ok()?;
+    let advisory = serde_yaml::from_str::<types::Advisory>(&content).ok()?;
+    let parent = path.strip_prefix(registry_path).ok()?.parent()?;
+    let repo_path = parent.to_string_lossy().to_string().replace('\\', "/");
+    Some((advisory, repo_path))
+}
+
+/// Opens a registry database, offering to rebuild it when it was written by a
+/// newer Zoi. Registry databases are pure indexes of the checkout, so
+/// discarding one loses nothing that the next indexing pass won't restore.
+fn open_registry_db(registry_handle: &str, m: Option<&MultiProgress>) -> Result<Connection> {
+    match db::open_connection(registry_handle) {
+        Ok(conn) => Ok(conn),
+        Err(e) if e.downcast_ref::<db::SchemaError>().is_some() => {
+            let prompt = format!(
+                "{}\nRebuild the '{}' database with this version of Zoi?",
+                e, registry_handle
+            );
+            let rebuild = match m {
+                Some(m_ref) => m_ref.suspend(|| utils::ask_for_confirmation(&prompt, false)),
+                None => utils::ask_for_confirmation(&prompt, false),
+            };
+            if !rebuild {
+                return Err(anyhow!(
+                    "{}. Refusing to modify it; upgrade Zoi or rebuild the database.",
+                    e
+                ));
+            }
+            db::remove_database(registry_handle)?;
+            db::open_connection(registry_handle)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Indexes a registry checkout into its metadata database.
+///
+/// When the database records the commit it was last indexed at, only the
+/// package and advisory files that changed between that commit and the
+/// current HEAD are re-parsed. A full rebuild is done otherwise.
+pub fn refresh_registry_db(
+    registry_handle: &str,
+    registry_path: &Path,
+    sync_files: bool,
+    m: Option<&MultiProgress>,
+    verbose: bool,
+    pb: Option<&ProgressBar>,
+) -> Result<()> {
+    let conn = open_registry_db(registry_handle, m)?;
+    let head = head_commit_id(registry_path);
+    let changes = head
+        .as_deref()
+        .and_then(|h| incremental_changes(&conn, registry_path, h, sync_files));
+    let marker: Vec<(&str, String)> = head
+        .map(|h| vec![(META_INDEXED_COMMIT, h)])
+        .unwrap_or_default();
+
+    write_registry_index(
+        conn,
+        registry_handle,
+        registry_path,
+        changes,
+        &marker,
+        sync_files,
+        m,
+        verbose,
+        pb,
+    )
+}
+
+/// Indexes a local directory registry in place. Files are re-parsed when they
+/// are new or their mtime is not older than the previous sync.
+pub fn refresh_local_registry_db(
     registry_handle: &str,
     registry_path: &Path,
     sync_files: bool,
     m: Option<&MultiProgress>,
     verbose: bool,
     pb: Option<&ProgressBar>,
+) -> Result<()> {
+    let conn = open_registry_db(registry_handle, m)?;
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let changes = local_changes(&conn, registry_path, sync_files);
+
+    write_registry_index(
+        conn,
+        registry_handle,
+        registry_path,
+        changes,
+        &[(META_INDEXED_MTIME, started.to_string())],
+        sync_files,
+        m,
+        verbose,
+        pb,
+    )
+}
+
+fn write_registry_index(
+    mut conn: Connection,
+    registry_handle: &str,
+    registry_path: &Path,
+    changes: Option<RegistryChanges>,
+    marker: &[(&str, String)],
+    sync_files: bool,
+    m: Option<&MultiProgress>,
+    verbose: bool,
+    pb: Option<&ProgressBar>,
 ) -> Result<()> {
     if verbose {
-        let msg = format!(
-            "Refreshing metadata database for {}...",
-            registry_handle.cyan()
-        );
+        let msg = match &changes {
+            Some(c) => format!(
+                "Updating metadata database for {} ({} changed, {} removed)...",
+                registry_handle.cyan(),
+                c.updated.len(),
+                c.removed.len()
+            ),
+            None => format!(
+                "Refreshing metadata database for {}...",
+                registry_handle.cyan()
+            ),
+        };
         if let Some(m_ref) = m {
             let _ = m_ref.println(&msg);
         } else {
@@ -38,23 +433,26 @@ fn refresh_registry_db(
         }
     }
 
-    let mut conn = db::open_connection(registry_handle)?;
-    db::clear_registry(&conn)?;
-
+    let full_rebuild = changes.is_none();
     let mut pkg_files = Vec::new();
     let mut sec_files = Vec::new();
-    for entry in WalkDir::new(registry_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        if entry.file_type().is_file() {
-            let name = entry.file_name().to_string_lossy();
+    let mut removed_sources = Vec::new();
+
+    if let Some(changes) = changes {
+        for path in changes.updated {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
             if name.ends_with(".pkg.lua") {
-                pkg_files.push(entry.path().to_path_buf());
+                pkg_files.push(path);
             } else if name.ends_with(".sec.yaml") {
-                sec_files.push(entry.path().to_path_buf());
+                sec_files.push(path);
             }
         }
+        removed_sources = changes.removed;
+    } else {
+        (pkg_files, sec_files) = collect_index_files(registry_path);
     }
 
     let repo_config = config::read_repo_config(registry_path).ok();
@@ -81,23 +479,7 @@ fn refresh_registry_db(
             if let Some(p) = pb {
                 p.inc(1);
             }
-            let path_str = path.to_string_lossy();
-            if let Ok(mut pkg) = crate::pkg::lua::parser::parse_lua_package(&path_str, None, true) {
-                if pkg.repo.is_empty()
-                    && let Ok(rel_path) = path.strip_prefix(registry_path)
-                    && let Some(parent) = rel_path.parent()
-                {
-                    let mut repo_path = parent.to_string_lossy().to_string().replace('\\', "/");
-                    let pkg_name_suffix = format!("/{}", pkg.name);
-                    if repo_path.ends_with(&pkg_name_suffix) {
-                        repo_path =
-                            repo_path[..repo_path.len() - pkg_name_suffix.len()].to_string();
-                    } else if repo_path == pkg.name {
-                        repo_path = String::new();
-                    }
-                    pkg.repo = repo_path;
-                }
-
+            if let Some(pkg) = parse_registry_package(registry_path, path) {
                 let mut file_list = None;
                 if sync_files
                     && let Some(c) = &client
@@ -135,29 +517,39 @@ fn refresh_registry_db(
         })
         .collect();
 
-    let parsed_advisories: Vec<(types::Advisory, String)> = sec_files
+    let parsed_advisories: Vec<(types::Advisory, String, PathBuf)> = sec_files
         .par_iter()
         .filter_map(|path| {
-            if let Ok(content) = fs::read_to_string(path)
-                && let Ok(advisory) = serde_yaml::from_str::<types::Advisory>(&content)
-                && let Ok(rel_path) = path.strip_prefix(registry_path)
-                && let Some(parent) = rel_path.parent()
-            {
-                let repo_path = parent.to_string_lossy().to_string().replace('\\', "/");
-                return Some((advisory, repo_path));
-            }
-            None
+            parse_registry_advisory(registry_path, path)
+                .map(|(advisory, repo_path)| (advisory, repo_path, path.clone()))
         })
         .collect();
 
     let tx = conn.transaction()?;
 
-    for (pkg, _path, file_list) in parsed_results {
+    if full_rebuild {
+        db::clear_registry(&tx)?;
+    } else {
+        for source in &removed_sources {
+            db::delete_source(&tx, source)?;
+        }
+    }
+
+    for (pkg, path, file_list) in parsed_results {
+        let source = registry_source_path(registry_path, &path);
         let pkg_id = db::update_package(&tx, &pkg, registry_handle, None, None, None)?;
+        if let Some(src) = &source {
+            db::set_package_source(&tx, pkg_id, src)?;
+        }
 
         if let Some(subs) = &pkg.sub_packages {
             for sub in subs {
-                let _ = db::update_package(&tx, &pkg, registry_handle, None, Some(sub), None);
+                if let Ok(sub_id) =
+                    db::update_package(&tx, &pkg, registry_handle, None, Some(sub), None)
+                    && let Some(src) = &source
+                {
+                    let _ = db::set_package_source(&tx, sub_id, src);
+                }
             }
         }
 
@@ -166,9 +558,23 @@ fn refresh_registry_db(
         }
     }
 
-    for (advisory, repo) in parsed_advisories {
-        let _ = db::update_advisory(&tx, &advisory, &repo, registry_handle);
+    for (advisory, repo, path) in parsed_advisories {
+        if db::update_advisory(&tx, &advisory, &repo, registry_handle).is_ok()
+            && let Some(src) = registry_source_path(registry_path, &path)
+        {
+            let _ = db::set_advisory_source(&tx, &advisory.id, &src);
+        }
+    }
+
+    for (key, value) in marker {
+        db::set_meta(&tx, key, value)?;
     }
+    db::set_meta(&tx, META_INDEX_SCHEMA, &db::SCHEMA_VERSION.to_string())?;
+    db::set_meta(
+        &tx,
+        META_FILES_INDEXED,
+        if sync_files { "true" } else { "false" },
+    )?;
 
     tx.commit()?;
 
@@ -179,6 +585,74 @@ fn refresh_registry_db(
     Ok(())
 }
 
+/// Indexes a registry installed from a snapshot. The snapshot's prebuilt
+/// index is loaded directly; registries published without one, or syncs that
+/// also need file lists, fall back to parsing the tree.
+pub fn index_snapshot_registry(
+    registry_handle: &str,
+    registry_path: &Path,
+    sync_files: bool,
+    m: Option<&MultiProgress>,
+    verbose: bool,
+    pb: Option<&ProgressBar>,
+) -> Result<()> {
+    let index = snapshot::read_index(registry_path)?;
+    let serial = index.serial.to_string();
+    let schema = db::SCHEMA_VERSION.to_string();
+
+    let mut conn = open_registry_db(registry_handle, m)?;
+    if db::get_meta(&conn, META_SNAPSHOT_SERIAL)?.as_deref() == Some(serial.as_str())
+        && db::get_meta(&conn, META_INDEX_SCHEMA)?.as_deref() == Some(schema.as_str())
+        && (!sync_files || db::get_meta(&conn, META_FILES_INDEXED)?.as_deref() == Some("true"))
+    {
+        return Ok(());
+    }
+
+    if index.packages.is_empty() || sync_files {
+        drop(conn);
+        refresh_registry_db(registry_handle, registry_path, sync_files, m, verbose, pb)?;
+        let conn = db::open_connection(registry_handle)?;
+        db::set_meta(&conn, META_SNAPSHOT_SERIAL, &serial)?;
+        return Ok(());
+    }
+
+    if verbose {
+        println!(
+            "Loading prebuilt index for {} (snapshot {})...",
+            registry_handle.cyan(),
+            serial
+        );
+    }
+
+    let tx = conn.transaction()?;
+    db::clear_registry(&tx)?;
+    for entry in &index.packages {
+        let pkg = &entry.package;
+        let pkg_id = db::update_package(&tx, pkg, registry_handle, None, None, None)?;
+        db::set_package_source(&tx, pkg_id, &entry.source)?;
+        if let Some(subs) = &pkg.sub_packages {
+            for sub in subs {
+                if let Ok(sub_id) =
+                    db::update_package(&tx, pkg, registry_handle, None, Some(sub), None)
+                {
+                    let _ = db::set_package_source(&tx, sub_id, &entry.source);
+                }
+            }
+        }
+    }
+    for entry in &index.advisories {
+        if db::update_advisory(&tx, &entry.advisory, &entry.repo, registry_handle).is_ok() {
+            let _ = db::set_advisory_source(&tx, &entry.advisory.id, &entry.source);
+        }
+    }
+    db::set_meta(&tx, META_INDEX_SCHEMA, &schema)?;
+    db::set_meta(&tx, META_FILES_INDEXED, "false")?;
+    db::set_meta(&tx, META_SNAPSHOT_SERIAL, &serial)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
 fn verify_registry_signature(
     repo_path: &Path,
     authorities: &[String],
@@ -208,20 +682,7 @@ fn verify_registry_signature(
         .extract_signature(&commit.id(), None)
         .map_err(|_| anyhow!("Registry commit is not signed. Sync aborted for security."))?;
 
-    let sig_bytes = &*sig;
-    let data_bytes = &*data;
-
-    let trusted_certs = pgp::get_certs_by_name_or_fingerprint(authorities)?;
-
-    let mut verified = false;
-    for cert in trusted_certs {
-        if pgp::verify_detached_signature_raw(data_bytes, sig_bytes, &cert).is_ok() {
-            verified = true;
-            break;
-        }
-    }
-
-    if verified {
+    if verify_with_authorities(&data, &sig, authorities)? {
         if verbose {
             println!("{}", "Registry signature verified successfully.".green());
         }
@@ -233,6 +694,14 @@ fn verify_registry_signature(
     }
 }
 
+/// Checks a detached signature against the registry's trusted authorities.
+pub fn verify_with_authorities(data: &[u8], sig: &[u8], authorities: &[String]) -> Result<bool> {
+    let trusted_certs = pgp::get_certs_by_name_or_fingerprint(authorities)?;
+    Ok(trusted_certs
+        .iter()
+        .any(|cert| pgp::verify_detached_signature_raw(data, sig, cert).is_ok()))
+}
+
 fn get_db_path() -> Result<PathBuf> {
     let home_dir = home::home_dir().ok_or_else(|| anyhow!("Could not find home directory."))?;
     Ok(crate::pkg::sysroot::apply_sysroot(
@@ -705,14 +1174,192 @@ fn fetch_handle_for_url(url: &str, verbose: bool) -> Result<String> {
     }
 }
 
+/// Returns the directory behind a `file://` registry URL.
+pub fn local_registry_path(url: &str) -> Option<PathBuf> {
+    url.strip_prefix("file://")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+}
+
+fn sync_local_registry(
+    reg: &mut types::Registry,
+    source_dir: &Path,
+    db_root: &Path,
+    verbose: bool,
+    sync_files: bool,
+    m: Option<&MultiProgress>,
+    pb: Option<&ProgressBar>,
+) -> Result<bool> {
+    if !source_dir.is_dir() {
+        return Err(anyhow!(
+            "Local registry directory '{}' does not exist.",
+            source_dir.display()
+        ));
+    }
+    let repo_config = config::read_repo_config(source_dir)?;
+
+    let mut reg_changed = false;
+    if reg.handle.is_empty() {
+        reg.handle = repo_config.name.clone();
+        reg_changed = true;
+    }
+    if repo_config.advisory_prefix != reg.advisory_prefix {
+        reg.advisory_prefix = repo_config.advisory_prefix;
+        reg_changed = true;
+    }
+
+    let target_dir = db_root.join(&reg.handle);
+    let linked = fs::read_link(&target_dir).is_ok_and(|t| t == source_dir);
+    if !linked {
+        if target_dir.exists() || target_dir.is_symlink() {
+            return Err(anyhow!(
+                "Cannot link local registry '{}': '{}' is already used by another registry.",
+                source_dir.display(),
+                target_dir.display()
+            ));
+        }
+        fs::create_dir_all(db_root)?;
+        utils::symlink_dir(source_dir, &target_dir)?;
+    }
+
+    if let Some(p) = pb {
+        p.set_message(format!("Indexing {}", source_dir.display()));
+    } else if verbose {
+        println!("Indexing local registry {}...", source_dir.display());
+    }
+
+    sync_pgp_keys_at_path(&target_dir, verbose, pb)?;
+    refresh_local_registry_db(&reg.handle, &target_dir, sync_files, m, verbose, pb)?;
+
+    if let Some(p) = pb {
+        p.finish_with_message(format!("Synced {}", reg.handle.cyan()));
+    }
+
+    Ok(reg_changed)
+}
+
+fn sync_snapshot_registry(
+    reg: &mut types::Registry,
+    db_root: &Path,
+    verbose: bool,
+    sync_files: bool,
+    m: Option<&MultiProgress>,
+    pb: Option<&ProgressBar>,
+) -> Result<bool> {
+    let report = |msg: String| -> Result<()> {
+        if let Some(p) = pb {
+            p.println(&msg);
+        } else if let Some(m_ref) = m {
+            m_ref.println(&msg)?;
+        } else {
+            eprintln!("{}", msg);
+        }
+        Ok(())
+    };
+
+    let existing = !reg.handle.is_empty() && db_root.join(&reg.handle).exists();
+    if crate::pkg::offline::is_offline() {
+        if existing {
+            report(format!(
+                "Zoi is offline. Skipping update for existing registry {}",
+                reg.handle
+            ))?;
+            return Ok(false);
+        }
+        return Err(anyhow!(
+            "Cannot sync registry '{}': Zoi is offline and no snapshot is installed.",
+            reg.url
+        ));
+    }
+
+    if let Some(p) = pb {
+        p.set_message(format!("Downloading {}", reg.url.cyan()));
+    } else if verbose {
+        println!("Downloading registry snapshot from {}...", reg.url.cyan());
+    }
+
+    let downloaded = match snapshot::download_snapshot(&reg.url, pb) {
+        Ok(d) => d,
+        Err(e) => {
+            report(format!("Sync with {} failed: {}", reg.url.yellow(), e))?;
+            return Ok(false);
+        }
+    };
+
+    let authorities = reg.authorities.clone().unwrap_or_default();
+    let installed = match snapshot::install_snapshot(
+        &downloaded.archive,
+        downloaded.signature.as_deref(),
+        &authorities,
+        db_root,
+        Some(&reg.handle),
+    ) {
+        Ok(i) => i,
+        Err(e) => {
+            report(format!(
+                "Security: Registry snapshot check failed for {}: {}",
+                reg.url.red(),
+                e
+            ))?;
+            return Err(e);
+        }
+    };
+
+    let mut reg_changed = false;
+    if reg.handle != installed.handle {
+        reg.handle = installed.handle.clone();
+        reg_changed = true;
+    }
+    if verbose {
+        if installed.updated {
+            println!("Installed registry snapshot {}.", installed.serial);
+        } else {
+            println!("Registry snapshot {} is up to date.", installed.serial);
+        }
+    }
+
+    let target_dir = db_root.join(&reg.handle);
+    sync_pgp_keys_at_path(&target_dir, verbose, pb)?;
+    index_snapshot_registry(&reg.handle, &target_dir, sync_files, m, verbose, pb)?;
+
+    if let Ok(repo_config) = config::read_repo_config(&target_dir)
+        && repo_config.advisory_prefix != reg.advisory_prefix
+    {
+        reg.advisory_prefix = repo_config.advisory_prefix;
+        reg_changed = true;
+    }
+
+    if let Some(p) = pb {
+        p.finish_with_message(format!("Synced {}", reg.handle.cyan()));
+    }
+
+    Ok(reg_changed)
+}
+
+struct SyncedRegistry {
+    reg: types::Registry,
+    changed: bool,
+    pinned: Option<String>,
+}
+
 fn sync_registry(
     mut reg: types::Registry,
     db_root: &Path,
     verbose: bool,
     sync_files: bool,
+    at: Option<&str>,
     m: Option<&MultiProgress>,
-) -> Result<(types::Registry, bool)> {
+) -> Result<SyncedRegistry> {
     let mut reg_changed = false;
+    let mut pinned = None;
+    let report = |msg: String| -> Result<()> {
+        if let Some(m_ref) = m {
+            m_ref.println(&msg)?;
+        } else {
+            eprintln!("{}", msg);
+        }
+        Ok(())
+    };
 
     let pb = if !verbose && let Some(m_ref) = m {
         let p = m_ref.add(ProgressBar::new(0));
@@ -729,6 +1376,43 @@ fn sync_registry(
         None
     };
 
+    if at.is_some()
+        && (local_registry_path(&reg.url).is_some() || snapshot::is_snapshot_url(&reg.url))
+    {
+        report(format!(
+            "{} Registry {} is not a git repository and cannot be synced to a past revision.",
+            "Warning:".yellow(),
+            reg.url.cyan()
+        ))?;
+    }
+
+    if let Some(source_dir) = local_registry_path(&reg.url) {
+        let changed = sync_local_registry(
+            &mut reg,
+            &source_dir,
+            db_root,
+            verbose,
+            sync_files,
+            m,
+            pb.as_ref(),
+        )?;
+        return Ok(SyncedRegistry {
+            reg,
+            changed,
+            pinned,
+        });
+    }
+
+    if snapshot::is_snapshot_url(&reg.url) {
+        let changed =
+            sync_snapshot_registry(&mut reg, db_root, verbose, sync_files, m, pb.as_ref())?;
+        return Ok(SyncedRegistry {
+            reg,
+            changed,
+            pinned,
+        });
+    }
+
     if reg.handle.is_empty() {
         if let Some(p) = &pb {
             p.set_message(format!("Fetching handle for {}", reg.url.cyan()));
@@ -748,6 +1432,13 @@ fn sync_registry(
         Err(_) => None,
     };
 
+    if target_dir.exists()
+        && let Err(e) = reattach_registry_head(&target_dir)
+        && verbose
+    {
+        println!("Could not move registry back onto its branch: {}", e);
+    }
+
     if let Err(e) = try_sync_at_path(&reg.url, &target_dir, verbose, m, pb.as_ref()) {
         let msg = format!("Sync with {} failed: {}", reg.url.yellow(), e);
         if let Some(p) = &pb {
@@ -800,10 +1491,38 @@ fn sync_registry(
             return Err(e);
         }
 
+        if let Some(rev) = at {
+            match checkout_registry_revision(&target_dir, rev) {
+                Ok(commit) => {
+                    if let Some(authorities) = &reg.authorities
+                        && let Err(e) = verify_registry_signature(&target_dir, authorities, verbose)
+                    {
+                        let _ = reattach_registry_head(&target_dir);
+                        report(format!(
+                            "Security: Registry signature check failed for {} at {}: {}",
+                            reg.url.red(),
+                            rev,
+                            e
+                        ))?;
+                        return Err(e);
+                    }
+                    pinned = Some(commit);
+                }
+                Err(e) => report(format!(
+                    "{} Could not sync {} to '{}': {}",
+                    "Warning:".yellow(),
+                    reg.handle.cyan(),
+                    rev,
+                    e
+                ))?,
+            }
+        }
+
         sync_pgp_keys_at_path(&target_dir, verbose, pb.as_ref())?;
 
         let mut db_downloaded = false;
-        if let Ok(repo_config) = config::read_repo_config(&target_dir)
+        if pinned.is_none()
+            && let Ok(repo_config) = config::read_repo_config(&target_dir)
             && let Some(db_url_template) = &repo_config.db
         {
             let platform = utils::get_platform().unwrap_or_default();
@@ -834,7 +1553,17 @@ fn sync_registry(
                 )
                 .is_ok()
                 {
-                    if fs::rename(&temp_db_path, &db_path).is_ok() {
+                    let too_new = db::probe_schema_version(&temp_db_path)
+                        .map(|v| v > db::SCHEMA_VERSION)
+                        .unwrap_or(true);
+                    if too_new {
+                        let _ = fs::remove_file(&temp_db_path);
+                        if verbose {
+                            println!(
+                                "Pre-indexed DB is unreadable or uses a newer schema, indexing locally instead."
+                            );
+                        }
+                    } else if fs::rename(&temp_db_path, &db_path).is_ok() {
                         db_downloaded = true;
                         if verbose {
                             println!("Successfully downloaded pre-indexed DB.");
@@ -865,14 +1594,142 @@ fn sync_registry(
         }
 
         if let Some(p) = pb {
-            p.finish_with_message(format!("Synced {}", reg.handle.cyan()));
+            match &pinned {
+                Some(commit) => p.finish_with_message(format!(
+                    "Synced {} at {}",
+                    reg.handle.cyan(),
+                    &commit[..commit.len().min(12)]
+                )),
+                None => p.finish_with_message(format!("Synced {}", reg.handle.cyan())),
+            }
+        }
+    }
+
+    Ok(SyncedRegistry {
+        reg,
+        changed: reg_changed,
+        pinned,
+    })
+}
+
+/// Registries a project install moved to their zoi.lock revisions. Dropping
+/// it puts each one back where it was, so other projects and user-scope
+/// installs keep resolving against the synced registry, also when the
+/// install fails.
+#[must_use]
+pub struct LockedRevisions {
+    /// Handle, checkout path and the commit HEAD was detached at before, if
+    /// it was detached at all.
+    registries: Vec<(String, PathBuf, Option<String>)>,
+}
+
+impl Drop for LockedRevisions {
+    fn drop(&mut self) {
+        if self.registries.is_empty() {
+            return;
+        }
+        let db_root = get_db_path().ok();
+        let protect_db = config::read_config().is_ok_and(|c| c.protect_db);
+        if protect_db && let Some(db_root) = &db_root {
+            let _ = utils::set_path_writable(db_root);
+        }
+        for (handle, path, previous) in self.registries.drain(..).rev() {
+            let restored = match &previous {
+                Some(commit) => checkout_registry_revision(&path, commit).map(|_| ()),
+                None => reattach_registry_head(&path),
+            }
+            .and_then(|_| refresh_registry_db(&handle, &path, false, None, false, None));
+            if let Err(e) = restored {
+                eprintln!(
+                    "{} Could not restore registry '{}' after using its locked revision: {}. Run 'zoi sync' to fix it.",
+                    "Warning:".yellow(),
+                    handle,
+                    e
+                );
+            }
+        }
+        if protect_db && let Some(db_root) = &db_root {
+            let _ = utils::set_path_read_only(db_root);
+        }
+    }
+}
+
+/// Checks out every registry at the revision recorded in `lock` for the
+/// duration of a project install. Keep the returned guard alive until the
+/// install is done.
+pub fn checkout_locked_revisions(lock: &types::ZoiLock, verbose: bool) -> Result<LockedRevisions> {
+    let mut locked = LockedRevisions {
+        registries: Vec::new(),
+    };
+    if lock.registry_revisions.is_empty() {
+        return Ok(locked);
+    }
+    let db_root = get_db_path()?;
+    let protect_db = config::read_config()?.protect_db;
+    if protect_db && let Err(e) = utils::set_path_writable(&db_root) {
+        eprintln!("Warning: could not make db writable: {}", e);
+    }
+
+    let mut handles: Vec<_> = lock.registry_revisions.iter().collect();
+    handles.sort();
+    for (handle, commit) in handles {
+        let path = db_root.join(handle);
+        if path.is_symlink() || Repository::open(&path).is_err() {
+            eprintln!(
+                "{} Registry '{}' is locked at {} but is not a git registry here. Using its current state.",
+                "Warning:".yellow(),
+                handle,
+                commit
+            );
+            continue;
         }
+        if head_commit_id(&path).as_deref() == Some(commit.as_str()) {
+            continue;
+        }
+
+        let previous = if Repository::open(&path)?.head_detached()? {
+            head_commit_id(&path)
+        } else {
+            None
+        };
+        locked
+            .registries
+            .push((handle.clone(), path.clone(), previous));
+
+        if resolve_registry_revision(&path, commit).is_err() {
+            let url = lock.registries.get(handle).ok_or_else(|| {
+                anyhow!(
+                    "Registry '{}' is locked at {}, which is not available locally, and zoi.lock has no URL to fetch it from.",
+                    handle,
+                    commit
+                )
+            })?;
+            reattach_registry_head(&path)?;
+            try_sync_at_path(url, &path, verbose, None, None)?;
+        }
+        checkout_registry_revision(&path, commit)?;
+        println!(
+            "{} Using registry {} at locked revision {}",
+            "::".bold().blue(),
+            handle.cyan(),
+            &commit[..commit.len().min(12)]
+        );
+        refresh_registry_db(handle, &path, false, None, verbose, None)?;
     }
 
-    Ok((reg, reg_changed))
+    if protect_db && let Err(e) = utils::set_path_read_only(&db_root) {
+        eprintln!("Warning: could not make db read-only: {}", e);
+    }
+    Ok(locked)
 }
 
-pub fn run(verbose: bool, _fallback: bool, no_pm: bool, sync_files: bool) -> Result<()> {
+pub fn run(
+    verbose: bool,
+    _fallback: bool,
+    no_pm: bool,
+    sync_files: bool,
+    at: Option<&str>,
+) -> Result<()> {
     let merged_config = config::read_config()?;
     if merged_config.protect_db {
         let db_root = get_db_path()?;
@@ -907,6 +1764,7 @@ pub fn run(verbose: bool, _fallback: bool, no_pm: bool, sync_files: bool) -> Res
         registries_to_sync.push((reg.clone(), false));
     }
 
+    let mut any_pinned = false;
     if !registries_to_sync.is_empty() {
         println!("{} Syncing registries...", "::".bold().blue());
         let m = if verbose {
@@ -915,25 +1773,32 @@ pub fn run(verbose: bool, _fallback: bool, no_pm: bool, sync_files: bool) -> Res
             Some(MultiProgress::new())
         };
 
-        let results: Vec<Result<(types::Registry, bool, bool)>> = registries_to_sync
+        let results: Vec<Result<(SyncedRegistry, bool)>> = registries_to_sync
             .into_par_iter()
             .map(|(reg, is_default)| {
-                let (synced_reg, changed) =
-                    sync_registry(reg, &db_root, verbose, sync_files, m.as_ref())?;
-                Ok((synced_reg, changed, is_default))
+                let synced = sync_registry(reg, &db_root, verbose, sync_files, at, m.as_ref())?;
+                Ok((synced, is_default))
             })
             .collect();
 
         let mut updated_added_registries = Vec::new();
         for res in results {
-            let (reg, changed, is_default) = res?;
-            if changed {
+            let (synced, is_default) = res?;
+            if synced.changed {
                 needs_config_update = true;
             }
+            if let Some(commit) = &synced.pinned {
+                any_pinned = true;
+                println!(
+                    "Registry {} is now at {}.",
+                    synced.reg.handle.cyan(),
+                    commit.yellow()
+                );
+            }
             if is_default {
-                config.default_registry = Some(reg);
+                config.default_registry = Some(synced.reg);
             } else {
-                updated_added_registries.push(reg);
+                updated_added_registries.push(synced.reg);
             }
         }
         config.added_registries = updated_added_registries;
@@ -969,5 +1834,11 @@ pub fn run(verbose: bool, _fallback: bool, no_pm: bool, sync_files: bool) -> Res
         }
     }
 
+    if let Some(rev) = at
+        && !any_pinned
+    {
+        return Err(anyhow!("No registry could be synced to '{}'.", rev));
+    }
+
     Ok(())
 }
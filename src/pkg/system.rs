@@ -16,7 +16,20 @@ fn get_machine_key() -> Result<Vec<u8>> {
         .parent()
         .ok_or_else(|| anyhow!("Invalid system config path"))?
         .join("machine_key");
+    load_or_create_key(&key_path, true)
+}
+
+/// Key for project secrets. Unlike the machine key it belongs to the
+/// current user, so no administrator privileges are needed.
+fn get_user_key() -> Result<Vec<u8>> {
+    let home_dir = home::home_dir().ok_or_else(|| anyhow!("Could not find home directory."))?;
+    let key_path = crate::pkg::sysroot::apply_sysroot(home_dir.join(".zoi").join("secret_key"));
+    load_or_create_key(&key_path, false)
+}
 
+/// Reads the 32-byte key at `key_path`, creating it readable only by its
+/// owner, or only by administrators when `admin_only` is set.
+fn load_or_create_key(key_path: &Path, admin_only: bool) -> Result<Vec<u8>> {
     if !key_path.exists() {
         if let Some(p) = key_path.parent() {
             fs::create_dir_all(p)?;
@@ -32,66 +45,117 @@ fn get_machine_key() -> Result<Vec<u8>> {
                 .create(true)
                 .truncate(true)
                 .mode(0o600)
-                .open(&key_path)?;
+                .open(key_path)?;
             file.write_all(&key)?;
         }
         #[cfg(windows)]
         {
-            fs::write(&key_path, &key)?;
-            let _ = Command::new("icacls")
-                .arg(&key_path)
-                .arg("/inheritance:r")
-                .arg("/grant:r")
-                .arg("*S-1-5-18:(F)")
-                .arg("/grant:r")
-                .arg("*S-1-5-32-544:(F)")
-                .status();
+            fs::write(key_path, &key)?;
+            if admin_only {
+                let _ = Command::new("icacls")
+                    .arg(key_path)
+                    .arg("/inheritance:r")
+                    .arg("/grant:r")
+                    .arg("*S-1-5-18:(F)")
+                    .arg("/grant:r")
+                    .arg("*S-1-5-32-544:(F)")
+                    .status();
+            }
         }
         #[cfg(not(any(unix, windows)))]
         {
-            fs::write(&key_path, &key)?;
+            fs::write(key_path, &key)?;
         }
     }
-    let key = fs::read(&key_path)?;
+    #[cfg(not(windows))]
+    let _ = admin_only;
+    let key = fs::read(key_path)?;
     if key.len() != 32 {
-        return Err(anyhow!("Invalid machine key length"));
+        return Err(anyhow!("Invalid key length in {}", key_path.display()));
     }
     Ok(key)
 }
 
-pub fn encrypt_password(phrase: &str) -> Result<String> {
-    if !utils::is_admin() {
-        return Err(anyhow!(
-            "Administrator privileges required to access the machine key."
-        ));
-    }
-    let key = get_machine_key()?;
+fn encrypt_with_key(key: &[u8], phrase: &str) -> Result<String> {
     let mut iv = vec![0; 16];
     rand_bytes(&mut iv).map_err(|e| anyhow!("Failed to generate IV: {}", e))?;
 
     let cipher = Cipher::aes_256_cbc();
-    let ciphertext = encrypt(cipher, &key, Some(&iv), phrase.as_bytes())
+    let ciphertext = encrypt(cipher, key, Some(&iv), phrase.as_bytes())
         .map_err(|e| anyhow!("Encryption failed: {}", e))?;
 
     Ok(format!("{}:{}", hex::encode(iv), hex::encode(ciphertext)))
 }
 
-pub fn decrypt_password(encrypted: &str) -> Result<String> {
-    let parts: Vec<&str> = encrypted.split(':').collect();
+fn decrypt_with_key(key: &[u8], encrypted: &str) -> Result<String> {
+    let parts: Vec<&str> = encrypted.trim().split(':').collect();
     if parts.len() != 2 {
-        return Err(anyhow!("Invalid encrypted password format"));
+        return Err(anyhow!("Invalid encrypted value format"));
     }
     let iv = hex::decode(parts[0])?;
     let ciphertext = hex::decode(parts[1])?;
-    let key = get_machine_key()?;
 
     let cipher = Cipher::aes_256_cbc();
-    let decrypted = decrypt(cipher, &key, Some(&iv), &ciphertext)
+    let decrypted = decrypt(cipher, key, Some(&iv), &ciphertext)
         .map_err(|e| anyhow!("Decryption failed: {}", e))?;
 
     Ok(String::from_utf8(decrypted)?)
 }
 
+pub fn encrypt_password(phrase: &str) -> Result<String> {
+    if !utils::is_admin() {
+        return Err(anyhow!(
+            "Administrator privileges required to access the machine key."
+        ));
+    }
+    encrypt_with_key(&get_machine_key()?, phrase)
+}
+
+pub fn decrypt_password(encrypted: &str) -> Result<String> {
+    decrypt_with_key(&get_machine_key()?, encrypted)
+}
+
+/// Encrypts `phrase` with the current user's key, for secrets in project
+/// env files.
+pub fn encrypt_secret(phrase: &str) -> Result<String> {
+    encrypt_with_key(&get_user_key()?, phrase)
+}
+
+pub fn decrypt_secret(encrypted: &str) -> Result<String> {
+    decrypt_with_key(&get_user_key()?, encrypted)
+}
+
+/// Implements `zoi system encrypt`.
+pub fn run_encrypt(phrase: Option<&str>, user: bool, file: Option<&Path>) -> Result<()> {
+    if let Some(file) = file {
+        let content = fs::read_to_string(file)?;
+        let mut out = file.as_os_str().to_owned();
+        out.push(".enc");
+        let out = PathBuf::from(out);
+        fs::write(&out, encrypt_secret(&content)?)?;
+        println!(
+            "Encrypted {} to {}",
+            file.display(),
+            out.display().to_string().cyan()
+        );
+        return Ok(());
+    }
+
+    let phrase = phrase.ok_or_else(|| anyhow!("Nothing to encrypt."))?;
+    if user {
+        println!(
+            "Encrypted value (safe for zoi.yaml env files):\nenc:{}",
+            encrypt_secret(phrase)?
+        );
+    } else {
+        println!(
+            "Encrypted value (safe for zoi.lua):\n{}",
+            encrypt_password(phrase)?
+        );
+    }
+    Ok(())
+}
+
 pub fn get_system_config_lua_path() -> PathBuf {
     if cfg!(target_os = "windows") {
         PathBuf::from(r"C:\ProgramData\zoi\zoi.lua")
@@ -667,6 +731,7 @@ fn apply_packages(
             3,
             false,
             false,
+            false,
         )?;
     } else {
         println!("All packages are already installed.");
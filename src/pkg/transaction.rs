@@ -1,4 +1,4 @@
-use crate::pkg::{audit, install, local, types, uninstall};
+use crate::pkg::{audit, install, journal, local, types, uninstall};
 use anyhow::{Result, anyhow};
 use chrono::Utc;
 use colored::*;
@@ -14,7 +14,7 @@ pub struct TransactionMetadata {
     pub operation_count: usize,
 }
 
-fn get_transactions_dir() -> Result<PathBuf> {
+pub fn get_transactions_dir() -> Result<PathBuf> {
     let home_dir = home::home_dir().ok_or_else(|| anyhow!("Could not find home directory."))?;
     let dir = home_dir.join(".zoi").join("transactions");
     fs::create_dir_all(&dir)?;
@@ -39,6 +39,7 @@ pub fn begin() -> Result<types::Transaction> {
     let path = get_transaction_path(&transaction.id)?;
     let content = serde_json::to_string_pretty(&transaction)?;
     fs::write(path, content)?;
+    journal::activate(&transaction.id);
     Ok(transaction)
 }
 
@@ -121,6 +122,7 @@ pub fn get_modified_files(transaction_id: &str) -> Result<Vec<String>> {
 }
 
 pub fn delete_log(transaction_id: &str) -> Result<()> {
+    journal::discard(transaction_id)?;
     let path = get_transaction_path(transaction_id)?;
     if path.exists() {
         fs::remove_file(path)?;
@@ -181,6 +183,7 @@ pub fn rollback(transaction_id: &str) -> Result<()> {
     }
     let content = fs::read_to_string(&path)?;
     let transaction: types::Transaction = serde_json::from_str(&content)?;
+    journal::deactivate();
 
     println!("\n{} Starting Rollback...", "::".bold().blue());
 
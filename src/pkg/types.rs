@@ -51,7 +51,7 @@ pub struct AdvisoryRegistry {
     pub advisories: HashMap<String, String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Scope {
     #[default]
@@ -178,6 +178,8 @@ pub struct Package {
     pub service: Option<Service>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub backup: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<HashMap<String, PackageFeature>>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub installed_size: Option<u64>,
@@ -185,6 +187,26 @@ pub struct Package {
     pub archive_size: Option<u64>,
 }
 
+/// A named build/runtime flag a package can be installed with, selected as
+/// `name[+feature,-feature]`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct PackageFeature {
+    #[serde(default)]
+    pub description: String,
+    /// Enabled unless turned off with `-feature` or `-default`.
+    #[serde(default)]
+    pub default: bool,
+    /// Runtime dependencies pulled in when the feature is enabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Build dependencies pulled in when the feature is enabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build_dependencies: Vec<String>,
+    /// Other features this one turns on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(untagged)]
 pub enum PlatformOrStringVec {
@@ -340,6 +362,24 @@ pub struct InstallManifest {
     pub installed_files: Vec<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub installed_size: Option<u64>,
+    /// Virtual packages this package was chosen to provide.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provider_for: Vec<String>,
+    /// Features the package was installed with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Content and permissions of `installed_files` as they were installed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_records: Vec<FileRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FileRecord {
+    pub path: String,
+    pub sha256: String,
+    /// Permission bits, `0` where the platform has none.
+    pub mode: u32,
+    pub size: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -506,12 +546,41 @@ pub struct Config {
     pub max_resolution_depth: Option<u8>,
     #[serde(default)]
     pub offline_mode: bool,
+    /// Run package hooks and build commands in a Linux namespace sandbox.
+    #[serde(default)]
+    pub sandbox: bool,
     #[serde(default)]
     pub pkg_dirs: Vec<String>,
     #[serde(default)]
     pub cache_mirrors: Vec<String>,
     #[serde(default)]
     pub versions: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preferences: Vec<PackagePreference>,
+    /// Preferred provider for each virtual package, e.g.
+    /// `java-runtime: openjdk`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub providers: HashMap<String, String>,
+}
+
+/// A rule deciding which registry or repo a package is taken from when it is
+/// available from several, similar to apt preferences.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PackagePreference {
+    /// Package name or glob pattern, e.g. `ripgrep` or `*`.
+    #[serde(default = "default_preference_package")]
+    pub package: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    /// Higher wins; candidates without a matching rule get 500 and negative
+    /// priorities are never picked unless requested explicitly.
+    pub priority: i32,
+}
+
+fn default_preference_package() -> String {
+    "*".to_string()
 }
 
 fn default_rollback_enabled() -> bool {
@@ -536,9 +605,12 @@ impl Default for Config {
             protect_db: false,
             max_resolution_depth: None,
             offline_mode: false,
+            sandbox: false,
             pkg_dirs: Vec::new(),
             cache_mirrors: Vec::new(),
             versions: HashMap::new(),
+            preferences: Vec::new(),
+            providers: HashMap::new(),
         }
     }
 }
@@ -570,6 +642,8 @@ pub struct Policy {
     #[serde(default, skip_serializing_if = "is_false")]
     pub offline_mode_unoverridable: bool,
     #[serde(default, skip_serializing_if = "is_false")]
+    pub sandbox_unoverridable: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
     pub pkg_dirs_unoverridable: bool,
     #[serde(default, skip_serializing_if = "is_false")]
     pub cache_mirrors_unoverridable: bool,
@@ -577,6 +651,8 @@ pub struct Policy {
     pub parallel_jobs_unoverridable: bool,
     #[serde(default, skip_serializing_if = "is_false")]
     pub advisory_enforcement_unoverridable: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub preferences_unoverridable: bool,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub allowed_licenses: Option<Vec<String>>,
@@ -632,6 +708,9 @@ pub struct ZoiLock {
     pub version: String,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub registries: HashMap<String, String>,
+    /// Registry commit each package was resolved against, keyed by handle.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub registry_revisions: HashMap<String, String>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub packages: HashMap<String, String>,
     #[serde(flatten)]
@@ -652,6 +731,10 @@ pub struct LockPackageDetail {
     pub options_dependencies: Vec<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub optionals_dependencies: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provider_for: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
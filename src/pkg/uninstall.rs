@@ -1,3 +1,4 @@
+use crate::pkg::journal::{self, Entry};
 use crate::pkg::{db, dependencies, hooks, local, recorder, resolve, types};
 use crate::utils;
 use anyhow::anyhow;
@@ -27,8 +28,8 @@ fn get_bin_root(scope: types::Scope) -> anyhow::Result<PathBuf> {
             }
         }
         types::Scope::Project => {
-            let current_dir = std::env::current_dir()?;
-            Ok(current_dir.join(".zoi").join("pkgs").join("bin"))
+            let project_root = crate::project::config::project_root()?;
+            Ok(project_root.join(".zoi").join("pkgs").join("bin"))
         }
     }
 }
@@ -106,6 +107,7 @@ fn uninstall_collection(
     if package_dir.exists() {
         let _ = crate::pkg::service::cleanup_service(&pkg.name, scope);
         fs::remove_dir_all(&package_dir)?;
+        let _ = crate::pkg::store::release(scope, &package_dir);
     }
     if let Err(e) = recorder::remove_package_from_record(manifest) {
         eprintln!(
@@ -176,7 +178,7 @@ fn find_installed_manifest(
     }
 }
 
-fn load_installed_package(
+pub fn load_installed_package(
     manifest: &types::InstallManifest,
     yes: bool,
 ) -> anyhow::Result<(types::Package, PathBuf)> {
@@ -227,8 +229,30 @@ pub fn run(
     let package_dir = local::get_package_dir(scope, handle, &pkg.repo, &pkg.name)?;
     let version_dir = package_dir.join(&manifest.version);
 
+    let default_version = local::get_default_version(scope, handle, &pkg.repo, &pkg.name)?;
+    let is_default = default_version
+        .as_deref()
+        .is_none_or(|version| version == manifest.version);
+    let mut remaining_versions = Vec::new();
+    for version in local::get_installed_versions(scope, handle, &pkg.repo, &pkg.name)? {
+        if version == manifest.version {
+            continue;
+        }
+        if let Some(other) = local::read_version_manifest(
+            scope,
+            handle,
+            &pkg.repo,
+            &pkg.name,
+            manifest.sub_package.as_deref(),
+            &version,
+        )? {
+            remaining_versions.push(other);
+        }
+    }
+    let keeps_other_versions = !remaining_versions.is_empty();
+
     let dependents = local::get_dependents(&package_dir)?;
-    if !dependents.is_empty() {
+    if !dependents.is_empty() && is_default {
         return Err(anyhow::anyhow!(
             "Cannot uninstall '{}' because other packages depend on it:\n  -{}\n\nPlease uninstall these packages first.",
             &pkg.name,
@@ -236,6 +260,13 @@ pub fn run(
         ));
     }
 
+    journal::record(
+        &version_dir,
+        Entry::BeginUninstall {
+            manifest: Box::new(manifest.clone()),
+        },
+    )?;
+
     let lua = Lua::new();
     crate::pkg::lua::functions::setup_lua_environment(
         &lua,
@@ -282,6 +313,12 @@ pub fn run(
 
                 let path = std::path::PathBuf::from(path_to_remove);
                 if path.exists() {
+                    journal::record(
+                        &version_dir,
+                        Entry::Remove {
+                            paths: vec![path.clone()],
+                        },
+                    )?;
                     println!("Removing {}...", path.display());
                     if path.is_dir() {
                         fs::remove_dir_all(path)?;
@@ -334,7 +371,12 @@ pub fn run(
         .bold()
     );
 
-    if let Some(bins) = &manifest.bins {
+    if keeps_other_versions {
+        println!(
+            "Keeping shims, other versions of '{}' remain installed.",
+            pkg.name
+        );
+    } else if let Some(bins) = &manifest.bins {
         let bin_root = get_bin_root(scope)?;
         for bin in bins {
             let symlink_path = bin_root.join(bin);
@@ -350,6 +392,12 @@ pub fn run(
                         bin.cyan(),
                         symlink_path.display()
                     );
+                    journal::record(
+                        &version_dir,
+                        Entry::Remove {
+                            paths: vec![symlink_path.clone()],
+                        },
+                    )?;
                     fs::remove_file(&symlink_path)?;
                 } else {
                     println!(
@@ -374,11 +422,23 @@ pub fn run(
                     bin.cyan(),
                     symlink_path.display()
                 );
+                journal::record(
+                    &version_dir,
+                    Entry::Remove {
+                        paths: vec![symlink_path.clone()],
+                    },
+                )?;
                 fs::remove_file(symlink_path)?;
             }
         }
     }
 
+    journal::record(
+        &version_dir,
+        Entry::Remove {
+            paths: manifest.installed_files.iter().map(PathBuf::from).collect(),
+        },
+    )?;
     for file_path_str in &manifest.installed_files {
         let file_path = PathBuf::from(file_path_str);
         if file_path.exists() {
@@ -397,6 +457,12 @@ pub fn run(
     };
     let manifest_path = version_dir.join(manifest_filename);
     if manifest_path.exists() {
+        journal::record(
+            &version_dir,
+            Entry::Remove {
+                paths: vec![manifest_path.clone()],
+            },
+        )?;
         fs::remove_file(manifest_path)?;
     }
 
@@ -416,11 +482,19 @@ pub fn run(
                 "Removing empty version directory: {}",
                 version_dir.display()
             );
+            journal::record(
+                &version_dir,
+                Entry::Remove {
+                    paths: vec![version_dir.clone()],
+                },
+            )?;
             fs::remove_dir_all(&version_dir)?;
+            local::unmark_version_kept(&version_dir);
+            let _ = crate::pkg::store::release(scope, &version_dir);
         }
     }
 
-    if package_dir.exists() {
+    if package_dir.exists() && !keeps_other_versions {
         let _ = crate::pkg::service::cleanup_service(&pkg.name, scope);
         let mut has_other_versions = false;
         if let Ok(entries) = fs::read_dir(&package_dir) {
@@ -434,7 +508,14 @@ pub fn run(
         }
         if !has_other_versions {
             println!("Removing package store: {}", package_dir.display());
+            journal::record(
+                &version_dir,
+                Entry::Remove {
+                    paths: vec![package_dir.clone()],
+                },
+            )?;
             fs::remove_dir_all(&package_dir)?;
+            let _ = crate::pkg::store::release(scope, &package_dir);
         }
     }
     let parent_id = format!(
@@ -474,22 +555,37 @@ pub fn run(
         }
     }
 
-    if let Err(e) = recorder::remove_package_from_record(&manifest) {
-        eprintln!(
-            "{} Failed to remove package from lockfile: {}",
-            "Warning:".yellow(),
-            e
-        );
-    }
+    if keeps_other_versions {
+        if is_default
+            && let Some(next) = remaining_versions
+                .iter()
+                .max_by(|a, b| local::compare_versions(&a.version, &b.version))
+        {
+            crate::pkg::switch::set_default(next, yes)?;
+            println!(
+                "'{}' now defaults to version {}.",
+                pkg.name.cyan(),
+                next.version.green()
+            );
+        }
+    } else {
+        if let Err(e) = recorder::remove_package_from_record(&manifest) {
+            eprintln!(
+                "{} Failed to remove package from lockfile: {}",
+                "Warning:".yellow(),
+                e
+            );
+        }
 
-    if let Ok(conn) = db::open_connection("local") {
-        let _ = db::delete_package(
-            &conn,
-            &pkg.name,
-            sub_package_to_uninstall.as_deref(),
-            &pkg.repo,
-            Some(scope),
-        );
+        if let Ok(conn) = db::open_connection("local") {
+            let _ = db::delete_package(
+                &conn,
+                &pkg.name,
+                sub_package_to_uninstall.as_deref(),
+                &pkg.repo,
+                Some(scope),
+            );
+        }
     }
 
     println!("Removed manifest for '{}'.", pkg.name);
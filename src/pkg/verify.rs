@@ -0,0 +1,163 @@
+use crate::pkg::store;
+use crate::pkg::types::{FileRecord, InstallManifest, Scope};
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
+pub enum Problem {
+    Missing,
+    /// Content differs from what was installed.
+    Modified,
+    /// A `backup` file the user has edited, which is expected.
+    ModifiedConfig,
+    ModeChanged {
+        expected: u32,
+        actual: u32,
+    },
+    /// The file could not be read to check its content.
+    Unreadable {
+        error: String,
+    },
+}
+
+impl Problem {
+    /// Whether this points at a damaged install rather than local
+    /// configuration.
+    pub fn is_corruption(&self) -> bool {
+        !matches!(self, Problem::ModifiedConfig)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileProblem {
+    pub path: String,
+    #[serde(flatten)]
+    pub problem: Problem,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageReport {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub_package: Option<String>,
+    pub version: String,
+    pub scope: Scope,
+    pub checked_files: usize,
+    /// Installed before file records were kept, so nothing could be checked.
+    pub unrecorded: bool,
+    pub problems: Vec<FileProblem>,
+}
+
+impl PackageReport {
+    pub fn is_ok(&self) -> bool {
+        !self.problems.iter().any(|p| p.problem.is_corruption())
+    }
+}
+
+/// Records the current content of `files`. Paths that are gone or are not
+/// regular files are left out.
+pub fn record_files(files: &[String]) -> Vec<FileRecord> {
+    files
+        .iter()
+        .filter_map(|path| record_file(Path::new(path)).ok().flatten())
+        .collect()
+}
+
+fn record_file(path: &Path) -> Result<Option<FileRecord>> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(FileRecord {
+        path: path.to_string_lossy().into_owned(),
+        sha256: store::hash_file(path)?,
+        mode: mode(&metadata),
+        size: metadata.len(),
+    }))
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &fs::Metadata) -> u32 {
+    0
+}
+
+/// Whether `path` is one of the package's `backup` files, which are listed
+/// relative to the version directory or the filesystem root.
+fn is_backup(manifest: &InstallManifest, path: &Path) -> bool {
+    manifest
+        .backup
+        .iter()
+        .flatten()
+        .any(|rel| path.ends_with(rel.trim_start_matches('/')))
+}
+
+/// Compares the installed files of `manifest` with what was recorded when
+/// it was installed.
+pub fn verify_package(manifest: &InstallManifest) -> PackageReport {
+    let mut report = PackageReport {
+        name: manifest.name.clone(),
+        sub_package: manifest.sub_package.clone(),
+        version: manifest.version.clone(),
+        scope: manifest.scope,
+        checked_files: manifest.file_records.len(),
+        unrecorded: manifest.file_records.is_empty() && !manifest.installed_files.is_empty(),
+        problems: Vec::new(),
+    };
+
+    for record in &manifest.file_records {
+        let path = Path::new(&record.path);
+        let mut push = |problem| {
+            report.problems.push(FileProblem {
+                path: record.path.clone(),
+                problem,
+            })
+        };
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            push(Problem::Missing);
+            continue;
+        };
+        if !metadata.is_file() {
+            push(Problem::Modified);
+            continue;
+        }
+
+        let changed = if metadata.len() != record.size {
+            true
+        } else {
+            match store::hash_file(path) {
+                Ok(hash) => hash != record.sha256,
+                Err(e) => {
+                    push(Problem::Unreadable {
+                        error: e.to_string(),
+                    });
+                    false
+                }
+            }
+        };
+        if changed {
+            push(if is_backup(manifest, path) {
+                Problem::ModifiedConfig
+            } else {
+                Problem::Modified
+            });
+        }
+        let actual = mode(&metadata);
+        if actual != record.mode {
+            push(Problem::ModeChanged {
+                expected: record.mode,
+                actual,
+            });
+        }
+    }
+
+    report
+}
@@ -0,0 +1,190 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Zoi's own version file, in the `.tool-versions` format.
+pub const ZOI_VERSION_FILE: &str = ".zoi-version";
+const TOOL_VERSIONS_FILE: &str = ".tool-versions";
+
+/// Tools known under another name by asdf and mise.
+const ALIASES: &[(&str, &str)] = &[("node", "nodejs"), ("go", "golang")];
+
+/// Single-tool version files and the tool names they pin.
+const TOOL_FILES: &[(&str, &[&str])] = &[
+    (".nvmrc", &["node", "nodejs"]),
+    (".node-version", &["node", "nodejs"]),
+    (".python-version", &["python", "python3"]),
+];
+
+/// A version pinned by a version file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pinned {
+    pub version: String,
+    pub file: PathBuf,
+}
+
+/// `names` plus their asdf/mise aliases.
+fn with_aliases(names: &[String]) -> Vec<String> {
+    let mut all = names.to_vec();
+    for (a, b) in ALIASES {
+        for name in names {
+            if name == a && !all.iter().any(|n| n == b) {
+                all.push(b.to_string());
+            } else if name == b && !all.iter().any(|n| n == a) {
+                all.push(a.to_string());
+            }
+        }
+    }
+    all
+}
+
+/// Only concrete versions are pinned; values like `system`, `latest` or
+/// `lts/*` leave the choice to Zoi.
+fn normalize(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches('"');
+    let value = value.strip_prefix('v').unwrap_or(value);
+    value
+        .starts_with(|c: char| c.is_ascii_digit())
+        .then(|| value.to_string())
+}
+
+fn read_tool_versions(content: &str, names: &[String]) -> Option<String> {
+    content.lines().find_map(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+        let tool = parts.next()?;
+        if !names.iter().any(|n| n == tool) {
+            return None;
+        }
+        parts.next().and_then(normalize)
+    })
+}
+
+fn read_single(content: &str) -> Option<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .find(|line| !line.is_empty())
+        .and_then(normalize)
+}
+
+fn read_mise(content: &str, names: &[String]) -> Option<String> {
+    let value: toml::Value = toml::from_str(content).ok()?;
+    let tools = value.get("tools")?.as_table()?;
+    names.iter().find_map(|name| {
+        let spec = tools.get(name)?;
+        let version = match spec {
+            toml::Value::String(s) => s.as_str(),
+            toml::Value::Array(items) => items.first()?.as_str()?,
+            toml::Value::Table(table) => table.get("version")?.as_str()?,
+            _ => return None,
+        };
+        normalize(version)
+    })
+}
+
+fn read_rust_toolchain(content: &str) -> Option<String> {
+    let value: toml::Value = toml::from_str(content).ok()?;
+    normalize(value.get("toolchain")?.get("channel")?.as_str()?)
+}
+
+/// The version pinned for any of `names` by a version file in `dir` itself.
+fn find_in_dir(dir: &Path, names: &[String]) -> Option<Pinned> {
+    let pinned =
+        |file: PathBuf, version: Option<String>| version.map(|version| Pinned { version, file });
+    let read = |name: &str| {
+        let path = dir.join(name);
+        fs::read_to_string(&path)
+            .ok()
+            .map(|content| (path, content))
+    };
+
+    for name in [ZOI_VERSION_FILE, TOOL_VERSIONS_FILE] {
+        if let Some((path, content)) = read(name)
+            && let Some(found) = pinned(path, read_tool_versions(&content, names))
+        {
+            return Some(found);
+        }
+    }
+    for (file, tools) in TOOL_FILES {
+        if names.iter().any(|n| tools.contains(&n.as_str()))
+            && let Some((path, content)) = read(file)
+            && let Some(found) = pinned(path, read_single(&content))
+        {
+            return Some(found);
+        }
+    }
+    if names
+        .iter()
+        .any(|n| ["rust", "rustc", "cargo"].contains(&n.as_str()))
+        && let Some((path, content)) = read("rust-toolchain.toml")
+        && let Some(found) = pinned(path, read_rust_toolchain(&content))
+    {
+        return Some(found);
+    }
+    for file in ["mise.toml", ".mise.toml"] {
+        if let Some((path, content)) = read(file)
+            && let Some(found) = pinned(path, read_mise(&content, names))
+        {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// Walks up from `start` and returns the version pinned for any of `names`
+/// by the nearest version file.
+pub fn find_version(start: &Path, names: &[String]) -> Option<Pinned> {
+    let names = with_aliases(names);
+    start.ancestors().find_map(|dir| find_in_dir(dir, &names))
+}
+
+/// Sets `name`'s line in a `.tool-versions`-style file, keeping the tool
+/// name already used there.
+fn set_tool_line(content: &str, names: &[String], version: &str) -> String {
+    let mut found = false;
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            let tool = line.split_whitespace().next().unwrap_or_default();
+            if !found && names.iter().any(|n| n == tool) {
+                found = true;
+                format!("{} {}", tool, version)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(format!("{} {}", names[0], version));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Pins `name` to `version` for `dir`, updating the version file there that
+/// already covers the tool, or `.zoi-version` otherwise. Returns the file
+/// written.
+pub fn write_local(dir: &Path, name: &str, version: &str) -> Result<PathBuf> {
+    let names = with_aliases(&[name.to_string()]);
+
+    let zoi_file = dir.join(ZOI_VERSION_FILE);
+    let tool_versions = dir.join(TOOL_VERSIONS_FILE);
+    let target = if zoi_file.exists() {
+        zoi_file
+    } else if tool_versions.exists() {
+        tool_versions
+    } else if let Some((file, _)) = TOOL_FILES
+        .iter()
+        .find(|(file, tools)| tools.contains(&name) && dir.join(file).exists())
+    {
+        let path = dir.join(file);
+        fs::write(&path, format!("{}\n", version))?;
+        return Ok(path);
+    } else {
+        zoi_file
+    };
+
+    let content = fs::read_to_string(&target).unwrap_or_default();
+    fs::write(&target, set_tool_line(&content, &names, version))?;
+    Ok(target)
+}
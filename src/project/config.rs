@@ -1,10 +1,14 @@
+use super::workspace;
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Deserialize, Default)]
+pub const CONFIG_FILE: &str = "zoi.yaml";
+
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct ProjectLocalConfig {
     #[serde(default)]
     pub local: bool,
@@ -14,9 +18,22 @@ pub struct ProjectLocalConfig {
 pub struct ShellSpec {
     #[serde(default)]
     pub env: PlatformOrEnvMap,
+    #[serde(default, deserialize_with = "deserialize_env_files")]
+    pub env_file: Vec<String>,
+    /// Let `zoi hook` install missing packages from zoi.lock on entering
+    /// the project.
+    #[serde(default)]
+    pub auto_install: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct WorkspaceSpec {
+    /// Globs of member directories, relative to the workspace root.
+    #[serde(default)]
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
 #[allow(dead_code)]
 pub struct ProjectConfig {
     pub name: String,
@@ -34,6 +51,29 @@ pub struct ProjectConfig {
     pub environments: Vec<EnvironmentSpec>,
     #[serde(default)]
     pub shell: Option<ShellSpec>,
+    /// Dotenv files loaded for every command, environment and dev shell.
+    #[serde(default, deserialize_with = "deserialize_env_files")]
+    pub env_file: Vec<String>,
+    /// Variables for every command, environment and dev shell, beneath
+    /// their own `env`.
+    #[serde(default)]
+    pub env: PlatformOrEnvMap,
+    #[serde(default)]
+    pub workspace: Option<WorkspaceSpec>,
+    /// Directory of the zoi.yaml this config was loaded from.
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+impl ProjectConfig {
+    /// Directory the project's commands run in. Configs that were not
+    /// loaded from a file use the current directory.
+    pub fn root_dir(&self) -> Result<PathBuf> {
+        match &self.dir {
+            Some(dir) => Ok(dir.clone()),
+            None => Ok(env::current_dir()?),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -63,6 +103,23 @@ where
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn deserialize_env_files<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(file) => vec![file],
+        OneOrMany::Many(files) => files,
+    })
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct PackageCheck {
     pub name: String,
     pub check: String,
@@ -95,12 +152,72 @@ impl Default for PlatformOrEnvMap {
     }
 }
 
+impl PlatformOrEnvMap {
+    /// Variables for `platform`, falling back to the `default` entry.
+    pub fn for_platform(&self, platform: &str) -> HashMap<String, String> {
+        match self {
+            PlatformOrEnvMap::EnvMap(m) => m.clone(),
+            PlatformOrEnvMap::Platform(p) => p
+                .get(platform)
+                .or_else(|| p.get("default"))
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// `self` with the variables of `other` layered on top, per platform.
+    pub fn merged_with(&self, other: &PlatformOrEnvMap) -> PlatformOrEnvMap {
+        if let (PlatformOrEnvMap::EnvMap(a), PlatformOrEnvMap::EnvMap(b)) = (self, other) {
+            let mut merged = a.clone();
+            merged.extend(b.clone());
+            return PlatformOrEnvMap::EnvMap(merged);
+        }
+
+        let mut platforms = vec!["default".to_string()];
+        for map in [self, other] {
+            if let PlatformOrEnvMap::Platform(p) = map {
+                platforms.extend(p.keys().cloned());
+            }
+        }
+        PlatformOrEnvMap::Platform(
+            platforms
+                .into_iter()
+                .map(|platform| {
+                    let mut merged = self.for_platform(&platform);
+                    merged.extend(other.for_platform(&platform));
+                    (platform, merged)
+                })
+                .collect(),
+        )
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct CommandSpec {
     pub cmd: String,
-    pub run: PlatformOrString,
+    /// Optional for commands that only group their `depends`.
+    #[serde(default)]
+    pub run: Option<PlatformOrString>,
     #[serde(default)]
     pub env: PlatformOrEnvMap,
+    /// Dotenv files loaded after the project's `env_file`. `env` still
+    /// takes precedence over both.
+    #[serde(default, deserialize_with = "deserialize_env_files")]
+    pub env_file: Vec<String>,
+    /// Commands that must succeed before this one runs.
+    #[serde(default)]
+    pub depends: Vec<String>,
+    /// Globs of the files the command reads. Commands with inputs are
+    /// skipped while their inputs and outputs are unchanged.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Globs of the files the command produces.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// Globs that trigger a re-run under `zoi run --watch`. Defaults to
+    /// `inputs`.
+    #[serde(default)]
+    pub watch: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -110,30 +227,59 @@ pub struct EnvironmentSpec {
     pub run: PlatformOrStringVec,
     #[serde(default)]
     pub env: PlatformOrEnvMap,
+    #[serde(default, deserialize_with = "deserialize_env_files")]
+    pub env_file: Vec<String>,
 }
 
-pub fn load() -> Result<ProjectConfig> {
-    let config_path = Path::new("zoi.yaml");
-    if !config_path.exists() {
-        return Err(anyhow!(
-            "No 'zoi.yaml' file found in the current directory."
-        ));
-    }
+/// Directory of the nearest zoi.yaml at or above `start`.
+pub fn find_nearest(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(CONFIG_FILE).is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Root of the current project: the enclosing workspace root, else the
+/// nearest directory with a zoi.yaml, else the current directory. The
+/// project's `.zoi` directory and zoi.lock live here.
+pub fn project_root() -> Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    Ok(workspace::find_root(&cwd).unwrap_or(cwd))
+}
 
-    let content = fs::read_to_string(config_path)?;
-    let config: ProjectConfig = serde_yaml::from_str(&content)?;
+/// Reads `dir/zoi.yaml` as is, without merging any workspace settings.
+pub fn read(dir: &Path) -> Result<ProjectConfig> {
+    let config_path = dir.join(CONFIG_FILE);
+    let content = fs::read_to_string(&config_path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", config_path.display(), e))?;
+    let mut config: ProjectConfig = serde_yaml::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", config_path.display(), e))?;
+    config.dir = Some(dir.to_path_buf());
     Ok(config)
 }
 
-pub fn add_packages_to_config(packages: &[String]) -> Result<()> {
-    let config_path = Path::new("zoi.yaml");
-    if !config_path.exists() {
-        return Err(anyhow!(
-            "No 'zoi.yaml' file found in the current directory."
-        ));
+/// Loads the project config for the current directory. Inside a workspace
+/// member this is the member's zoi.yaml merged over the workspace root's.
+pub fn load() -> Result<ProjectConfig> {
+    let cwd = env::current_dir()?;
+    let root = workspace::find_root(&cwd).ok_or_else(|| {
+        anyhow!("No 'zoi.yaml' file found in the current directory or any parent directory.")
+    })?;
+    let config = read(&root)?;
+    if config.workspace.is_none() {
+        return Ok(config);
     }
+    Ok(workspace::Workspace::from_root(config)?.config_for(&cwd))
+}
 
-    let content = fs::read_to_string(config_path)?;
+pub fn add_packages_to_config(packages: &[String]) -> Result<()> {
+    let config_path = find_nearest(&env::current_dir()?)
+        .ok_or_else(|| {
+            anyhow!("No 'zoi.yaml' file found in the current directory or any parent directory.")
+        })?
+        .join(CONFIG_FILE);
+
+    let content = fs::read_to_string(&config_path)?;
     let mut yaml_value: serde_yaml::Value = serde_yaml::from_str(&content)?;
 
     if let Some(mapping) = yaml_value.as_mapping_mut() {
@@ -153,18 +299,18 @@ pub fn add_packages_to_config(packages: &[String]) -> Result<()> {
     }
 
     let new_content = serde_yaml::to_string(&yaml_value)?;
-    fs::write(config_path, new_content)?;
+    fs::write(&config_path, new_content)?;
 
     Ok(())
 }
 
 pub fn remove_packages_from_config(packages_to_remove: &[String]) -> Result<()> {
-    let config_path = Path::new("zoi.yaml");
-    if !config_path.exists() {
+    let Some(dir) = find_nearest(&env::current_dir()?) else {
         return Ok(());
-    }
+    };
+    let config_path = dir.join(CONFIG_FILE);
 
-    let content = fs::read_to_string(config_path)?;
+    let content = fs::read_to_string(&config_path)?;
     let mut yaml_value: serde_yaml::Value = serde_yaml::from_str(&content)?;
 
     if let Some(mapping) = yaml_value.as_mapping_mut()
@@ -194,7 +340,7 @@ pub fn remove_packages_from_config(packages_to_remove: &[String]) -> Result<()>
     }
 
     let new_content = serde_yaml::to_string(&yaml_value)?;
-    fs::write(config_path, new_content)?;
+    fs::write(&config_path, new_content)?;
 
     Ok(())
 }
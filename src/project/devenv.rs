@@ -0,0 +1,128 @@
+use super::config::ProjectConfig;
+use super::dotenv;
+use crate::pkg::{local, types};
+use crate::utils;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Directories to prepend to each search-path variable, in order.
+pub type SearchPaths = Vec<(String, Vec<PathBuf>)>;
+
+fn lib_path_var() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "DYLD_LIBRARY_PATH"
+    } else {
+        "LD_LIBRARY_PATH"
+    }
+}
+
+/// Project bin directory, where `zoi install --local` links package binaries.
+pub fn project_bin_dir() -> Result<PathBuf> {
+    Ok(super::config::project_root()?
+        .join(".zoi")
+        .join("pkgs")
+        .join("bin"))
+}
+
+/// Search paths exposing the `bin`, `lib`, `include` and pkg-config
+/// directories of the given installed package versions.
+pub fn search_paths(version_dirs: &[PathBuf]) -> SearchPaths {
+    let mut bin_paths = Vec::new();
+    let mut lib_paths = Vec::new();
+    let mut include_paths = Vec::new();
+    let mut pkg_config_paths = Vec::new();
+
+    for version_dir in version_dirs {
+        let bin_dir = version_dir.join("bin");
+        if bin_dir.exists() {
+            bin_paths.push(bin_dir);
+        }
+
+        let lib_dir = version_dir.join("lib");
+        if lib_dir.exists() {
+            lib_paths.push(lib_dir.clone());
+            let pkgconfig_dir = lib_dir.join("pkgconfig");
+            if pkgconfig_dir.exists() {
+                pkg_config_paths.push(pkgconfig_dir);
+            }
+        }
+
+        let include_dir = version_dir.join("include");
+        if include_dir.exists() {
+            include_paths.push(include_dir);
+        }
+
+        let pkgconfig_dir = version_dir.join("share").join("pkgconfig");
+        if pkgconfig_dir.exists() {
+            pkg_config_paths.push(pkgconfig_dir);
+        }
+    }
+
+    let mut paths = vec![
+        ("PATH".to_string(), bin_paths),
+        (lib_path_var().to_string(), lib_paths),
+    ];
+    for var in ["CPATH", "C_INCLUDE_PATH", "CPLUS_INCLUDE_PATH"] {
+        paths.push((var.to_string(), include_paths.clone()));
+    }
+    paths.push(("PKG_CONFIG_PATH".to_string(), pkg_config_paths));
+    paths.retain(|(_, dirs)| !dirs.is_empty());
+    paths
+}
+
+/// Version directories of the packages installed in the project scope.
+pub fn installed_version_dirs() -> Result<Vec<PathBuf>> {
+    let mut manifests = local::get_installed_manifests_in_scope(types::Scope::Project)?;
+    manifests.sort_by(|a, b| a.name.cmp(&b.name));
+    manifests
+        .iter()
+        .map(|m| {
+            local::get_package_version_dir(
+                m.scope,
+                &m.registry_handle,
+                &m.repo,
+                &m.name,
+                &m.version,
+            )
+        })
+        .collect()
+}
+
+/// The variables of the project's development environment: `paths`
+/// prepended to the `inherited` values, then the project's and shell's env
+/// files, then their `env` maps.
+pub fn compose(
+    config: &ProjectConfig,
+    paths: &SearchPaths,
+    inherited: &HashMap<String, String>,
+) -> Result<HashMap<String, String>> {
+    let sep = if cfg!(windows) { ";" } else { ":" };
+    let mut env_vars: HashMap<String, String> = HashMap::new();
+
+    for (var, dirs) in paths {
+        let mut value = dirs
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join(sep);
+        if let Some(old) = env_vars.get(var).or_else(|| inherited.get(var)) {
+            value = format!("{}{}{}", value, sep, old);
+        }
+        env_vars.insert(var.clone(), value);
+    }
+
+    let mut env_files = config.env_file.clone();
+    if let Some(shell_spec) = &config.shell {
+        env_files.extend(shell_spec.env_file.iter().cloned());
+    }
+    let loaded = dotenv::load(&config.root_dir()?, &env_files, &env_vars)?;
+    env_vars.extend(loaded);
+
+    let platform = utils::get_platform()?;
+    env_vars.extend(config.env.for_platform(&platform));
+    if let Some(shell_spec) = &config.shell {
+        env_vars.extend(shell_spec.env.for_platform(&platform));
+    }
+    Ok(env_vars)
+}
@@ -0,0 +1,282 @@
+use super::executor;
+use crate::pkg::system;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+
+/// Prefix of values encrypted with `zoi system encrypt --user`.
+const ENCRYPTED_PREFIX: &str = "enc:";
+/// Extension of env files encrypted as a whole.
+const ENCRYPTED_EXTENSION: &str = "enc";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quoting {
+    None,
+    Single,
+    Double,
+}
+
+/// Loads the dotenv `files` (relative to `root`) in order, later files
+/// overriding earlier ones. Missing files are skipped.
+///
+/// `${VAR}`, `${VAR:-default}` and `$(command)` are expanded against the
+/// variables loaded so far, then `base`, then the process environment.
+/// Single-quoted values are taken literally.
+pub fn load(
+    root: &Path,
+    files: &[String],
+    base: &HashMap<String, String>,
+) -> Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    for file in files {
+        let path = root.join(file);
+        if !path.is_file() {
+            continue;
+        }
+        let mut content = fs::read_to_string(&path)?;
+        if path.extension().is_some_and(|e| e == ENCRYPTED_EXTENSION) {
+            content = system::decrypt_secret(&content)
+                .map_err(|e| anyhow!("Could not decrypt '{}': {}", file, e))?;
+        }
+
+        for (line, key, raw, quoting) in parse(&content).map_err(|e| anyhow!("{}:{}", file, e))? {
+            let value = if let Some(encrypted) = raw.strip_prefix(ENCRYPTED_PREFIX) {
+                system::decrypt_secret(encrypted)
+                    .map_err(|e| anyhow!("{}:{}: could not decrypt {}: {}", file, line, key, e))?
+            } else if quoting == Quoting::Single {
+                raw
+            } else {
+                let expander = Expander {
+                    root,
+                    vars: &vars,
+                    base,
+                };
+                expander
+                    .expand(&raw, quoting)
+                    .map_err(|e| anyhow!("{}:{}: {}", file, line, e))?
+            };
+            vars.insert(key, value);
+        }
+    }
+    Ok(vars)
+}
+
+/// Splits dotenv `content` into `(line, key, raw value, quoting)` entries.
+/// Quoted values may span several lines.
+fn parse(content: &str) -> Result<Vec<(usize, String, String, Quoting)>> {
+    let mut entries = Vec::new();
+    let mut lines = content.lines().enumerate().map(|(n, l)| (n + 1, l));
+    while let Some((n, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+        let (key, rest) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("{}: expected KEY=VALUE", n))?;
+        let key = key.trim();
+        if !is_valid_name(key) {
+            return Err(anyhow!("{}: invalid variable name '{}'", n, key));
+        }
+
+        let rest = rest.trim_start();
+        let (value, quoting) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut body = rest[1..].to_string();
+                let end = loop {
+                    if let Some(end) = closing_quote(&body, quote) {
+                        break end;
+                    }
+                    let (_, next) = lines
+                        .next()
+                        .ok_or_else(|| anyhow!("{}: unterminated {} quote", n, quote))?;
+                    body.push('\n');
+                    body.push_str(next);
+                };
+                let tail = body[end + 1..].trim();
+                if !tail.is_empty() && !tail.starts_with('#') {
+                    return Err(anyhow!("{}: unexpected text after closing quote", n));
+                }
+                body.truncate(end);
+                let quoting = if quote == '"' {
+                    Quoting::Double
+                } else {
+                    Quoting::Single
+                };
+                (body, quoting)
+            }
+            _ => {
+                let value = match rest.find(" #") {
+                    Some(comment) => &rest[..comment],
+                    None => rest,
+                };
+                (value.trim_end().to_string(), Quoting::None)
+            }
+        };
+        entries.push((n, key.to_string(), value, quoting));
+    }
+    Ok(entries)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn closing_quote(body: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        if quote == '"' && c == '\\' && !escaped {
+            escaped = true;
+            continue;
+        }
+        if c == quote && !escaped {
+            return Some(i);
+        }
+        escaped = false;
+    }
+    None
+}
+
+struct Expander<'a> {
+    root: &'a Path,
+    vars: &'a HashMap<String, String>,
+    base: &'a HashMap<String, String>,
+}
+
+impl Expander<'_> {
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.vars
+            .get(name)
+            .or_else(|| self.base.get(name))
+            .cloned()
+            .or_else(|| env::var(name).ok())
+    }
+
+    fn expand(&self, input: &str, quoting: Quoting) -> Result<String> {
+        let mut out = String::new();
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match (chars.next(), quoting) {
+                    (Some('$'), _) => out.push('$'),
+                    (Some('n'), Quoting::Double) => out.push('\n'),
+                    (Some('t'), Quoting::Double) => out.push('\t'),
+                    (Some('r'), Quoting::Double) => out.push('\r'),
+                    (Some(c @ ('"' | '\\')), Quoting::Double) => out.push(c),
+                    (Some(c), _) => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                    (None, _) => out.push('\\'),
+                },
+                '$' => match chars.peek() {
+                    Some('{') => {
+                        chars.next();
+                        let inner = take_until_closing(&mut chars, '{', '}')?;
+                        out.push_str(&self.expand_braced(&inner)?);
+                    }
+                    Some('(') => {
+                        chars.next();
+                        let command = take_until_closing(&mut chars, '(', ')')?;
+                        out.push_str(&self.run_command(&command)?);
+                    }
+                    Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                        let mut name = String::new();
+                        while let Some(c) = chars.peek() {
+                            if !(c.is_ascii_alphanumeric() || *c == '_') {
+                                break;
+                            }
+                            name.push(*c);
+                            chars.next();
+                        }
+                        out.push_str(&self.lookup(&name).unwrap_or_default());
+                    }
+                    _ => out.push('$'),
+                },
+                c => out.push(c),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Expands the inside of `${...}`: `VAR`, `VAR:-default` (also used
+    /// when VAR is empty) or `VAR-default` (only when VAR is unset).
+    fn expand_braced(&self, inner: &str) -> Result<String> {
+        let name_len = inner
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(inner.len());
+        let (name, op) = inner.split_at(name_len);
+        if !is_valid_name(name) {
+            return Err(anyhow!("invalid expansion '${{{}}}'", inner));
+        }
+        let value = self.lookup(name);
+        if op.is_empty() {
+            return Ok(value.unwrap_or_default());
+        }
+        if let Some(default) = op.strip_prefix(":-") {
+            return match value {
+                Some(v) if !v.is_empty() => Ok(v),
+                _ => self.expand(default, Quoting::None),
+            };
+        }
+        if let Some(default) = op.strip_prefix('-') {
+            return match value {
+                Some(v) => Ok(v),
+                None => self.expand(default, Quoting::None),
+            };
+        }
+        Err(anyhow!("unsupported expansion '${{{}}}'", inner))
+    }
+
+    fn run_command(&self, command: &str) -> Result<String> {
+        let output = executor::get_shell_command(command)
+            .current_dir(self.root)
+            .envs(self.base)
+            .envs(self.vars)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "'$({})' failed with exit code {}",
+                command,
+                output.status
+            ));
+        }
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+/// Consumes input up to the `close` matching an already consumed `open`.
+fn take_until_closing(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    open: char,
+    close: char,
+) -> Result<String> {
+    let mut depth = 1;
+    let mut inner = String::new();
+    for c in chars.by_ref() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Ok(inner);
+            }
+        }
+        inner.push(c);
+    }
+    Err(anyhow!("missing closing '{}'", close))
+}
@@ -1,8 +1,9 @@
-use super::{config, executor};
+use super::{config, dotenv, executor};
 use crate::utils;
 use anyhow::{Result, anyhow};
 use colored::*;
 use dialoguer::{Select, theme::ColorfulTheme};
+use std::collections::HashMap;
 use std::io::{self, Write};
 use std::process::Stdio;
 
@@ -59,17 +60,14 @@ pub fn setup(env_alias: Option<&str>, config: &config::ProjectConfig) -> Result<
             })?,
     };
 
-    let env_vars = match &env_to_setup.env {
-        config::PlatformOrEnvMap::EnvMap(m) => m.clone(),
-        config::PlatformOrEnvMap::Platform(p) => p
-            .get(&platform)
-            .or_else(|| p.get("default"))
-            .cloned()
-            .unwrap_or_default(),
-    };
+    let root = config.root_dir()?;
+    let env_files = [config.env_file.as_slice(), &env_to_setup.env_file].concat();
+    let mut env_vars = dotenv::load(&root, &env_files, &HashMap::new())?;
+    env_vars.extend(config.env.for_platform(&platform));
+    env_vars.extend(env_to_setup.env.for_platform(&platform));
 
     for cmd_str in &run_cmds {
-        executor::run_shell_command(cmd_str, &env_vars)?;
+        executor::run_shell_command(cmd_str, &root, &env_vars)?;
     }
 
     Ok(())
@@ -86,6 +84,7 @@ fn check_packages(config: &config::ProjectConfig) -> Result<()> {
         let _ = io::stdout().flush();
 
         let status = executor::get_shell_command(&package.check)
+            .current_dir(config.root_dir()?)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status()?;
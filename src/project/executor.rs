@@ -1,11 +1,19 @@
 use anyhow::{Result, anyhow};
 use colored::*;
 use std::collections::HashMap;
+use std::path::Path;
 use std::process::Command;
 
-pub fn run_shell_command(command_str: &str, envs: &HashMap<String, String>) -> Result<()> {
+pub fn run_shell_command(
+    command_str: &str,
+    dir: &Path,
+    envs: &HashMap<String, String>,
+) -> Result<()> {
     println!("> {}", command_str.cyan());
-    let status = get_shell_command(command_str).envs(envs).status()?;
+    let status = get_shell_command(command_str)
+        .current_dir(dir)
+        .envs(envs)
+        .status()?;
 
     if !status.success() {
         return Err(anyhow!("Command failed with exit code {status}"));
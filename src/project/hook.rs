@@ -0,0 +1,331 @@
+use super::config::{self, ProjectConfig};
+use super::devenv::{self, SearchPaths};
+use super::workspace;
+use crate::pkg::{local, resolve, types};
+use anyhow::{Result, anyhow};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Variable in which the shell carries what the hook changed, so the next
+/// prompt can tell whether anything needs to happen and how to undo it.
+pub const STATE_VAR: &str = "ZOI_HOOK_STATE";
+const CACHE_FILE: &str = "hook.json";
+
+/// A variable to set, or to unset when the value is `None`.
+pub type Change = (String, Option<String>);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    key: String,
+    name: String,
+    /// Values the changed variables had before, `None` if they were unset.
+    previous: BTreeMap<String, Option<String>>,
+}
+
+impl State {
+    fn from_env() -> Option<State> {
+        let encoded = env::var(STATE_VAR).ok()?;
+        let json = hex::decode(encoded).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    fn encode(&self) -> Result<String> {
+        Ok(hex::encode(serde_json::to_vec(self)?))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HookCache {
+    key: String,
+    paths: SearchPaths,
+}
+
+fn hash_files(files: &[PathBuf]) -> String {
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(file).unwrap_or_default());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// The files the environment of `config` comes from: the workspace root's
+/// zoi.yaml inside a workspace member, the env files the project and its
+/// shell load, and last the project's own zoi.yaml.
+fn config_files(config: &ProjectConfig) -> Result<Vec<PathBuf>> {
+    let root = config.root_dir()?;
+    let mut files = vec![config::project_root()?.join(config::CONFIG_FILE)];
+    let env_files = config
+        .env_file
+        .iter()
+        .chain(config.shell.iter().flat_map(|shell| shell.env_file.iter()));
+    for file in env_files {
+        let path = root.join(file);
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    let own = root.join(config::CONFIG_FILE);
+    files.retain(|file| *file != own);
+    files.push(own);
+    Ok(files)
+}
+
+/// Changes whenever the project's zoi.yaml, env files or zoi.lock do.
+fn project_key(config: &ProjectConfig) -> Result<String> {
+    let mut files = config_files(config)?;
+    files.push(config::project_root()?.join("zoi.lock"));
+    Ok(hash_files(&files))
+}
+
+fn allowed_path() -> Result<PathBuf> {
+    let home_dir = home::home_dir().ok_or_else(|| anyhow!("Could not find home directory."))?;
+    Ok(crate::pkg::sysroot::apply_sysroot(
+        home_dir.join(".zoi").join("hook").join("allowed"),
+    ))
+}
+
+fn read_allowed() -> Result<Vec<String>> {
+    match fs::read_to_string(allowed_path()?) {
+        Ok(content) => Ok(content.lines().map(str::to_string).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_allowed(allowed: &[String]) -> Result<()> {
+    let path = allowed_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut content = allowed.join("\n");
+    content.push('\n');
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Whether the hook may apply `config`. Trust covers the exact contents of
+/// its zoi.yaml and env files, so any edit has to be allowed again.
+pub fn is_allowed(config: &ProjectConfig) -> Result<bool> {
+    let id = hash_files(&config_files(config)?);
+    Ok(read_allowed()?.contains(&id))
+}
+
+/// Trusts the current project's zoi.yaml and returns its path.
+pub fn allow() -> Result<PathBuf> {
+    let config = config::load()?;
+    let files = config_files(&config)?;
+    let id = hash_files(&files);
+    let mut allowed = read_allowed()?;
+    if !allowed.contains(&id) {
+        allowed.push(id);
+        write_allowed(&allowed)?;
+    }
+    Ok(files[files.len() - 1].clone())
+}
+
+/// Revokes trust in the current project's zoi.yaml and returns its path.
+pub fn deny() -> Result<PathBuf> {
+    let config = config::load()?;
+    let files = config_files(&config)?;
+    let id = hash_files(&files);
+    let mut allowed = read_allowed()?;
+    if allowed.contains(&id) {
+        allowed.retain(|a| *a != id);
+        write_allowed(&allowed)?;
+    }
+    Ok(files[files.len() - 1].clone())
+}
+
+fn is_valid_name(var: &str) -> bool {
+    let mut chars = var.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The variable changes the shell should apply in the current directory:
+/// undoing the environment of a project it left and applying that of the
+/// project it entered. Empty when nothing changed since the last prompt.
+pub fn export() -> Result<Vec<Change>> {
+    let state = State::from_env();
+    let cwd = env::current_dir()?;
+    let project = match workspace::find_root(&cwd) {
+        Some(_) => Some(config::load()?),
+        None => None,
+    };
+
+    let key = match &project {
+        Some(config) => {
+            let status = if is_allowed(config)? {
+                "allowed"
+            } else {
+                "denied"
+            };
+            Some((project_key(config)?, status))
+        }
+        None => None,
+    };
+    let state_key = key
+        .as_ref()
+        .map(|(hash, status)| format!("{}-{}", hash, status));
+    if state.as_ref().map(|s| &s.key) == state_key.as_ref() {
+        return Ok(Vec::new());
+    }
+
+    let mut changes: BTreeMap<String, Option<String>> = BTreeMap::new();
+    let mut inherited: HashMap<String, String> = env::vars().collect();
+    if let Some(state) = &state {
+        for (var, value) in &state.previous {
+            match value {
+                Some(value) => inherited.insert(var.clone(), value.clone()),
+                None => inherited.remove(var),
+            };
+            changes.insert(var.clone(), value.clone());
+        }
+        if !state.previous.is_empty() {
+            eprintln!(
+                "{} Unloaded the environment of {}",
+                "::".bold().blue(),
+                state.name.cyan()
+            );
+        }
+    }
+
+    let (Some(config), Some((hash, status))) = (project, key) else {
+        changes.insert(STATE_VAR.to_string(), None);
+        return Ok(changes.into_iter().collect());
+    };
+
+    let mut new_state = State {
+        key: state_key.unwrap_or_default(),
+        name: config.name.clone(),
+        previous: BTreeMap::new(),
+    };
+    if status == "allowed" {
+        let paths = search_paths(&config, &hash)?;
+        for (var, value) in devenv::compose(&config, &paths, &inherited)? {
+            if !is_valid_name(&var) || var == STATE_VAR {
+                eprintln!(
+                    "{} Skipping invalid variable name '{}'",
+                    "Warning:".yellow(),
+                    var
+                );
+                continue;
+            }
+            new_state
+                .previous
+                .insert(var.clone(), inherited.get(&var).cloned());
+            changes.insert(var, Some(value));
+        }
+        eprintln!(
+            "{} Loaded the environment of {}",
+            "::".bold().blue(),
+            config.name.cyan().bold()
+        );
+    } else {
+        eprintln!(
+            "{} {} is not allowed to change your environment. Run 'zoi hook --allow' to trust it.",
+            "::".bold().yellow(),
+            config.root_dir()?.join(config::CONFIG_FILE).display()
+        );
+    }
+    changes.insert(STATE_VAR.to_string(), Some(new_state.encode()?));
+    Ok(changes.into_iter().collect())
+}
+
+/// Search paths of the project's installed packages, cached under `key`.
+/// Missing packages are installed from zoi.lock first when the project
+/// sets `shell.auto_install`.
+fn search_paths(config: &ProjectConfig, key: &str) -> Result<SearchPaths> {
+    let root = config::project_root()?;
+    let cache_path = root.join(".zoi").join("cache").join(CACHE_FILE);
+    if let Some(cache) = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<HookCache>(&content).ok())
+        && cache.key == key
+    {
+        return Ok(cache.paths);
+    }
+
+    let mut missing = missing_pkgs(config)?;
+    if !missing.is_empty() {
+        let auto_install = config.shell.as_ref().is_some_and(|s| s.auto_install);
+        if auto_install && root.join("zoi.lock").is_file() {
+            install_locked(&root)?;
+            missing = missing_pkgs(config)?;
+        } else {
+            eprintln!(
+                "{} Project packages not installed: {}. Run 'zoi install' to install them.",
+                "Warning:".yellow(),
+                missing.join(", ")
+            );
+        }
+    }
+
+    let mut paths = devenv::search_paths(&devenv::installed_version_dirs()?);
+    paths.push(("PATH".to_string(), vec![devenv::project_bin_dir()?]));
+
+    // Caching before everything is installed would hide it once it is.
+    if missing.is_empty() {
+        let cache = HookCache {
+            key: key.to_string(),
+            paths: paths.clone(),
+        };
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&cache_path, serde_json::to_string_pretty(&cache)?)?;
+    }
+    Ok(paths)
+}
+
+/// Packages of `config` with no version installed in the project.
+fn missing_pkgs(config: &ProjectConfig) -> Result<Vec<String>> {
+    let installed: HashSet<String> =
+        local::get_installed_manifests_in_scope(types::Scope::Project)?
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+    Ok(config
+        .pkgs
+        .iter()
+        .filter(|spec| {
+            resolve::parse_source_string(spec).is_ok_and(|req| !installed.contains(&req.name))
+        })
+        .cloned()
+        .collect())
+}
+
+/// Runs `zoi install --frozen-lockfile`. Its output goes to stderr, since
+/// the shell evaluates whatever the hook prints.
+fn install_locked(root: &Path) -> Result<()> {
+    eprintln!(
+        "{} Installing missing project packages from zoi.lock...",
+        "::".bold().blue()
+    );
+    let status = Command::new(env::current_exe()?)
+        .args(["install", "--frozen-lockfile", "--yes"])
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(io::stderr()))
+        .status()?;
+    if !status.success() {
+        eprintln!(
+            "{} Installing the project packages failed with exit code {}",
+            "Warning:".yellow(),
+            status
+        );
+    }
+    Ok(())
+}
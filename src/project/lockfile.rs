@@ -4,7 +4,7 @@ use std::collections::HashSet;
 use std::fs;
 
 fn get_lockfile_path() -> Result<std::path::PathBuf> {
-    Ok(std::env::current_dir()?.join("zoi.lock"))
+    Ok(super::config::project_root()?.join("zoi.lock"))
 }
 
 pub fn read_zoi_lock() -> Result<types::ZoiLock> {
@@ -46,6 +46,8 @@ pub struct FrozenLockPackage {
     pub chosen_optionals: Vec<String>,
     pub dependencies: Vec<String>,
     pub git_sha: Option<String>,
+    pub provider_for: Vec<String>,
+    pub features: Vec<String>,
 }
 
 fn append_sub_package_if_needed(base_id: &str, sub_package: Option<&str>) -> String {
@@ -75,6 +77,8 @@ pub fn locked_packages(lockfile: &types::ZoiLock) -> Vec<FrozenLockPackage> {
                 chosen_optionals: detail.optionals_dependencies.clone(),
                 dependencies: detail.dependencies.clone(),
                 git_sha: detail.git_sha.clone(),
+                provider_for: detail.provider_for.clone(),
+                features: detail.features.clone(),
             });
         }
     }
@@ -91,6 +95,8 @@ pub fn locked_packages(lockfile: &types::ZoiLock) -> Vec<FrozenLockPackage> {
                     chosen_optionals: Vec::new(),
                     dependencies: Vec::new(),
                     git_sha: None,
+                    provider_for: Vec::new(),
+                    features: Vec::new(),
                 }),
         );
     }
@@ -1,6 +1,12 @@
 pub mod config;
+pub mod devenv;
+pub mod dotenv;
 pub mod environment;
 pub mod executor;
+pub mod hook;
 pub mod lockfile;
 pub mod runner;
+pub mod tasks;
 pub mod verify;
+pub mod watch;
+pub mod workspace;
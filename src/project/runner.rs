@@ -1,21 +1,120 @@
-use super::{config, executor};
+use super::tasks::{self, Fingerprint, TaskCache};
+use super::{config, dotenv, executor, workspace};
 use crate::utils;
 use anyhow::{Result, anyhow};
 use colored::*;
 use dialoguer::{Select, theme::ColorfulTheme};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
 
 pub fn run(cmd_alias: Option<&str>, args: &[String], config: &config::ProjectConfig) -> Result<()> {
+    let command_to_run = select_command(cmd_alias, args, config)?;
+
+    let waves = tasks::plan(&config.commands, &command_to_run.cmd)?;
+    let root = config.root_dir()?;
+    let mut cache = TaskCache::load(&root);
+    // Loaded once, so encrypted env files are decrypted once per run.
+    let project_env = dotenv::load(&root, &config.env_file, &HashMap::new())?;
+
+    for wave in waves {
+        let results: Vec<(&str, Result<Option<Fingerprint>>)> = wave
+            .par_iter()
+            .map(|spec| {
+                let task_args = if spec.cmd == command_to_run.cmd {
+                    args
+                } else {
+                    &[]
+                };
+                let result = run_task(
+                    spec,
+                    task_args,
+                    &root,
+                    config,
+                    &project_env,
+                    cache.get(&spec.cmd),
+                );
+                (spec.cmd.as_str(), result)
+            })
+            .collect();
+
+        let mut failure = None;
+        for (cmd, result) in results {
+            match result {
+                Ok(fingerprint) => cache.set(cmd, fingerprint),
+                Err(e) => {
+                    cache.set(cmd, None);
+                    failure.get_or_insert(anyhow!("Command '{}' failed: {}", cmd, e));
+                }
+            }
+        }
+        if let Err(e) = cache.save(&root) {
+            eprintln!("Warning: could not save the command cache: {}", e);
+        }
+        if let Some(e) = failure {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Runs `cmd_alias` in every workspace member that defines or inherits it,
+/// one after another, and reports the members it failed in.
+pub fn run_all(cmd_alias: &str, args: &[String], workspace: &workspace::Workspace) -> Result<()> {
+    let mut ran = 0;
+    let mut failed = Vec::new();
+    for member in &workspace.members {
+        if !member.config.commands.iter().any(|c| c.cmd == cmd_alias) {
+            continue;
+        }
+        ran += 1;
+        println!(
+            "\n{} {} ({})",
+            "::".bold().blue(),
+            member.config.name.bold(),
+            member.path
+        );
+        if let Err(e) = run(Some(cmd_alias), args, &member.config) {
+            eprintln!("{}: {}", "Error".red().bold(), e);
+            failed.push(member.config.name.as_str());
+        }
+    }
+
+    if ran == 0 {
+        return Err(anyhow!(
+            "No workspace member defines the command '{}'",
+            cmd_alias
+        ));
+    }
+    if !failed.is_empty() {
+        return Err(anyhow!(
+            "Command '{}' failed in {} of {} members: {}",
+            cmd_alias,
+            failed.len(),
+            ran,
+            failed.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Finds the command `cmd_alias` names, or asks for one when it is `None`.
+pub fn select_command(
+    cmd_alias: Option<&str>,
+    args: &[String],
+    config: &config::ProjectConfig,
+) -> Result<config::CommandSpec> {
     if config.commands.is_empty() {
         return Err(anyhow!("No commands defined in zoi.yaml"));
     }
 
-    let command_to_run = match cmd_alias {
-        Some(alias) => config
+    match cmd_alias {
+        Some(alias) => Ok(config
             .commands
             .iter()
             .find(|c| c.cmd == alias)
             .ok_or_else(|| anyhow!("Command alias '{}' not found in zoi.yaml", alias))?
-            .clone(),
+            .clone()),
         None => {
             if !args.is_empty() {
                 return Err(anyhow!("Cannot pass arguments when in interactive mode."));
@@ -28,13 +127,28 @@ pub fn run(cmd_alias: Option<&str>, args: &[String], config: &config::ProjectCon
                 .interact_opt()?
                 .ok_or(anyhow!("No command chosen."))?;
 
-            config.commands[selection].clone()
+            Ok(config.commands[selection].clone())
         }
-    };
+    }
+}
 
+/// Runs `spec` unless its inputs and outputs match `previous`, and returns
+/// the fingerprint to remember for it. `project_env` holds the variables of
+/// the project's own env files.
+fn run_task(
+    spec: &config::CommandSpec,
+    args: &[String],
+    root: &Path,
+    project: &config::ProjectConfig,
+    project_env: &HashMap<String, String>,
+    previous: Option<&Fingerprint>,
+) -> Result<Option<Fingerprint>> {
     let platform = utils::get_platform()?;
 
-    let run_cmd = match &command_to_run.run {
+    let Some(run) = &spec.run else {
+        return Ok(None);
+    };
+    let run_cmd = match run {
         config::PlatformOrString::String(s) => s.clone(),
         config::PlatformOrString::Platform(p) => p
             .get(&platform)
@@ -48,24 +162,44 @@ pub fn run(cmd_alias: Option<&str>, args: &[String], config: &config::ProjectCon
             })?,
     };
 
-    let env_vars = match &command_to_run.env {
-        config::PlatformOrEnvMap::EnvMap(m) => m.clone(),
-        config::PlatformOrEnvMap::Platform(p) => p
-            .get(&platform)
-            .or_else(|| p.get("default"))
-            .cloned()
-            .unwrap_or_default(),
-    };
+    let mut env_vars = project_env.clone();
+    env_vars.extend(dotenv::load(root, &spec.env_file, project_env)?);
+    env_vars.extend(project.env.for_platform(&platform));
+    env_vars.extend(spec.env.for_platform(&platform));
 
-    println!(
-        "{} Running command: {}...",
-        "::".bold().blue(),
-        command_to_run.cmd.bold()
-    );
     let mut full_command = run_cmd;
     if !args.is_empty() {
         full_command.push(' ');
         full_command.push_str(&args.join(" "));
     }
-    executor::run_shell_command(&full_command, &env_vars)
+
+    let inputs = tasks::input_fingerprint(root, spec, &full_command, &env_vars)?;
+    if let (Some((command, inputs)), Some(previous)) = (&inputs, previous)
+        && previous.command == *command
+        && previous.inputs == *inputs
+        && tasks::output_hash(root, spec)?.as_ref() == Some(&previous.outputs)
+    {
+        println!(
+            "{} Skipping command: {} (inputs and outputs unchanged)",
+            "::".bold().blue(),
+            spec.cmd.bold()
+        );
+        return Ok(Some(previous.clone()));
+    }
+
+    println!(
+        "{} Running command: {}...",
+        "::".bold().blue(),
+        spec.cmd.bold()
+    );
+    executor::run_shell_command(&full_command, root, &env_vars)?;
+
+    let Some((command, inputs)) = inputs else {
+        return Ok(None);
+    };
+    Ok(tasks::output_hash(root, spec)?.map(|outputs| Fingerprint {
+        command,
+        inputs,
+        outputs,
+    }))
 }
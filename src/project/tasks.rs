@@ -0,0 +1,181 @@
+use super::config::CommandSpec;
+use crate::pkg::hash;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const CACHE_FILE: &str = "tasks.json";
+
+/// Orders the commands `target` needs into waves. Every command only
+/// depends on commands in earlier waves, so each wave can run in parallel.
+pub fn plan<'a>(commands: &'a [CommandSpec], target: &str) -> Result<Vec<Vec<&'a CommandSpec>>> {
+    let by_name: HashMap<&str, &CommandSpec> =
+        commands.iter().map(|c| (c.cmd.as_str(), c)).collect();
+    let root = by_name
+        .get(target)
+        .ok_or_else(|| anyhow!("Command alias '{}' not found in zoi.yaml", target))?;
+
+    let mut needed = HashSet::new();
+    let mut stack = Vec::new();
+    visit(root, &by_name, &mut needed, &mut stack)?;
+
+    let mut done: HashSet<&str> = HashSet::new();
+    let mut waves = Vec::new();
+    while done.len() < needed.len() {
+        let wave: Vec<&CommandSpec> = commands
+            .iter()
+            .filter(|c| needed.contains(c.cmd.as_str()) && !done.contains(c.cmd.as_str()))
+            .filter(|c| c.depends.iter().all(|d| done.contains(d.as_str())))
+            .collect();
+        done.extend(wave.iter().map(|c| c.cmd.as_str()));
+        waves.push(wave);
+    }
+    Ok(waves)
+}
+
+fn visit<'a>(
+    spec: &'a CommandSpec,
+    by_name: &HashMap<&str, &'a CommandSpec>,
+    needed: &mut HashSet<&'a str>,
+    stack: &mut Vec<&'a str>,
+) -> Result<()> {
+    if let Some(pos) = stack.iter().position(|c| *c == spec.cmd) {
+        let mut cycle = stack[pos..].to_vec();
+        cycle.push(&spec.cmd);
+        return Err(anyhow!(
+            "Commands in zoi.yaml depend on each other in a cycle: {}",
+            cycle.join(" -> ")
+        ));
+    }
+    if !needed.insert(&spec.cmd) {
+        return Ok(());
+    }
+    stack.push(&spec.cmd);
+    for dep in &spec.depends {
+        let dep_spec = by_name.get(dep.as_str()).ok_or_else(|| {
+            anyhow!(
+                "Command '{}' depends on unknown command '{}'",
+                spec.cmd,
+                dep
+            )
+        })?;
+        visit(dep_spec, by_name, needed, stack)?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// Files under `root` matched by `patterns`. A matched directory stands for
+/// every file in it. Returns `None` if a pattern matches nothing.
+pub fn expand(root: &Path, patterns: &[String]) -> Result<Option<Vec<PathBuf>>> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let full = root.join(pattern);
+        let mut matched = false;
+        for entry in glob::glob(&full.to_string_lossy())? {
+            let path = entry?;
+            matched = true;
+            if path.is_dir() {
+                files.extend(
+                    WalkDir::new(&path)
+                        .into_iter()
+                        .filter_map(|e| e.ok())
+                        .filter(|e| e.file_type().is_file())
+                        .map(|e| e.into_path()),
+                );
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+        if !matched {
+            return Ok(None);
+        }
+    }
+    Ok(Some(files))
+}
+
+/// What a command saw and produced on its last successful run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// Hash of the resolved command line and environment.
+    pub command: String,
+    pub inputs: String,
+    pub outputs: String,
+}
+
+/// Fingerprint of the inputs of `spec` as the command would run now, or
+/// `None` if it declares no inputs or some are missing.
+pub fn input_fingerprint(
+    root: &Path,
+    spec: &CommandSpec,
+    command: &str,
+    env: &HashMap<String, String>,
+) -> Result<Option<(String, String)>> {
+    if spec.inputs.is_empty() {
+        return Ok(None);
+    }
+    let Some(files) = expand(root, &spec.inputs)? else {
+        return Ok(None);
+    };
+    // Only a hash is stored, since the environment may hold secrets.
+    let env: BTreeMap<_, _> = env.iter().collect();
+    let command = hex::encode(Sha512::digest(format!("{}\n{:?}", command, env)));
+    Ok(Some((command, hash::calculate_files_hash(root, &files)?)))
+}
+
+/// Hash of the outputs of `spec`, or `None` if any of them is missing.
+pub fn output_hash(root: &Path, spec: &CommandSpec) -> Result<Option<String>> {
+    match expand(root, &spec.outputs)? {
+        Some(files) => Ok(Some(hash::calculate_files_hash(root, &files)?)),
+        None => Ok(None),
+    }
+}
+
+/// Fingerprints of the last successful run of each command, kept in the
+/// project's `.zoi/cache`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TaskCache {
+    #[serde(default)]
+    tasks: BTreeMap<String, Fingerprint>,
+}
+
+impl TaskCache {
+    fn path(root: &Path) -> PathBuf {
+        root.join(".zoi").join("cache").join(CACHE_FILE)
+    }
+
+    pub fn load(root: &Path) -> TaskCache {
+        fs::read_to_string(Self::path(root))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = Self::path(root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, cmd: &str) -> Option<&Fingerprint> {
+        self.tasks.get(cmd)
+    }
+
+    pub fn set(&mut self, cmd: &str, fingerprint: Option<Fingerprint>) {
+        match fingerprint {
+            Some(f) => {
+                self.tasks.insert(cmd.to_string(), f);
+            }
+            None => {
+                self.tasks.remove(cmd);
+            }
+        }
+    }
+}
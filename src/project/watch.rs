@@ -0,0 +1,357 @@
+use super::{config, runner, tasks};
+use anyhow::{Result, anyhow};
+use colored::*;
+use crossterm::{
+    cursor::MoveTo,
+    execute,
+    terminal::{Clear, ClearType},
+};
+use glob::{MatchOptions, Pattern};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How long the tree has to stay quiet before a change triggers a run.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+const POLL: Duration = Duration::from_millis(100);
+/// How long a stopped run gets to exit before it is killed.
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// Directories whose changes never trigger a run.
+const IGNORED_DIRS: &[&str] = &[".git", ".zoi"];
+
+static STOP: AtomicBool = AtomicBool::new(false);
+/// Whether the current run was made the terminal's foreground group.
+static HANDED_OVER: AtomicBool = AtomicBool::new(false);
+
+/// Globs to watch for `target`: the `watch` list of it and every command
+/// it depends on, or their `inputs` where `watch` is empty.
+pub fn watch_patterns(commands: &[config::CommandSpec], target: &str) -> Result<Vec<String>> {
+    let mut patterns = Vec::new();
+    for spec in tasks::plan(commands, target)?.into_iter().flatten() {
+        let globs = if spec.watch.is_empty() {
+            &spec.inputs
+        } else {
+            &spec.watch
+        };
+        for glob in globs {
+            if !patterns.contains(glob) {
+                patterns.push(glob.clone());
+            }
+        }
+    }
+    if patterns.is_empty() {
+        return Err(anyhow!(
+            "Command '{}' has no `inputs` or `watch` globs to watch",
+            target
+        ));
+    }
+    Ok(patterns)
+}
+
+/// Compiled watch globs, matched against paths relative to the project root.
+pub struct WatchSet {
+    patterns: Vec<Pattern>,
+}
+
+impl WatchSet {
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Pattern::new(p.trim_start_matches("./")))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Whether a change to `rel` should trigger a run. Like `inputs`, a
+    /// glob matching a directory covers everything inside it.
+    pub fn matches(&self, rel: &Path) -> bool {
+        let parts: Vec<String> = rel
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        if parts
+            .first()
+            .is_some_and(|first| IGNORED_DIRS.contains(&first.as_str()))
+        {
+            return false;
+        }
+
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        (1..=parts.len()).any(|n| {
+            let candidate = parts[..n].join("/");
+            self.patterns
+                .iter()
+                .any(|p| p.matches_with(&candidate, options))
+        })
+    }
+}
+
+/// Runs `zoi run` for the chosen command, then restarts it whenever one of
+/// its watched files changes, until interrupted.
+pub fn run(
+    cmd_alias: Option<&str>,
+    args: &[String],
+    config: &config::ProjectConfig,
+    member: Option<&str>,
+) -> Result<()> {
+    let spec = runner::select_command(cmd_alias, args, config)?;
+    let patterns = watch_patterns(&config.commands, &spec.cmd)?;
+    let set = WatchSet::new(&patterns)?;
+    let root = config.root_dir()?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res| {
+        let _ = tx.send(res);
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+    install_stop_handler()?;
+
+    let mut changed: Option<PathBuf> = None;
+    loop {
+        clear_screen();
+        if let Some(path) = changed.take() {
+            println!("{} Changed: {}", "::".bold().blue(), path.display());
+        }
+        println!(
+            "{} Watching {} for {} (Ctrl-C to stop)",
+            "::".bold().blue(),
+            patterns.join(", ").cyan(),
+            spec.cmd.bold()
+        );
+        let mut child = Some(spawn(&spec.cmd, args, member)?);
+
+        loop {
+            if STOP.load(Ordering::SeqCst) {
+                if let Some(child) = child.take() {
+                    terminate(child)?;
+                }
+                return Ok(());
+            }
+            if let Some(running) = child.as_mut()
+                && let Some(status) = running.try_wait()?
+            {
+                child = None;
+                take_terminal();
+                if interrupted(&status) {
+                    return Ok(());
+                }
+                if status.success() {
+                    println!("{} Done. Waiting for changes...", "::".bold().green());
+                } else {
+                    println!(
+                        "{} Command failed with exit code {}. Waiting for changes...",
+                        "::".bold().red(),
+                        status
+                    );
+                }
+            }
+            if let Some(path) = next_change(&rx, &root, &set, POLL)? {
+                changed = Some(path);
+                break;
+            }
+        }
+
+        // Let bursts of writes (editors, formatters) settle into one run.
+        let mut quiet_since = Instant::now();
+        while quiet_since.elapsed() < DEBOUNCE && !STOP.load(Ordering::SeqCst) {
+            if next_change(&rx, &root, &set, POLL)?.is_some() {
+                quiet_since = Instant::now();
+            }
+        }
+        if let Some(child) = child.take()
+            && interrupted(&terminate(child)?)
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Waits up to `timeout` for a change to a watched file and returns its
+/// path relative to `root`.
+fn next_change(
+    rx: &Receiver<notify::Result<Event>>,
+    root: &Path,
+    set: &WatchSet,
+    timeout: Duration,
+) -> Result<Option<PathBuf>> {
+    let event = match rx.recv_timeout(timeout) {
+        Ok(Ok(event)) => event,
+        Ok(Err(e)) => {
+            eprintln!("{} File watcher error: {}", "Warning:".yellow(), e);
+            return Ok(None);
+        }
+        Err(RecvTimeoutError::Timeout) => return Ok(None),
+        Err(RecvTimeoutError::Disconnected) => {
+            return Err(anyhow!("The file watcher stopped unexpectedly"));
+        }
+    };
+    if !matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+        return Ok(None);
+    }
+    Ok(event
+        .paths
+        .iter()
+        .map(|p| p.strip_prefix(root).unwrap_or(p))
+        .find(|rel| set.matches(rel))
+        .map(Path::to_path_buf))
+}
+
+fn clear_screen() {
+    let mut stdout = io::stdout();
+    if stdout.is_terminal() {
+        let _ = execute!(stdout, Clear(ClearType::All), MoveTo(0, 0));
+    }
+}
+
+/// Starts `zoi run` for `cmd` in its own process group, so a restart can
+/// stop everything the command spawned, and makes that group the terminal's
+/// foreground group so the command can read input.
+fn spawn(cmd: &str, args: &[String], member: Option<&str>) -> Result<Child> {
+    let mut command = Command::new(env::current_exe()?);
+    command.arg("run");
+    if let Some(member) = member {
+        command.arg("--member").arg(member);
+    }
+    command.arg(cmd).arg("--").args(args);
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{SigHandler, Signal, signal};
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+        // SAFETY: restoring a default disposition is async-signal-safe.
+        unsafe {
+            command.pre_exec(|| {
+                // Ignored dispositions survive exec; the run must not inherit
+                // the one this process uses for SIGTTOU.
+                signal(Signal::SIGTTOU, SigHandler::SigDfl)?;
+                Ok(())
+            });
+        }
+    }
+    let child = command.spawn()?;
+    give_terminal(&child);
+    Ok(child)
+}
+
+#[cfg(unix)]
+fn give_terminal(child: &Child) {
+    use nix::sys::signal::{Signal, killpg};
+    use nix::unistd::{Pid, getpgrp, tcgetpgrp, tcsetpgrp};
+
+    let stdin = io::stdin();
+    // Only the foreground job may hand the terminal on.
+    if !stdin.is_terminal() || tcgetpgrp(&stdin) != Ok(getpgrp()) {
+        return;
+    }
+    let group = Pid::from_raw(child.id() as i32);
+    if tcsetpgrp(&stdin, group).is_ok() {
+        HANDED_OVER.store(true, Ordering::SeqCst);
+    }
+    // It may have been stopped for reading before it got the terminal.
+    let _ = killpg(group, Signal::SIGCONT);
+}
+
+#[cfg(not(unix))]
+fn give_terminal(_child: &Child) {}
+
+/// Makes this process the terminal's foreground group again once a run is
+/// over.
+#[cfg(unix)]
+fn take_terminal() {
+    use nix::unistd::{getpgrp, tcsetpgrp};
+
+    if HANDED_OVER.swap(false, Ordering::SeqCst) {
+        let _ = tcsetpgrp(io::stdin(), getpgrp());
+    }
+}
+
+#[cfg(not(unix))]
+fn take_terminal() {}
+
+/// Whether a run ended because of Ctrl-C, which goes to the run while it
+/// holds the terminal and stops watching as well.
+#[cfg(unix)]
+fn interrupted(status: &ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal() == Some(nix::libc::SIGINT) || status.code() == Some(130)
+}
+
+#[cfg(not(unix))]
+fn interrupted(_status: &ExitStatus) -> bool {
+    false
+}
+
+/// Stops a run and everything it started, and returns how it exited.
+#[cfg(unix)]
+fn terminate(mut child: Child) -> Result<ExitStatus> {
+    use nix::sys::signal::{Signal, killpg};
+    use nix::unistd::Pid;
+
+    let group = Pid::from_raw(child.id() as i32);
+    let _ = killpg(group, Signal::SIGTERM);
+    let deadline = Instant::now() + KILL_GRACE;
+    while child.try_wait()?.is_none() && Instant::now() < deadline {
+        std::thread::sleep(POLL);
+    }
+    // Also catches anything the command left running in the background.
+    let _ = killpg(group, Signal::SIGKILL);
+    let status = child.wait()?;
+    take_terminal();
+    Ok(status)
+}
+
+#[cfg(not(unix))]
+fn terminate(mut child: Child) -> Result<ExitStatus> {
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .output();
+    let _ = child.kill();
+    Ok(child.wait()?)
+}
+
+/// Remembers Ctrl-C and SIGTERM so the current run can be stopped before
+/// exiting. While a run holds the terminal, Ctrl-C reaches it instead and
+/// its exit status tells the loop to stop.
+#[cfg(unix)]
+fn install_stop_handler() -> Result<()> {
+    use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+
+    extern "C" fn on_stop(_: nix::libc::c_int) {
+        STOP.store(true, Ordering::SeqCst);
+    }
+
+    let action = SigAction::new(
+        SigHandler::Handler(on_stop),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    for signal in [Signal::SIGINT, Signal::SIGTERM] {
+        // SAFETY: the handler only stores to an atomic.
+        unsafe { sigaction(signal, &action) }?;
+    }
+    // Taking the terminal back, and printing, while a run holds it would
+    // otherwise stop this process.
+    // SAFETY: ignoring a signal installs no handler.
+    unsafe { nix::sys::signal::signal(Signal::SIGTTOU, SigHandler::SigIgn) }?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn install_stop_handler() -> Result<()> {
+    Ok(())
+}
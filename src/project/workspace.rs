@@ -0,0 +1,280 @@
+use super::config::{self, ProjectConfig, ProjectLocalConfig, ShellSpec, WorkspaceSpec};
+use crate::pkg::resolve;
+use anyhow::{Result, anyhow};
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+/// A workspace root zoi.yaml and the member projects it declares.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub config: ProjectConfig,
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// Directory relative to the workspace root, with `/` separators.
+    pub path: String,
+    /// The member's zoi.yaml merged over the workspace root's.
+    pub config: ProjectConfig,
+}
+
+/// Finds the project root for `start`: the nearest directory with a
+/// zoi.yaml, or the workspace above it if that lists it as a member.
+pub fn find_root(start: &Path) -> Option<PathBuf> {
+    let nearest = config::find_nearest(start)?;
+    for dir in nearest.ancestors() {
+        if !dir.join(config::CONFIG_FILE).is_file() {
+            continue;
+        }
+        let Ok(cfg) = config::read(dir) else {
+            continue;
+        };
+        if let Some(spec) = &cfg.workspace
+            && (dir == nearest
+                || member_dirs(dir, spec).is_ok_and(|members| members.contains(&nearest)))
+        {
+            return Some(dir.to_path_buf());
+        }
+    }
+    Some(nearest)
+}
+
+/// Directories under `root` matched by the member globs that contain a
+/// zoi.yaml, sorted.
+fn member_dirs(root: &Path, spec: &WorkspaceSpec) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for pattern in &spec.members {
+        let full = root.join(pattern.trim_end_matches('/'));
+        for entry in glob::glob(&full.to_string_lossy())? {
+            let dir = entry?;
+            if dir != root && dir.join(config::CONFIG_FILE).is_file() {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs.sort();
+    dirs.dedup();
+    Ok(dirs)
+}
+
+impl Workspace {
+    /// The workspace enclosing the current directory, if any.
+    pub fn load() -> Result<Option<Workspace>> {
+        let Some(root) = find_root(&env::current_dir()?) else {
+            return Ok(None);
+        };
+        let config = config::read(&root)?;
+        if config.workspace.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Workspace::from_root(config)?))
+    }
+
+    pub fn from_root(config: ProjectConfig) -> Result<Workspace> {
+        let root = config.root_dir()?;
+        let spec = config.workspace.clone().unwrap_or_default();
+        let mut members = Vec::new();
+        for dir in member_dirs(&root, &spec)? {
+            let member = config::read(&dir)?;
+            let path = relative_path(&root, &dir);
+            members.push(Member {
+                path,
+                config: merge(&config, member),
+            });
+        }
+        Ok(Workspace { config, members })
+    }
+
+    /// Config to use in `dir`: that of the member containing it, or the
+    /// root's own.
+    pub fn config_for(&self, dir: &Path) -> ProjectConfig {
+        self.members
+            .iter()
+            .filter(|m| m.config.dir.as_ref().is_some_and(|d| dir.starts_with(d)))
+            .max_by_key(|m| m.path.len())
+            .map(|m| m.config.clone())
+            .unwrap_or_else(|| self.config.clone())
+    }
+
+    /// Finds a member by its `name` or its path.
+    pub fn member(&self, name: &str) -> Result<&Member> {
+        let path = name.trim_start_matches("./").trim_end_matches('/');
+        self.members
+            .iter()
+            .find(|m| m.config.name == name || m.path == path)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self
+                    .members
+                    .iter()
+                    .map(|m| m.config.name.as_str())
+                    .collect();
+                anyhow!(
+                    "No workspace member named '{}'. Members: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// Packages of the root and every member, for the shared zoi.lock.
+    /// A member's pin replaces the root's, as it does in the member's
+    /// merged config, but two members pinning different versions of the
+    /// same package are an error.
+    pub fn all_pkgs(&self) -> Result<Vec<String>> {
+        // Name, spec, and the member that pinned it (`None` for the root).
+        let mut pkgs: Vec<(String, String, Option<&str>)> = self
+            .config
+            .pkgs
+            .iter()
+            .map(|spec| (split_pkg(spec).0, spec.clone(), None))
+            .collect();
+        for member in &self.members {
+            let origin = member.config.name.as_str();
+            for spec in &member.config.pkgs {
+                if self.config.pkgs.contains(spec) {
+                    continue;
+                }
+                let (name, version) = split_pkg(spec);
+                match pkgs.iter_mut().find(|(n, _, _)| *n == name) {
+                    None => pkgs.push((name, spec.clone(), Some(origin))),
+                    Some((_, existing, existing_origin)) => {
+                        if existing == spec || version.is_none() {
+                            continue;
+                        }
+                        if let Some(other) = existing_origin
+                            && split_pkg(existing).1.is_some()
+                        {
+                            return Err(anyhow!(
+                                "Workspace members need different versions of '{}': '{}' ({}) and '{}' ({}). \
+                                 A workspace shares one zoi.lock, so pin the same version in both.",
+                                name,
+                                existing,
+                                other,
+                                spec,
+                                origin
+                            ));
+                        }
+                        *existing = spec.clone();
+                        *existing_origin = Some(origin);
+                    }
+                }
+            }
+        }
+        Ok(pkgs.into_iter().map(|(_, spec, _)| spec).collect())
+    }
+}
+
+fn relative_path(root: &Path, dir: &Path) -> String {
+    dir.strip_prefix(root)
+        .unwrap_or(dir)
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn split_pkg(spec: &str) -> (String, Option<String>) {
+    match resolve::parse_source_string(spec) {
+        Ok(req) => (req.name, req.version_spec),
+        Err(_) => (spec.to_string(), None),
+    }
+}
+
+/// Makes the env file paths of `files` independent of where they are used.
+fn absolute_env_files(dir: &Option<PathBuf>, files: &[String]) -> Vec<String> {
+    match dir {
+        Some(dir) => files
+            .iter()
+            .map(|f| dir.join(f).to_string_lossy().into_owned())
+            .collect(),
+        None => files.to_vec(),
+    }
+}
+
+/// Layers `member` over `root`. Member packages replace root packages of
+/// the same name, and root commands and environments the member does not
+/// define are inherited and run in the member's directory.
+fn merge(root: &ProjectConfig, member: ProjectConfig) -> ProjectConfig {
+    let mut pkgs: Vec<String> = root
+        .pkgs
+        .iter()
+        .filter(|r| {
+            let name = split_pkg(r).0;
+            !member.pkgs.iter().any(|m| split_pkg(m).0 == name)
+        })
+        .cloned()
+        .collect();
+    pkgs.extend(member.pkgs.iter().cloned());
+
+    let registries = match (&root.registries, &member.registries) {
+        (None, None) => None,
+        (r, m) => {
+            let mut all: Vec<String> = r.iter().flatten().cloned().collect();
+            for reg in m.iter().flatten() {
+                if !all.contains(reg) {
+                    all.push(reg.clone());
+                }
+            }
+            Some(all)
+        }
+    };
+
+    let mut commands = member.commands.clone();
+    for cmd in &root.commands {
+        if !commands.iter().any(|c| c.cmd == cmd.cmd) {
+            let mut cmd = cmd.clone();
+            cmd.env_file = absolute_env_files(&root.dir, &cmd.env_file);
+            commands.push(cmd);
+        }
+    }
+    let mut environments = member.environments.clone();
+    for environment in &root.environments {
+        if !environments.iter().any(|e| e.cmd == environment.cmd) {
+            let mut environment = environment.clone();
+            environment.env_file = absolute_env_files(&root.dir, &environment.env_file);
+            environments.push(environment);
+        }
+    }
+
+    let shell = match (&root.shell, &member.shell) {
+        (None, None) => None,
+        (r, m) => {
+            let r = r.clone().unwrap_or_default();
+            let m = m.clone().unwrap_or_default();
+            let mut env_file = absolute_env_files(&root.dir, &r.env_file);
+            env_file.extend(absolute_env_files(&member.dir, &m.env_file));
+            Some(ShellSpec {
+                env: r.env.merged_with(&m.env),
+                env_file,
+                auto_install: r.auto_install || m.auto_install,
+            })
+        }
+    };
+
+    let mut env_file = absolute_env_files(&root.dir, &root.env_file);
+    env_file.extend(absolute_env_files(&member.dir, &member.env_file));
+
+    let mut packages = root.packages.clone();
+    packages.extend(member.packages.iter().cloned());
+
+    ProjectConfig {
+        name: member.name,
+        registries,
+        packages,
+        pkgs,
+        config: ProjectLocalConfig {
+            local: root.config.local || member.config.local,
+        },
+        commands,
+        environments,
+        shell,
+        env_file,
+        env: root.env.merged_with(&member.env),
+        workspace: None,
+        dir: member.dir,
+    }
+}
@@ -258,6 +258,9 @@ pub fn set_path_read_only(path: &Path) -> anyhow::Result<()> {
     }
     for entry in WalkDir::new(path) {
         let entry = entry?;
+        if entry.path_is_symlink() {
+            continue;
+        }
         let mut perms = fs::metadata(entry.path())?.permissions();
         if !perms.readonly() {
             perms.set_readonly(true);
@@ -273,6 +276,9 @@ pub fn set_path_writable(path: &Path) -> anyhow::Result<()> {
     }
     for entry in WalkDir::new(path) {
         let entry = entry?;
+        if entry.path_is_symlink() {
+            continue;
+        }
         let mut perms = fs::metadata(entry.path())?.permissions();
         if perms.readonly() {
             #[cfg(unix)]
@@ -29,6 +29,9 @@ fn test_manifest(name: &str, version: &str) -> InstallManifest {
         service: None,
         installed_files: vec![],
         installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
     }
 }
 
@@ -1,5 +1,7 @@
 use std::fs;
+use std::path::{Path, PathBuf};
 use tempfile::tempdir;
+use zoi::pkg::backup::{self, Outcome};
 
 #[test]
 fn test_zoinew_upgrade_logic() {
@@ -18,17 +20,9 @@ fn test_zoinew_upgrade_logic() {
 
     fs::write(&new_config_path, "user-custom-setting: false").unwrap();
 
-    if old_config_path.exists() {
-        if new_config_path.exists() {
-            let extension = new_config_path
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or("");
-            let zoinew_path = new_config_path.with_extension(format!("{}.zoinew", extension));
-            fs::rename(&new_config_path, &zoinew_path).unwrap();
-        }
-        fs::rename(&old_config_path, &new_config_path).unwrap();
-    }
+    // Installed before shipped copies were kept, so both sides count as changed.
+    let changes =
+        backup::carry_over(&old_ver_dir, &new_ver_dir, &[config_rel_path.to_string()]).unwrap();
 
     assert_eq!(
         fs::read_to_string(&new_config_path).unwrap(),
@@ -36,11 +30,127 @@ fn test_zoinew_upgrade_logic() {
     );
 
     let zoinew_expected = new_ver_dir.join("etc/config.yaml.zoinew");
-    assert!(zoinew_expected.exists());
+    assert_eq!(
+        changes[0].outcome,
+        Outcome::Conflict {
+            zoinew: zoinew_expected.clone()
+        }
+    );
     assert_eq!(
         fs::read_to_string(zoinew_expected).unwrap(),
         "user-custom-setting: false"
     );
+    // The old version keeps its copy for rollbacks.
+    assert!(old_config_path.exists());
+}
+
+/// Installs `shipped` as version `version` of a package with `etc/app.conf`
+/// as its backup file.
+fn install(root: &Path, version: &str, shipped: &str) -> PathBuf {
+    let version_dir = root.join(version);
+    fs::create_dir_all(version_dir.join("etc")).unwrap();
+    fs::write(version_dir.join("etc/app.conf"), shipped).unwrap();
+    backup::save_shipped(&version_dir, &["etc/app.conf".to_string()]).unwrap();
+    version_dir
+}
+
+fn upgrade(old: &Path, new: &Path) -> Outcome {
+    let changes = backup::carry_over(old, new, &["etc/app.conf".to_string()]).unwrap();
+    changes[0].outcome.clone()
+}
+
+#[test]
+fn test_three_way_upgrade_applies_one_sided_changes() {
+    let dir = tempdir().unwrap();
+    let base = "port = 80\nworkers = 4\n";
+
+    // Only the package changed it: the new default is installed.
+    let old = install(&dir.path().join("a"), "1.0.0", base);
+    let new = install(&dir.path().join("a"), "2.0.0", "port = 8080\nworkers = 4\n");
+    assert_eq!(upgrade(&old, &new), Outcome::Updated);
+    assert_eq!(
+        fs::read_to_string(new.join("etc/app.conf")).unwrap(),
+        "port = 8080\nworkers = 4\n"
+    );
+
+    // Only the user changed it: their copy is kept.
+    let old = install(&dir.path().join("b"), "1.0.0", base);
+    fs::write(old.join("etc/app.conf"), "port = 80\nworkers = 16\n").unwrap();
+    let new = install(&dir.path().join("b"), "2.0.0", base);
+    assert_eq!(upgrade(&old, &new), Outcome::Kept);
+    assert_eq!(
+        fs::read_to_string(new.join("etc/app.conf")).unwrap(),
+        "port = 80\nworkers = 16\n"
+    );
+    assert!(!new.join("etc/app.conf.zoinew").exists());
+
+    // Both changed it the same way.
+    let old = install(&dir.path().join("c"), "1.0.0", base);
+    fs::write(old.join("etc/app.conf"), "port = 9000\n").unwrap();
+    let new = install(&dir.path().join("c"), "2.0.0", "port = 9000\n");
+    assert_eq!(upgrade(&old, &new), Outcome::Unchanged);
+}
+
+#[test]
+fn test_conflicting_changes_are_merged_later() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("pkg");
+    let old = install(&root, "1.0.0", "port = 80\nlog = info\nworkers = 4\n");
+    fs::write(
+        old.join("etc/app.conf"),
+        "port = 80\nlog = info\nworkers = 16\n",
+    )
+    .unwrap();
+    let new = install(&root, "2.0.0", "port = 8080\nlog = info\nworkers = 4\n");
+
+    let zoinew = new.join("etc/app.conf.zoinew");
+    assert_eq!(
+        upgrade(&old, &new),
+        Outcome::Conflict {
+            zoinew: zoinew.clone()
+        }
+    );
+    // Shipped copies stay pristine for the next upgrade.
+    assert_eq!(
+        fs::read_to_string(backup::shipped_path(&new, "etc/app.conf")).unwrap(),
+        "port = 8080\nlog = info\nworkers = 4\n"
+    );
+
+    let pending = backup::Pending {
+        path: new.join("etc/app.conf"),
+        zoinew: zoinew.clone(),
+        base: Some(new.join(".zoi-merge-base/etc/app.conf")),
+    };
+    let merged = backup::merge(&pending).unwrap().unwrap();
+    assert_eq!(merged, "port = 8080\nlog = info\nworkers = 16\n");
+
+    let diff = backup::diff(&pending.path, &zoinew, false)
+        .unwrap()
+        .unwrap();
+    assert!(diff.contains("-port = 80\n+port = 8080"));
+
+    backup::resolve(&pending, merged.as_bytes()).unwrap();
+    assert!(!zoinew.exists());
+    assert!(!new.join(".zoi-merge-base/etc/app.conf").exists());
+    assert_eq!(fs::read_to_string(&pending.path).unwrap(), merged);
+}
+
+#[test]
+fn test_merge_without_base_conflicts() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("app.conf");
+    fs::write(&path, "mine\n").unwrap();
+    let zoinew = backup::zoinew_path(&path);
+    fs::write(&zoinew, "theirs\n").unwrap();
+
+    let pending = backup::Pending {
+        path,
+        zoinew,
+        base: None,
+    };
+    let conflicted = backup::merge(&pending).unwrap().unwrap_err();
+    assert!(conflicted.contains("<<<<<<<"));
+    assert!(conflicted.contains("mine") && conflicted.contains("theirs"));
 }
 
 #[test]
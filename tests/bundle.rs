@@ -0,0 +1,159 @@
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+use zoi::pkg::{bundle, cache, config, db, pgp, types};
+
+mod common;
+
+const REPO_YAML: &str = r#"name: Zoidberg
+description: Bundle test registry
+handle: zoidberg
+git:
+  - type: main
+    url: https://example.invalid/zoidberg.git
+pkg:
+  - type: main
+    url: https://example.invalid/pkgs/{repo}/{name}/{version}
+    hash: https://example.invalid/pkgs/{repo}/{name}/{version}/{name}.hash
+repos:
+  - name: core
+    type: official
+    active: true
+"#;
+
+#[test]
+fn test_bundle_export_and_import() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    let home = root.join("home");
+    fs::create_dir_all(&home).unwrap();
+    let db_dir = root.join("db");
+    ctx.set_env_var("HOME", &home);
+    ctx.set_env_var("ZOI_DB_DIR", &db_dir);
+    ctx.set_sysroot(root.clone());
+    ctx.set_offline(true);
+
+    let reg = db_dir.join("zoidberg");
+    let pkg_dir = reg.join("core").join("hello");
+    fs::create_dir_all(&pkg_dir).unwrap();
+    fs::write(reg.join("repo.yaml"), REPO_YAML).unwrap();
+    fs::write(
+        pkg_dir.join("hello.pkg.lua"),
+        r#"metadata({
+  name = "hello",
+  repo = "core",
+  version = "1.0.0",
+  description = "Bundle test",
+  maintainer = { name = "Zoi", email = "zoi@example.com" },
+  types = { "pre-compiled" },
+})"#,
+    )
+    .unwrap();
+
+    config::write_user_config(&types::Config {
+        default_registry: Some(types::Registry {
+            handle: "zoidberg".to_string(),
+            url: "https://example.invalid/zoidberg.git".to_string(),
+            advisory_prefix: None,
+            authorities: None,
+        }),
+        repos: vec!["core".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let platform = zoi::utils::get_platform().unwrap();
+    let archive_name = format!("hello-1.0.0-{}.pkg.tar.zst", platform);
+    let archive_cache = cache::get_archive_cache_root().unwrap();
+    fs::create_dir_all(&archive_cache).unwrap();
+    fs::write(archive_cache.join(&archive_name), b"archive").unwrap();
+    fs::write(
+        archive_cache.join(format!("{}.hash", archive_name)),
+        "deadbeef",
+    )
+    .unwrap();
+
+    let out = root.join("out.zoibundle");
+    let manifest = bundle::export_bundle(&out, &["hello".to_string()], None).unwrap();
+    assert_eq!(manifest.registries.len(), 1);
+    assert_eq!(manifest.archives, vec![archive_name.clone()]);
+
+    fs::remove_dir_all(&db_dir).unwrap();
+    fs::remove_dir_all(&archive_cache).unwrap();
+    fs::remove_file(home.join(".zoi").join("config.yaml")).ok();
+    config::write_user_config(&types::Config::default()).unwrap();
+
+    let summary = bundle::import_bundle(&out, false, true).unwrap();
+    assert_eq!(summary.registries, vec!["zoidberg".to_string()]);
+    assert_eq!(summary.archives, 1);
+    assert!(archive_cache.join(&archive_name).exists());
+    assert_eq!(
+        cache::read_cached_sidecar(&archive_name, "hash").as_deref(),
+        Some("deadbeef")
+    );
+
+    let packages = db::list_all_packages("zoidberg").unwrap();
+    assert!(packages.iter().any(|p| p.name == "hello"));
+    let cfg = config::read_config().unwrap();
+    assert_eq!(
+        cfg.default_registry.map(|r| r.handle).as_deref(),
+        Some("zoidberg")
+    );
+
+    // An existing git checkout is only replaced on request.
+    fs::create_dir_all(db_dir.join("zoidberg").join(".git")).unwrap();
+    let err = bundle::import_bundle(&out, false, true).unwrap_err();
+    assert!(err.to_string().contains("--replace"), "{}", err);
+    bundle::import_bundle(&out, true, true).unwrap();
+    fs::remove_dir_all(db_dir.join("zoidberg").join(".git")).unwrap();
+
+    // Registries with authorities need a snapshot signed by them.
+    config::write_user_config(&types::Config {
+        default_registry: Some(types::Registry {
+            handle: "zoidberg".to_string(),
+            url: "https://example.invalid/zoidberg.git".to_string(),
+            advisory_prefix: None,
+            authorities: Some(vec!["publisher".to_string()]),
+        }),
+        ..Default::default()
+    })
+    .unwrap();
+    let err = bundle::import_bundle(&out, false, true).unwrap_err();
+    assert!(err.to_string().contains("authorities"), "{}", err);
+
+    // Signing the export with an authority's key lets it in without --replace.
+    if !zoi::utils::command_exists("gpg") {
+        return;
+    }
+    let gnupg = root.join("gnupg");
+    fs::create_dir_all(&gnupg).unwrap();
+    ctx.set_env_var("GNUPGHOME", &gnupg);
+    let gpg = |args: &[&str]| {
+        let output = Command::new("gpg")
+            .args(["--batch", "--pinentry-mode", "loopback", "--passphrase", ""])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output.stdout
+    };
+    gpg(&[
+        "--quick-gen-key",
+        "Publisher <publisher@example.com>",
+        "ed25519",
+        "sign",
+        "never",
+    ]);
+    let public_key = gpg(&["--armor", "--export", "publisher@example.com"]);
+    pgp::add_key_from_bytes(&public_key, "publisher", true).unwrap();
+
+    let signed = root.join("signed.zoibundle");
+    bundle::export_bundle(&signed, &[], Some("publisher@example.com")).unwrap();
+    let summary = bundle::import_bundle(&signed, false, true).unwrap();
+    assert_eq!(summary.registries, vec!["zoidberg".to_string()]);
+}
@@ -0,0 +1,130 @@
+use rusqlite::Connection;
+use tempfile::tempdir;
+use zoi::pkg::{db, doctor, types};
+
+mod common;
+
+fn column_names(conn: &Connection, table: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare("SELECT name FROM pragma_table_info(?1)")
+        .unwrap();
+    stmt.query_map([table], |row| row.get(0))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect()
+}
+
+#[test]
+fn test_fresh_database_is_at_latest_schema() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", dir.path());
+
+    let conn = db::open_connection("fresh").unwrap();
+    assert_eq!(db::schema_version(&conn).unwrap(), db::SCHEMA_VERSION);
+
+    let columns = column_names(&conn, "packages");
+    for col in ["bins", "dependencies", "source"] {
+        assert!(columns.iter().any(|c| c == col), "missing column {}", col);
+    }
+}
+
+#[test]
+fn test_migrations_apply_step_by_step() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", dir.path());
+
+    let conn = db::open_connection_no_setup("stepwise").unwrap();
+    for target in 1..=db::SCHEMA_VERSION {
+        db::migrate_to(&conn, target).unwrap();
+        assert_eq!(db::schema_version(&conn).unwrap(), target);
+    }
+
+    let pkg = types::Package {
+        name: "ripgrep".to_string(),
+        repo: "core".to_string(),
+        version: Some("14.0.0".to_string()),
+        description: "Fast grep".to_string(),
+        ..Default::default()
+    };
+    db::update_package(&conn, &pkg, "stepwise", None, None, None).unwrap();
+    drop(conn);
+
+    let results = db::search_packages("stepwise", "ripgrep").unwrap();
+    assert_eq!(results.len(), 1);
+}
+
+#[test]
+fn test_legacy_unversioned_database_is_migrated() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", dir.path());
+
+    let path = db::get_db_path("legacy").unwrap();
+    {
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE packages (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                sub_package TEXT,
+                repo TEXT NOT NULL,
+                version TEXT,
+                description TEXT,
+                package_type TEXT,
+                tags TEXT,
+                license TEXT,
+                registry TEXT,
+                scope TEXT,
+                reason TEXT,
+                UNIQUE(name, sub_package, repo, scope)
+            );
+            INSERT INTO packages (name, repo, version, description, package_type, tags, license)
+                VALUES ('hello', 'core', '1.0.0', 'Hello world', 'package', '[]', 'MIT');",
+        )
+        .unwrap();
+    }
+
+    let conn = db::open_connection("legacy").unwrap();
+    assert_eq!(db::schema_version(&conn).unwrap(), db::SCHEMA_VERSION);
+    let columns = column_names(&conn, "packages");
+    assert!(columns.iter().any(|c| c == "bins"));
+    assert!(columns.iter().any(|c| c == "dependencies"));
+    drop(conn);
+
+    let results = db::search_packages("legacy", "hello").unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].version.as_deref(), Some("1.0.0"));
+}
+
+#[test]
+fn test_newer_database_is_refused_and_reported() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", dir.path());
+
+    drop(db::open_connection("future").unwrap());
+    let path = db::get_db_path("future").unwrap();
+    {
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", db::SCHEMA_VERSION + 1)
+            .unwrap();
+    }
+
+    let err = db::open_connection("future").unwrap_err();
+    assert!(err.downcast_ref::<db::SchemaError>().is_some());
+    assert_eq!(
+        db::probe_schema_version(&path).unwrap(),
+        db::SCHEMA_VERSION + 1,
+        "a refused database must be left untouched"
+    );
+
+    let newer = doctor::check_database_schemas().unwrap();
+    assert_eq!(newer.len(), 1);
+    assert_eq!(newer[0].0, path);
+
+    db::remove_database("future").unwrap();
+    let conn = db::open_connection("future").unwrap();
+    assert_eq!(db::schema_version(&conn).unwrap(), db::SCHEMA_VERSION);
+}
@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::install::{installer, plan, resolver::InstallNode};
+use zoi::pkg::package::delta;
+use zoi::pkg::{cache, helper, local, serve, types};
+
+mod common;
+
+/// Deterministic, poorly compressible bytes.
+fn noise(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as u8
+        })
+        .collect()
+}
+
+fn old_and_new() -> (Vec<u8>, Vec<u8>) {
+    let old = noise(256 * 1024, 7);
+    let mut new = old.clone();
+    new[1000..1100].copy_from_slice(&noise(100, 9));
+    new.extend(noise(4096, 11));
+    (old, new)
+}
+
+#[test]
+fn test_delta_round_trip() {
+    let dir = tempdir().unwrap();
+    let (old, new) = old_and_new();
+    let old_path = dir.path().join("old");
+    let new_path = dir.path().join("new");
+    fs::write(&old_path, &old).unwrap();
+    fs::write(&new_path, &new).unwrap();
+
+    let delta_path = dir.path().join("new.delta");
+    delta::create(&old_path, &new_path, &delta_path).unwrap();
+    assert!(fs::metadata(&delta_path).unwrap().len() < new.len() as u64 / 4);
+
+    let rebuilt = dir.path().join("rebuilt");
+    delta::apply(&old_path, &delta_path, &rebuilt).unwrap();
+    assert_eq!(fs::read(&rebuilt).unwrap(), new);
+}
+
+#[test]
+fn test_archive_names() {
+    assert_eq!(
+        delta::delta_filename("hello", "1.0.0", "1.1.0", "linux-amd64"),
+        "hello-1.0.0-1.1.0-linux-amd64.pkg.tar.zst.delta"
+    );
+    assert_eq!(
+        delta::archive_version(
+            "hello-1.0.0-linux-amd64.pkg.tar.zst",
+            "hello",
+            "linux-amd64"
+        ),
+        Some("1.0.0")
+    );
+    assert_eq!(
+        delta::archive_version(
+            "hello-1.0.0-macos-arm64.pkg.tar.zst",
+            "hello",
+            "linux-amd64"
+        ),
+        None
+    );
+    assert_eq!(
+        delta::archive_version(
+            "hello-world-1.0.0-linux-amd64.pkg.tar.zst",
+            "hello",
+            "linux-amd64"
+        ),
+        None
+    );
+}
+
+fn installed(version: &str) -> types::InstallManifest {
+    types::InstallManifest {
+        name: "hello".to_string(),
+        version: version.to_string(),
+        sub_package: None,
+        repo: "core".to_string(),
+        registry_handle: "zoidberg".to_string(),
+        package_type: types::PackageType::Package,
+        reason: types::InstallReason::Direct,
+        scope: types::Scope::User,
+        bins: None,
+        conflicts: None,
+        replaces: None,
+        provides: None,
+        backup: None,
+        installed_dependencies: vec![],
+        chosen_options: vec![],
+        chosen_optionals: vec![],
+        install_method: Some("test".to_string()),
+        service: None,
+        installed_files: vec![],
+        installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
+    }
+}
+
+fn node(version: &str) -> InstallNode {
+    InstallNode {
+        pkg: types::Package {
+            name: "hello".to_string(),
+            repo: "core".to_string(),
+            ..Default::default()
+        },
+        version: version.to_string(),
+        sub_package: None,
+        reason: types::InstallReason::Direct,
+        source: "hello".to_string(),
+        registry_handle: "zoidberg".to_string(),
+        chosen_options: Vec::new(),
+        chosen_optionals: Vec::new(),
+        dependencies: Vec::new(),
+        git_sha: None,
+        provider_for: Vec::new(),
+        features: Vec::new(),
+    }
+}
+
+fn write_hash(path: &Path, archive: &Path) {
+    let hash = helper::get_hash(archive.to_str().unwrap(), helper::HashType::Sha512).unwrap();
+    let name = archive.file_name().unwrap().to_string_lossy();
+    fs::write(path, format!("{}  {}\n", hash, name)).unwrap();
+}
+
+#[test]
+fn test_download_rebuilds_archive_from_cached_delta() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    ctx.set_env_var("HOME", root.path());
+    ctx.set_env_var("ZOI_DB_DIR", root.path().join("db"));
+    ctx.set_sysroot(root.path().to_path_buf());
+
+    let platform = zoi::utils::get_platform().unwrap();
+    let old_name = delta::archive_filename("hello", "1.0.0", &platform);
+    let new_name = delta::archive_filename("hello", "1.1.0", &platform);
+    let (old, new) = old_and_new();
+
+    let cache_root = cache::get_archive_cache_root().unwrap();
+    fs::create_dir_all(&cache_root).unwrap();
+    fs::write(cache_root.join(&old_name), &old).unwrap();
+
+    let publish = tempdir().unwrap();
+    let new_archive = publish.path().join(&new_name);
+    fs::write(&new_archive, &new).unwrap();
+    delta::create(
+        &cache_root.join(&old_name),
+        &new_archive,
+        &cache_root.join(delta::delta_filename("hello", "1.0.0", "1.1.0", &platform)),
+    )
+    .unwrap();
+    write_hash(&cache_root.join(format!("{}.hash", new_name)), &new_archive);
+    local::write_manifest(&installed("1.0.0")).unwrap();
+
+    let listener = serve::bind("127.0.0.1", 0).unwrap();
+    let base = format!("http://{}/archives", listener.local_addr().unwrap());
+    std::thread::spawn(move || serve::serve(listener));
+
+    let details = plan::PrebuiltDetails {
+        info: types::PrebuiltInfo {
+            final_url: format!("{}/{}", base, new_name),
+            pgp_url: None,
+            hash_url: Some(format!("{}/{}.hash", base, new_name)),
+            size_url: None,
+            files_url: None,
+        },
+        download_size: new.len() as u64,
+        installed_size: 0,
+    };
+
+    let path = installer::download_and_cache_archive(&node("1.1.0"), &details, None).unwrap();
+    assert_eq!(path, cache_root.join(&new_name));
+    assert_eq!(fs::read(&path).unwrap(), new);
+}
+
+#[test]
+fn test_bad_delta_is_not_installed() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    ctx.set_env_var("HOME", root.path());
+    ctx.set_env_var("ZOI_DB_DIR", root.path().join("db"));
+    ctx.set_sysroot(root.path().to_path_buf());
+
+    let platform = zoi::utils::get_platform().unwrap();
+    let old_name = delta::archive_filename("hello", "1.0.0", &platform);
+    let new_name = delta::archive_filename("hello", "1.1.0", &platform);
+    let (old, new) = old_and_new();
+
+    let cache_root = cache::get_archive_cache_root().unwrap();
+    fs::create_dir_all(&cache_root).unwrap();
+    fs::write(cache_root.join(&old_name), &old).unwrap();
+    fs::write(
+        cache_root.join(delta::delta_filename("hello", "1.0.0", "1.1.0", &platform)),
+        b"not a delta",
+    )
+    .unwrap();
+    local::write_manifest(&installed("1.0.0")).unwrap();
+
+    let publish = tempdir().unwrap();
+    let new_archive = publish.path().join(&new_name);
+    fs::write(&new_archive, &new).unwrap();
+    write_hash(&cache_root.join(format!("{}.hash", new_name)), &new_archive);
+
+    let listener = serve::bind("127.0.0.1", 0).unwrap();
+    let base = format!("http://{}/archives", listener.local_addr().unwrap());
+    std::thread::spawn(move || serve::serve(listener));
+
+    let details = plan::PrebuiltDetails {
+        info: types::PrebuiltInfo {
+            final_url: format!("{}/{}", base, new_name),
+            pgp_url: None,
+            hash_url: Some(format!("{}/{}.hash", base, new_name)),
+            size_url: None,
+            files_url: None,
+        },
+        download_size: new.len() as u64,
+        installed_size: 0,
+    };
+
+    // The server has no full archive, so the fallback download fails and
+    // nothing built from the bad delta may end up in the cache.
+    let err = installer::download_and_cache_archive(&node("1.1.0"), &details, None)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Failed to download package archive"));
+    assert!(!cache_root.join(&new_name).exists());
+}
@@ -70,3 +70,141 @@ fn test_get_versions_does_not_leak_across_distinct_explicit_sources() {
         pubgrub::SemVersion(Version::parse("1.0.0").unwrap())
     );
 }
+
+fn pkg(name: &str) -> PkgName {
+    PkgName {
+        name: name.to_string(),
+        sub_package: None,
+        repo: "core".to_string(),
+        registry: "zoidberg".to_string(),
+        explicit_source: None,
+    }
+}
+
+fn ver(v: &str) -> pubgrub::SemVersion {
+    pubgrub::SemVersion(Version::parse(v).unwrap())
+}
+
+fn solve_conflict(
+    provider: &::pubgrub::OfflineDependencyProvider<
+        PkgName,
+        ::pubgrub::Ranges<pubgrub::SemVersion>,
+    >,
+) -> zoi::pkg::install::conflict::ConflictTree {
+    match ::pubgrub::resolve(provider, pkg("$root"), ver("0.0.0")) {
+        Err(::pubgrub::PubGrubError::NoSolution(tree)) => tree,
+        other => panic!("expected a conflict, got {:?}", other.is_ok()),
+    }
+}
+
+#[test]
+fn test_explain_conflict_names_both_requirements() {
+    use zoi::pkg::install::conflict::{ConflictHints, explain_conflict};
+
+    let mut provider = ::pubgrub::OfflineDependencyProvider::new();
+    provider.add_dependencies(
+        pkg("$root"),
+        ver("0.0.0"),
+        [
+            (pkg("app"), pubgrub::semver_to_range("^2")),
+            (pkg("tool"), ::pubgrub::Ranges::full()),
+        ],
+    );
+    provider.add_dependencies(
+        pkg("app"),
+        ver("2.1.0"),
+        [(pkg("libfoo"), pubgrub::semver_to_range("<1.0.0"))],
+    );
+    provider.add_dependencies(
+        pkg("tool"),
+        ver("1.0.0"),
+        [(pkg("libfoo"), pubgrub::semver_to_range(">=1.2.0"))],
+    );
+    provider.add_dependencies(pkg("libfoo"), ver("0.9.0"), []);
+    provider.add_dependencies(pkg("libfoo"), ver("1.2.0"), []);
+
+    let tree = solve_conflict(&provider);
+    let text = explain_conflict(&tree, &ConflictHints::default());
+
+    assert!(text.contains("You requested app 2.x"), "{}", text);
+    assert!(text.contains("requires libfoo <1.0.0"), "{}", text);
+    assert!(text.contains("requires libfoo >=1.2.0"), "{}", text);
+    assert!(text.contains("conflict:"), "{}", text);
+    assert!(text.contains("To resolve this"), "{}", text);
+    assert!(
+        text.contains("Relax the version you pinned for app (currently 2.x)"),
+        "{}",
+        text
+    );
+    assert!(text.contains("Remove tool from the request"), "{}", text);
+}
+
+#[test]
+fn test_explain_conflict_suggests_relaxing_pin_and_optionals() {
+    use zoi::pkg::install::conflict::{ConflictHints, explain_conflict};
+
+    let mut provider = ::pubgrub::OfflineDependencyProvider::new();
+    provider.add_dependencies(
+        pkg("$root"),
+        ver("0.0.0"),
+        [
+            (pkg("editor"), ::pubgrub::Ranges::full()),
+            (pkg("spell"), pubgrub::semver_to_range("1.0.0")),
+        ],
+    );
+    provider.add_dependencies(
+        pkg("editor"),
+        ver("3.0.0"),
+        [(pkg("spell"), pubgrub::semver_to_range(">=2.0.0"))],
+    );
+    provider.add_dependencies(pkg("spell"), ver("1.0.0"), []);
+    provider.add_dependencies(pkg("spell"), ver("2.0.0"), []);
+
+    let tree = solve_conflict(&provider);
+    let mut hints = ConflictHints::default();
+    hints
+        .optional
+        .insert(("editor".to_string(), "spell".to_string()));
+    let text = explain_conflict(&tree, &hints);
+
+    assert!(text.contains("1. "), "{}", text);
+    assert!(
+        text.contains("Relax the version you pinned for spell (currently 1.0.0)"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("Skip the optional dependency spell of editor"),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn test_explain_conflict_lists_available_versions() {
+    use zoi::pkg::install::conflict::{ConflictHints, explain_conflict};
+
+    let mut provider = ::pubgrub::OfflineDependencyProvider::new();
+    provider.add_dependencies(
+        pkg("$root"),
+        ver("0.0.0"),
+        [(pkg("hello"), pubgrub::semver_to_range("^3"))],
+    );
+    provider.add_dependencies(pkg("hello"), ver("1.0.0"), []);
+    provider.add_dependencies(pkg("hello"), ver("2.0.0"), []);
+
+    let tree = solve_conflict(&provider);
+    let mut hints = ConflictHints::default();
+    hints
+        .available
+        .insert("hello".to_string(), vec![ver("1.0.0"), ver("2.0.0")]);
+    let text = explain_conflict(&tree, &hints);
+
+    assert!(text.contains("hello 3.x"), "{}", text);
+    assert!(text.contains("available: 1.0.0, 2.0.0"), "{}", text);
+    assert!(
+        text.contains("Relax the version you pinned for hello"),
+        "{}",
+        text
+    );
+}
@@ -103,6 +103,9 @@ end
         service: None,
         installed_files: vec![binary_path.to_string_lossy().to_string()],
         installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
     };
     fs::write(
         version_dir.join("manifest.yaml"),
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::{config, features, install, resolve, types};
+
+mod common;
+
+fn feature(default: bool, requires: &[&str]) -> types::PackageFeature {
+    types::PackageFeature {
+        default,
+        requires: requires.iter().map(|s| s.to_string()).collect(),
+        ..Default::default()
+    }
+}
+
+fn declared() -> HashMap<String, types::PackageFeature> {
+    HashMap::from([
+        ("x11".to_string(), feature(true, &[])),
+        ("vaapi".to_string(), feature(false, &["drm"])),
+        ("drm".to_string(), feature(false, &[])),
+    ])
+}
+
+fn selection(entries: &[&str]) -> Vec<String> {
+    entries.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn test_split_source_strips_selection() {
+    let (source, sel) = features::split_source("@core/ffmpeg@6.1[+vaapi,-x11]");
+    assert_eq!(source, "@core/ffmpeg@6.1");
+    assert_eq!(sel, selection(&["+vaapi", "-x11"]));
+
+    let (source, sel) = features::split_source("ffmpeg[vaapi]");
+    assert_eq!(source, "ffmpeg");
+    assert_eq!(sel, selection(&["+vaapi"]));
+
+    let (source, sel) = features::split_source("ffmpeg");
+    assert_eq!(source, "ffmpeg");
+    assert!(sel.is_empty());
+
+    for path in [
+        "./pkgs/foo[1].pkg.lua",
+        "./pkgs/foo.pkg.lua[1]",
+        "https://example.com/pkgs/foo[v2].pkg.lua",
+    ] {
+        let (source, sel) = features::split_source(path);
+        assert_eq!(source, path);
+        assert!(sel.is_empty());
+    }
+    let (source, sel) = features::split_source("./pkgs/foo.pkg.lua[+vaapi]");
+    assert_eq!(source, "./pkgs/foo.pkg.lua");
+    assert_eq!(sel, selection(&["+vaapi"]));
+
+    let request = resolve::parse_source_string("#zoidberg@core/ffmpeg[+vaapi]").unwrap();
+    assert_eq!(request.name, "ffmpeg");
+    assert_eq!(request.features, selection(&["+vaapi"]));
+}
+
+#[test]
+fn test_resolve_applies_defaults_and_requires() {
+    let declared = declared();
+
+    let enabled = features::resolve("ffmpeg", Some(&declared), &[]).unwrap();
+    assert_eq!(enabled, selection(&["x11"]));
+
+    let enabled =
+        features::resolve("ffmpeg", Some(&declared), &selection(&["+vaapi", "-x11"])).unwrap();
+    assert_eq!(enabled, selection(&["drm", "vaapi"]));
+
+    let enabled = features::resolve("ffmpeg", Some(&declared), &selection(&["-default"])).unwrap();
+    assert!(enabled.is_empty());
+}
+
+#[test]
+fn test_resolve_rejects_bad_selections() {
+    let declared = declared();
+
+    let err = features::resolve("ffmpeg", Some(&declared), &selection(&["+opengl"]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("opengl"));
+    assert!(err.contains("drm, vaapi, x11"));
+
+    let err = features::resolve("ffmpeg", Some(&declared), &selection(&["+vaapi", "-drm"]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("requires 'drm'"));
+}
+
+#[test]
+fn test_exact_selection_round_trips() {
+    let declared = declared();
+    let enabled = selection(&["vaapi", "drm"]);
+    let source = features::with_features("ffmpeg", &enabled);
+    let (_, sel) = features::split_source(&source);
+    let mut expected = enabled.clone();
+    expected.sort();
+    assert_eq!(
+        features::resolve("ffmpeg", Some(&declared), &sel).unwrap(),
+        expected
+    );
+}
+
+fn write_pkg(db_dir: &Path, name: &str, body: &str) {
+    let dir = db_dir.join("zoidberg").join("core").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(format!("{}.pkg.lua", name)), body).unwrap();
+}
+
+#[test]
+fn test_feature_dependencies_join_the_graph() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    let db_dir = root.path().join("db");
+    ctx.set_env_var("HOME", root.path());
+    ctx.set_env_var("ZOI_DB_DIR", &db_dir);
+    ctx.set_sysroot(root.path().to_path_buf());
+
+    config::write_user_config(&types::Config {
+        default_registry: Some(types::Registry {
+            handle: "zoidberg".to_string(),
+            url: "https://example.invalid/zoidberg.git".to_string(),
+            advisory_prefix: None,
+            authorities: None,
+        }),
+        repos: vec!["core".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    write_pkg(
+        &db_dir,
+        "libva",
+        r#"metadata({
+  name = "libva",
+  repo = "core",
+  version = "2.20.0",
+  description = "Video acceleration",
+  maintainer = { name = "Zoi", email = "zoi@example.com" },
+  types = { "source" },
+})"#,
+    );
+    write_pkg(
+        &db_dir,
+        "ffmpeg",
+        r#"metadata({
+  name = "ffmpeg",
+  repo = "core",
+  version = "6.1.0",
+  description = "Media toolkit",
+  maintainer = { name = "Zoi", email = "zoi@example.com" },
+  types = { "source" },
+  features = {
+    x11 = { description = "X11 capture", default = true },
+    vaapi = { description = "VA-API decoding", dependencies = { "zoi:libva" } },
+  },
+})"#,
+    );
+
+    let resolve_graph = |source: &str| {
+        install::resolver::resolve_dependency_graph(
+            &[source.to_string()],
+            Some(types::Scope::User),
+            false,
+            true,
+            false,
+            None,
+            true,
+        )
+        .unwrap()
+        .0
+    };
+    let ffmpeg = |graph: &install::resolver::DependencyGraph| {
+        graph
+            .nodes
+            .values()
+            .find(|n| n.pkg.name == "ffmpeg")
+            .unwrap()
+            .features
+            .clone()
+    };
+
+    let graph = resolve_graph("ffmpeg");
+    assert_eq!(ffmpeg(&graph), selection(&["x11"]));
+    assert!(!graph.nodes.values().any(|n| n.pkg.name == "libva"));
+
+    let graph = resolve_graph("ffmpeg[+vaapi,-x11]");
+    assert_eq!(ffmpeg(&graph), selection(&["vaapi"]));
+    assert!(graph.nodes.values().any(|n| n.pkg.name == "libva"));
+}
+
+#[test]
+fn test_features_requested_by_later_dependents_are_applied() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    let db_dir = root.path().join("db");
+    ctx.set_env_var("HOME", root.path());
+    ctx.set_env_var("ZOI_DB_DIR", &db_dir);
+    ctx.set_sysroot(root.path().to_path_buf());
+
+    config::write_user_config(&types::Config {
+        default_registry: Some(types::Registry {
+            handle: "zoidberg".to_string(),
+            url: "https://example.invalid/zoidberg.git".to_string(),
+            advisory_prefix: None,
+            authorities: None,
+        }),
+        repos: vec!["core".to_string()],
+        ..Default::default()
+    })
+    .unwrap();
+
+    let meta = |name: &str, extra: &str| {
+        format!(
+            r#"metadata({{
+  name = "{name}",
+  repo = "core",
+  version = "1.0.0",
+  description = "Feature unification",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+{extra}}})"#
+        )
+    };
+    let depends_on = |name: &str, dep: &str| {
+        format!(
+            "{}\n\ndependencies({{\n  runtime = {{\n    required = {{ \"{}\" }},\n  }},\n}})",
+            meta(name, ""),
+            dep
+        )
+    };
+    write_pkg(&db_dir, "libva", &meta("libva", ""));
+    write_pkg(
+        &db_dir,
+        "ffmpeg",
+        &meta(
+            "ffmpeg",
+            r#"  features = {
+    x11 = { description = "X11 capture", default = true },
+    vaapi = { description = "VA-API decoding", dependencies = { "zoi:libva" } },
+  },
+"#,
+        ),
+    );
+    write_pkg(&db_dir, "player", &depends_on("player", "zoi:ffmpeg[+x11]"));
+    // Reached only after ffmpeg's own dependencies were read.
+    write_pkg(&db_dir, "app", &depends_on("app", "zoi:recorder"));
+    write_pkg(
+        &db_dir,
+        "recorder",
+        &depends_on("recorder", "zoi:ffmpeg[+vaapi]"),
+    );
+
+    let (graph, _) = install::resolver::resolve_dependency_graph(
+        &["player".to_string(), "app".to_string()],
+        Some(types::Scope::User),
+        false,
+        true,
+        false,
+        None,
+        true,
+    )
+    .unwrap();
+    let ffmpeg = graph
+        .nodes
+        .values()
+        .find(|n| n.pkg.name == "ffmpeg")
+        .unwrap();
+    assert_eq!(ffmpeg.features, selection(&["vaapi", "x11"]));
+    assert!(graph.nodes.values().any(|n| n.pkg.name == "libva"));
+}
@@ -0,0 +1,445 @@
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::foreign::{self, Format};
+use zoi::pkg::types;
+
+mod common;
+
+enum Item<'a> {
+    File(&'a str, &'a [u8], u32),
+    Link(&'a str, &'a str),
+}
+
+fn tar_gz(items: &[Item]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for item in items {
+        let mut header = tar::Header::new_gnu();
+        match item {
+            Item::File(path, data, mode) => {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(data.len() as u64);
+                header.set_mode(*mode);
+                builder.append_data(&mut header, path, *data).unwrap();
+            }
+            Item::Link(path, target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                header.set_mode(0o777);
+                builder.append_link(&mut header, path, target).unwrap();
+            }
+        }
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+fn write_deb(path: &Path, control: &str, scripts: &[(&str, &str)], data: &[Item]) {
+    let mut control_items = vec![Item::File("./control", control.as_bytes(), 0o644)];
+    for (name, body) in scripts {
+        control_items.push(Item::File(name, body.as_bytes(), 0o755));
+    }
+    let members = [
+        ("debian-binary", b"2.0\n".to_vec()),
+        ("control.tar.gz", tar_gz(&control_items)),
+        ("data.tar.gz", tar_gz(data)),
+    ];
+
+    let mut builder = ar::Builder::new(fs::File::create(path).unwrap());
+    for (name, content) in members {
+        let header = ar::Header::new(name.as_bytes().to_vec(), content.len() as u64);
+        builder.append(&header, content.as_slice()).unwrap();
+    }
+}
+
+const CONTROL: &str = "Package: vendor-tool
+Version: 1:2.4.0-1
+Architecture: amd64
+Maintainer: Jane Doe <jane@example.com>
+Depends: libc6 (>= 2.17), libgtk-3-0 | libgtk-3-0t64, python3:any
+Homepage: https://vendor.example.com
+Description: A vendor tool
+ Shipped as a .deb only.
+";
+
+fn write_vendor_deb(path: &Path, marker: &Path) {
+    let postinst = format!("#!/bin/sh\necho \"$1\" > {}\n", marker.display());
+    write_deb(
+        path,
+        CONTROL,
+        &[
+            ("./postinst", &postinst),
+            ("./prerm", "#!/bin/bash\nexit 0\n"),
+        ],
+        &[
+            Item::File("./usr/bin/vendor-tool", b"#!/bin/sh\necho tool\n", 0o755),
+            Item::File("./usr/share/doc/vendor-tool/README", b"readme", 0o644),
+            Item::File("./opt/vendor/app", b"app", 0o755),
+            Item::Link("./usr/bin/vendor-app", "/opt/vendor/app"),
+            Item::Link("./usr/bin/vendor-editor", "/etc/alternatives/editor"),
+        ],
+    );
+}
+
+#[test]
+fn test_deb_metadata_becomes_a_package() {
+    let dir = tempdir().unwrap();
+    let deb = dir.path().join("vendor-tool_2.4.0-1_amd64.deb");
+    write_vendor_deb(&deb, &dir.path().join("marker"));
+
+    let foreign = foreign::read(&deb).unwrap();
+    assert_eq!(foreign.format, Format::Deb);
+    assert_eq!(foreign.name, "vendor-tool");
+    assert_eq!(foreign.version, "2.4.0-1");
+    assert_eq!(foreign.description, "A vendor tool");
+    assert_eq!(foreign.depends, vec!["libc6", "libgtk-3-0", "python3"]);
+    assert_eq!(
+        foreign.bins,
+        vec!["vendor-tool", "vendor-app", "vendor-editor"]
+    );
+    let postinst = foreign.scripts.post_install.as_ref().unwrap();
+    assert_eq!(postinst.interpreter, "/bin/sh");
+    assert_eq!(postinst.arg, "configure");
+    assert_eq!(
+        foreign.scripts.pre_remove.as_ref().unwrap().interpreter,
+        "/bin/bash"
+    );
+    assert!(foreign.scripts.pre_install.is_none());
+
+    let pkg = foreign::to_package(&foreign);
+    assert_eq!(pkg.version.as_deref(), Some("2.4.0-1"));
+    assert_eq!(pkg.maintainer.name, "Jane Doe");
+    assert_eq!(pkg.maintainer.email, "jane@example.com");
+    assert_eq!(pkg.website.as_deref(), Some("https://vendor.example.com"));
+    let Some(types::DependencyGroup::Complex(group)) = pkg.dependencies.and_then(|d| d.runtime)
+    else {
+        panic!("expected runtime dependencies");
+    };
+    let mut deps = group.required;
+    deps.extend(group.optional);
+    assert_eq!(deps, vec!["apt:libc6", "apt:libgtk-3-0", "apt:python3"]);
+    let hooks = pkg.hooks.unwrap();
+    assert!(hooks.post_install.is_some() && hooks.pre_remove.is_some());
+    assert!(hooks.pre_install.is_none());
+}
+
+#[test]
+fn test_deb_stages_into_pkgstore_and_usrroot() {
+    let dir = tempdir().unwrap();
+    let deb = dir.path().join("vendor.deb");
+    write_deb(
+        &deb,
+        CONTROL,
+        &[],
+        &[
+            Item::File("./usr/bin/vendor-tool", b"tool", 0o755),
+            Item::File("./usr/local/share/vendor/data", b"data", 0o644),
+            Item::File("./opt/vendor/app", b"app", 0o755),
+            Item::File("./etc/vendor.conf", b"conf", 0o644),
+            Item::Link("./usr/bin/vendor-app", "/opt/vendor/app"),
+            Item::Link("./usr/bin/vendor-editor", "/etc/alternatives/editor"),
+        ],
+    );
+
+    let staged = dir.path().join("staged");
+    foreign::stage(&deb, &staged).unwrap();
+
+    let pkgstore = staged.join("pkgstore");
+    assert_eq!(fs::read(pkgstore.join("bin/vendor-tool")).unwrap(), b"tool");
+    assert_eq!(
+        fs::read(pkgstore.join("share/vendor/data")).unwrap(),
+        b"data"
+    );
+    assert_eq!(fs::read(pkgstore.join("opt/vendor/app")).unwrap(), b"app");
+    assert_eq!(
+        fs::read_link(pkgstore.join("bin/vendor-app")).unwrap(),
+        Path::new("../opt/vendor/app")
+    );
+    assert_eq!(fs::read(pkgstore.join("bin/vendor-app")).unwrap(), b"app");
+    assert!(
+        pkgstore
+            .join("bin/vendor-editor")
+            .symlink_metadata()
+            .is_err()
+    );
+    assert_eq!(
+        fs::read(staged.join("usrroot/etc/vendor.conf")).unwrap(),
+        b"conf"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(pkgstore.join("bin/vendor-tool"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+    let leftovers: Vec<_> = fs::read_dir(&staged)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(leftovers.len(), 2, "{:?}", leftovers);
+}
+
+/// An rpm header holding `tags` as `(tag, type, count, data)`.
+fn rpm_header(tags: &[(u32, u32, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut index = Vec::new();
+    let mut store: Vec<u8> = Vec::new();
+    for (tag, kind, count, data) in tags {
+        index.extend(tag.to_be_bytes());
+        index.extend(kind.to_be_bytes());
+        index.extend((store.len() as u32).to_be_bytes());
+        index.extend(count.to_be_bytes());
+        store.extend(data);
+    }
+    let mut header = vec![0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0];
+    header.extend((tags.len() as u32).to_be_bytes());
+    header.extend((store.len() as u32).to_be_bytes());
+    header.extend(index);
+    header.extend(store);
+    header
+}
+
+fn string(value: &str) -> (u32, Vec<u8>) {
+    (1, [value.as_bytes(), b"\0"].concat())
+}
+
+fn strings(values: &[&str]) -> (u32, Vec<u8>) {
+    let data = values
+        .iter()
+        .flat_map(|v| [v.as_bytes(), b"\0"].concat())
+        .collect();
+    (values.len() as u32, data)
+}
+
+fn cpio(entries: &[(&str, u32, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let pad = |out: &mut Vec<u8>| {
+        while !out.len().is_multiple_of(4) {
+            out.push(0);
+        }
+    };
+    let trailer: (&str, u32, &[u8]) = ("TRAILER!!!", 0, b"");
+    for (ino, (name, mode, data)) in entries.iter().chain([&trailer]).enumerate() {
+        out.extend(
+            format!(
+                "070701{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}{:08x}",
+                ino + 1,
+                mode,
+                0,
+                0,
+                1,
+                0,
+                data.len(),
+                0,
+                0,
+                0,
+                0,
+                name.len() + 1,
+                0
+            )
+            .as_bytes(),
+        );
+        out.extend(name.as_bytes());
+        out.push(0);
+        pad(&mut out);
+        out.extend(*data);
+        pad(&mut out);
+    }
+    out
+}
+
+fn write_rpm(path: &Path) {
+    write_rpm_with_payload(
+        path,
+        &[
+            ("./usr", 0o040755, b""),
+            ("./usr/bin", 0o040755, b""),
+            ("./usr/bin/vendor-cli", 0o100755, b"#!/bin/sh\necho cli\n"),
+            ("./etc/vendor.conf", 0o100644, b"conf"),
+            ("./usr/bin/vcli", 0o120777, b"vendor-cli"),
+        ],
+    );
+}
+
+fn write_rpm_with_payload(path: &Path, entries: &[(&str, u32, &[u8])]) {
+    let tag = |tag: u32, kind: u32, (count, data): (u32, Vec<u8>)| (tag, kind, count, data);
+    let modes: Vec<u8> = [0o100755u16, 0o100644, 0o120777]
+        .iter()
+        .flat_map(|m| m.to_be_bytes())
+        .collect();
+    let dir_indexes: Vec<u8> = [0u32, 1, 0].iter().flat_map(|i| i.to_be_bytes()).collect();
+    let header = rpm_header(&[
+        tag(1000, 6, string("vendor-cli")),
+        tag(1001, 6, string("3.1.0")),
+        tag(1002, 6, string("2.fc40")),
+        tag(1004, 9, string("A vendor CLI")),
+        tag(1014, 6, string("MIT")),
+        tag(1015, 6, string("Vendor Builds <builds@example.com>")),
+        tag(1024, 6, string("echo installed")),
+        tag(1086, 8, strings(&["/bin/sh"])),
+        tag(1025, 6, string("echo removing")),
+        tag(1087, 8, strings(&["<lua>"])),
+        tag(1026, 8, (0, Vec::new())),
+        (1030, 3, 3, modes),
+        tag(
+            1049,
+            8,
+            strings(&[
+                "rpmlib(CompressedFileNames)",
+                "/bin/sh",
+                "libc.so.6(GLIBC_2.17)(64bit)",
+                "openssl-libs",
+                "openssl-libs",
+            ]),
+        ),
+        (1116, 4, 3, dir_indexes),
+        tag(1117, 8, strings(&["vendor-cli", "vendor.conf", "vcli"])),
+        tag(1118, 8, strings(&["/usr/bin/", "/etc/"])),
+    ]);
+
+    let payload = cpio(entries);
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&payload).unwrap();
+
+    let mut lead = vec![0u8; 96];
+    lead[..4].copy_from_slice(&[0xed, 0xab, 0xee, 0xdb]);
+    let signature = rpm_header(&[(62, 7, 16, vec![0u8; 16])]);
+    let padding = vec![0u8; (8 - signature.len() % 8) % 8];
+
+    let mut file = fs::File::create(path).unwrap();
+    for part in [lead, signature, padding, header, encoder.finish().unwrap()] {
+        file.write_all(&part).unwrap();
+    }
+}
+
+#[test]
+fn test_rpm_metadata_and_payload() {
+    let dir = tempdir().unwrap();
+    let rpm = dir.path().join("vendor-cli-3.1.0-2.fc40.x86_64.rpm");
+    write_rpm(&rpm);
+
+    let foreign = foreign::read(&rpm).unwrap();
+    assert_eq!(foreign.format, Format::Rpm);
+    assert_eq!(foreign.name, "vendor-cli");
+    assert_eq!(foreign.version, "3.1.0-2.fc40");
+    assert_eq!(foreign.description, "A vendor CLI");
+    assert_eq!(foreign.license.as_deref(), Some("MIT"));
+    assert_eq!(foreign.depends, vec!["openssl-libs"]);
+    assert_eq!(foreign.bins, vec!["vendor-cli", "vcli"]);
+    let postin = foreign.scripts.post_install.as_ref().unwrap();
+    assert_eq!(postin.arg, "1");
+    assert!(postin.to_hook().starts_with("/bin/sh -s 1 <<"));
+    assert!(foreign.scripts.pre_remove.is_none());
+    assert!(foreign.scripts.post_remove.is_none());
+
+    let staged = dir.path().join("staged");
+    foreign::stage(&rpm, &staged).unwrap();
+    assert_eq!(
+        fs::read_to_string(staged.join("pkgstore/bin/vendor-cli")).unwrap(),
+        "#!/bin/sh\necho cli\n"
+    );
+    assert_eq!(
+        fs::read_link(staged.join("pkgstore/bin/vcli")).unwrap(),
+        Path::new("vendor-cli")
+    );
+    assert_eq!(
+        fs::read(staged.join("usrroot/etc/vendor.conf")).unwrap(),
+        b"conf"
+    );
+}
+
+#[test]
+fn test_rpm_entries_replace_earlier_symlinks() {
+    let dir = tempdir().unwrap();
+    let rpm = dir.path().join("evil-1.0-1.x86_64.rpm");
+    write_rpm_with_payload(
+        &rpm,
+        &[
+            ("./x", 0o120777, b"../outside"),
+            ("./x", 0o100644, b"pwned"),
+        ],
+    );
+
+    let root = dir.path().join("root");
+    foreign::unpack(&rpm, &root).unwrap();
+    assert!(!dir.path().join("outside").exists());
+    assert!(fs::symlink_metadata(root.join("x")).unwrap().is_file());
+    assert_eq!(fs::read(root.join("x")).unwrap(), b"pwned");
+}
+
+#[test]
+fn test_appimage_is_named_from_its_file() {
+    let dir = tempdir().unwrap();
+    let mut image = b"\x7fELF\x02\x01\x01\x00AI\x02".to_vec();
+    image.extend([0u8; 64]);
+    let path = dir.path().join("Editor-1.2.3-x86_64.AppImage");
+    fs::write(&path, &image).unwrap();
+
+    let foreign = foreign::read(&path).unwrap();
+    assert_eq!(foreign.format, Format::AppImage);
+    assert_eq!(foreign.name, "editor");
+    assert_eq!(foreign.version, "1.2.3");
+    assert_eq!(foreign.bins, vec!["editor"]);
+
+    let staged = dir.path().join("staged");
+    foreign::stage(&path, &staged).unwrap();
+    assert_eq!(fs::read(staged.join("pkgstore/bin/editor")).unwrap(), image);
+
+    let plain = dir.path().join("tool.AppImage");
+    fs::write(&plain, b"\x7fELF\x02\x01\x01\x00\x00\x00\x00").unwrap();
+    assert!(foreign::read(&plain).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_install_deb_like_a_local_package() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    ctx.set_env_var("HOME", root.path());
+    ctx.set_env_var("ZOI_DB_DIR", root.path().join("db"));
+    ctx.set_sysroot(root.path().to_path_buf());
+
+    let deb = root.path().join("vendor-tool.deb");
+    let marker = root.path().join("postinst-ran");
+    write_deb(
+        &deb,
+        "Package: vendor-tool\nVersion: 2.4.0\nDescription: A vendor tool\n",
+        &[(
+            "./postinst",
+            &format!("#!/bin/sh\necho \"$1\" > {}\n", marker.display()),
+        )],
+        &[Item::File(
+            "./usr/bin/vendor-tool",
+            b"#!/bin/sh\necho tool\n",
+            0o755,
+        )],
+    );
+
+    zoi::install_sources(
+        &[deb.to_string_lossy().to_string()],
+        &zoi::SourceInstallOptions {
+            yes: true,
+            scope_override: Some(zoi::Scope::User),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let installed = zoi::pkg::local::is_package_installed("vendor-tool", None, types::Scope::User)
+        .unwrap()
+        .unwrap();
+    assert_eq!(installed.version, "2.4.0");
+    assert!(
+        installed
+            .installed_files
+            .iter()
+            .any(|f| f.ends_with("bin/vendor-tool"))
+    );
+    let bin_root = zoi::pkg::package::install::get_bin_root(types::Scope::User).unwrap();
+    assert!(bin_root.join("vendor-tool").exists());
+    assert_eq!(fs::read_to_string(&marker).unwrap(), "configure\n");
+}
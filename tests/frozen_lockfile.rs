@@ -46,6 +46,8 @@ fn test_sources_from_lock_falls_back_to_details_when_packages_empty() {
             dependencies: vec![],
             options_dependencies: vec![],
             optionals_dependencies: vec![],
+            provider_for: vec![],
+            features: vec![],
         },
     );
     lock.details.insert("#zoidberg".to_string(), reg_details);
@@ -74,6 +76,8 @@ fn test_locked_packages_preserve_direct_flags_and_metadata() {
             dependencies: vec!["zoi:#zoidberg@core/lib@2.0.0".to_string()],
             options_dependencies: vec!["feature-a".to_string()],
             optionals_dependencies: vec!["feature-b".to_string()],
+            provider_for: vec![],
+            features: vec![],
         },
     );
     reg_details.insert(
@@ -86,6 +90,8 @@ fn test_locked_packages_preserve_direct_flags_and_metadata() {
             dependencies: vec![],
             options_dependencies: vec![],
             optionals_dependencies: vec![],
+            provider_for: vec![],
+            features: vec![],
         },
     );
     lock.details.insert("#zoidberg".to_string(), reg_details);
@@ -134,6 +140,7 @@ fn test_install_frozen_lockfile_rejects_explicit_sources() {
         3,
         false,
         false,
+        false,
     )
     .expect_err("frozen lockfile with explicit source must fail");
 
@@ -175,6 +182,7 @@ fn test_install_frozen_lockfile_requires_zoi_lock() {
         3,
         false,
         false,
+        false,
     )
     .expect_err("missing zoi.lock must fail in frozen mode");
 
@@ -0,0 +1,257 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use zoi::pkg::journal::{self, Entry, Recovery};
+use zoi::pkg::types::{InstallManifest, InstallReason, PackageType, Scope};
+use zoi::pkg::{local, transaction};
+
+mod common;
+
+fn setup(ctx: &mut common::TestContextGuard, root: &Path) {
+    ctx.set_env_var("HOME", root);
+    ctx.set_sysroot(root.to_path_buf());
+}
+
+fn sample_manifest(files: Vec<String>) -> InstallManifest {
+    InstallManifest {
+        name: "journaled".to_string(),
+        version: "1.0.0".to_string(),
+        sub_package: None,
+        repo: "core".to_string(),
+        registry_handle: "local".to_string(),
+        package_type: PackageType::Package,
+        reason: InstallReason::Direct,
+        scope: Scope::User,
+        bins: None,
+        conflicts: None,
+        replaces: None,
+        provides: None,
+        backup: None,
+        installed_dependencies: vec![],
+        chosen_options: vec![],
+        chosen_optionals: vec![],
+        install_method: Some("test".to_string()),
+        service: None,
+        installed_files: files,
+        installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
+    }
+}
+
+fn dirs() -> (PathBuf, PathBuf) {
+    let package_dir = local::get_package_dir(Scope::User, "local", "core", "journaled").unwrap();
+    let version_dir = package_dir.join("1.0.0");
+    (package_dir, version_dir)
+}
+
+fn transaction_log(root: &Path, id: &str) -> PathBuf {
+    root.join(".zoi/transactions").join(format!("{}.json", id))
+}
+
+#[test]
+fn test_journal_is_inactive_outside_transactions() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let transaction = transaction::begin().unwrap();
+    transaction::commit(&transaction.id).unwrap();
+
+    journal::record(
+        Path::new("/nowhere"),
+        Entry::CreateDir {
+            path: PathBuf::from("/nowhere"),
+        },
+    )
+    .unwrap();
+    assert!(journal::read(&transaction.id).unwrap().is_empty());
+    assert!(journal::interrupted_transactions().unwrap().is_empty());
+}
+
+#[test]
+fn test_torn_last_line_is_ignored() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let transaction = transaction::begin().unwrap();
+    let (_, version_dir) = dirs();
+    journal::record(&version_dir, Entry::BeginInstall { scope: Scope::User }).unwrap();
+    journal::deactivate();
+
+    let journal_path = root
+        .path()
+        .join(".zoi/transactions")
+        .join(format!("{}.journal", transaction.id));
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(journal_path)
+        .unwrap();
+    file.write_all(br#"{"target":"/x","op":"create_d"#).unwrap();
+
+    let records = journal::read(&transaction.id).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].target, version_dir);
+    transaction::delete_log(&transaction.id).unwrap();
+}
+
+#[test]
+fn test_forward_removes_half_written_install() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let transaction = transaction::begin().unwrap();
+    let (package_dir, version_dir) = dirs();
+    let shim = root.path().join(".zoi/pkgs/bin/journaled");
+
+    journal::record(&version_dir, Entry::BeginInstall { scope: Scope::User }).unwrap();
+    journal::record(
+        &version_dir,
+        Entry::CreateDir {
+            path: package_dir.clone(),
+        },
+    )
+    .unwrap();
+    journal::record(
+        &version_dir,
+        Entry::CreateDir {
+            path: version_dir.clone(),
+        },
+    )
+    .unwrap();
+    fs::create_dir_all(version_dir.join("bin")).unwrap();
+    fs::write(version_dir.join("bin/journaled"), "half").unwrap();
+    journal::record(
+        &version_dir,
+        Entry::CreateFiles {
+            paths: vec![shim.clone()],
+        },
+    )
+    .unwrap();
+    fs::create_dir_all(shim.parent().unwrap()).unwrap();
+    fs::write(&shim, "shim").unwrap();
+
+    // The process dies here; the next one only sees what is on disk.
+    journal::deactivate();
+    assert_eq!(
+        journal::interrupted_transactions().unwrap(),
+        vec![transaction.id.clone()]
+    );
+
+    journal::recover(&transaction.id, Recovery::Forward).unwrap();
+    assert!(!package_dir.exists());
+    assert!(!shim.exists());
+    assert!(!transaction_log(root.path(), &transaction.id).exists());
+    assert!(journal::interrupted_transactions().unwrap().is_empty());
+}
+
+#[test]
+fn test_back_undoes_finished_install_missing_from_log() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let transaction = transaction::begin().unwrap();
+    let (package_dir, version_dir) = dirs();
+    journal::record(&version_dir, Entry::BeginInstall { scope: Scope::User }).unwrap();
+    journal::record(
+        &version_dir,
+        Entry::CreateDir {
+            path: version_dir.clone(),
+        },
+    )
+    .unwrap();
+
+    let file = version_dir.join("bin/journaled");
+    fs::create_dir_all(file.parent().unwrap()).unwrap();
+    fs::write(&file, "done").unwrap();
+    let manifest = sample_manifest(vec![file.to_string_lossy().to_string()]);
+    local::write_manifest(&manifest).unwrap();
+    let source = root.path().join("journaled.pkg.lua");
+    fs::write(
+        &source,
+        r#"metadata({
+  name = "journaled",
+  repo = "core",
+  version = "1.0.0",
+  description = "test",
+  maintainer = { name = "Zoi", email = "zoi@example.com" },
+  types = { "source" },
+})
+
+function uninstall(_args)
+end
+"#,
+    )
+    .unwrap();
+    local::persist_package_source(&manifest, &source).unwrap();
+
+    // Killed after the manifest was written but before the transaction log
+    // heard about the install.
+    journal::deactivate();
+    journal::recover(&transaction.id, Recovery::Back).unwrap();
+
+    assert!(!file.exists());
+    assert!(!package_dir.exists());
+    assert!(!transaction_log(root.path(), &transaction.id).exists());
+}
+
+#[test]
+fn test_forward_finishes_interrupted_uninstall() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let (package_dir, version_dir) = dirs();
+    let leftover = version_dir.join("share/leftover");
+    fs::create_dir_all(leftover.parent().unwrap()).unwrap();
+    fs::write(&leftover, "data").unwrap();
+    let manifest = sample_manifest(vec![leftover.to_string_lossy().to_string()]);
+
+    let transaction = transaction::begin().unwrap();
+    journal::record(
+        &version_dir,
+        Entry::BeginUninstall {
+            manifest: Box::new(manifest.clone()),
+        },
+    )
+    .unwrap();
+    journal::record(
+        &version_dir,
+        Entry::Remove {
+            paths: vec![version_dir.join("manifest.yaml")],
+        },
+    )
+    .unwrap();
+    journal::record(
+        &version_dir,
+        Entry::Remove {
+            paths: vec![version_dir.clone()],
+        },
+    )
+    .unwrap();
+
+    journal::deactivate();
+    journal::recover(&transaction.id, Recovery::Forward).unwrap();
+
+    assert!(!version_dir.exists());
+    assert!(package_dir.exists());
+    assert!(!transaction_log(root.path(), &transaction.id).exists());
+}
+
+#[test]
+fn test_empty_interrupted_transactions_are_dropped_silently() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let transaction = transaction::begin().unwrap();
+    journal::deactivate();
+
+    journal::recover_interrupted().unwrap();
+    assert!(!transaction_log(root.path(), &transaction.id).exists());
+}
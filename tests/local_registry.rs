@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+use zoi::pkg::{config, db, sync, types};
+
+mod common;
+
+fn write_pkg(root: &Path, name: &str, version: &str) {
+    let dir = root.join("core").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.pkg.lua", name)),
+        format!(
+            r#"metadata({{
+  name = "{}",
+  repo = "core",
+  version = "{}",
+  description = "Local package",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+}})
+"#,
+            name, version
+        ),
+    )
+    .unwrap();
+}
+
+fn backdate(path: &Path) {
+    let old = SystemTime::now() - Duration::from_secs(3600);
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+}
+
+fn version_of(handle: &str, name: &str) -> Option<String> {
+    db::list_all_packages(handle)
+        .unwrap()
+        .into_iter()
+        .find(|p| p.name == name)
+        .and_then(|p| p.version)
+}
+
+#[test]
+fn test_local_registry_url() {
+    assert_eq!(
+        sync::local_registry_path("file:///srv/zoi-registry").as_deref(),
+        Some(Path::new("/srv/zoi-registry"))
+    );
+    assert!(sync::local_registry_path("https://example.com/repo.git").is_none());
+}
+
+#[test]
+fn test_local_registry_picks_up_changes_by_mtime() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let db_dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", db_dir.path());
+
+    let reg_dir = tempdir().unwrap();
+    let root = reg_dir.path();
+    fs::copy("tests/assets/repo.yaml", root.join("repo.yaml")).unwrap();
+    write_pkg(root, "alpha", "1.0.0");
+    write_pkg(root, "beta", "1.0.0");
+    backdate(&root.join("repo.yaml"));
+    backdate(&root.join("core/alpha/alpha.pkg.lua"));
+    backdate(&root.join("core/beta/beta.pkg.lua"));
+
+    let handle = "local";
+    sync::refresh_local_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("1.0.0"));
+    assert_eq!(version_of(handle, "beta").as_deref(), Some("1.0.0"));
+
+    let marker = types::Package {
+        name: "marker".to_string(),
+        repo: "core".to_string(),
+        version: Some("0.1.0".to_string()),
+        ..Default::default()
+    };
+    let conn = db::open_connection(handle).unwrap();
+    db::update_package(&conn, &marker, handle, None, None, None).unwrap();
+    drop(conn);
+
+    write_pkg(root, "alpha", "2.0.0");
+    write_pkg(root, "gamma", "1.0.0");
+    fs::remove_dir_all(root.join("core").join("beta")).unwrap();
+
+    sync::refresh_local_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("2.0.0"));
+    assert_eq!(version_of(handle, "gamma").as_deref(), Some("1.0.0"));
+    assert_eq!(version_of(handle, "beta"), None);
+    assert_eq!(
+        version_of(handle, "marker").as_deref(),
+        Some("0.1.0"),
+        "unchanged files must not trigger a full rebuild"
+    );
+}
+
+#[test]
+fn test_local_registry_cannot_take_an_existing_handle() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    let db_dir = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    ctx.set_env_var("ZOI_DB_DIR", db_dir.path());
+
+    let checkout = db_dir.path().join("Zoidberg");
+    fs::create_dir_all(&checkout).unwrap();
+    fs::write(checkout.join("marker"), "keep").unwrap();
+
+    let reg_dir = tempdir().unwrap();
+    fs::copy("tests/assets/repo.yaml", reg_dir.path().join("repo.yaml")).unwrap();
+    assert!(config::add_local_registry(reg_dir.path().to_str().unwrap()).is_err());
+    assert!(checkout.join("marker").exists());
+    assert!(!checkout.is_symlink());
+}
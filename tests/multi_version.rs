@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::{hash, local, switch, types, uninstall};
+
+mod common;
+
+fn manifest(name: &str, version: &str) -> types::InstallManifest {
+    types::InstallManifest {
+        name: name.to_string(),
+        version: version.to_string(),
+        sub_package: None,
+        repo: "core".to_string(),
+        registry_handle: "local".to_string(),
+        package_type: types::PackageType::Package,
+        reason: types::InstallReason::Direct,
+        scope: types::Scope::User,
+        bins: Some(vec![name.to_string()]),
+        conflicts: None,
+        replaces: None,
+        provides: None,
+        backup: None,
+        installed_dependencies: vec![],
+        chosen_options: vec![],
+        chosen_optionals: vec![],
+        install_method: Some("test".to_string()),
+        service: None,
+        installed_files: vec![],
+        installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
+    }
+}
+
+fn install_version(root: &Path, name: &str, version: &str) -> types::InstallManifest {
+    let manifest = manifest(name, version);
+    local::write_manifest(&manifest).unwrap();
+
+    let source = root.join(format!("{}-{}.pkg.lua", name, version));
+    fs::write(
+        &source,
+        format!(
+            r#"metadata({{
+  name = "{name}",
+  repo = "core",
+  version = "{version}",
+  description = "test",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+  bins = {{ "{name}" }},
+}})
+
+function uninstall(_args)
+end
+"#
+        ),
+    )
+    .unwrap();
+    local::persist_package_source(&manifest, &source).unwrap();
+    manifest
+}
+
+fn default_version(name: &str) -> Option<String> {
+    local::get_default_version(types::Scope::User, "local", "core", name).unwrap()
+}
+
+#[test]
+fn test_switch_retargets_default_version() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    ctx.set_env_var("HOME", &root);
+    ctx.set_env_var("ZOI_DB_DIR", root.join("db"));
+    ctx.set_sysroot(root.clone());
+
+    install_version(&root, "node", "18.19.0");
+    install_version(&root, "node", "20.11.1");
+
+    assert_eq!(
+        local::get_installed_versions(types::Scope::User, "local", "core", "node").unwrap(),
+        vec!["20.11.1".to_string(), "18.19.0".to_string()]
+    );
+    assert_eq!(default_version("node").as_deref(), Some("20.11.1"));
+
+    let chosen = switch::run("node", "18", None, true).unwrap();
+    assert_eq!(chosen.version, "18.19.0");
+    assert_eq!(default_version("node").as_deref(), Some("18.19.0"));
+
+    let default = local::is_package_installed("node", None, types::Scope::User)
+        .unwrap()
+        .unwrap();
+    assert_eq!(default.version, "18.19.0");
+    let bin_root = zoi::pkg::package::install::get_bin_root(types::Scope::User).unwrap();
+    assert!(bin_root.join("node").is_symlink());
+
+    let err = switch::run("node", "22", None, true).unwrap_err();
+    assert!(err.to_string().contains("18.19.0"), "{}", err);
+}
+
+#[test]
+fn test_uninstall_single_version_keeps_the_others() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    ctx.set_env_var("HOME", &root);
+    ctx.set_env_var("ZOI_DB_DIR", root.join("db"));
+    ctx.set_sysroot(root.clone());
+
+    install_version(&root, "node", "18.19.0");
+    install_version(&root, "node", "20.11.1");
+    install_version(&root, "node", "21.0.0");
+    switch::run("node", "20", None, true).unwrap();
+
+    uninstall::run("node@18.19.0", Some(types::Scope::User), true).unwrap();
+    assert_eq!(default_version("node").as_deref(), Some("20.11.1"));
+    assert_eq!(
+        local::get_installed_versions(types::Scope::User, "local", "core", "node").unwrap(),
+        vec!["21.0.0".to_string(), "20.11.1".to_string()]
+    );
+
+    uninstall::run("node@20.11.1", Some(types::Scope::User), true).unwrap();
+    assert_eq!(default_version("node").as_deref(), Some("21.0.0"));
+
+    uninstall::run("node", Some(types::Scope::User), true).unwrap();
+    let package_dir = local::get_package_dir(types::Scope::User, "local", "core", "node").unwrap();
+    assert!(!package_dir.exists());
+}
+
+#[test]
+fn test_kept_versions_are_listed_and_matched() {
+    assert!(switch::version_matches("20.11.1", "20"));
+    assert!(switch::version_matches("20.11.1", "v20.11.1"));
+    assert!(!switch::version_matches("18.19.0", "20"));
+
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    ctx.set_env_var("HOME", &root);
+    ctx.set_sysroot(root.clone());
+
+    install_version(&root, "tool", "1.0.0");
+    install_version(&root, "tool", "2.0.0");
+
+    let version_dir =
+        local::get_package_version_dir(types::Scope::User, "local", "core", "tool", "1.0.0")
+            .unwrap();
+    assert!(!local::is_version_kept(&version_dir));
+    let hash_before = hash::calculate_dir_hash(&version_dir).unwrap();
+    local::mark_version_kept(&version_dir).unwrap();
+    assert!(local::is_version_kept(&version_dir));
+    // Keeping a version must not change the hash zoi.lock records for it.
+    assert_eq!(hash::calculate_dir_hash(&version_dir).unwrap(), hash_before);
+
+    let all = local::get_all_version_manifests_in_scope(types::Scope::User).unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(
+        local::get_installed_manifests_in_scope(types::Scope::User)
+            .unwrap()
+            .len(),
+        1
+    );
+}
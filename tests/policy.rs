@@ -35,6 +35,8 @@ fn graph_with_package(
             chosen_optionals: Vec::new(),
             dependencies: Vec::new(),
             git_sha: None,
+            provider_for: Vec::new(),
+            features: Vec::new(),
         },
     );
 
@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::types::{self, PackagePreference};
+use zoi::pkg::{config, preference, resolve};
+
+mod common;
+
+fn rule(
+    package: &str,
+    registry: Option<&str>,
+    repo: Option<&str>,
+    priority: i32,
+) -> PackagePreference {
+    PackagePreference {
+        package: package.to_string(),
+        registry: registry.map(str::to_string),
+        repo: repo.map(str::to_string),
+        priority,
+    }
+}
+
+fn write_pkg(db_dir: &Path, handle: &str, repo: &str, name: &str, version: &str) {
+    let dir = db_dir.join(handle).join(repo).join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.pkg.lua", name)),
+        format!(
+            r#"metadata({{
+  name = "{}",
+  repo = "{}",
+  version = "{}",
+  description = "Preference test",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+}})"#,
+            name, repo, version
+        ),
+    )
+    .unwrap();
+}
+
+fn registry(handle: &str) -> types::Registry {
+    types::Registry {
+        handle: handle.to_string(),
+        url: format!("https://example.invalid/{}.git", handle),
+        advisory_prefix: None,
+        authorities: None,
+    }
+}
+
+#[test]
+fn test_priority_rules() {
+    let prefs = vec![
+        rule("*", None, Some("community"), -1),
+        rule("ripgrep", Some("#internal"), None, 1000),
+        rule("*", Some("internal"), None, 700),
+    ];
+
+    assert_eq!(
+        preference::priority_for(&prefs, "ripgrep", "internal", "main"),
+        1000
+    );
+    assert_eq!(
+        preference::priority_for(&prefs, "fd", "internal", "main"),
+        700
+    );
+    assert_eq!(
+        preference::priority_for(&prefs, "fd", "zoidberg", "community/tools"),
+        -1
+    );
+    assert_eq!(
+        preference::priority_for(&prefs, "fd", "zoidberg", "core"),
+        preference::DEFAULT_PRIORITY
+    );
+    assert_eq!(
+        preference::preferred_registries(&prefs, "ripgrep"),
+        vec!["internal"]
+    );
+
+    assert!(preference::conflict_for(&prefs, "ripgrep", "zoidberg", "core").is_some());
+    assert!(preference::conflict_for(&prefs, "ripgrep", "internal", "core").is_none());
+    assert!(preference::conflict_for(&prefs, "fd", "zoidberg", "community").is_some());
+}
+
+#[test]
+fn test_resolver_honours_preferences() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    let home = root.join("home");
+    fs::create_dir_all(&home).unwrap();
+    let db_dir = root.join("db");
+    ctx.set_env_var("HOME", &home);
+    ctx.set_env_var("ZOI_DB_DIR", &db_dir);
+    ctx.set_sysroot(root.clone());
+
+    write_pkg(&db_dir, "zoidberg", "core", "ripgrep", "14.0.0");
+    write_pkg(&db_dir, "internal", "main", "ripgrep", "14.1.0-internal");
+    write_pkg(&db_dir, "zoidberg", "community", "fd", "9.0.0");
+    write_pkg(&db_dir, "zoidberg", "core", "bat", "0.24.0");
+    write_pkg(&db_dir, "zoidberg", "community", "bat", "0.25.0");
+
+    let cfg = types::Config {
+        default_registry: Some(registry("zoidberg")),
+        added_registries: vec![registry("internal")],
+        repos: vec!["core".to_string(), "community".to_string()],
+        preferences: vec![
+            rule("ripgrep", Some("internal"), None, 1000),
+            rule("*", None, Some("community"), -1),
+        ],
+        ..Default::default()
+    };
+    config::write_user_config(&cfg).unwrap();
+
+    let resolved = resolve::resolve_source("ripgrep", true, true).unwrap();
+    assert_eq!(resolved.registry_handle.as_deref(), Some("internal"));
+
+    let resolved = resolve::resolve_source("bat", true, true).unwrap();
+    assert_eq!(resolved.repo_name.as_deref(), Some("core"));
+
+    assert!(resolve::resolve_source("fd", true, true).is_err());
+    let resolved = resolve::resolve_source("@community/fd", true, true).unwrap();
+    assert_eq!(resolved.repo_name.as_deref(), Some("community"));
+}
@@ -31,3 +31,21 @@ pkgs:
             || cfg.pkgs.contains(&"fzf: 0.44.1".to_string())
     );
 }
+
+#[test]
+fn test_deserialize_project_config_env_files() {
+    let yaml = r#"
+name: env-project
+env_file: .env
+commands:
+  - cmd: dev
+    run: npm run dev
+    env_file: [.env, .env.local]
+shell:
+  env_file: .env.shell
+"#;
+    let cfg: config::ProjectConfig = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(cfg.env_file, vec![".env"]);
+    assert_eq!(cfg.commands[0].env_file, vec![".env", ".env.local"]);
+    assert_eq!(cfg.shell.unwrap().env_file, vec![".env.shell"]);
+}
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
 use tempfile::tempdir;
-use zoi::project::{config, runner};
+use zoi::pkg::system;
+use zoi::project::{config, dotenv, runner, watch};
 
 mod common;
 
@@ -28,3 +31,292 @@ commands:
     let content = fs::read_to_string(out_file).unwrap();
     assert_eq!(content.trim(), "hello");
 }
+
+#[test]
+fn test_project_run_orders_dependencies() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+
+    let yaml = r#"
+name: test-workflow
+commands:
+  - cmd: ci
+    depends: [test, lint]
+  - cmd: test
+    run: test -f built.txt && echo test >> log.txt
+    depends: [build]
+  - cmd: lint
+    run: echo lint >> lint.txt
+  - cmd: build
+    run: echo build >> log.txt && touch built.txt
+"#;
+    fs::write(root.join("zoi.yaml"), yaml).unwrap();
+    ctx.set_current_dir(&root);
+
+    let cfg = config::load().unwrap();
+    let waves: Vec<Vec<&str>> = zoi::project::tasks::plan(&cfg.commands, "ci")
+        .unwrap()
+        .iter()
+        .map(|wave| wave.iter().map(|c| c.cmd.as_str()).collect())
+        .collect();
+    assert_eq!(waves, vec![vec!["lint", "build"], vec!["test"], vec!["ci"]]);
+
+    runner::run(Some("ci"), &[], &cfg).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("log.txt")).unwrap(),
+        "build\ntest\n"
+    );
+    assert_eq!(fs::read_to_string(root.join("lint.txt")).unwrap(), "lint\n");
+}
+
+#[test]
+fn test_project_run_rejects_bad_dependencies() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+
+    let yaml = r#"
+name: test-workflow
+commands:
+  - cmd: a
+    run: "true"
+    depends: [b]
+  - cmd: b
+    run: "true"
+    depends: [a]
+  - cmd: c
+    run: "true"
+    depends: [missing]
+"#;
+    fs::write(root.join("zoi.yaml"), yaml).unwrap();
+    ctx.set_current_dir(&root);
+
+    let cfg = config::load().unwrap();
+    let err = runner::run(Some("a"), &[], &cfg).unwrap_err().to_string();
+    assert!(err.contains("a -> b -> a"), "{}", err);
+    let err = runner::run(Some("c"), &[], &cfg).unwrap_err().to_string();
+    assert!(err.contains("unknown command 'missing'"), "{}", err);
+}
+
+#[test]
+fn test_project_run_skips_unchanged_commands() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+
+    let yaml = r#"
+name: test-workflow
+commands:
+  - cmd: build
+    run: echo run >> runs.txt && mkdir -p out && cat src/*.txt > out/all.txt
+    inputs: ["src/*.txt"]
+    outputs: ["out"]
+"#;
+    fs::write(root.join("zoi.yaml"), yaml).unwrap();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/a.txt"), "a\n").unwrap();
+    ctx.set_current_dir(&root);
+    let cfg = config::load().unwrap();
+    let runs = || {
+        fs::read_to_string(root.join("runs.txt"))
+            .unwrap()
+            .lines()
+            .count()
+    };
+
+    runner::run(Some("build"), &[], &cfg).unwrap();
+    runner::run(Some("build"), &[], &cfg).unwrap();
+    assert_eq!(runs(), 1);
+
+    // Changed input.
+    fs::write(root.join("src/b.txt"), "b\n").unwrap();
+    runner::run(Some("build"), &[], &cfg).unwrap();
+    assert_eq!(runs(), 2);
+    assert_eq!(
+        fs::read_to_string(root.join("out/all.txt")).unwrap(),
+        "a\nb\n"
+    );
+
+    // Tampered output.
+    fs::write(root.join("out/all.txt"), "stale").unwrap();
+    runner::run(Some("build"), &[], &cfg).unwrap();
+    assert_eq!(runs(), 3);
+
+    // Removed output.
+    fs::remove_dir_all(root.join("out")).unwrap();
+    runner::run(Some("build"), &[], &cfg).unwrap();
+    assert_eq!(runs(), 4);
+
+    // Extra arguments change the command.
+    runner::run(Some("build"), &["src/a.txt".to_string()], &cfg).unwrap();
+    assert_eq!(runs(), 5);
+    runner::run(Some("build"), &[], &cfg).unwrap();
+    assert_eq!(runs(), 6);
+    runner::run(Some("build"), &[], &cfg).unwrap();
+    assert_eq!(runs(), 6);
+}
+
+#[test]
+fn test_project_watch_patterns() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+
+    let yaml = r#"
+name: test-workflow
+commands:
+  - cmd: serve
+    run: "true"
+    depends: [build]
+    watch: ["static/**"]
+  - cmd: build
+    run: "true"
+    inputs: ["src/**/*.rs", "Cargo.toml"]
+  - cmd: fmt
+    run: "true"
+"#;
+    fs::write(root.join("zoi.yaml"), yaml).unwrap();
+    ctx.set_current_dir(&root);
+    let cfg = config::load().unwrap();
+
+    let patterns = watch::watch_patterns(&cfg.commands, "serve").unwrap();
+    assert_eq!(patterns, vec!["src/**/*.rs", "Cargo.toml", "static/**"]);
+    let err = watch::watch_patterns(&cfg.commands, "fmt")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("no `inputs` or `watch` globs"), "{}", err);
+
+    let set = watch::WatchSet::new(&patterns).unwrap();
+    assert!(set.matches(Path::new("src/main.rs")));
+    assert!(set.matches(Path::new("src/a/b.rs")));
+    assert!(set.matches(Path::new("Cargo.toml")));
+    assert!(set.matches(Path::new("static/css/site.css")));
+    assert!(!set.matches(Path::new("src/notes.md")));
+    assert!(!set.matches(Path::new("target/Cargo.toml")));
+
+    let set = watch::WatchSet::new(&["src".to_string(), "*".to_string()]).unwrap();
+    assert!(set.matches(Path::new("src/deep/file.txt")));
+    assert!(!set.matches(Path::new(".zoi/cache/tasks.json")));
+    assert!(!set.matches(Path::new(".git/index")));
+}
+
+#[test]
+fn test_project_env_files() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    ctx.set_env_var("ZOI_TEST_OUTER", "outer");
+
+    fs::write(
+        root.join(".env"),
+        r#"
+# comment
+export NAME=zoi
+GREETING="hello ${NAME}\n"
+URL=http://${HOST:-localhost}:${PORT-8080} # trailing comment
+RAW='${NAME} $(not run)'
+OUTER=$ZOI_TEST_OUTER
+ESCAPED=\${NAME}
+MULTI="one
+two"
+"#,
+    )
+    .unwrap();
+    fs::write(
+        root.join(".env.local"),
+        "NAME=local\nFROM_CMD=$(echo \"$NAME-cmd\")\nPORT=\n",
+    )
+    .unwrap();
+
+    let files = vec![
+        ".env".to_string(),
+        ".env.local".to_string(),
+        ".env.missing".to_string(),
+    ];
+    let vars = dotenv::load(&root, &files, &HashMap::new()).unwrap();
+    assert_eq!(vars["NAME"], "local");
+    assert_eq!(vars["GREETING"], "hello zoi\n");
+    assert_eq!(vars["URL"], "http://localhost:8080");
+    assert_eq!(vars["RAW"], "${NAME} $(not run)");
+    assert_eq!(vars["OUTER"], "outer");
+    assert_eq!(vars["ESCAPED"], "${NAME}");
+    assert_eq!(vars["MULTI"], "one\ntwo");
+    assert_eq!(vars["FROM_CMD"], "local-cmd");
+    assert_eq!(vars["PORT"], "");
+
+    let base = HashMap::from([("HOST".to_string(), "example.com".to_string())]);
+    let vars = dotenv::load(&root, &files[..1], &base).unwrap();
+    assert_eq!(vars["URL"], "http://example.com:8080");
+
+    fs::write(root.join(".env.bad"), "FAIL=$(exit 3)\n").unwrap();
+    let err = dotenv::load(&root, &[".env.bad".to_string()], &HashMap::new())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(".env.bad:1"), "{}", err);
+}
+
+#[test]
+fn test_project_run_applies_env_files() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    ctx.set_env_var("HOME", root.join("home"));
+
+    let yaml = r#"
+name: test-workflow
+env_file: .env
+commands:
+  - cmd: show
+    run: echo "$A $B $C $SECRET $FILE_SECRET" > out.txt
+    env_file: [.env.cmd, .env.secret.enc]
+    env:
+      C: literal
+"#;
+    fs::write(root.join("zoi.yaml"), yaml).unwrap();
+    fs::write(root.join(".env"), "A=project\nB=project\nC=project\n").unwrap();
+    let secret = system::encrypt_secret("hunter2").unwrap();
+    fs::write(
+        root.join(".env.cmd"),
+        format!("B=command-${{A}}\nSECRET=enc:{}\n", secret),
+    )
+    .unwrap();
+    fs::write(
+        root.join(".env.secret.enc"),
+        system::encrypt_secret("FILE_SECRET=from-file\n").unwrap(),
+    )
+    .unwrap();
+    ctx.set_current_dir(&root);
+
+    let cfg = config::load().unwrap();
+    runner::run(Some("show"), &[], &cfg).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("out.txt")).unwrap().trim(),
+        "project command-project literal hunter2 from-file"
+    );
+
+    // The project's env file is read once per run, not once per task.
+    let yaml = r#"
+name: test-workflow
+env_file: .env.count
+commands:
+  - cmd: first
+    run: "true"
+  - cmd: second
+    run: "true"
+  - cmd: all
+    run: "true"
+    depends: [first, second]
+"#;
+    fs::write(root.join("zoi.yaml"), yaml).unwrap();
+    fs::write(root.join(".env.count"), "LOADED=$(echo x >> loads.txt)\n").unwrap();
+    let cfg = config::load().unwrap();
+    runner::run(Some("all"), &[], &cfg).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("loads.txt"))
+            .unwrap()
+            .lines()
+            .count(),
+        1
+    );
+}
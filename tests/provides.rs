@@ -1,5 +1,7 @@
+use std::fs;
+use std::path::Path;
 use tempfile::tempdir;
-use zoi::pkg::{db, types};
+use zoi::pkg::{config, db, install, local, types};
 
 mod common;
 
@@ -30,3 +32,221 @@ fn test_find_provides_logic() {
     assert!(!results.is_empty());
     assert_eq!(results[0].0.name, "git");
 }
+
+fn write_pkg(db_dir: &Path, name: &str, extra: &str) {
+    let dir = db_dir.join("zoidberg").join("core").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.pkg.lua", name)),
+        format!(
+            r#"metadata({{
+  name = "{name}",
+  repo = "core",
+  version = "1.0.0",
+  description = "Virtual package test",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+{extra}
+}})"#
+        ),
+    )
+    .unwrap();
+}
+
+fn write_app(db_dir: &Path, name: &str, virtual_name: &str) {
+    let dir = db_dir.join("zoidberg").join("core").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.pkg.lua", name)),
+        format!(
+            r#"metadata({{
+  name = "{name}",
+  repo = "core",
+  version = "1.0.0",
+  description = "Virtual package consumer",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+}})
+
+dependencies({{
+  runtime = {{
+    required = {{ "zoi:{virtual_name}" }},
+  }},
+}})"#
+        ),
+    )
+    .unwrap();
+}
+
+fn setup_registry(ctx: &mut common::TestContextGuard, root: &Path, providers: &[(&str, &str)]) {
+    let db_dir = root.join("db");
+    ctx.set_env_var("HOME", root);
+    ctx.set_env_var("ZOI_DB_DIR", &db_dir);
+    ctx.set_sysroot(root.to_path_buf());
+
+    let cfg = types::Config {
+        default_registry: Some(types::Registry {
+            handle: "zoidberg".to_string(),
+            url: "https://example.invalid/zoidberg.git".to_string(),
+            advisory_prefix: None,
+            authorities: None,
+        }),
+        repos: vec!["core".to_string()],
+        providers: providers
+            .iter()
+            .map(|(v, p)| (v.to_string(), p.to_string()))
+            .collect(),
+        ..Default::default()
+    };
+    config::write_user_config(&cfg).unwrap();
+}
+
+fn resolve(source: &str) -> install::resolver::DependencyGraph {
+    install::resolver::resolve_dependency_graph(
+        &[source.to_string()],
+        Some(types::Scope::User),
+        false,
+        true,
+        false,
+        None,
+        true,
+    )
+    .unwrap()
+    .0
+}
+
+fn node<'a>(
+    graph: &'a install::resolver::DependencyGraph,
+    name: &str,
+) -> Option<&'a install::resolver::InstallNode> {
+    graph.nodes.values().find(|n| n.pkg.name == name)
+}
+
+fn manifest(name: &str) -> types::InstallManifest {
+    types::InstallManifest {
+        name: name.to_string(),
+        version: "1.0.0".to_string(),
+        sub_package: None,
+        repo: "core".to_string(),
+        registry_handle: "zoidberg".to_string(),
+        package_type: types::PackageType::Package,
+        reason: types::InstallReason::Direct,
+        scope: types::Scope::User,
+        bins: None,
+        conflicts: None,
+        replaces: None,
+        provides: None,
+        backup: None,
+        installed_dependencies: vec![],
+        chosen_options: vec![],
+        chosen_optionals: vec![],
+        install_method: None,
+        service: None,
+        installed_files: vec![],
+        installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
+    }
+}
+
+#[test]
+fn test_find_virtual_providers() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", dir.path());
+
+    let conn = db::open_connection("zoidberg").unwrap();
+    for (name, provides) in [("openjdk", "java-runtime"), ("temurin", "java-runtime")] {
+        let pkg = types::Package {
+            name: name.to_string(),
+            repo: "core".to_string(),
+            version: Some("17.0.0".to_string()),
+            provides: Some(vec![provides.to_string()]),
+            ..Default::default()
+        };
+        db::update_package(&conn, &pkg, "zoidberg", None, None, None).unwrap();
+    }
+
+    let providers = db::find_virtual_providers("zoidberg", "java-runtime").unwrap();
+    let names: Vec<&str> = providers.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["openjdk", "temurin"]);
+    assert!(
+        db::find_virtual_providers("zoidberg", "java")
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_virtual_dependency_uses_configured_provider() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    setup_registry(&mut ctx, &root, &[("jre-a", "openjdk")]);
+
+    let db_dir = root.join("db");
+    write_app(&db_dir, "app", "jre-a");
+    write_pkg(&db_dir, "temurin", r#"  provides = { "jre-a" },"#);
+    write_pkg(&db_dir, "openjdk", r#"  provides = { "jre-a" },"#);
+
+    let graph = resolve("app");
+    assert!(node(&graph, "temurin").is_none());
+    let provider = node(&graph, "openjdk").expect("configured provider is installed");
+    assert_eq!(provider.provider_for, vec!["jre-a".to_string()]);
+
+    let app = node(&graph, "app").unwrap();
+    assert!(app.provider_for.is_empty());
+    assert_eq!(
+        app.dependencies,
+        vec!["zoi:#zoidberg@core/openjdk@1.0.0".to_string()]
+    );
+}
+
+#[test]
+fn test_virtual_dependency_prefers_installed_provider() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    setup_registry(&mut ctx, &root, &[("jre-b", "openjdk")]);
+
+    let db_dir = root.join("db");
+    write_app(&db_dir, "app", "jre-b");
+    write_pkg(&db_dir, "temurin", r#"  provides = { "jre-b" },"#);
+    write_pkg(&db_dir, "openjdk", r#"  provides = { "jre-b" },"#);
+
+    let mut installed = manifest("temurin");
+    installed.provides = Some(vec!["jre-b".to_string()]);
+    local::write_manifest(&installed).unwrap();
+
+    let graph = resolve("app");
+    assert!(node(&graph, "openjdk").is_none());
+    assert_eq!(
+        node(&graph, "temurin").unwrap().provider_for,
+        vec!["jre-b".to_string()]
+    );
+}
+
+#[test]
+fn test_virtual_dependency_skips_conflicting_provider() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    setup_registry(&mut ctx, &root, &[("jre-c", "openjdk")]);
+
+    let db_dir = root.join("db");
+    write_pkg(
+        &db_dir,
+        "openjdk",
+        r#"  provides = { "jre-c" },
+  conflicts = { "legacy-java" },"#,
+    );
+    write_pkg(&db_dir, "temurin", r#"  provides = { "jre-c" },"#);
+    local::write_manifest(&manifest("legacy-java")).unwrap();
+
+    let graph = resolve("jre-c");
+    assert!(node(&graph, "openjdk").is_none());
+    let provider = node(&graph, "temurin").unwrap();
+    assert_eq!(provider.provider_for, vec!["jre-c".to_string()]);
+    assert!(matches!(provider.reason, types::InstallReason::Direct));
+}
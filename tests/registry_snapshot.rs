@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::{db, snapshot, sync};
+
+mod common;
+
+fn write_pkg(root: &Path, name: &str, version: &str) {
+    let dir = root.join("core").join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.pkg.lua", name)),
+        format!(
+            r#"metadata({{
+  name = "{}",
+  repo = "core",
+  version = "{}",
+  description = "Snapshot package",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+}})
+"#,
+            name, version
+        ),
+    )
+    .unwrap();
+}
+
+fn version_of(handle: &str, name: &str) -> Option<String> {
+    db::list_all_packages(handle)
+        .unwrap()
+        .into_iter()
+        .find(|p| p.name == name)
+        .and_then(|p| p.version)
+}
+
+#[test]
+fn test_snapshot_url_detection() {
+    assert!(snapshot::is_snapshot_url(
+        "https://mirror.internal/zoidberg.tar.zst"
+    ));
+    assert!(snapshot::is_snapshot_url(
+        "snapshot+https://mirror.internal/zoidberg/"
+    ));
+    assert!(!snapshot::is_snapshot_url(
+        "https://gitlab.com/Zillowe/Zillwen/Zusty/Zoidberg.git"
+    ));
+}
+
+#[test]
+fn test_next_serial_always_increases() {
+    let now = snapshot::next_serial(None);
+    assert!(snapshot::next_serial(Some(1)) >= now);
+    assert_eq!(snapshot::next_serial(Some(now + 10)), now + 11);
+}
+
+#[test]
+fn test_snapshot_install_and_index() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let db_dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", db_dir.path());
+
+    let src = tempdir().unwrap();
+    fs::copy("tests/assets/repo.yaml", src.path().join("repo.yaml")).unwrap();
+    fs::create_dir_all(src.path().join("core")).unwrap();
+    fs::copy(
+        "tests/assets/ZSA-2026-D0042.sec.yaml",
+        src.path().join("core").join("ZSA-2026-D0042.sec.yaml"),
+    )
+    .unwrap();
+    write_pkg(src.path(), "alpha", "1.0.0");
+
+    let out = tempdir().unwrap();
+    let first = out.path().join("zoidberg-1.tar.zst");
+    let index = snapshot::create_snapshot(src.path(), &first, 1).unwrap();
+    assert_eq!(index.packages.len(), 1);
+    assert_eq!(index.packages[0].source, "core/alpha/alpha.pkg.lua");
+    assert_eq!(snapshot::read_archive_index(&first).unwrap().serial, 1);
+
+    let installed = snapshot::install_snapshot(&first, None, &[], db_dir.path(), None).unwrap();
+    assert_eq!(installed.handle, "Zoidberg");
+    assert!(installed.updated);
+
+    let reg_path = db_dir.path().join(&installed.handle);
+    assert!(reg_path.join("core/alpha/alpha.pkg.lua").exists());
+    sync::index_snapshot_registry(&installed.handle, &reg_path, false, None, false, None).unwrap();
+    assert_eq!(
+        version_of(&installed.handle, "alpha").as_deref(),
+        Some("1.0.0")
+    );
+    assert!(
+        !db::list_all_advisories(&installed.handle)
+            .unwrap()
+            .is_empty()
+    );
+
+    write_pkg(src.path(), "alpha", "2.0.0");
+    let second = out.path().join("zoidberg-2.tar.zst");
+    snapshot::create_snapshot(src.path(), &second, 2).unwrap();
+
+    let installed =
+        snapshot::install_snapshot(&second, None, &[], db_dir.path(), Some("Zoidberg")).unwrap();
+    assert!(installed.updated);
+    sync::index_snapshot_registry(&installed.handle, &reg_path, false, None, false, None).unwrap();
+    assert_eq!(
+        version_of(&installed.handle, "alpha").as_deref(),
+        Some("2.0.0")
+    );
+
+    let again =
+        snapshot::install_snapshot(&second, None, &[], db_dir.path(), Some("Zoidberg")).unwrap();
+    assert!(!again.updated);
+
+    let rollback = snapshot::install_snapshot(&first, None, &[], db_dir.path(), Some("Zoidberg"));
+    assert!(rollback.is_err(), "older snapshots must be refused");
+    assert_eq!(snapshot::read_index(&reg_path).unwrap().serial, 2);
+}
+
+#[test]
+fn test_unsigned_snapshot_rejected_with_authorities() {
+    let src = tempdir().unwrap();
+    fs::copy("tests/assets/repo.yaml", src.path().join("repo.yaml")).unwrap();
+    let out = tempdir().unwrap();
+    let archive = out.path().join("zoidberg.tar.zst");
+    snapshot::create_snapshot(src.path(), &archive, 1).unwrap();
+
+    let db_root = tempdir().unwrap();
+    let result = snapshot::install_snapshot(
+        &archive,
+        None,
+        &["842293159C4B03357C8328D3A75793A3E674252E".to_string()],
+        db_root.path(),
+        None,
+    );
+    assert!(result.is_err());
+    assert!(!db_root.path().join("Zoidberg").exists());
+}
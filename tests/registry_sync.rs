@@ -0,0 +1,208 @@
+use git2::{Repository, Signature};
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::{db, sync, types};
+
+mod common;
+
+fn write_pkg(root: &Path, repo: &str, name: &str, version: &str) {
+    let dir = root.join(repo).join(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join(format!("{}.pkg.lua", name)),
+        format!(
+            r#"metadata({{
+  name = "{}",
+  repo = "{}",
+  version = "{}",
+  description = "Test package",
+  maintainer = {{ name = "Zoi", email = "zoi@example.com" }},
+  types = {{ "source" }},
+}})
+"#,
+            name, repo, version
+        ),
+    )
+    .unwrap();
+}
+
+fn commit_all(repo: &Repository, message: &str) {
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index
+        .update_all(["*"].iter(), None)
+        .expect("index should track deletions");
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("Zoi", "zoi@example.com").unwrap();
+    let parents = match repo.head() {
+        Ok(head) => vec![head.peel_to_commit().unwrap()],
+        Err(_) => Vec::new(),
+    };
+    let parent_refs: Vec<_> = parents.iter().collect();
+    repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parent_refs)
+        .unwrap();
+}
+
+fn version_of(handle: &str, name: &str) -> Option<String> {
+    db::list_all_packages(handle)
+        .unwrap()
+        .into_iter()
+        .find(|p| p.name == name)
+        .and_then(|p| p.version)
+}
+
+#[test]
+fn test_incremental_registry_sync() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let db_dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", db_dir.path());
+
+    let reg_dir = tempdir().unwrap();
+    let root = reg_dir.path();
+    let repo = Repository::init(root).unwrap();
+    fs::copy("tests/assets/repo.yaml", root.join("repo.yaml")).unwrap();
+    write_pkg(root, "core", "alpha", "1.0.0");
+    write_pkg(root, "core", "beta", "1.0.0");
+    commit_all(&repo, "initial");
+
+    let handle = "incremental";
+    sync::refresh_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("1.0.0"));
+    assert_eq!(version_of(handle, "beta").as_deref(), Some("1.0.0"));
+
+    let marker = types::Package {
+        name: "marker".to_string(),
+        repo: "core".to_string(),
+        version: Some("0.1.0".to_string()),
+        ..Default::default()
+    };
+    let conn = db::open_connection(handle).unwrap();
+    db::update_package(&conn, &marker, handle, None, None, None).unwrap();
+    drop(conn);
+
+    write_pkg(root, "core", "alpha", "2.0.0");
+    write_pkg(root, "core", "gamma", "1.0.0");
+    fs::remove_dir_all(root.join("core").join("beta")).unwrap();
+    commit_all(&repo, "update");
+
+    sync::refresh_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("2.0.0"));
+    assert_eq!(version_of(handle, "gamma").as_deref(), Some("1.0.0"));
+    assert_eq!(version_of(handle, "beta"), None);
+    assert_eq!(
+        version_of(handle, "marker").as_deref(),
+        Some("0.1.0"),
+        "an incremental sync must not rebuild the whole database"
+    );
+
+    let conn = db::open_connection(handle).unwrap();
+    let head = repo.head().unwrap().target().unwrap().to_string();
+    assert_eq!(
+        db::get_meta(&conn, "indexed_commit").unwrap().as_deref(),
+        Some(head.as_str())
+    );
+}
+
+#[test]
+fn test_registry_sync_rebuilds_without_recorded_commit() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let db_dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", db_dir.path());
+
+    let reg_dir = tempdir().unwrap();
+    let root = reg_dir.path();
+    let repo = Repository::init(root).unwrap();
+    write_pkg(root, "core", "alpha", "1.0.0");
+    commit_all(&repo, "initial");
+
+    let handle = "rebuild";
+    let marker = types::Package {
+        name: "stale".to_string(),
+        repo: "core".to_string(),
+        version: Some("0.1.0".to_string()),
+        ..Default::default()
+    };
+    let conn = db::open_connection(handle).unwrap();
+    db::update_package(&conn, &marker, handle, None, None, None).unwrap();
+    drop(conn);
+
+    sync::refresh_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("1.0.0"));
+    assert_eq!(version_of(handle, "stale"), None);
+}
+
+#[test]
+fn test_sync_registry_to_past_revision() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let db_dir = tempdir().unwrap();
+    ctx.set_env_var("ZOI_DB_DIR", db_dir.path());
+
+    let reg_dir = tempdir().unwrap();
+    let root = reg_dir.path();
+    let repo = Repository::init(root).unwrap();
+    write_pkg(root, "core", "alpha", "1.0.0");
+    commit_all(&repo, "initial");
+    let first = repo.head().unwrap().target().unwrap();
+    repo.tag_lightweight("v1", &repo.find_object(first, None).unwrap(), false)
+        .unwrap();
+    write_pkg(root, "core", "alpha", "2.0.0");
+    commit_all(&repo, "update");
+
+    let handle = "timetravel";
+    sync::refresh_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("2.0.0"));
+
+    let pinned = sync::checkout_registry_revision(root, "v1").unwrap();
+    assert_eq!(pinned, first.to_string());
+    sync::refresh_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("1.0.0"));
+
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let tip = sync::resolve_registry_revision(root, &today).unwrap();
+    assert_ne!(tip, first, "a date resolves against the branch, not HEAD");
+    assert!(sync::resolve_registry_revision(root, "1970-01-01").is_err());
+
+    sync::reattach_registry_head(root).unwrap();
+    assert!(!repo.head_detached().unwrap());
+    sync::refresh_registry_db(handle, root, false, None, false, None).unwrap();
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("2.0.0"));
+}
+
+#[test]
+fn test_locked_revisions_are_restored_after_install() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    let db_dir = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    ctx.set_env_var("ZOI_DB_DIR", db_dir.path());
+
+    let handle = "locked";
+    let root = home.path().join(".zoi/pkgs/db").join(handle);
+    let repo = Repository::init(&root).unwrap();
+    write_pkg(&root, "core", "alpha", "1.0.0");
+    commit_all(&repo, "initial");
+    let first = repo.head().unwrap().target().unwrap().to_string();
+    write_pkg(&root, "core", "alpha", "2.0.0");
+    commit_all(&repo, "update");
+    sync::refresh_registry_db(handle, &root, false, None, false, None).unwrap();
+
+    let mut lock = types::ZoiLock::default();
+    lock.registry_revisions
+        .insert(handle.to_string(), first.clone());
+    let locked = sync::checkout_locked_revisions(&lock, false).unwrap();
+    assert!(repo.head_detached().unwrap());
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("1.0.0"));
+    drop(locked);
+    assert!(!repo.head_detached().unwrap());
+    assert_eq!(version_of(handle, "alpha").as_deref(), Some("2.0.0"));
+
+    // A revision that isn't available locally needs a URL to fetch it.
+    lock.registry_revisions
+        .insert(handle.to_string(), "0".repeat(40));
+    assert!(sync::checkout_locked_revisions(&lock, false).is_err());
+    assert!(!repo.head_detached().unwrap());
+}
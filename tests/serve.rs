@@ -0,0 +1,128 @@
+use std::fs;
+use tempfile::tempdir;
+use zoi::pkg::{cache, serve, snapshot};
+
+mod common;
+
+const REPO_YAML: &str = r#"name: Zoidberg
+description: Serve test registry
+git:
+  - type: main
+    url: https://example.invalid/zoidberg.git
+repos:
+  - name: core
+    type: official
+    active: true
+"#;
+
+fn get(base: &str, path: &str) -> (u16, Vec<u8>) {
+    let response = reqwest::blocking::get(format!("{}{}", base, path)).unwrap();
+    let status = response.status().as_u16();
+    (status, response.bytes().unwrap().to_vec())
+}
+
+#[test]
+fn test_serve_archives_and_registries() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    let home = root.join("home");
+    fs::create_dir_all(&home).unwrap();
+    let db_dir = root.join("db");
+    ctx.set_env_var("HOME", &home);
+    ctx.set_env_var("ZOI_DB_DIR", &db_dir);
+    ctx.set_sysroot(root.clone());
+
+    let reg = db_dir.join("zoidberg");
+    let pkg_dir = reg.join("core").join("hello");
+    fs::create_dir_all(&pkg_dir).unwrap();
+    fs::create_dir_all(reg.join(".git")).unwrap();
+    fs::write(reg.join(".git").join("config"), "secret").unwrap();
+    fs::write(reg.join("repo.yaml"), REPO_YAML).unwrap();
+    fs::write(
+        pkg_dir.join("hello.pkg.lua"),
+        r#"metadata({
+  name = "hello",
+  repo = "core",
+  version = "1.0.0",
+  description = "Serve test",
+  maintainer = { name = "Zoi", email = "zoi@example.com" },
+})"#,
+    )
+    .unwrap();
+
+    let archive_name = "hello-1.0.0-linux-amd64.pkg.tar.zst";
+    let archive_cache = cache::get_archive_cache_root().unwrap();
+    fs::create_dir_all(&archive_cache).unwrap();
+    fs::write(archive_cache.join(archive_name), b"archive").unwrap();
+    fs::write(
+        archive_cache.join(format!("{}.hash", archive_name)),
+        "deadbeef",
+    )
+    .unwrap();
+
+    let listener = serve::bind("127.0.0.1", 0).unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || serve::serve(listener));
+
+    let (status, body) = get(&base, &format!("/archives/{}", archive_name));
+    assert_eq!(status, 200);
+    assert_eq!(body, b"archive");
+
+    let (status, body) = get(
+        &base,
+        &format!("/archives/core/linux/amd64/1.0.0/{}.hash", archive_name),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(body, b"deadbeef");
+
+    let (status, body) = get(&base, "/archives/");
+    assert_eq!(status, 200);
+    assert!(String::from_utf8(body).unwrap().contains(archive_name));
+
+    let (status, body) = get(&base, "/registries/zoidberg/repo.yaml");
+    assert_eq!(status, 200);
+    assert_eq!(body, REPO_YAML.as_bytes());
+
+    assert_eq!(get(&base, "/registries/zoidberg/.git/config").0, 404);
+    assert_eq!(
+        get(&base, "/archives/%2e%2e/%2e%2e/db/zoidberg/repo.yaml").0,
+        404
+    );
+    assert_eq!(get(&base, "/archives/missing.pkg.tar.zst").0, 404);
+
+    let (status, body) = get(&base, "/registries/zoidberg.tar.zst");
+    assert_eq!(status, 200);
+    let snapshot_path = root.join("zoidberg.tar.zst");
+    fs::write(&snapshot_path, body).unwrap();
+    let mirror_db = root.join("mirror-db");
+    let installed =
+        snapshot::install_snapshot(&snapshot_path, None, &[], &mirror_db, None).unwrap();
+    assert_eq!(installed.handle, "Zoidberg");
+    assert!(
+        mirror_db
+            .join("Zoidberg")
+            .join("core")
+            .join("hello")
+            .join("hello.pkg.lua")
+            .exists()
+    );
+
+    // Rebuilds within the same second still get a newer serial.
+    let first = snapshot::read_archive_index(&snapshot_path).unwrap().serial;
+    let (_, body) = get(&base, "/registries/zoidberg.tar.zst");
+    fs::write(&snapshot_path, body).unwrap();
+    let second = snapshot::read_archive_index(&snapshot_path).unwrap().serial;
+    assert!(second > first);
+
+    // More connections than workers are queued, not dropped.
+    let clients: Vec<_> = (0..40)
+        .map(|_| {
+            let base = base.clone();
+            std::thread::spawn(move || get(&base, "/registries/zoidberg/repo.yaml").0)
+        })
+        .collect();
+    for client in clients {
+        assert_eq!(client.join().unwrap(), 200);
+    }
+}
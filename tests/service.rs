@@ -66,6 +66,9 @@ fn test_linux_service_lifecycle() {
         service: Some(service_config.clone()),
         installed_files: vec![],
         installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
     };
 
     let manifest_path = version_path.join("manifest.yaml");
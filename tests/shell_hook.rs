@@ -0,0 +1,115 @@
+use std::fs;
+use tempfile::tempdir;
+use zoi::cli::HookShell;
+use zoi::cmd::hook::{render, script};
+use zoi::project::hook::{self, Change, STATE_VAR};
+
+mod common;
+
+fn value<'a>(changes: &'a [Change], var: &str) -> Option<&'a Option<String>> {
+    changes.iter().find(|(k, _)| k == var).map(|(_, v)| v)
+}
+
+fn apply(ctx: &mut common::TestContextGuard, changes: &[Change]) {
+    for (var, value) in changes {
+        ctx.set_env_var(var, value.as_deref().unwrap_or(""));
+    }
+}
+
+#[test]
+fn test_hook_loads_and_unloads_trusted_projects() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().canonicalize().unwrap();
+    let project = root.join("app");
+    fs::create_dir_all(project.join("src")).unwrap();
+    fs::write(
+        project.join("zoi.yaml"),
+        "name: app\nenv_file: .env\nshell:\n  env:\n    GREETING: hello\n",
+    )
+    .unwrap();
+    fs::write(project.join(".env"), "TOKEN=abc\n").unwrap();
+
+    ctx.set_env_var("HOME", root.join("home"));
+    ctx.set_env_var("PATH", "/usr/bin");
+    ctx.set_env_var("GREETING", "outer");
+    ctx.set_env_var(STATE_VAR, "");
+    ctx.set_current_dir(&project.join("src"));
+
+    // Untrusted projects only record that they were seen.
+    let changes = hook::export().unwrap();
+    assert_eq!(changes.len(), 1);
+    assert!(value(&changes, "GREETING").is_none());
+    apply(&mut ctx, &changes);
+    assert!(hook::export().unwrap().is_empty());
+
+    hook::allow().unwrap();
+    let changes = hook::export().unwrap();
+    assert_eq!(
+        value(&changes, "GREETING"),
+        Some(&Some("hello".to_string()))
+    );
+    assert_eq!(value(&changes, "TOKEN"), Some(&Some("abc".to_string())));
+    let path = value(&changes, "PATH").unwrap().clone().unwrap();
+    assert!(path.starts_with(&project.join(".zoi/pkgs/bin").to_string_lossy().to_string()));
+    assert!(path.ends_with(":/usr/bin"));
+    assert!(project.join(".zoi/cache/hook.json").is_file());
+    apply(&mut ctx, &changes);
+    assert!(hook::export().unwrap().is_empty());
+
+    // Editing an env file revokes trust too.
+    fs::write(project.join(".env"), "TOKEN=changed\n").unwrap();
+    let changes = hook::export().unwrap();
+    assert_eq!(value(&changes, "TOKEN"), Some(&None));
+    hook::allow().unwrap();
+    let changes = hook::export().unwrap();
+    assert_eq!(value(&changes, "TOKEN"), Some(&Some("changed".to_string())));
+    apply(&mut ctx, &changes);
+
+    ctx.set_current_dir(&root);
+    let changes = hook::export().unwrap();
+    assert_eq!(value(&changes, "PATH"), Some(&Some("/usr/bin".to_string())));
+    assert_eq!(
+        value(&changes, "GREETING"),
+        Some(&Some("outer".to_string()))
+    );
+    assert_eq!(value(&changes, "TOKEN"), Some(&None));
+    assert_eq!(value(&changes, STATE_VAR), Some(&None));
+
+    // Editing zoi.yaml revokes trust.
+    apply(&mut ctx, &changes);
+    ctx.set_current_dir(&project);
+    fs::write(project.join("zoi.yaml"), "name: app\n").unwrap();
+    let changes = hook::export().unwrap();
+    assert_eq!(changes.len(), 1);
+    apply(&mut ctx, &changes);
+    hook::allow().unwrap();
+    hook::deny().unwrap();
+    assert!(hook::export().unwrap().is_empty());
+}
+
+#[test]
+fn test_hook_renders_shell_syntax() {
+    let changes = vec![
+        ("PATH".to_string(), Some("/a:/b".to_string())),
+        ("MSG".to_string(), Some("it's".to_string())),
+        ("OLD".to_string(), None),
+    ];
+    assert_eq!(
+        render(HookShell::Bash, &changes),
+        "export PATH='/a:/b';\nexport MSG='it'\\''s';\nunset OLD;\n"
+    );
+    assert_eq!(
+        render(HookShell::Fish, &changes),
+        "set -gx PATH (string split -- : '/a:/b');\nset -gx MSG 'it\\'s';\nset -e OLD;\n"
+    );
+    assert_eq!(
+        render(HookShell::Pwsh, &changes),
+        "${env:PATH} = '/a:/b'\n${env:MSG} = 'it''s'\nRemove-Item -Path Env:OLD -ErrorAction SilentlyContinue\n"
+    );
+
+    assert!(script(HookShell::Bash, "/bin/zoi").contains("'/bin/zoi' hook bash --export"));
+    assert!(script(HookShell::Zsh, "/bin/zoi").contains("chpwd_functions"));
+    assert!(script(HookShell::Fish, "/bin/zoi").contains("hook fish --export | source"));
+    assert!(script(HookShell::Pwsh, "/bin/zoi").contains("Invoke-Expression"));
+}
@@ -71,3 +71,51 @@ fn test_shim_resolution_logic() {
     println!("Resolved v2 override: {}", resolved_v2.display());
     assert!(resolved_v2.to_string_lossy().contains(v2));
 }
+
+#[test]
+fn test_shim_honours_version_files() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().to_path_buf();
+    let home = root.join("home");
+    fs::create_dir_all(&home).unwrap();
+    ctx.set_env_var("HOME", home.clone());
+    ctx.set_sysroot(root.clone());
+
+    let pkg_id = zoi::pkg::utils::generate_package_id("local", "core", "nodejs");
+    let pkg_dir = local::get_store_base_dir(types::Scope::User)
+        .unwrap()
+        .join(zoi::pkg::utils::get_package_dir_name(&pkg_id, "nodejs"));
+    for v in ["18.20.0", "20.11.1", "22.1.0"] {
+        let bin_dir = pkg_dir.join(v).join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("node"), v).unwrap();
+    }
+    let conn = db::open_connection("local").unwrap();
+    let pkg = types::Package {
+        name: "nodejs".to_string(),
+        repo: "core".to_string(),
+        version: Some("22.1.0".to_string()),
+        bins: Some(vec!["node".to_string()]),
+        ..Default::default()
+    };
+    db::update_package(&conn, &pkg, "local", Some(types::Scope::User), None, None).unwrap();
+    let pm = PluginManager::new().unwrap();
+
+    // Pinned by the package name two directories up.
+    let project = root.join("project");
+    let nested = project.join("src/app");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(project.join(".tool-versions"), "nodejs 20.11.1\n").unwrap();
+    ctx.set_current_dir(&nested);
+    let resolved = shim::resolve_to_installed_bin("node", &pm).unwrap();
+    assert!(resolved.to_string_lossy().contains("20.11.1"));
+
+    // A nearer .nvmrc wins, unless it names no concrete version.
+    fs::write(nested.join(".nvmrc"), "lts/*\n").unwrap();
+    let resolved = shim::resolve_to_installed_bin("node", &pm).unwrap();
+    assert!(resolved.to_string_lossy().contains("20.11.1"));
+    fs::write(nested.join(".nvmrc"), "v18\n").unwrap();
+    let resolved = shim::resolve_to_installed_bin("node", &pm).unwrap();
+    assert!(resolved.to_string_lossy().contains("18.20.0"));
+}
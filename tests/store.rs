@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+use zoi::pkg::{local, store, types::Scope};
+
+mod common;
+
+fn setup(ctx: &mut common::TestContextGuard, root: &Path) {
+    ctx.set_env_var("HOME", root);
+    ctx.set_env_var("ZOI_DB_DIR", root.join("db"));
+    ctx.set_sysroot(root.to_path_buf());
+}
+
+fn install_version(version: &str, files: &[(&str, &str)]) -> (PathBuf, Vec<PathBuf>) {
+    let version_dir =
+        local::get_package_version_dir(Scope::User, "zoidberg", "core", "toolchain", version)
+            .unwrap();
+    let mut rels = Vec::new();
+    for (rel, content) in files {
+        let path = version_dir.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        rels.push(PathBuf::from(rel));
+    }
+    (version_dir, rels)
+}
+
+fn object_count() -> usize {
+    let objects = store::get_objects_dir(Scope::User).unwrap();
+    walkdir::WalkDir::new(objects)
+        .min_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .count()
+}
+
+#[cfg(unix)]
+fn inode(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).unwrap().ino()
+}
+
+#[test]
+fn test_identical_files_are_stored_once() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let (v1, files1) = install_version(
+        "1.0.0",
+        &[("lib/libcore.so", "shared bytes"), ("bin/tool", "v1")],
+    );
+    let stats = store::ingest(Scope::User, &v1, &files1, &[]).unwrap();
+    assert_eq!(stats.added, 2);
+
+    let (v2, files2) = install_version(
+        "2.0.0",
+        &[("lib/libcore.so", "shared bytes"), ("bin/tool", "v2")],
+    );
+    let stats = store::ingest(Scope::User, &v2, &files2, &[]).unwrap();
+    assert_eq!(stats.shared, 1);
+    assert_eq!(stats.added, 1);
+    assert_eq!(stats.saved_bytes, "shared bytes".len() as u64);
+    assert_eq!(object_count(), 3);
+
+    #[cfg(unix)]
+    assert_eq!(
+        inode(&v1.join("lib/libcore.so")),
+        inode(&v2.join("lib/libcore.so"))
+    );
+    assert_eq!(
+        fs::read_to_string(v2.join("lib/libcore.so")).unwrap(),
+        "shared bytes"
+    );
+}
+
+#[test]
+fn test_release_frees_only_unshared_objects() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let (v1, files1) = install_version("1.0.0", &[("a", "same"), ("b", "only v1")]);
+    store::ingest(Scope::User, &v1, &files1, &[]).unwrap();
+    let (v2, files2) = install_version("2.0.0", &[("a", "same")]);
+    store::ingest(Scope::User, &v2, &files2, &[]).unwrap();
+    assert_eq!(object_count(), 2);
+
+    fs::remove_dir_all(&v1).unwrap();
+    let freed = store::release(Scope::User, &v1).unwrap();
+    assert_eq!(freed, "only v1".len() as u64);
+    assert_eq!(object_count(), 1);
+    assert_eq!(fs::read_to_string(v2.join("a")).unwrap(), "same");
+
+    let package_dir = v2.parent().unwrap().to_path_buf();
+    fs::remove_dir_all(&package_dir).unwrap();
+    store::release(Scope::User, &package_dir).unwrap();
+    assert_eq!(object_count(), 0);
+}
+
+#[test]
+fn test_skipped_files_stay_private() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let (v1, files1) = install_version("1.0.0", &[("etc/app.conf", "defaults")]);
+    store::ingest(Scope::User, &v1, &files1, &["etc/app.conf".to_string()]).unwrap();
+    assert_eq!(object_count(), 0);
+}
+
+#[test]
+fn test_unshare_protects_other_versions_on_reinstall() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let (v1, files1) = install_version("1.0.0", &[("a", "same")]);
+    store::ingest(Scope::User, &v1, &files1, &[]).unwrap();
+    let (v2, files2) = install_version("2.0.0", &[("a", "same")]);
+    store::ingest(Scope::User, &v2, &files2, &[]).unwrap();
+
+    store::unshare(&v2, &files2).unwrap();
+    fs::write(v2.join("a"), "rebuilt").unwrap();
+    store::ingest(Scope::User, &v2, &files2, &[]).unwrap();
+
+    assert_eq!(fs::read_to_string(v1.join("a")).unwrap(), "same");
+    assert_eq!(fs::read_to_string(v2.join("a")).unwrap(), "rebuilt");
+    assert_eq!(object_count(), 2);
+}
+
+#[test]
+fn test_gc_removes_objects_of_deleted_versions() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let (v1, files1) = install_version("1.0.0", &[("a", "old"), ("b", "kept")]);
+    store::ingest(Scope::User, &v1, &files1, &[]).unwrap();
+    let (v2, files2) = install_version("2.0.0", &[("b", "kept")]);
+    store::ingest(Scope::User, &v2, &files2, &[]).unwrap();
+
+    fs::remove_dir_all(&v1).unwrap();
+
+    let report = store::gc(Scope::User, true).unwrap();
+    assert_eq!(report.removed_objects, 1);
+    assert_eq!(object_count(), 2);
+
+    let report = store::gc(Scope::User, false).unwrap();
+    assert_eq!(report.stale_versions, 1);
+    assert_eq!(report.removed_objects, 1);
+    assert_eq!(report.freed_bytes, "old".len() as u64);
+    assert_eq!(object_count(), 1);
+    assert!(store::verify(Scope::User, false).unwrap().is_ok());
+}
+
+#[test]
+fn test_verify_reports_corrupt_objects() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let root = tempdir().unwrap();
+    setup(&mut ctx, root.path());
+
+    let (v1, files1) = install_version("1.0.0", &[("a", "good")]);
+    store::ingest(Scope::User, &v1, &files1, &[]).unwrap();
+    assert!(store::verify(Scope::User, false).unwrap().is_ok());
+
+    fs::write(v1.join("a"), "evil").unwrap();
+
+    let report = store::verify(Scope::User, false).unwrap();
+    assert_eq!(report.checked_objects, 1);
+    assert_eq!(report.corrupt.len(), 1);
+    assert_eq!(report.corrupt[0].1, vec![v1.join("a")]);
+    assert!(!report.is_ok());
+    assert_eq!(object_count(), 1);
+
+    let report = store::verify(Scope::User, true).unwrap();
+    assert_eq!(report.corrupt.len(), 1);
+    assert_eq!(object_count(), 0);
+}
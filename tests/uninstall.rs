@@ -29,6 +29,9 @@ fn sample_manifest(name: &str, repo: &str) -> types::InstallManifest {
         service: None,
         installed_files: vec![],
         installed_size: None,
+        provider_for: vec![],
+        features: vec![],
+        file_records: vec![],
     }
 }
 
@@ -0,0 +1,123 @@
+use std::fs;
+use tempfile::tempdir;
+use zoi::pkg::install::manifest::create_manifest;
+use zoi::pkg::types::{InstallReason, Package};
+use zoi::pkg::verify::{self, Problem};
+
+#[test]
+fn test_verify_reports_changes_since_install() {
+    let dir = tempdir().unwrap();
+    let bin = dir.path().join("bin/tool");
+    let lib = dir.path().join("lib/libtool.so");
+    let doc = dir.path().join("share/README");
+    let conf = dir.path().join("etc/tool.conf");
+    for path in [&bin, &lib, &doc, &conf] {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "original").unwrap();
+    }
+    let files: Vec<String> = [&bin, &lib, &doc, &conf]
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    let pkg = Package {
+        name: "tool".to_string(),
+        version: Some("1.0.0".to_string()),
+        backup: Some(vec!["etc/tool.conf".to_string()]),
+        ..Default::default()
+    };
+    let manifest = create_manifest(
+        &pkg,
+        InstallReason::Direct,
+        vec![],
+        None,
+        files,
+        "local",
+        &[],
+        &[],
+        None,
+    )
+    .unwrap();
+    assert_eq!(manifest.file_records.len(), 4);
+    assert_eq!(manifest.file_records[0].size, 8);
+
+    let report = verify::verify_package(&manifest);
+    assert!(report.is_ok());
+    assert!(report.problems.is_empty());
+
+    fs::write(&conf, "edited by the admin").unwrap();
+    let report = verify::verify_package(&manifest);
+    assert!(report.is_ok());
+    assert_eq!(report.problems[0].problem, Problem::ModifiedConfig);
+
+    // Same size, different content.
+    fs::write(&bin, "tampered").unwrap();
+    fs::remove_file(&lib).unwrap();
+    let report = verify::verify_package(&manifest);
+    assert!(!report.is_ok());
+    let problem = |path: &std::path::Path| {
+        report
+            .problems
+            .iter()
+            .filter(|p| p.path == path.to_string_lossy())
+            .map(|p| p.problem.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(problem(&bin), vec![Problem::Modified]);
+    assert_eq!(problem(&lib), vec![Problem::Missing]);
+    assert!(problem(&doc).is_empty());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let expected = manifest.file_records[2].mode;
+        fs::set_permissions(&doc, fs::Permissions::from_mode(0o4777)).unwrap();
+        let report = verify::verify_package(&manifest);
+        assert!(report.problems.contains(&verify::FileProblem {
+            path: doc.to_string_lossy().to_string(),
+            problem: Problem::ModeChanged {
+                expected,
+                actual: 0o4777,
+            },
+        }));
+    }
+
+    let json = serde_json::to_value(verify::verify_package(&manifest)).unwrap();
+    assert_eq!(json["name"], "tool");
+    assert_eq!(json["scope"], "user");
+    assert!(
+        json["problems"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|p| p["kind"] == "modified-config")
+    );
+}
+
+#[test]
+fn test_manifests_without_records_are_flagged() {
+    let pkg = Package {
+        name: "old".to_string(),
+        version: Some("0.1.0".to_string()),
+        ..Default::default()
+    };
+    let mut manifest = create_manifest(
+        &pkg,
+        InstallReason::Direct,
+        vec![],
+        None,
+        vec!["/nonexistent/old".to_string()],
+        "local",
+        &[],
+        &[],
+        None,
+    )
+    .unwrap();
+    assert!(manifest.file_records.is_empty());
+    let report = verify::verify_package(&manifest);
+    assert!(report.unrecorded);
+    assert!(report.is_ok());
+
+    manifest.installed_files.clear();
+    assert!(!verify::verify_package(&manifest).unrecorded);
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use tempfile::tempdir;
+use zoi::pkg::version_files::{self, Pinned};
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|n| n.to_string()).collect()
+}
+
+#[test]
+fn test_version_files_are_read() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    let find = |tool: &[&str]| version_files::find_version(root, &names(tool)).map(|p| p.version);
+
+    fs::write(
+        root.join(".tool-versions"),
+        "# pinned tools\nnodejs 20.11.1 18.19.0\nterraform system\n",
+    )
+    .unwrap();
+    fs::write(root.join(".python-version"), "3.12.2\n").unwrap();
+    fs::write(
+        root.join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"1.77.0\"\n",
+    )
+    .unwrap();
+    fs::write(
+        root.join("mise.toml"),
+        "[tools]\ngo = [\"1.22\", \"1.21\"]\nterraform = { version = \"1.7.5\" }\n",
+    )
+    .unwrap();
+
+    // asdf names node "nodejs".
+    assert_eq!(find(&["node"]).as_deref(), Some("20.11.1"));
+    assert_eq!(find(&["python"]).as_deref(), Some("3.12.2"));
+    assert_eq!(find(&["cargo"]).as_deref(), Some("1.77.0"));
+    assert_eq!(find(&["golang"]).as_deref(), Some("1.22"));
+    // `system` leaves the choice to Zoi, so mise.toml is consulted next.
+    assert_eq!(find(&["terraform"]).as_deref(), Some("1.7.5"));
+    assert_eq!(find(&["ruby"]), None);
+
+    fs::write(root.join(".zoi-version"), "node 22.1.0\n").unwrap();
+    assert_eq!(
+        version_files::find_version(root, &names(&["node"])),
+        Some(Pinned {
+            version: "22.1.0".to_string(),
+            file: root.join(".zoi-version"),
+        })
+    );
+}
+
+#[test]
+fn test_nearest_version_file_wins() {
+    let dir = tempdir().unwrap();
+    let nested = dir.path().join("a/b/c");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(dir.path().join(".zoi-version"), "node 20\n").unwrap();
+    fs::write(dir.path().join("a/.nvmrc"), "lts/*\n").unwrap();
+
+    let found = version_files::find_version(&nested, &names(&["node"])).unwrap();
+    assert_eq!(found.version, "20");
+
+    fs::write(dir.path().join("a/b/.node-version"), "v21.7.1").unwrap();
+    let found = version_files::find_version(&nested, &names(&["node"])).unwrap();
+    assert_eq!(found.version, "21.7.1");
+    assert_eq!(found.file, dir.path().join("a/b/.node-version"));
+}
+
+#[test]
+fn test_write_local_updates_the_right_file() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+
+    let file = version_files::write_local(root, "node", "20").unwrap();
+    assert_eq!(file, root.join(".zoi-version"));
+    version_files::write_local(root, "python", "3.12").unwrap();
+    version_files::write_local(root, "node", "22").unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "node 22\npython 3.12\n");
+
+    let asdf = tempdir().unwrap();
+    fs::write(
+        asdf.path().join(".tool-versions"),
+        "nodejs 18.0.0\nruby 3.3.0\n",
+    )
+    .unwrap();
+    let file = version_files::write_local(asdf.path(), "node", "20.11.1").unwrap();
+    assert_eq!(
+        fs::read_to_string(file).unwrap(),
+        "nodejs 20.11.1\nruby 3.3.0\n"
+    );
+
+    let nvm = tempdir().unwrap();
+    fs::write(nvm.path().join(".nvmrc"), "18\n").unwrap();
+    let file = version_files::write_local(nvm.path(), "node", "20").unwrap();
+    assert_eq!(file, nvm.path().join(".nvmrc"));
+    assert_eq!(fs::read_to_string(file).unwrap(), "20\n");
+}
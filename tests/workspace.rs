@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+use zoi::pkg::{local, types};
+use zoi::project::{config, runner, workspace};
+
+mod common;
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn setup(root: &Path, web_node: &str) {
+    write(
+        &root.join("zoi.yaml"),
+        r#"
+name: mono
+workspace:
+  members: ["services/*"]
+pkgs:
+  - node
+  - jq@1.7
+env:
+  A: root
+  B: unset
+commands:
+  - cmd: test
+    run: echo "$A $B" > out.txt
+"#,
+    );
+    write(
+        &root.join("services/api/zoi.yaml"),
+        r#"
+name: api
+pkgs:
+  - node@20
+env:
+  B: api
+"#,
+    );
+    write(
+        &root.join("services/web/zoi.yaml"),
+        &format!(
+            r#"
+name: web
+pkgs:
+  - {}
+commands:
+  - cmd: build
+    run: echo web > built.txt
+"#,
+            web_node
+        ),
+    );
+    fs::create_dir_all(root.join("services/docs")).unwrap();
+}
+
+#[test]
+fn test_workspace_discovers_root_and_merges_members() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().canonicalize().unwrap();
+    setup(&root, "node@20");
+
+    let nested = root.join("services/api/src");
+    fs::create_dir_all(&nested).unwrap();
+    ctx.set_current_dir(&nested);
+
+    assert_eq!(config::project_root().unwrap(), root);
+    assert_eq!(
+        local::get_store_base_dir(types::Scope::Project).unwrap(),
+        root.join(".zoi/pkgs/store")
+    );
+
+    let cfg = config::load().unwrap();
+    assert_eq!(cfg.name, "api");
+    assert_eq!(
+        cfg.dir.as_deref(),
+        Some(root.join("services/api").as_path())
+    );
+    assert_eq!(cfg.pkgs, vec!["jq@1.7", "node@20"]);
+    assert_eq!(cfg.env.for_platform("linux-amd64")["A"], "root");
+    assert_eq!(cfg.env.for_platform("linux-amd64")["B"], "api");
+    assert!(cfg.commands.iter().any(|c| c.cmd == "test"));
+
+    let ws = workspace::Workspace::load().unwrap().unwrap();
+    let paths: Vec<&str> = ws.members.iter().map(|m| m.path.as_str()).collect();
+    assert_eq!(paths, vec!["services/api", "services/web"]);
+    assert_eq!(ws.all_pkgs().unwrap(), vec!["node@20", "jq@1.7"]);
+    assert_eq!(ws.member("web").unwrap().path, "services/web");
+    assert_eq!(ws.member("./services/api/").unwrap().config.name, "api");
+    assert!(ws.member("docs").is_err());
+
+    ctx.set_current_dir(&root.join("services/docs"));
+    assert_eq!(config::load().unwrap().name, "mono");
+}
+
+#[test]
+fn test_workspace_rejects_conflicting_pins() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().canonicalize().unwrap();
+    setup(&root, "node@18");
+    ctx.set_current_dir(&root);
+
+    let ws = workspace::Workspace::load().unwrap().unwrap();
+    let err = ws.all_pkgs().unwrap_err().to_string();
+    assert!(
+        err.contains("'node@20' (api) and 'node@18' (web)"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_workspace_member_pins_override_the_root() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().canonicalize().unwrap();
+    setup(&root, "node");
+    let root_config = fs::read_to_string(root.join("zoi.yaml")).unwrap();
+    write(
+        &root.join("zoi.yaml"),
+        &root_config.replace("  - node\n", "  - node@18\n"),
+    );
+    ctx.set_current_dir(&root);
+
+    let ws = workspace::Workspace::load().unwrap().unwrap();
+    assert_eq!(
+        ws.member("api").unwrap().config.pkgs,
+        vec!["jq@1.7", "node@20"]
+    );
+    assert_eq!(ws.all_pkgs().unwrap(), vec!["node@20", "jq@1.7"]);
+}
+
+#[test]
+fn test_workspace_runs_commands_in_members() {
+    let mut ctx = common::TestContextGuard::acquire();
+    let tmp = tempdir().unwrap();
+    let root = tmp.path().canonicalize().unwrap();
+    setup(&root, "node@20");
+    ctx.set_current_dir(&root);
+
+    let ws = workspace::Workspace::load().unwrap().unwrap();
+    runner::run_all("test", &[], &ws).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("services/api/out.txt")).unwrap(),
+        "root api\n"
+    );
+    assert_eq!(
+        fs::read_to_string(root.join("services/web/out.txt")).unwrap(),
+        "root unset\n"
+    );
+    assert!(!root.join("out.txt").exists());
+
+    runner::run(Some("build"), &[], &ws.member("web").unwrap().config).unwrap();
+    assert!(root.join("services/web/built.txt").exists());
+
+    let err = runner::run_all("missing", &[], &ws)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("No workspace member defines"), "{}", err);
+}